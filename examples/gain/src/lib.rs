@@ -1,5 +1,7 @@
+use std::ops::Mul;
+
 use audioplug::core::{Color, Size};
-use audioplug::param::{BoolParameter, FloatParameter, ParameterId, Params};
+use audioplug::param::{BoolParameter, ByPassParameter, FloatParameter, ParameterId, Params};
 use audioplug::ui::prelude::*;
 use audioplug::ui::style::UiRect;
 use audioplug::wrapper::vst3::VST3Categories;
@@ -14,6 +16,7 @@ params!(
     struct MyPluginParams {
         pub enabled: BoolParameter,
         pub gain: FloatParameter,
+        pub bypass: ByPassParameter,
     }
 );
 
@@ -24,6 +27,7 @@ impl Params for MyPluginParams {
             gain: FloatParameter::new(ParameterId(2), "Gain")
                 .with_linear_range(0.0, 1.0)
                 .with_default(0.5),
+            bypass: ByPassParameter::new(ParameterId(3)),
         }
     }
 }
@@ -74,6 +78,7 @@ impl Plugin for MyPlugin {
             channel: ChannelType::Stereo,
         }),
     };
+    const SUPPORTS_F64: bool = true;
     type Editor = MyEditor;
     type Parameters = MyPluginParams;
 
@@ -84,15 +89,22 @@ impl Plugin for MyPlugin {
     fn prepare(&mut self, _sample_rate: f64, _max_samples_per_frame: usize) {}
 
     fn process(&mut self, ctx: ProcessContext, parameters: &MyPluginParams) {
-        let gain = parameters.gain.value() as f32;
-        for (in_channel, mut out_channel) in ctx
-            .input
-            .channels_iter()
-            .zip(ctx.output.channels_iter_mut())
-        {
-            for (in_sample, out_sample) in in_channel.iter().zip(out_channel.iter_mut()) {
-                *out_sample = in_sample * gain;
-            }
+        apply_gain(ctx, parameters.gain.value() as f32);
+    }
+
+    fn process_f64(&mut self, ctx: ProcessContext<f64>, parameters: &MyPluginParams) {
+        apply_gain(ctx, parameters.gain.value());
+    }
+}
+
+fn apply_gain<T: Copy + Mul<Output = T>>(ctx: ProcessContext<T>, gain: T) {
+    for (in_channel, mut out_channel) in ctx
+        .input
+        .channels_iter()
+        .zip(ctx.output.channels_iter_mut())
+    {
+        for (in_sample, out_sample) in in_channel.iter().zip(out_channel.iter_mut()) {
+            *out_sample = in_sample * gain;
         }
     }
 }
@@ -1,7 +1,6 @@
-use core::f32;
-
 use audioplug::{
     audioplug_auv3_plugin, audioplug_clap_plugin, audioplug_vst3_plugin,
+    dsp::{FilterType, StateVariableFilter},
     midi::NoteEvent,
     wrapper::{clap::ClapFeature, vst3::VST3Categories},
     AudioLayout, Bus, ChannelType, ClapPlugin, HostInfo, Plugin, Uuid, VST3Plugin,
@@ -17,7 +16,7 @@ mod voice;
 
 struct SynthPlugin {
     active_voice: Voice,
-    dt: f32,
+    filter: StateVariableFilter<f32>,
 }
 
 impl Plugin for SynthPlugin {
@@ -40,19 +39,29 @@ impl Plugin for SynthPlugin {
     fn new(_: HostInfo) -> Self {
         Self {
             active_voice: Voice::new(48000.0, Default::default()),
-            dt: 0.0,
+            filter: StateVariableFilter::new(FilterType::LowPass, 48000.0),
         }
     }
 
     fn prepare(&mut self, sample_rate: f64, _max_buffer_size: usize) {
-        self.dt = 1.0 / sample_rate as f32;
+        self.active_voice.set_sample_rate(sample_rate as f32);
+        self.filter.set_sample_rate(sample_rate as f32);
     }
 
     fn process(&mut self, context: audioplug::ProcessContext, parameters: &Self::Parameters) {
+        let filter_enabled = parameters.filter.enabled.value();
+        self.filter.set_parameters(
+            parameters.filter.cutoff.value() as f32,
+            0.5 + 9.5 * parameters.filter.resonance.value() as f32,
+            0.0,
+        );
+
         for sample in context.output.channel_mut(0).iter_mut() {
-            *sample = parameters.amplitude.value() as f32
-                * f32::sin(self.active_voice.ang_freq * self.active_voice.t);
-            self.active_voice.t += self.dt;
+            let mut value = self.active_voice.oscillator.tick();
+            if filter_enabled {
+                value = self.filter.tick(value);
+            }
+            *sample = parameters.amplitude.value() as f32 * value;
         }
     }
 
@@ -76,6 +85,7 @@ impl Plugin for SynthPlugin {
 
     fn reset(&mut self) {
         self.active_voice.reset();
+        self.filter.reset();
     }
 
     fn tail_time(&self) -> std::time::Duration {
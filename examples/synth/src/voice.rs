@@ -1,12 +1,11 @@
 use audioplug::{
-    dsp::{ADSREnvelope, ADSRParameters},
+    dsp::{ADSREnvelope, ADSRParameters, PolyBlepOscillator, Waveform},
     midi::Note,
 };
 
 pub struct Voice {
     pub note: Note,
-    pub ang_freq: f32,
-    pub t: f32,
+    pub oscillator: PolyBlepOscillator<f32>,
     pub envelope: ADSREnvelope<f32>,
 }
 
@@ -14,22 +13,24 @@ impl Voice {
     pub fn new(sample_rate: f32, env_parameters: ADSRParameters<f32>) -> Self {
         Self {
             note: Note::from_midi(0),
-            t: 0.0,
-            ang_freq: 0.0,
+            oscillator: PolyBlepOscillator::new(sample_rate, Waveform::Sine),
             envelope: ADSREnvelope::new(sample_rate, env_parameters),
         }
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.oscillator.set_sample_rate(sample_rate);
+    }
+
     pub fn reset(&mut self) {
         self.envelope.reset();
     }
 
     pub fn note_on(&mut self, note: Note) {
-        use std::f32::consts::TAU;
-        self.ang_freq = note.frequency_hz() * TAU;
+        self.oscillator.set_frequency(note.frequency_hz());
+        self.oscillator.reset();
         self.note = note;
         self.envelope.note_on();
-        self.t = 0.0;
     }
 
     pub fn note_off(&mut self) {
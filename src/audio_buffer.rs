@@ -1,40 +1,90 @@
 use std::marker::PhantomData;
+use std::ops::{Bound, Deref, Range, RangeBounds};
 
-pub struct AudioBuffer {
+use crate::dsp::DspFloat;
+
+/// Non-owning view of the channels of an audio buffer. The sample type defaults to `f32`,
+/// double precision buffers are passed to [`crate::Plugin::process_f64`].
+///
+/// The samples can only be modified through a mutable reference to the buffer, and views
+/// into the buffer (channels, frames and sub-blocks) borrow from it.
+pub struct AudioBuffer<'a, T = f32> {
     num_channels: usize,
     num_samples: usize,
-    channel_samples: *mut *mut f32,
+    sample_offset: usize,
+    channel_samples: *const *mut T,
+    _phantom: PhantomData<&'a mut [T]>,
+}
+
+/// Read the pointer to the first sample of a channel
+///
+/// # Safety
+///
+/// `index` must be less than the number of channels in `channel_samples`
+#[inline]
+unsafe fn channel_ptr<T>(
+    channel_samples: *const *mut T,
+    index: usize,
+    sample_offset: usize,
+) -> *mut T {
+    // Weird, tried to derefence but ableton gave us an unaligned pointer which caused
+    // panic. Use read_unaligned instead
+    unsafe {
+        channel_samples
+            .add(index)
+            .read_unaligned()
+            .add(sample_offset)
+    }
+}
+
+fn resolve_range(range: impl RangeBounds<usize>, len: usize) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(
+        start <= end && end <= len,
+        "Range {start}..{end} out of bounds for {len} samples"
+    );
+    start..end
 }
 
-impl AudioBuffer {
-    pub fn from_slice<const NCHANNELS: usize, const NSAMPLES: usize>(
-        channel_samples: &[&mut [f32; NSAMPLES]; NCHANNELS],
+impl<'a, T> AudioBuffer<'a, T> {
+    /// A buffer over arrays of samples, which it borrows mutably
+    pub fn from_slice<'b, const NCHANNELS: usize, const NSAMPLES: usize>(
+        channel_samples: &'a mut [&'b mut [T; NSAMPLES]; NCHANNELS],
     ) -> Self {
-        let channel_samples = channel_samples as *const _;
-        let channel_samples = unsafe {
-            std::mem::transmute::<*const [&mut [f32; NSAMPLES]; NCHANNELS], *mut *mut f32>(
-                channel_samples,
-            )
-        };
         Self {
             num_channels: NCHANNELS,
             num_samples: NSAMPLES,
-            channel_samples,
+            sample_offset: 0,
+            channel_samples: channel_samples.as_ptr().cast(),
+            _phantom: PhantomData,
         }
     }
 
     /// # Safety
     ///
-    /// The caller must ensure that channel_samples has a length of num_channels * num_samples
+    /// The caller must ensure that channel_samples points to num_channels channel pointers,
+    /// that each channel has num_samples samples, and that the samples are not accessed by
+    /// anything else for the lifetime of the buffer
     pub unsafe fn from_ptr(
-        channel_samples: *mut *mut f32,
+        channel_samples: *mut *mut T,
         num_channels: usize,
         num_samples: usize,
     ) -> Self {
         Self {
             num_channels,
             num_samples,
+            sample_offset: 0,
             channel_samples,
+            _phantom: PhantomData,
         }
     }
 
@@ -42,7 +92,9 @@ impl AudioBuffer {
         Self {
             num_channels: 0,
             num_samples: 0,
-            channel_samples: std::ptr::null_mut(),
+            sample_offset: 0,
+            channel_samples: std::ptr::null(),
+            _phantom: PhantomData,
         }
     }
 
@@ -54,124 +106,392 @@ impl AudioBuffer {
         self.num_channels
     }
 
-    pub fn channel(&self, index: usize) -> ChannelSamples<'_> {
+    pub fn channel(&self, index: usize) -> ChannelSamples<'_, T> {
         assert!(index < self.num_channels);
         ChannelSamples {
-            samples: unsafe { *self.channel_samples.add(index) },
+            samples: unsafe { channel_ptr(self.channel_samples, index, self.sample_offset) },
             num_samples: self.num_samples,
             _phantom: PhantomData,
         }
     }
 
-    pub fn channel_mut(&self, index: usize) -> ChannelSamplesMut<'_> {
+    pub fn channel_mut(&mut self, index: usize) -> ChannelSamplesMut<'_, T> {
         assert!(index < self.num_channels);
         ChannelSamplesMut {
-            samples: unsafe { *self.channel_samples.add(index) },
+            samples: unsafe { channel_ptr(self.channel_samples, index, self.sample_offset) },
             num_samples: self.num_samples,
             _phantom: PhantomData,
         }
     }
 
-    pub fn channels_iter(&self) -> ChannelsIter<'_> {
+    pub fn channels_iter(&self) -> ChannelsIter<'_, T> {
         ChannelsIter {
-            current_channel_samples: self.channel_samples as *const _,
-            end_channel_samples: unsafe { self.channel_samples.add(self.num_channels) as *const _ },
+            channel_samples: self.channel_samples,
+            channels: 0..self.num_channels,
+            sample_offset: self.sample_offset,
             num_samples: self.num_samples,
             _phantom: PhantomData,
         }
     }
 
-    pub fn channels_iter_mut(&mut self) -> ChannelsIterMut<'_> {
+    pub fn channels_iter_mut(&mut self) -> ChannelsIterMut<'_, T> {
         ChannelsIterMut {
-            current_channel_samples: self.channel_samples,
-            end_channel_samples: unsafe { self.channel_samples.add(self.num_channels) as *const _ },
+            channel_samples: self.channel_samples,
+            channels: 0..self.num_channels,
+            sample_offset: self.sample_offset,
             num_samples: self.num_samples,
             _phantom: PhantomData,
         }
     }
+
+    /// Iterate over the frames of the buffer, i.e. the samples of all channels at each
+    /// sample index
+    pub fn frames(&self) -> FrameIterator<'_, T> {
+        FrameIterator {
+            channel_samples: self.channel_samples,
+            num_channels: self.num_channels,
+            samples: self.sample_offset..self.sample_offset + self.num_samples,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn frames_mut(&mut self) -> FrameIteratorMut<'_, T> {
+        FrameIteratorMut {
+            channel_samples: self.channel_samples,
+            num_channels: self.num_channels,
+            samples: self.sample_offset..self.sample_offset + self.num_samples,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// A view of the samples in `range`, for all channels. Useful for splitting a block at
+    /// the sample offsets of events.
+    pub fn sub_block(&self, range: impl RangeBounds<usize>) -> AudioBufferRef<'_, T> {
+        let range = resolve_range(range, self.num_samples);
+        AudioBufferRef(AudioBuffer {
+            num_channels: self.num_channels,
+            num_samples: range.len(),
+            sample_offset: self.sample_offset + range.start,
+            channel_samples: self.channel_samples,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// A mutable view of the samples in `range`, for all channels
+    pub fn sub_block_mut(&mut self, range: impl RangeBounds<usize>) -> AudioBuffer<'_, T> {
+        let range = resolve_range(range, self.num_samples);
+        AudioBuffer {
+            num_channels: self.num_channels,
+            num_samples: range.len(),
+            sample_offset: self.sample_offset + range.start,
+            channel_samples: self.channel_samples,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Whether a channel of this buffer has the same samples as a channel of `other`, which
+    /// is the case when a host processes in place
+    pub(crate) fn shares_samples_with(&self, other: &AudioBuffer<T>) -> bool {
+        let channel = |buffer: &AudioBuffer<T>, index| unsafe {
+            channel_ptr(buffer.channel_samples, index, buffer.sample_offset)
+        };
+        (0..self.num_channels).any(|index| {
+            (0..other.num_channels)
+                .any(|other_index| channel(self, index) == channel(other, other_index))
+        })
+    }
+
+    /// Split the buffer into two mutable buffers, with the channels `0..mid` and
+    /// `mid..channels()` respectively
+    pub fn split_channels_at_mut(
+        &mut self,
+        mid: usize,
+    ) -> (AudioBuffer<'_, T>, AudioBuffer<'_, T>) {
+        assert!(mid <= self.num_channels);
+        let first = AudioBuffer {
+            num_channels: mid,
+            num_samples: self.num_samples,
+            sample_offset: self.sample_offset,
+            channel_samples: self.channel_samples,
+            _phantom: PhantomData,
+        };
+        let second = AudioBuffer {
+            num_channels: self.num_channels - mid,
+            num_samples: self.num_samples,
+            sample_offset: self.sample_offset,
+            channel_samples: self.channel_samples.wrapping_add(mid),
+            _phantom: PhantomData,
+        };
+        (first, second)
+    }
+}
+
+impl<T: DspFloat> AudioBuffer<'_, T> {
+    /// Set all samples to zero
+    pub fn clear(&mut self) {
+        for mut channel in self.channels_iter_mut() {
+            channel.as_mut_slice().fill(T::zero());
+        }
+    }
+
+    /// Copy the samples of `other` to this buffer. Channels that only exist in one of the
+    /// buffers are left untouched.
+    pub fn copy_from(&mut self, other: &AudioBuffer<T>) {
+        assert_eq!(self.num_samples, other.num_samples);
+        for index in 0..self.num_channels.min(other.num_channels) {
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    channel_ptr(other.channel_samples, index, other.sample_offset),
+                    channel_ptr(self.channel_samples, index, self.sample_offset),
+                    self.num_samples,
+                );
+            }
+        }
+    }
+
+    /// Add the samples of `other` to this buffer. Channels that only exist in one of the
+    /// buffers are left untouched.
+    pub fn add_from(&mut self, other: &AudioBuffer<T>) {
+        assert_eq!(self.num_samples, other.num_samples);
+        for index in 0..self.num_channels.min(other.num_channels) {
+            let source = unsafe { channel_ptr(other.channel_samples, index, other.sample_offset) };
+            let destination =
+                unsafe { channel_ptr(self.channel_samples, index, self.sample_offset) };
+            for i in 0..self.num_samples {
+                unsafe { *destination.add(i) = *destination.add(i) + *source.add(i) };
+            }
+        }
+    }
+
+    /// Multiply all samples with `gain`
+    pub fn apply_gain(&mut self, gain: T) {
+        for mut channel in self.channels_iter_mut() {
+            for sample in channel.iter_mut() {
+                *sample = *sample * gain;
+            }
+        }
+    }
+
+    /// Multiply the samples with a gain that changes linearly from `start_gain` at the first
+    /// sample. `end_gain` is reached at the sample after the last one, so that ramps over
+    /// consecutive blocks join up without discontinuities.
+    pub fn apply_gain_ramp(&mut self, start_gain: T, end_gain: T) {
+        if self.num_samples == 0 {
+            return;
+        }
+        let increment = (end_gain - start_gain) / T::from_f64(self.num_samples as f64);
+        for mut channel in self.channels_iter_mut() {
+            let mut gain = start_gain;
+            for sample in channel.iter_mut() {
+                *sample = *sample * gain;
+                gain = gain + increment;
+            }
+        }
+    }
+}
+
+/// Read-only view of an [`AudioBuffer`], returned by [`AudioBuffer::sub_block`]
+pub struct AudioBufferRef<'a, T = f32>(pub(crate) AudioBuffer<'a, T>);
+
+impl<'a, T> Deref for AudioBufferRef<'a, T> {
+    type Target = AudioBuffer<'a, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub struct FrameIterator<'a, T = f32> {
+    channel_samples: *const *mut T,
+    num_channels: usize,
+    samples: Range<usize>,
+    _phantom: PhantomData<&'a [T]>,
+}
+
+impl<'a, T> Iterator for FrameIterator<'a, T> {
+    type Item = FrameSamples<'a, T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.samples.next().map(|sample_index| FrameSamples {
+            channel_samples: self.channel_samples,
+            num_channels: self.num_channels,
+            sample_index,
+            _phantom: PhantomData,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.samples.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for FrameIterator<'_, T> {}
+
+/// The samples of all channels at one sample index
+pub struct FrameSamples<'a, T = f32> {
+    channel_samples: *const *mut T,
+    num_channels: usize,
+    sample_index: usize,
+    _phantom: PhantomData<&'a [T]>,
+}
+
+impl<'a, T: Copy> FrameSamples<'a, T> {
+    /// Number of channels
+    pub fn len(&self) -> usize {
+        self.num_channels
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_channels == 0
+    }
+
+    pub fn get(&self, channel: usize) -> T {
+        assert!(channel < self.num_channels);
+        unsafe { *channel_ptr(self.channel_samples, channel, self.sample_index) }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + 'a {
+        let channel_samples = self.channel_samples;
+        let sample_index = self.sample_index;
+        (0..self.num_channels)
+            .map(move |channel| unsafe { *channel_ptr(channel_samples, channel, sample_index) })
+    }
 }
 
-pub struct FrameIterator<'a> {
-    _phantom: PhantomData<&'a f32>,
+pub struct FrameIteratorMut<'a, T = f32> {
+    channel_samples: *const *mut T,
+    num_channels: usize,
+    samples: Range<usize>,
+    _phantom: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T> Iterator for FrameIteratorMut<'a, T> {
+    type Item = FrameSamplesMut<'a, T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.samples.next().map(|sample_index| FrameSamplesMut {
+            channel_samples: self.channel_samples,
+            num_channels: self.num_channels,
+            sample_index,
+            _phantom: PhantomData,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.samples.size_hint()
+    }
 }
 
-pub struct FrameSamples<'a> {
-    _phantom: PhantomData<&'a f32>,
+impl<T> ExactSizeIterator for FrameIteratorMut<'_, T> {}
+
+pub struct FrameSamplesMut<'a, T = f32> {
+    channel_samples: *const *mut T,
+    num_channels: usize,
+    sample_index: usize,
+    _phantom: PhantomData<&'a mut [T]>,
 }
 
-pub struct ChannelsIter<'a> {
-    current_channel_samples: *const *const f32,
-    end_channel_samples: *const *const f32,
+impl<'a, T: Copy> FrameSamplesMut<'a, T> {
+    /// Number of channels
+    pub fn len(&self) -> usize {
+        self.num_channels
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_channels == 0
+    }
+
+    pub fn get(&self, channel: usize) -> T {
+        assert!(channel < self.num_channels);
+        unsafe { *channel_ptr(self.channel_samples, channel, self.sample_index) }
+    }
+
+    pub fn get_mut(&mut self, channel: usize) -> &mut T {
+        assert!(channel < self.num_channels);
+        unsafe { &mut *channel_ptr(self.channel_samples, channel, self.sample_index) }
+    }
+
+    pub fn set(&mut self, channel: usize, value: T) {
+        *self.get_mut(channel) = value;
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        let channel_samples = self.channel_samples;
+        let sample_index = self.sample_index;
+        (0..self.num_channels).map(move |channel| unsafe {
+            &mut *channel_ptr(channel_samples, channel, sample_index)
+        })
+    }
+}
+
+pub struct ChannelsIter<'a, T = f32> {
+    channel_samples: *const *mut T,
+    channels: Range<usize>,
+    sample_offset: usize,
     num_samples: usize,
-    _phantom: PhantomData<&'a &'a f32>,
+    _phantom: PhantomData<&'a [T]>,
 }
 
-impl<'a> Iterator for ChannelsIter<'a> {
-    type Item = ChannelSamples<'a>;
+impl<'a, T> Iterator for ChannelsIter<'a, T> {
+    type Item = ChannelSamples<'a, T>;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_channel_samples == self.end_channel_samples {
-            None
-        } else {
-            let channel_samples = unsafe {
-                // Weird, tried to derefence but ableton gave us an unaligned pointer which caused
-                // panic. Use read_unaligned instead
-                let samples = self.current_channel_samples.read_unaligned();
-                ChannelSamples {
-                    samples,
-                    num_samples: self.num_samples,
-                    _phantom: PhantomData,
-                }
-            };
-            self.current_channel_samples = unsafe { self.current_channel_samples.offset(1) };
-            Some(channel_samples)
-        }
+        self.channels.next().map(|index| ChannelSamples {
+            samples: unsafe { channel_ptr(self.channel_samples, index, self.sample_offset) },
+            num_samples: self.num_samples,
+            _phantom: PhantomData,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.channels.size_hint()
     }
 }
 
-pub struct ChannelsIterMut<'a> {
-    current_channel_samples: *const *mut f32,
-    end_channel_samples: *const *mut f32,
+impl<T> ExactSizeIterator for ChannelsIter<'_, T> {}
+
+pub struct ChannelsIterMut<'a, T = f32> {
+    channel_samples: *const *mut T,
+    channels: Range<usize>,
+    sample_offset: usize,
     num_samples: usize,
-    _phantom: PhantomData<&'a f32>,
+    _phantom: PhantomData<&'a mut [T]>,
 }
 
-impl<'a> Iterator for ChannelsIterMut<'a> {
-    type Item = ChannelSamplesMut<'a>;
+impl<'a, T> Iterator for ChannelsIterMut<'a, T> {
+    type Item = ChannelSamplesMut<'a, T>;
 
+    #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_channel_samples == self.end_channel_samples {
-            None
-        } else {
-            let channel_samples = unsafe {
-                // Weird, tried to derefence but ableton gave us an unaligned pointer which caused
-                // panic. Use read_unaligned instead
-                let samples = self.current_channel_samples.read_unaligned();
-                ChannelSamplesMut {
-                    samples,
-                    num_samples: self.num_samples,
-                    _phantom: PhantomData,
-                }
-            };
-            self.current_channel_samples = unsafe { self.current_channel_samples.offset(1) };
-            Some(channel_samples)
-        }
+        self.channels.next().map(|index| ChannelSamplesMut {
+            samples: unsafe { channel_ptr(self.channel_samples, index, self.sample_offset) },
+            num_samples: self.num_samples,
+            _phantom: PhantomData,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.channels.size_hint()
     }
 }
 
-pub struct ChannelSamples<'a> {
-    samples: *const f32,
+impl<T> ExactSizeIterator for ChannelsIterMut<'_, T> {}
+
+pub struct ChannelSamples<'a, T = f32> {
+    samples: *const T,
     num_samples: usize,
-    _phantom: PhantomData<&'a f32>,
+    _phantom: PhantomData<&'a [T]>,
 }
 
-impl<'a> ChannelSamples<'a> {
-    pub fn iter(&self) -> ChannelSamplesIter<'a> {
+impl<'a, T> ChannelSamples<'a, T> {
+    pub fn iter(&self) -> ChannelSamplesIter<'a, T> {
         ChannelSamplesIter::new(self.samples, self.num_samples)
     }
 
-    pub fn as_slice(&self) -> &'a [f32] {
+    pub fn as_slice(&self) -> &'a [T] {
         unsafe { std::slice::from_raw_parts(self.samples, self.num_samples) }
     }
 
@@ -184,23 +504,23 @@ impl<'a> ChannelSamples<'a> {
     }
 }
 
-impl<'a> IntoIterator for ChannelSamples<'a> {
-    type Item = f32;
-    type IntoIter = ChannelSamplesIter<'a>;
+impl<'a, T: Copy> IntoIterator for ChannelSamples<'a, T> {
+    type Item = T;
+    type IntoIter = ChannelSamplesIter<'a, T>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
     }
 }
 
-pub struct ChannelSamplesIter<'a> {
-    current_sample: *const f32,
-    last_sample: *const f32,
-    _phantom: PhantomData<&'a f32>,
+pub struct ChannelSamplesIter<'a, T = f32> {
+    current_sample: *const T,
+    last_sample: *const T,
+    _phantom: PhantomData<&'a T>,
 }
 
-impl<'a> ChannelSamplesIter<'a> {
-    fn new(current_sample: *const f32, num_samples: usize) -> Self {
+impl<'a, T> ChannelSamplesIter<'a, T> {
+    fn new(current_sample: *const T, num_samples: usize) -> Self {
         Self {
             current_sample,
             last_sample: unsafe { current_sample.add(num_samples) },
@@ -209,8 +529,8 @@ impl<'a> ChannelSamplesIter<'a> {
     }
 }
 
-impl<'a> Iterator for ChannelSamplesIter<'a> {
-    type Item = f32;
+impl<'a, T: Copy> Iterator for ChannelSamplesIter<'a, T> {
+    type Item = T;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
@@ -224,18 +544,23 @@ impl<'a> Iterator for ChannelSamplesIter<'a> {
     }
 }
 
-pub struct ChannelSamplesMut<'a> {
-    samples: *mut f32,
+pub struct ChannelSamplesMut<'a, T = f32> {
+    samples: *mut T,
     num_samples: usize,
-    _phantom: PhantomData<&'a mut f32>,
+    _phantom: PhantomData<&'a mut [T]>,
 }
 
-impl<'a> ChannelSamplesMut<'a> {
-    pub fn as_slice(&self) -> &'a [f32] {
+impl<'a, T> ChannelSamplesMut<'a, T> {
+    pub fn as_slice(&self) -> &[T] {
         unsafe { std::slice::from_raw_parts(self.samples, self.num_samples) }
     }
 
-    pub fn as_mut_slice(&mut self) -> &'a mut [f32] {
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.samples, self.num_samples) }
+    }
+
+    /// Convert into a slice that lives as long as the borrow of the buffer
+    pub fn into_mut_slice(self) -> &'a mut [T] {
         unsafe { std::slice::from_raw_parts_mut(self.samples, self.num_samples) }
     }
 
@@ -249,12 +574,25 @@ impl<'a> ChannelSamplesMut<'a> {
     }
 }
 
-impl<'a> ChannelSamplesMut<'a> {
-    pub fn iter(&self) -> ChannelSamplesIter<'a> {
+impl<'a, T> ChannelSamplesMut<'a, T> {
+    pub fn iter(&self) -> ChannelSamplesIter<'_, T> {
         ChannelSamplesIter::new(self.samples, self.num_samples)
     }
 
-    pub fn iter_mut(&mut self) -> ChannelSamplesIterMut<'a> {
+    pub fn iter_mut(&mut self) -> ChannelSamplesIterMut<'_, T> {
+        ChannelSamplesIterMut {
+            current_sample: self.samples,
+            last_sample: unsafe { self.samples.add(self.num_samples) },
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for ChannelSamplesMut<'a, T> {
+    type Item = &'a mut T;
+    type IntoIter = ChannelSamplesIterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
         ChannelSamplesIterMut {
             current_sample: self.samples,
             last_sample: unsafe { self.samples.add(self.num_samples) },
@@ -263,14 +601,14 @@ impl<'a> ChannelSamplesMut<'a> {
     }
 }
 
-pub struct ChannelSamplesIterMut<'a> {
-    current_sample: *mut f32,
-    last_sample: *mut f32,
-    _phantom: PhantomData<&'a mut f32>,
+pub struct ChannelSamplesIterMut<'a, T = f32> {
+    current_sample: *mut T,
+    last_sample: *mut T,
+    _phantom: PhantomData<&'a mut T>,
 }
 
-impl<'a> Iterator for ChannelSamplesIterMut<'a> {
-    type Item = &'a mut f32;
+impl<'a, T> Iterator for ChannelSamplesIterMut<'a, T> {
+    type Item = &'a mut T;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
@@ -290,7 +628,7 @@ mod test {
 
     #[test]
     pub fn empty_buffer() {
-        let mut buffer = AudioBuffer::from_slice::<0, 0>(&[]);
+        let mut buffer = AudioBuffer::<f32>::from_slice::<0, 0>(&mut []);
         let mut channel_iter = buffer.channels_iter();
         assert!(channel_iter.next().is_none());
         let mut channel_iter = buffer.channels_iter_mut();
@@ -301,8 +639,8 @@ mod test {
     pub fn channel_iter() {
         let mut channel1_data = [1.0f32, 2.0f32, 3.0f32];
         let mut channel2_data = [4.0f32, 5.0, 6.0];
-        let data = [&mut channel1_data, &mut channel2_data];
-        let buffer = AudioBuffer::from_slice(&data);
+        let mut data = [&mut channel1_data, &mut channel2_data];
+        let buffer = AudioBuffer::from_slice(&mut data);
 
         let mut channel_iter = buffer.channels_iter();
         let samples = channel_iter.next();
@@ -324,8 +662,8 @@ mod test {
     pub fn channel_iter_mut() {
         let mut channel1_data = [0.0f32; 3];
         let mut channel2_data = [0.0f32; 3];
-        let data = [&mut channel1_data, &mut channel2_data];
-        let mut buffer = AudioBuffer::from_slice(&data);
+        let mut data = [&mut channel1_data, &mut channel2_data];
+        let mut buffer = AudioBuffer::from_slice(&mut data);
 
         let data = [[0.0f32, 1.0, 2.0], [3.0, 4.0, 5.0]];
         for (i, mut channel) in buffer.channels_iter_mut().enumerate() {
@@ -337,4 +675,90 @@ mod test {
         assert_eq!(buffer.channel(0).as_slice(), data[0]);
         assert_eq!(buffer.channel(1).as_slice(), data[1]);
     }
+
+    #[test]
+    pub fn f64_channel_iter_mut() {
+        let mut channel1_data = [0.0f64; 2];
+        let mut channel2_data = [0.0f64; 2];
+        let mut data = [&mut channel1_data, &mut channel2_data];
+        let mut buffer = AudioBuffer::from_slice(&mut data);
+
+        for mut channel in buffer.channels_iter_mut() {
+            for sample in channel.iter_mut() {
+                *sample = 0.1;
+            }
+        }
+
+        assert_eq!(buffer.channel(0).as_slice(), [0.1f64; 2]);
+        assert_eq!(buffer.channel(1).iter().sum::<f64>(), 0.2);
+    }
+
+    #[test]
+    pub fn frames() {
+        let mut channel1_data = [1.0f32, 2.0, 3.0];
+        let mut channel2_data = [4.0f32, 5.0, 6.0];
+        let mut data = [&mut channel1_data, &mut channel2_data];
+        let mut buffer = AudioBuffer::from_slice(&mut data);
+
+        for mut frame in buffer.frames_mut() {
+            let left = frame.get(0);
+            frame.set(0, frame.get(1));
+            frame.set(1, left);
+        }
+
+        let frames: Vec<Vec<f32>> = buffer
+            .frames()
+            .map(|frame| frame.iter().collect())
+            .collect();
+        assert_eq!(frames, [[4.0, 1.0], [5.0, 2.0], [6.0, 3.0]]);
+    }
+
+    #[test]
+    pub fn sub_blocks_and_split_channels() {
+        let mut channel1_data = [0.0f32; 4];
+        let mut channel2_data = [0.0f32; 4];
+        let mut data = [&mut channel1_data, &mut channel2_data];
+        let mut buffer = AudioBuffer::from_slice(&mut data);
+
+        {
+            let mut block = buffer.sub_block_mut(1..3);
+            assert_eq!(block.samples(), 2);
+            let (mut left, mut right) = block.split_channels_at_mut(1);
+            assert_eq!((left.channels(), right.channels()), (1, 1));
+            left.channel_mut(0).as_mut_slice().fill(1.0);
+            right
+                .sub_block_mut(1..)
+                .channel_mut(0)
+                .as_mut_slice()
+                .fill(2.0);
+        }
+
+        assert_eq!(buffer.channel(0).as_slice(), [0.0, 1.0, 1.0, 0.0]);
+        assert_eq!(buffer.channel(1).as_slice(), [0.0, 0.0, 2.0, 0.0]);
+        assert_eq!(buffer.sub_block(2..).channel(1).as_slice(), [2.0, 0.0]);
+    }
+
+    #[test]
+    pub fn gain_helpers() {
+        let mut channel1_data = [1.0f32; 4];
+        let mut channel2_data = [2.0f32; 4];
+        let mut data = [&mut channel1_data, &mut channel2_data];
+        let mut buffer = AudioBuffer::from_slice(&mut data);
+
+        let mut other_data = [0.5f32; 4];
+        let mut other_channels = [&mut other_data];
+        let other = AudioBuffer::from_slice(&mut other_channels);
+
+        buffer.apply_gain_ramp(0.0, 1.0);
+        assert_eq!(buffer.channel(0).as_slice(), [0.0, 0.25, 0.5, 0.75]);
+        buffer.add_from(&other);
+        assert_eq!(buffer.channel(0).as_slice(), [0.5, 0.75, 1.0, 1.25]);
+        assert_eq!(buffer.channel(1).as_slice(), [0.0, 0.5, 1.0, 1.5]);
+        buffer.apply_gain(2.0);
+        assert_eq!(buffer.channel(1).as_slice(), [0.0, 1.0, 2.0, 3.0]);
+        buffer.copy_from(&other);
+        assert_eq!(buffer.channel(0).as_slice(), [0.5; 4]);
+        buffer.clear();
+        assert_eq!(buffer.channel(1).as_slice(), [0.0; 4]);
+    }
 }
@@ -0,0 +1,337 @@
+use std::time::Duration;
+
+use super::DspFloat;
+
+/// Number of extra samples needed around the read position by the interpolators
+const INTERPOLATION_MARGIN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Round the delay to the nearest sample
+    None,
+    Linear,
+    /// 4-point, 3rd order Hermite interpolation. Delays shorter than one sample are rounded up.
+    Cubic,
+    /// First order allpass interpolation. Has a flat magnitude response, which makes it
+    /// suitable for delays in feedback loops (e.g. physical modelling), but it is not well
+    /// suited for quickly modulated delays.
+    AllPass,
+}
+
+/// A read position in a [`DelayLine`]. A delay line can be read by any number of taps, each
+/// with their own delay and interpolation.
+#[derive(Debug, Clone)]
+pub struct DelayTap<T = f32> {
+    delay: T,
+    interpolation: Interpolation,
+    allpass_previous_input: T,
+    allpass_previous_output: T,
+}
+
+impl<T: DspFloat> DelayTap<T> {
+    pub fn new(interpolation: Interpolation) -> Self {
+        Self {
+            delay: T::zero(),
+            interpolation,
+            allpass_previous_input: T::zero(),
+            allpass_previous_output: T::zero(),
+        }
+    }
+
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+        self.reset();
+    }
+
+    /// The delay, in samples
+    pub fn delay(&self) -> T {
+        self.delay
+    }
+
+    /// Set the delay, in (fractional) samples. Can be called for every sample to modulate the delay.
+    /// A delay of zero returns the most recently written sample.
+    #[inline]
+    pub fn set_delay(&mut self, delay_samples: T) {
+        self.delay = delay_samples.max(T::zero());
+    }
+
+    /// Set the delay in seconds
+    #[inline]
+    pub fn set_delay_time(&mut self, seconds: T, sample_rate: T) {
+        self.set_delay(seconds * sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.allpass_previous_input = T::zero();
+        self.allpass_previous_output = T::zero();
+    }
+}
+
+/// Circular delay line. All memory is allocated in [`DelayLine::prepare`], which should be
+/// called from [`Plugin::prepare`](crate::Plugin::prepare), so that no allocations
+/// happen during processing.
+#[derive(Debug, Clone)]
+pub struct DelayLine<T = f32> {
+    buffer: Box<[T]>,
+    mask: usize,
+    write_index: usize,
+    max_delay_samples: usize,
+    max_block_size: usize,
+}
+
+impl<T: DspFloat> DelayLine<T> {
+    /// Create an empty delay line, [`DelayLine::prepare`] must be called before use
+    pub fn new() -> Self {
+        Self {
+            buffer: Box::new([]),
+            mask: 0,
+            write_index: 0,
+            max_delay_samples: 0,
+            max_block_size: 0,
+        }
+    }
+
+    /// Allocate memory for delays of up to `max_delay`. `max_buffer_size` is the max number of
+    /// samples that are written with [`DelayLine::write_block`] before being read with
+    /// [`DelayLine::read_block`].
+    pub fn prepare(&mut self, sample_rate: f64, max_buffer_size: usize, max_delay: Duration) {
+        let max_delay_samples = (max_delay.as_secs_f64() * sample_rate).ceil() as usize;
+        self.prepare_samples(max_delay_samples, max_buffer_size);
+    }
+
+    /// Same as [`DelayLine::prepare`], but with the max delay given in samples
+    pub fn prepare_samples(&mut self, max_delay_samples: usize, max_buffer_size: usize) {
+        let size =
+            (max_delay_samples + max_buffer_size.max(1) + INTERPOLATION_MARGIN).next_power_of_two();
+        if size != self.buffer.len() {
+            self.buffer = vec![T::zero(); size].into_boxed_slice();
+        }
+        self.mask = size - 1;
+        self.max_delay_samples = max_delay_samples;
+        self.max_block_size = max_buffer_size.max(1);
+        self.reset();
+    }
+
+    /// The max delay, in samples, that can be read from the delay line
+    pub fn max_delay_samples(&self) -> usize {
+        self.max_delay_samples
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(T::zero());
+        self.write_index = 0;
+    }
+
+    /// Write a sample to the delay line
+    #[inline]
+    pub fn write(&mut self, sample: T) {
+        debug_assert!(
+            !self.buffer.is_empty(),
+            "DelayLine::prepare has not been called"
+        );
+        self.write_index = (self.write_index + 1) & self.mask;
+        self.buffer[self.write_index] = sample;
+    }
+
+    /// Read from a tap. The delay is relative to the most recently written sample.
+    #[inline]
+    pub fn read(&self, tap: &mut DelayTap<T>) -> T {
+        self.read_at(tap, 0)
+    }
+
+    /// Write a sample and read it back through a tap
+    #[inline]
+    pub fn tick(&mut self, input: T, tap: &mut DelayTap<T>) -> T {
+        self.write(input);
+        self.read(tap)
+    }
+
+    /// Write a block of samples. The block must not be larger than the `max_buffer_size`
+    /// passed to [`DelayLine::prepare`].
+    pub fn write_block(&mut self, samples: &[T]) {
+        assert!(samples.len() <= self.max_block_size);
+        for &sample in samples {
+            self.write(sample);
+        }
+    }
+
+    /// Read a block through a tap, where `output.len()` is the size of the most recently
+    /// written block. Output sample `i` is delayed relative to input sample `i` of the block.
+    pub fn read_block(&self, tap: &mut DelayTap<T>, output: &mut [T]) {
+        assert!(output.len() <= self.max_block_size);
+        let len = output.len();
+        for (i, sample) in output.iter_mut().enumerate() {
+            *sample = self.read_at(tap, len - 1 - i);
+        }
+    }
+
+    /// Same as [`DelayLine::read_block`], but with a per-sample delay, e.g. from an LFO
+    pub fn read_block_modulated(&self, tap: &mut DelayTap<T>, delays: &[T], output: &mut [T]) {
+        assert!(output.len() <= self.max_block_size);
+        let len = output.len();
+        for (i, (sample, &delay)) in output.iter_mut().zip(delays).enumerate() {
+            tap.set_delay(delay);
+            *sample = self.read_at(tap, len - 1 - i);
+        }
+    }
+
+    #[inline]
+    fn sample(&self, delay: usize) -> T {
+        self.buffer[self.write_index.wrapping_sub(delay) & self.mask]
+    }
+
+    /// Read from a tap, with an additional integer offset (used when reading blocks)
+    #[inline]
+    fn read_at(&self, tap: &mut DelayTap<T>, offset: usize) -> T {
+        let max_delay = T::from_f64(self.max_delay_samples as f64);
+        let delay = tap.delay.min(max_delay);
+        let integer = delay.floor();
+        let fraction = delay - integer;
+        let index = integer.to_usize().unwrap_or(0) + offset;
+
+        match tap.interpolation {
+            Interpolation::None => self.sample(delay.round().to_usize().unwrap_or(0) + offset),
+            Interpolation::Linear => {
+                let x0 = self.sample(index);
+                let x1 = self.sample(index + 1);
+                x0 + fraction * (x1 - x0)
+            }
+            Interpolation::Cubic => {
+                // Needs one newer sample than the read position, so the min delay is one sample
+                let (index, fraction) = if index == offset {
+                    (index + 1, T::zero())
+                } else {
+                    (index, fraction)
+                };
+                let xm1 = self.sample(index - 1);
+                let x0 = self.sample(index);
+                let x1 = self.sample(index + 1);
+                let x2 = self.sample(index + 2);
+                hermite(fraction, xm1, x0, x1, x2)
+            }
+            Interpolation::AllPass => {
+                // Keep the fractional delay in [0.5, 1.5) where the allpass is well behaved
+                let (index, fraction) = if fraction < T::from_f32(0.5) && index > offset {
+                    (index - 1, fraction + T::one())
+                } else {
+                    (index, fraction)
+                };
+                let eta = (T::one() - fraction) / (T::one() + fraction);
+                let input = self.sample(index);
+                let output =
+                    eta * (input - tap.allpass_previous_output) + tap.allpass_previous_input;
+                tap.allpass_previous_input = input;
+                tap.allpass_previous_output = output;
+                output
+            }
+        }
+    }
+}
+
+impl<T: DspFloat> Default for DelayLine<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 4-point Hermite interpolation, where x0 is the value at fraction = 0, and x1 at fraction = 1.
+/// The values are ordered from newest (xm1) to oldest (x2).
+#[inline]
+fn hermite<T: DspFloat>(fraction: T, xm1: T, x0: T, x1: T, x2: T) -> T {
+    let half = T::from_f32(0.5);
+    let c1 = half * (x1 - xm1);
+    let c2 = xm1 - T::from_f32(2.5) * x0 + T::from_f32(2.0) * x1 - half * x2;
+    let c3 = half * (x2 - xm1) + T::from_f32(1.5) * (x0 - x1);
+    ((c3 * fraction + c2) * fraction + c1) * fraction + x0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ramp_line() -> DelayLine<f64> {
+        let mut line = DelayLine::new();
+        line.prepare_samples(100, 16);
+        for i in 0..200 {
+            line.write(i as f64);
+        }
+        line
+    }
+
+    #[test]
+    fn integer_delay() {
+        let mut line = DelayLine::new();
+        line.prepare_samples(10, 1);
+        let mut tap = DelayTap::new(Interpolation::None);
+        tap.set_delay(3.0);
+        let output: Vec<f64> = (1..=8).map(|i| line.tick(i as f64, &mut tap)).collect();
+        assert_eq!(output, [0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn fractional_delay_of_ramp() {
+        let line = ramp_line();
+        for interpolation in [Interpolation::Linear, Interpolation::Cubic] {
+            let mut tap = DelayTap::new(interpolation);
+            for delay in [1.0, 2.25, 10.5, 99.75] {
+                tap.set_delay(delay);
+                let value = line.read(&mut tap);
+                assert!(
+                    (value - (199.0 - delay)).abs() < 1e-9,
+                    "{interpolation:?}: delay {delay} gave {value}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn delay_is_clamped_to_max() {
+        let line = ramp_line();
+        let mut tap = DelayTap::new(Interpolation::Linear);
+        tap.set_delay(1000.0);
+        assert_eq!(line.read(&mut tap), 99.0);
+    }
+
+    #[test]
+    fn allpass_phase_delay() {
+        let mut line = DelayLine::new();
+        line.prepare_samples(64, 1);
+        let mut tap = DelayTap::new(Interpolation::AllPass);
+        tap.set_delay(10.3);
+        // A low frequency sine should be delayed by the fractional delay
+        let omega = std::f64::consts::TAU * 0.005;
+        let mut error = 0.0f64;
+        for i in 0..2000 {
+            let output = line.tick((omega * i as f64).sin(), &mut tap);
+            if i > 1000 {
+                let expected = (omega * (i as f64 - 10.3)).sin();
+                error = error.max((output - expected).abs());
+            }
+        }
+        assert!(error < 1e-3);
+    }
+
+    #[test]
+    fn block_read_matches_sample_read() {
+        let input: Vec<f64> = (0..16).map(|i| (i as f64 * 0.37).sin()).collect();
+        let mut block_line = DelayLine::new();
+        block_line.prepare_samples(32, 16);
+        let mut sample_line = block_line.clone();
+
+        let mut tap = DelayTap::new(Interpolation::Cubic);
+        tap.set_delay(5.5);
+        let mut sample_tap = tap.clone();
+
+        block_line.write_block(&input);
+        let mut block_output = [0.0; 16];
+        block_line.read_block(&mut tap, &mut block_output);
+
+        for (&x, &y) in input.iter().zip(block_output.iter()) {
+            assert!((sample_line.tick(x, &mut sample_tap) - y).abs() < 1e-12);
+        }
+    }
+}
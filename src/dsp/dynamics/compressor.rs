@@ -0,0 +1,141 @@
+use super::{Detector, EnvelopeFollower, GainReductionMeter, compressor_gain_db};
+use crate::dsp::{DspFloat, db_to_gain, gain_to_db};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressorParameters<T = f32> {
+    pub threshold_db: T,
+    pub ratio: T,
+    /// Width of the soft knee, centered on the threshold
+    pub knee_db: T,
+    /// Attack time in seconds
+    pub attack: T,
+    /// Release time in seconds
+    pub release: T,
+    pub makeup_db: T,
+    pub detector: Detector,
+}
+
+impl<T: DspFloat> Default for CompressorParameters<T> {
+    fn default() -> Self {
+        Self {
+            threshold_db: T::from_f32(-18.0),
+            ratio: T::from_f32(4.0),
+            knee_db: T::from_f32(6.0),
+            attack: T::from_f32(0.01),
+            release: T::from_f32(0.1),
+            makeup_db: T::zero(),
+            detector: Detector::Peak,
+        }
+    }
+}
+
+/// Feed-forward compressor. The level is measured on a sidechain signal, which is the input
+/// itself when using [`Compressor::tick`]. For linked multi-channel compression, compute the
+/// gain once per frame with [`Compressor::gain`] and apply it to all channels.
+#[derive(Debug, Clone)]
+pub struct Compressor<T = f32> {
+    parameters: CompressorParameters<T>,
+    follower: EnvelopeFollower<T>,
+    meter: GainReductionMeter<T>,
+}
+
+impl<T: DspFloat> Compressor<T> {
+    pub fn new(sample_rate: T, parameters: CompressorParameters<T>) -> Self {
+        Self {
+            follower: EnvelopeFollower::new(
+                sample_rate,
+                parameters.detector,
+                parameters.attack,
+                parameters.release,
+            ),
+            parameters,
+            meter: GainReductionMeter::new(),
+        }
+    }
+
+    pub fn parameters(&self) -> &CompressorParameters<T> {
+        &self.parameters
+    }
+
+    pub fn set_parameters(&mut self, parameters: CompressorParameters<T>) {
+        self.follower.set_detector(parameters.detector);
+        self.follower
+            .set_times(parameters.attack, parameters.release);
+        self.parameters = parameters;
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.follower.set_sample_rate(sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.follower.reset();
+        self.meter.reset();
+    }
+
+    /// The current gain reduction in dB, as a positive value
+    pub fn gain_reduction_db(&self) -> T {
+        self.meter.current
+    }
+
+    /// The largest gain reduction (in dB) since the last call, for updating a meter
+    pub fn take_max_gain_reduction_db(&mut self) -> T {
+        self.meter.take_max()
+    }
+
+    /// Advance one sample with the given sidechain input, returning the linear gain
+    /// (including makeup gain) to apply to the signal
+    #[inline]
+    pub fn gain(&mut self, sidechain: T) -> T {
+        let p = &self.parameters;
+        let level_db = gain_to_db(self.follower.tick(sidechain));
+        let gain_db = compressor_gain_db(level_db, p.threshold_db, p.ratio, p.knee_db);
+        self.meter.update(-gain_db);
+        db_to_gain(gain_db + p.makeup_db)
+    }
+
+    #[inline]
+    pub fn tick(&mut self, input: T) -> T {
+        input * self.gain(input)
+    }
+
+    /// Process a sample, with the level measured on an external sidechain signal
+    #[inline]
+    pub fn tick_sidechain(&mut self, input: T, sidechain: T) -> T {
+        input * self.gain(sidechain)
+    }
+
+    pub fn process(&mut self, samples: &mut [T]) {
+        for sample in samples.iter_mut() {
+            *sample = self.tick(*sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn steady_state_gain_reduction() {
+        let parameters = CompressorParameters {
+            threshold_db: -20.0,
+            ratio: 4.0,
+            knee_db: 0.0,
+            makeup_db: 3.0,
+            ..Default::default()
+        };
+        let mut compressor = Compressor::<f64>::new(48000.0, parameters);
+        // A constant level 12 dB above the threshold should be reduced by 9 dB
+        let input = db_to_gain(-8.0);
+        let output = (0..48000).map(|_| compressor.tick(input)).last().unwrap();
+        assert!((compressor.gain_reduction_db() - 9.0).abs() < 1e-3);
+        assert!((gain_to_db(output) - (-8.0 - 9.0 + 3.0)).abs() < 1e-3);
+        assert!((compressor.take_max_gain_reduction_db() - 9.0).abs() < 1e-3);
+
+        // Below the threshold only the makeup gain is applied
+        compressor.reset();
+        let output = compressor.tick_sidechain(1.0, db_to_gain(-40.0));
+        assert!((gain_to_db(output) - 3.0).abs() < 1e-9);
+    }
+}
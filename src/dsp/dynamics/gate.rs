@@ -0,0 +1,169 @@
+use super::{GainReductionMeter, expander_gain_db, time_coefficient};
+use crate::dsp::{DspFloat, db_to_gain, gain_to_db};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GateParameters<T = f32> {
+    pub threshold_db: T,
+    /// Expansion ratio below the threshold, use a large ratio for a hard gate
+    pub ratio: T,
+    /// Max attenuation
+    pub range_db: T,
+    /// Width of the soft knee, centered on the threshold
+    pub knee_db: T,
+    /// Time to open the gate, in seconds
+    pub attack: T,
+    /// Time to keep the gate open after the level has dropped below the threshold, in seconds
+    pub hold: T,
+    /// Time to close the gate, in seconds
+    pub release: T,
+}
+
+impl<T: DspFloat> Default for GateParameters<T> {
+    fn default() -> Self {
+        Self {
+            threshold_db: T::from_f32(-40.0),
+            ratio: T::from_f32(100.0),
+            range_db: T::from_f32(80.0),
+            knee_db: T::zero(),
+            attack: T::from_f32(0.001),
+            hold: T::from_f32(0.01),
+            release: T::from_f32(0.1),
+        }
+    }
+}
+
+/// Noise gate / downward expander, with the level measured on a sidechain signal.
+#[derive(Debug, Clone)]
+pub struct Gate<T = f32> {
+    parameters: GateParameters<T>,
+    sample_rate: T,
+    attack_coefficient: T,
+    release_coefficient: T,
+    hold_samples: usize,
+    hold_counter: usize,
+    gain_db: T,
+    meter: GainReductionMeter<T>,
+}
+
+impl<T: DspFloat> Gate<T> {
+    pub fn new(sample_rate: T, parameters: GateParameters<T>) -> Self {
+        let mut gate = Self {
+            parameters,
+            sample_rate,
+            attack_coefficient: T::zero(),
+            release_coefficient: T::zero(),
+            hold_samples: 0,
+            hold_counter: 0,
+            gain_db: T::zero(),
+            meter: GainReductionMeter::new(),
+        };
+        gate.set_parameters(parameters);
+        gate.reset();
+        gate
+    }
+
+    pub fn parameters(&self) -> &GateParameters<T> {
+        &self.parameters
+    }
+
+    pub fn set_parameters(&mut self, parameters: GateParameters<T>) {
+        self.parameters = parameters;
+        self.attack_coefficient = time_coefficient(self.sample_rate, parameters.attack);
+        self.release_coefficient = time_coefficient(self.sample_rate, parameters.release);
+        self.hold_samples = (parameters.hold * self.sample_rate)
+            .max(T::zero())
+            .to_usize()
+            .unwrap_or(0);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.set_parameters(self.parameters);
+    }
+
+    /// Reset to the closed state
+    pub fn reset(&mut self) {
+        self.gain_db = -self.parameters.range_db.abs();
+        self.hold_counter = 0;
+        self.meter.reset();
+    }
+
+    /// The current gain reduction in dB, as a positive value
+    pub fn gain_reduction_db(&self) -> T {
+        self.meter.current
+    }
+
+    /// The largest gain reduction (in dB) since the last call, for updating a meter
+    pub fn take_max_gain_reduction_db(&mut self) -> T {
+        self.meter.take_max()
+    }
+
+    /// Advance one sample with the given sidechain input, returning the linear gain
+    /// to apply to the signal
+    #[inline]
+    pub fn gain(&mut self, sidechain: T) -> T {
+        let p = &self.parameters;
+        let target_db = expander_gain_db(
+            gain_to_db(sidechain.abs()),
+            p.threshold_db,
+            p.ratio,
+            p.knee_db,
+        )
+        .max(-p.range_db.abs());
+
+        if target_db >= self.gain_db {
+            self.hold_counter = self.hold_samples;
+            self.gain_db = target_db + self.attack_coefficient * (self.gain_db - target_db);
+        } else if self.hold_counter > 0 {
+            self.hold_counter -= 1;
+        } else {
+            self.gain_db = target_db + self.release_coefficient * (self.gain_db - target_db);
+        }
+        self.meter.update(-self.gain_db);
+        db_to_gain(self.gain_db)
+    }
+
+    #[inline]
+    pub fn tick(&mut self, input: T) -> T {
+        input * self.gain(input)
+    }
+
+    /// Process a sample, with the level measured on an external sidechain signal
+    #[inline]
+    pub fn tick_sidechain(&mut self, input: T, sidechain: T) -> T {
+        input * self.gain(sidechain)
+    }
+
+    pub fn process(&mut self, samples: &mut [T]) {
+        for sample in samples.iter_mut() {
+            *sample = self.tick(*sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn opens_and_closes() {
+        let sample_rate = 48000.0;
+        let mut gate = Gate::<f64>::new(sample_rate, GateParameters::default());
+        let signal = |amplitude: f64, i: usize| {
+            amplitude * (std::f64::consts::TAU * 440.0 * i as f64 / sample_rate).sin()
+        };
+
+        // Loud signal opens the gate
+        for i in 0..4800 {
+            gate.tick(signal(0.5, i));
+        }
+        assert!(gate.gain_reduction_db() < 0.1);
+
+        // Quiet signal closes it, limited by the range
+        for i in 0..48000 {
+            gate.tick(signal(0.001, i));
+        }
+        assert!((gate.gain_reduction_db() - 80.0).abs() < 0.1);
+        assert!((gate.take_max_gain_reduction_db() - 80.0).abs() < 0.1);
+    }
+}
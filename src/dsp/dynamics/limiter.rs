@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use super::{GainReductionMeter, time_coefficient};
+use crate::dsp::{DelayLine, DelayTap, DspFloat, Interpolation, db_to_gain, gain_to_db};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimiterParameters<T = f32> {
+    /// The max output level
+    pub ceiling_db: T,
+    /// Release time in seconds
+    pub release: T,
+}
+
+impl<T: DspFloat> Default for LimiterParameters<T> {
+    fn default() -> Self {
+        Self {
+            ceiling_db: T::from_f32(-0.3),
+            release: T::from_f32(0.05),
+        }
+    }
+}
+
+/// Brickwall limiter with lookahead. The signal is delayed by the lookahead time, so that
+/// the gain can be reduced smoothly before a peak arrives, guaranteeing that the output
+/// never exceeds the ceiling. The delay must be reported to the host, by returning
+/// [`Limiter::latency_samples`] from [`Plugin::latency_samples`](crate::Plugin::latency_samples).
+///
+/// All channels share the same gain reduction, to keep the stereo image intact.
+#[derive(Debug, Clone)]
+pub struct Limiter<T = f32> {
+    parameters: LimiterParameters<T>,
+    sample_rate: T,
+    ceiling: T,
+    release_coefficient: T,
+    delays: Vec<DelayLine<T>>,
+    tap: DelayTap<T>,
+    minimum: SlidingMinimum<T>,
+    average: Box<[T]>,
+    average_index: usize,
+    average_sum: T,
+    envelope: T,
+    meter: GainReductionMeter<T>,
+}
+
+impl<T: DspFloat> Limiter<T> {
+    /// Create a limiter without lookahead, [`Limiter::prepare`] must be called before use
+    pub fn new(parameters: LimiterParameters<T>) -> Self {
+        let mut limiter = Self {
+            parameters,
+            sample_rate: T::from_f32(44100.0),
+            ceiling: T::one(),
+            release_coefficient: T::zero(),
+            delays: Vec::new(),
+            tap: DelayTap::new(Interpolation::None),
+            minimum: SlidingMinimum::new(1),
+            average: Box::new([T::one()]),
+            average_index: 0,
+            average_sum: T::one(),
+            envelope: T::one(),
+            meter: GainReductionMeter::new(),
+        };
+        limiter.set_parameters(parameters);
+        limiter
+    }
+
+    /// Allocate the lookahead buffers, should be called from
+    /// [`Plugin::prepare`](crate::Plugin::prepare)
+    pub fn prepare(&mut self, sample_rate: f64, num_channels: usize, lookahead: Duration) {
+        let lookahead_samples = (lookahead.as_secs_f64() * sample_rate).round() as usize;
+        self.sample_rate = T::from_f64(sample_rate);
+        self.delays.resize_with(num_channels, DelayLine::new);
+        for delay in self.delays.iter_mut() {
+            delay.prepare_samples(lookahead_samples, 1);
+        }
+        self.tap.set_delay(T::from_f64(lookahead_samples as f64));
+        self.minimum = SlidingMinimum::new(lookahead_samples + 1);
+        self.average = vec![T::one(); lookahead_samples + 1].into_boxed_slice();
+        self.set_parameters(self.parameters);
+        self.reset();
+    }
+
+    /// The delay introduced by the lookahead
+    pub fn latency_samples(&self) -> usize {
+        self.average.len() - 1
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.delays.len()
+    }
+
+    pub fn parameters(&self) -> &LimiterParameters<T> {
+        &self.parameters
+    }
+
+    pub fn set_parameters(&mut self, parameters: LimiterParameters<T>) {
+        self.parameters = parameters;
+        self.ceiling = db_to_gain(parameters.ceiling_db);
+        self.release_coefficient = time_coefficient(self.sample_rate, parameters.release);
+    }
+
+    pub fn reset(&mut self) {
+        for delay in self.delays.iter_mut() {
+            delay.reset();
+        }
+        self.minimum.reset();
+        self.average.fill(T::one());
+        self.average_index = 0;
+        self.average_sum = T::from_f64(self.average.len() as f64);
+        self.envelope = T::one();
+        self.meter.reset();
+    }
+
+    /// The current gain reduction in dB, as a positive value
+    pub fn gain_reduction_db(&self) -> T {
+        self.meter.current
+    }
+
+    /// The largest gain reduction (in dB) since the last call, for updating a meter
+    pub fn take_max_gain_reduction_db(&mut self) -> T {
+        self.meter.take_max()
+    }
+
+    /// Process one frame in place, containing one sample per channel
+    #[inline]
+    pub fn tick(&mut self, frame: &mut [T]) {
+        let peak = frame
+            .iter()
+            .fold(T::zero(), |peak, sample| peak.max(sample.abs()));
+        self.tick_sidechain(frame, peak);
+    }
+
+    /// Process one frame in place, with the level measured on an external sidechain signal
+    #[inline]
+    pub fn tick_sidechain(&mut self, frame: &mut [T], sidechain: T) {
+        debug_assert_eq!(frame.len(), self.delays.len());
+        let sidechain = sidechain.abs();
+        let required = if sidechain > self.ceiling {
+            self.ceiling / sidechain
+        } else {
+            T::one()
+        };
+
+        // Hold the lowest required gain for the lookahead time, with instant attack and
+        // exponential release. The moving average then ramps the gain down over the
+        // lookahead time, without going above the required gain when the peak is output.
+        let held = self.minimum.push(required);
+        self.envelope = if held < self.envelope {
+            held
+        } else {
+            held + self.release_coefficient * (self.envelope - held)
+        };
+
+        self.average_sum = self.average_sum + self.envelope - self.average[self.average_index];
+        self.average[self.average_index] = self.envelope;
+        self.average_index += 1;
+        if self.average_index == self.average.len() {
+            self.average_index = 0;
+            // Avoid drift in the running sum
+            self.average_sum = self.average.iter().fold(T::zero(), |sum, &x| sum + x);
+        }
+        let gain = (self.average_sum / T::from_f64(self.average.len() as f64)).min(T::one());
+        self.meter.update(-gain_to_db(gain));
+
+        for (sample, delay) in frame.iter_mut().zip(self.delays.iter_mut()) {
+            *sample = delay.tick(*sample, &mut self.tap) * gain;
+        }
+    }
+}
+
+/// Minimum over a sliding window, using a monotonic queue
+#[derive(Debug, Clone)]
+struct SlidingMinimum<T> {
+    queue: Box<[(usize, T)]>,
+    head: usize,
+    len: usize,
+    position: usize,
+}
+
+impl<T: DspFloat> SlidingMinimum<T> {
+    fn new(window: usize) -> Self {
+        Self {
+            queue: vec![(0, T::zero()); window.max(1)].into_boxed_slice(),
+            head: 0,
+            len: 0,
+            position: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.head = 0;
+        self.len = 0;
+        self.position = 0;
+    }
+
+    #[inline]
+    fn push(&mut self, value: T) -> T {
+        let window = self.queue.len();
+        if self.len > 0 && self.position - self.queue[self.head].0 >= window {
+            self.head = (self.head + 1) % window;
+            self.len -= 1;
+        }
+        while self.len > 0 && self.queue[(self.head + self.len - 1) % window].1 >= value {
+            self.len -= 1;
+        }
+        self.queue[(self.head + self.len) % window] = (self.position, value);
+        self.len += 1;
+        self.position += 1;
+        self.queue[self.head].1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn output_never_exceeds_ceiling() {
+        let sample_rate = 48000.0;
+        let mut limiter = Limiter::<f64>::new(LimiterParameters {
+            ceiling_db: -1.0,
+            release: 0.05,
+        });
+        limiter.prepare(sample_rate, 2, Duration::from_millis(5));
+        assert_eq!(limiter.latency_samples(), 240);
+
+        let ceiling = db_to_gain(-1.0);
+        let mut max_output = 0.0f64;
+        for i in 0..48000 {
+            // Bursts of a loud sine, with the right channel louder than the left
+            let t = i as f64 / sample_rate;
+            let amplitude = if (i / 3000) % 2 == 0 { 4.0 } else { 0.2 };
+            let x = amplitude * (std::f64::consts::TAU * 220.0 * t).sin();
+            let mut frame = [0.5 * x, x];
+            limiter.tick(&mut frame);
+            max_output = max_output.max(frame[0].abs()).max(frame[1].abs());
+        }
+        assert!(max_output <= ceiling + 1e-9);
+        assert!(max_output > ceiling - 0.05);
+        assert!(limiter.take_max_gain_reduction_db() > 10.0);
+    }
+
+    #[test]
+    fn quiet_signal_is_delayed() {
+        let mut limiter = Limiter::<f64>::new(LimiterParameters::default());
+        limiter.prepare(1000.0, 1, Duration::from_millis(10));
+        let output: Vec<f64> = (0..20)
+            .map(|i| {
+                let mut frame = [if i == 0 { 0.5 } else { 0.0 }];
+                limiter.tick(&mut frame);
+                frame[0]
+            })
+            .collect();
+        assert_eq!(output[10], 0.5);
+        assert_eq!(output.iter().filter(|x| **x != 0.0).count(), 1);
+    }
+
+    #[test]
+    fn sliding_minimum() {
+        let mut minimum = SlidingMinimum::new(3);
+        let values = [5.0, 3.0, 4.0, 6.0, 7.0, 1.0, 2.0, 8.0, 9.0, 9.0];
+        let output: Vec<f64> = values.iter().map(|&x| minimum.push(x)).collect();
+        assert_eq!(output, [5.0, 3.0, 3.0, 3.0, 4.0, 1.0, 1.0, 1.0, 2.0, 8.0]);
+    }
+}
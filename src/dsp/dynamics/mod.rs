@@ -0,0 +1,220 @@
+mod compressor;
+mod gate;
+mod limiter;
+
+pub use compressor::{Compressor, CompressorParameters};
+pub use gate::{Gate, GateParameters};
+pub use limiter::{Limiter, LimiterParameters};
+
+use super::DspFloat;
+
+/// How the level of a signal is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detector {
+    Peak,
+    /// Root mean square, follows the perceived loudness more closely than the peak level
+    Rms,
+}
+
+/// Follows the level of a signal, with separate attack and release times (in seconds).
+#[derive(Debug, Clone)]
+pub struct EnvelopeFollower<T = f32> {
+    detector: Detector,
+    sample_rate: T,
+    attack: T,
+    release: T,
+    attack_coefficient: T,
+    release_coefficient: T,
+    state: T,
+}
+
+impl<T: DspFloat> EnvelopeFollower<T> {
+    pub fn new(sample_rate: T, detector: Detector, attack: T, release: T) -> Self {
+        Self {
+            detector,
+            sample_rate,
+            attack,
+            release,
+            attack_coefficient: time_coefficient(sample_rate, attack),
+            release_coefficient: time_coefficient(sample_rate, release),
+            state: T::zero(),
+        }
+    }
+
+    pub fn detector(&self) -> Detector {
+        self.detector
+    }
+
+    pub fn set_detector(&mut self, detector: Detector) {
+        if detector != self.detector {
+            self.detector = detector;
+            self.reset();
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.set_times(self.attack, self.release);
+    }
+
+    /// Set the attack and release times, in seconds
+    pub fn set_times(&mut self, attack: T, release: T) {
+        self.attack = attack;
+        self.release = release;
+        self.attack_coefficient = time_coefficient(self.sample_rate, attack);
+        self.release_coefficient = time_coefficient(self.sample_rate, release);
+    }
+
+    /// The current level (linear)
+    pub fn level(&self) -> T {
+        match self.detector {
+            Detector::Peak => self.state,
+            Detector::Rms => self.state.sqrt(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.state = T::zero();
+    }
+
+    /// Process a sample, returning the current level (linear)
+    #[inline]
+    pub fn tick(&mut self, input: T) -> T {
+        let x = match self.detector {
+            Detector::Peak => input.abs(),
+            Detector::Rms => input * input,
+        };
+        let coefficient = if x > self.state {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+        self.state = x + coefficient * (self.state - x);
+        self.level()
+    }
+}
+
+/// Keeps track of the gain reduction for display in a meter. The max value is held until read,
+/// so that short peaks are not missed when the meter is only updated once per block.
+#[derive(Debug, Clone)]
+struct GainReductionMeter<T> {
+    current: T,
+    max: T,
+}
+
+impl<T: DspFloat> GainReductionMeter<T> {
+    fn new() -> Self {
+        Self {
+            current: T::zero(),
+            max: T::zero(),
+        }
+    }
+
+    #[inline]
+    fn update(&mut self, gain_reduction_db: T) {
+        self.current = gain_reduction_db;
+        self.max = self.max.max(gain_reduction_db);
+    }
+
+    fn take_max(&mut self) -> T {
+        std::mem::replace(&mut self.max, self.current)
+    }
+
+    fn reset(&mut self) {
+        self.current = T::zero();
+        self.max = T::zero();
+    }
+}
+
+/// Coefficient of a one-pole smoother that reaches 1 - 1/e of a step in `time` seconds
+fn time_coefficient<T: DspFloat>(sample_rate: T, time: T) -> T {
+    if time > T::zero() {
+        (-T::one() / (time * sample_rate)).exp()
+    } else {
+        T::zero()
+    }
+}
+
+/// Static curve of a downward compressor with a soft knee. Returns the gain (in dB, <= 0)
+/// to apply to a signal at `level_db`.
+fn compressor_gain_db<T: DspFloat>(level_db: T, threshold_db: T, ratio: T, knee_db: T) -> T {
+    let two = T::from_f32(2.0);
+    let overshoot = level_db - threshold_db;
+    let slope = T::one() / ratio.max(T::one()) - T::one();
+    if two * overshoot <= -knee_db {
+        T::zero()
+    } else if two * overshoot < knee_db {
+        let x = overshoot + knee_db / two;
+        slope * x * x / (two * knee_db)
+    } else {
+        slope * overshoot
+    }
+}
+
+/// Static curve of a downward expander with a soft knee, the mirror image of
+/// [`compressor_gain_db`] around the threshold. Returns the gain (in dB, <= 0).
+fn expander_gain_db<T: DspFloat>(level_db: T, threshold_db: T, ratio: T, knee_db: T) -> T {
+    let two = T::from_f32(2.0);
+    let overshoot = level_db - threshold_db;
+    let slope = ratio.max(T::one()) - T::one();
+    if two * overshoot >= knee_db {
+        T::zero()
+    } else if two * overshoot > -knee_db {
+        let x = overshoot - knee_db / two;
+        -slope * x * x / (two * knee_db)
+    } else {
+        slope * overshoot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rms_of_sine() {
+        let sample_rate = 48000.0;
+        let mut peak = EnvelopeFollower::new(sample_rate, Detector::Peak, 0.0, 0.5);
+        let mut rms = EnvelopeFollower::new(sample_rate, Detector::Rms, 0.05, 0.05);
+        for i in 0..48000 {
+            let x = (std::f64::consts::TAU * 1000.0 * i as f64 / sample_rate).sin();
+            peak.tick(x);
+            rms.tick(x);
+        }
+        assert!((peak.level() - 1.0).abs() < 0.01);
+        assert!((rms.level() - std::f64::consts::FRAC_1_SQRT_2).abs() < 0.01);
+    }
+
+    #[test]
+    fn attack_time_constant() {
+        let sample_rate = 48000.0;
+        let mut follower = EnvelopeFollower::new(sample_rate, Detector::Peak, 0.01, 0.1);
+        let level = (0..480).map(|_| follower.tick(1.0)).last().unwrap();
+        assert!((level - (1.0 - (-1.0f64).exp())).abs() < 1e-3);
+    }
+
+    #[test]
+    fn soft_knee_is_continuous() {
+        for (threshold, ratio, knee) in [(-20.0, 4.0, 6.0), (-10.0, 2.0, 0.0), (-30.0, 10.0, 12.0)]
+        {
+            let mut previous = compressor_gain_db(-60.0, threshold, ratio, knee);
+            let mut previous_expander = expander_gain_db(-60.0, threshold, ratio, knee);
+            for i in 1..=6000 {
+                let level: f64 = -60.0 + i as f64 * 0.01;
+                let gain = compressor_gain_db(level, threshold, ratio, knee);
+                let expander_gain = expander_gain_db(level, threshold, ratio, knee);
+                // The slope is bounded by the ratio, so a 0.01 dB step can't change the gain by much
+                assert!((gain - previous).abs() <= 0.01);
+                assert!((expander_gain - previous_expander).abs() <= 0.01 * ratio + 1e-9);
+                previous = gain;
+                previous_expander = expander_gain;
+            }
+            assert_eq!(
+                compressor_gain_db(threshold - knee, threshold, ratio, knee),
+                0.0
+            );
+            let above = compressor_gain_db(threshold + 20.0, threshold, ratio, knee);
+            assert!((above - (20.0 / ratio - 20.0)).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+mod stft;
+mod window;
+
+pub use stft::Stft;
+pub use window::Window;
+
+use num::Complex;
+
+use super::DspFloat;
+
+/// Radix-2 complex FFT. The twiddle factors are computed up front, so transforms don't allocate.
+#[derive(Debug, Clone)]
+pub struct Fft<T = f32> {
+    twiddles: Box<[Complex<T>]>,
+    bit_reverse: Box<[usize]>,
+}
+
+impl<T: DspFloat> Fft<T> {
+    /// Create an FFT of the given size, which must be a power of two
+    pub fn new(size: usize) -> Self {
+        assert!(size.is_power_of_two(), "FFT size must be a power of two");
+        let bits = size.trailing_zeros();
+        let bit_reverse = (0..size)
+            .map(|i| {
+                if bits == 0 {
+                    0
+                } else {
+                    i.reverse_bits() >> (usize::BITS - bits)
+                }
+            })
+            .collect();
+        Self {
+            twiddles: twiddles(size, size / 2),
+            bit_reverse,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.bit_reverse.len()
+    }
+
+    /// Forward transform, in place
+    pub fn forward(&self, data: &mut [Complex<T>]) {
+        self.transform(data, false);
+    }
+
+    /// Inverse transform, in place. The output is scaled by 1/N, so that the inverse of the
+    /// forward transform returns the original data.
+    pub fn inverse(&self, data: &mut [Complex<T>]) {
+        self.transform(data, true);
+        let scale = T::one() / T::from_f64(self.size() as f64);
+        for x in data.iter_mut() {
+            *x = *x * scale;
+        }
+    }
+
+    fn transform(&self, data: &mut [Complex<T>], inverse: bool) {
+        let n = self.size();
+        assert_eq!(data.len(), n);
+        for (i, &j) in self.bit_reverse.iter().enumerate() {
+            if j > i {
+                data.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+        while len <= n {
+            let half = len / 2;
+            let stride = n / len;
+            for block in data.chunks_exact_mut(len) {
+                let (a, b) = block.split_at_mut(half);
+                for (k, (a, b)) in a.iter_mut().zip(b.iter_mut()).enumerate() {
+                    let twiddle = self.twiddles[k * stride];
+                    let twiddle = if inverse { twiddle.conj() } else { twiddle };
+                    let t = *b * twiddle;
+                    *b = *a - t;
+                    *a = *a + t;
+                }
+            }
+            len *= 2;
+        }
+    }
+}
+
+/// FFT of real signals, computed with a complex FFT of half the size. A real signal of N
+/// samples has a spectrum of N / 2 + 1 bins, from DC up to and including Nyquist.
+#[derive(Debug, Clone)]
+pub struct RealFft<T = f32> {
+    fft: Fft<T>,
+    twiddles: Box<[Complex<T>]>,
+    scratch: Box<[Complex<T>]>,
+}
+
+impl<T: DspFloat> RealFft<T> {
+    /// Create an FFT of the given size, which must be a power of two, and at least 2
+    pub fn new(size: usize) -> Self {
+        assert!(
+            size.is_power_of_two() && size >= 2,
+            "FFT size must be a power of two"
+        );
+        let half = size / 2;
+        Self {
+            fft: Fft::new(half),
+            twiddles: twiddles(size, half + 1),
+            scratch: vec![Complex::new(T::zero(), T::zero()); half].into_boxed_slice(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        2 * self.scratch.len()
+    }
+
+    /// Number of bins in the spectrum
+    pub fn spectrum_size(&self) -> usize {
+        self.scratch.len() + 1
+    }
+
+    /// Transform `input` (of length [`RealFft::size`]) to `spectrum`
+    /// (of length [`RealFft::spectrum_size`])
+    pub fn forward(&mut self, input: &[T], spectrum: &mut [Complex<T>]) {
+        let half = self.scratch.len();
+        assert_eq!(input.len(), 2 * half);
+        assert_eq!(spectrum.len(), half + 1);
+
+        // Pack the even samples in the real part and the odd samples in the imaginary part
+        for (z, x) in self.scratch.iter_mut().zip(input.chunks_exact(2)) {
+            *z = Complex::new(x[0], x[1]);
+        }
+        self.fft.forward(&mut self.scratch);
+
+        // Separate the spectra of the even and odd samples, and combine them
+        let half_factor = T::from_f32(0.5);
+        for (k, bin) in spectrum.iter_mut().enumerate() {
+            let z = self.scratch[k % half];
+            let z_mirror = self.scratch[(half - k) % half].conj();
+            let even = (z + z_mirror) * half_factor;
+            let odd = (z - z_mirror) * Complex::new(T::zero(), -half_factor);
+            *bin = even + self.twiddles[k] * odd;
+        }
+    }
+
+    /// Transform `spectrum` back to `output`. The output is scaled by 1/N, so that the inverse
+    /// of the forward transform returns the original signal. The imaginary parts of the DC and
+    /// Nyquist bins are ignored.
+    pub fn inverse(&mut self, spectrum: &[Complex<T>], output: &mut [T]) {
+        let half = self.scratch.len();
+        assert_eq!(spectrum.len(), half + 1);
+        assert_eq!(output.len(), 2 * half);
+
+        let half_factor = T::from_f32(0.5);
+        let i = Complex::new(T::zero(), T::one());
+        for (k, z) in self.scratch.iter_mut().enumerate() {
+            let x = spectrum[k];
+            let x_mirror = spectrum[half - k].conj();
+            let even = (x + x_mirror) * half_factor;
+            let odd = (x - x_mirror) * half_factor * self.twiddles[k].conj();
+            *z = even + i * odd;
+        }
+        self.fft.inverse(&mut self.scratch);
+
+        for (x, z) in output.chunks_exact_mut(2).zip(self.scratch.iter()) {
+            x[0] = z.re;
+            x[1] = z.im;
+        }
+    }
+}
+
+/// The first `count` powers of exp(-2 pi i / size)
+fn twiddles<T: DspFloat>(size: usize, count: usize) -> Box<[Complex<T>]> {
+    (0..count)
+        .map(|k| {
+            let angle = -std::f64::consts::TAU * k as f64 / size as f64;
+            Complex::new(T::from_f64(angle.cos()), T::from_f64(angle.sin()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn naive_dft(input: &[Complex<f64>]) -> Vec<Complex<f64>> {
+        let n = input.len();
+        (0..n)
+            .map(|k| {
+                input
+                    .iter()
+                    .enumerate()
+                    .map(|(j, x)| {
+                        let angle = -std::f64::consts::TAU * (j * k) as f64 / n as f64;
+                        x * Complex::new(angle.cos(), angle.sin())
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn test_signal(n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|i| (i as f64 * 0.73).sin() + 0.5 * (i as f64 * 2.1).cos() + 0.1 * i as f64)
+            .collect()
+    }
+
+    #[test]
+    fn matches_naive_dft() {
+        for size in [1, 2, 4, 8, 64] {
+            let input: Vec<Complex<f64>> = test_signal(2 * size)
+                .chunks_exact(2)
+                .map(|x| Complex::new(x[0], x[1]))
+                .collect();
+            let expected = naive_dft(&input);
+            let mut data = input.clone();
+            let fft = Fft::new(size);
+            fft.forward(&mut data);
+            for (a, b) in data.iter().zip(expected.iter()) {
+                assert!((a - b).norm() < 1e-9);
+            }
+            fft.inverse(&mut data);
+            for (a, b) in data.iter().zip(input.iter()) {
+                assert!((a - b).norm() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn real_fft_matches_naive_dft() {
+        for size in [2, 4, 16, 256] {
+            let input = test_signal(size);
+            let expected = naive_dft(
+                &input
+                    .iter()
+                    .map(|&x| Complex::new(x, 0.0))
+                    .collect::<Vec<_>>(),
+            );
+            let mut fft = RealFft::new(size);
+            let mut spectrum = vec![Complex::new(0.0, 0.0); fft.spectrum_size()];
+            fft.forward(&input, &mut spectrum);
+            for (a, b) in spectrum.iter().zip(expected.iter()) {
+                assert!((a - b).norm() < 1e-9, "{a} != {b}");
+            }
+
+            let mut output = vec![0.0; size];
+            fft.inverse(&spectrum, &mut output);
+            for (a, b) in output.iter().zip(input.iter()) {
+                assert!((a - b).abs() < 1e-12);
+            }
+        }
+    }
+}
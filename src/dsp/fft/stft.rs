@@ -0,0 +1,220 @@
+use num::Complex;
+
+use super::{RealFft, Window};
+use crate::dsp::DspFloat;
+
+/// Short-time Fourier transform with overlap-add resynthesis, for spectral processing.
+///
+/// The input is buffered into overlapping frames of `fft_size` samples, spaced `hop_size`
+/// samples apart. Each frame is windowed, transformed, handed to a processing closure,
+/// transformed back, windowed again and added to the output. Host blocks of any size can be
+/// processed, the frames don't need to line up with the blocks.
+///
+/// The window is applied both before and after processing, so the squared window should add
+/// up to a constant at the chosen hop size for perfect reconstruction, e.g. a
+/// [`Window::Hann`] with a hop size of at most `fft_size / 4`.
+///
+/// All memory is allocated in [`Stft::new`], which should be called from
+/// [`Plugin::prepare`](crate::Plugin::prepare).
+#[derive(Debug, Clone)]
+pub struct Stft<T = f32> {
+    fft: RealFft<T>,
+    hop_size: usize,
+    window: Box<[T]>,
+    scale: T,
+    frame: Box<[T]>,
+    spectrum: Box<[Complex<T>]>,
+    channels: Vec<StftChannel<T>>,
+}
+
+#[derive(Debug, Clone)]
+struct StftChannel<T> {
+    input: Box<[T]>,
+    output: Box<[T]>,
+    count: usize,
+}
+
+impl<T: DspFloat> Stft<T> {
+    pub fn new(num_channels: usize, fft_size: usize, hop_size: usize, window: Window) -> Self {
+        assert!(
+            hop_size > 0 && hop_size <= fft_size,
+            "Hop size must be in the range [1, fft_size]"
+        );
+        let fft = RealFft::new(fft_size);
+        let mut window_values = vec![T::zero(); fft_size].into_boxed_slice();
+        window.fill(&mut window_values);
+
+        // Normalize by the sum of the overlapping (squared) windows
+        let window_sum = window_values.iter().fold(T::zero(), |sum, &w| sum + w * w);
+        let scale = T::from_f64(hop_size as f64) / window_sum;
+
+        let channel = StftChannel {
+            input: vec![T::zero(); fft_size].into_boxed_slice(),
+            output: vec![T::zero(); fft_size].into_boxed_slice(),
+            count: 0,
+        };
+
+        Self {
+            spectrum: vec![Complex::new(T::zero(), T::zero()); fft.spectrum_size()]
+                .into_boxed_slice(),
+            fft,
+            hop_size,
+            window: window_values,
+            scale,
+            frame: vec![T::zero(); fft_size].into_boxed_slice(),
+            channels: vec![channel; num_channels],
+        }
+    }
+
+    pub fn fft_size(&self) -> usize {
+        self.frame.len()
+    }
+
+    pub fn hop_size(&self) -> usize {
+        self.hop_size
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Number of bins in the spectra passed to the processing closure
+    pub fn spectrum_size(&self) -> usize {
+        self.spectrum.len()
+    }
+
+    /// The delay introduced by the buffering, in samples
+    pub fn latency_samples(&self) -> usize {
+        self.fft_size() - 1
+    }
+
+    pub fn reset(&mut self) {
+        for channel in self.channels.iter_mut() {
+            channel.input.fill(T::zero());
+            channel.output.fill(T::zero());
+            channel.count = 0;
+        }
+    }
+
+    /// Process all channels in place. `f` is called with the channel index and the spectrum
+    /// of every frame that is completed.
+    pub fn process(
+        &mut self,
+        channels: &mut [&mut [T]],
+        mut f: impl FnMut(usize, &mut [Complex<T>]),
+    ) {
+        for (index, samples) in channels.iter_mut().enumerate() {
+            self.process_channel(index, samples, |spectrum| f(index, spectrum));
+        }
+    }
+
+    /// Process a single channel in place, `f` is called with the spectrum of every frame
+    /// that is completed.
+    pub fn process_channel(
+        &mut self,
+        channel_index: usize,
+        samples: &mut [T],
+        mut f: impl FnMut(&mut [Complex<T>]),
+    ) {
+        let fft_size = self.frame.len();
+        let hop_size = self.hop_size;
+        let channel = &mut self.channels[channel_index];
+
+        for sample in samples.iter_mut() {
+            channel.input[fft_size - hop_size + channel.count] = *sample;
+            channel.count += 1;
+
+            if channel.count == hop_size {
+                channel.count = 0;
+
+                for ((frame, &input), &window) in self
+                    .frame
+                    .iter_mut()
+                    .zip(channel.input.iter())
+                    .zip(self.window.iter())
+                {
+                    *frame = input * window;
+                }
+                self.fft.forward(&self.frame, &mut self.spectrum);
+                f(&mut self.spectrum);
+                self.fft.inverse(&self.spectrum, &mut self.frame);
+
+                // The first hop of the output has been consumed, shift it out and add the
+                // new frame
+                channel.output.copy_within(hop_size.., 0);
+                channel.output[fft_size - hop_size..].fill(T::zero());
+                for ((output, &frame), &window) in channel
+                    .output
+                    .iter_mut()
+                    .zip(self.frame.iter())
+                    .zip(self.window.iter())
+                {
+                    *output = *output + frame * window * self.scale;
+                }
+                channel.input.copy_within(hop_size.., 0);
+
+                *sample = channel.output[0];
+            } else {
+                *sample = channel.output[channel.count];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reconstruction() {
+        let input: Vec<f64> = (0..4000)
+            .map(|i| (i as f64 * 0.05).sin() + 0.3 * (i as f64 * 0.71).cos())
+            .collect();
+
+        for (fft_size, hop_size, window) in [
+            (256, 64, Window::Hann),
+            (512, 128, Window::Hann),
+            (64, 64, Window::Rectangular),
+        ] {
+            let mut stft = Stft::<f64>::new(2, fft_size, hop_size, window);
+            let latency = stft.latency_samples();
+
+            // Process in irregular block sizes, with the second channel inverted
+            let mut left = input.clone();
+            let mut right: Vec<f64> = input.iter().map(|x| -x).collect();
+            let mut start = 0;
+            for block_size in [1, 17, 300, 64, 1000].iter().cycle() {
+                let end = (start + block_size).min(input.len());
+                stft.process(
+                    &mut [&mut left[start..end], &mut right[start..end]],
+                    |_, _| {},
+                );
+                start = end;
+                if start == input.len() {
+                    break;
+                }
+            }
+
+            for i in (fft_size + latency)..input.len() {
+                assert!((left[i] - input[i - latency]).abs() < 1e-9);
+                assert!((right[i] + input[i - latency]).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn spectral_processing() {
+        // Remove a sine on top of a constant signal. The windowed constant only occupies the
+        // first two bins, and the sine is far above them.
+        let mut stft = Stft::<f64>::new(1, 128, 32, Window::Hann);
+        let mut samples: Vec<f64> = (0..2048)
+            .map(|i| 0.5 + (std::f64::consts::TAU * i as f64 / 8.0).sin())
+            .collect();
+        stft.process_channel(0, &mut samples, |spectrum| {
+            spectrum[2..].fill(Complex::new(0.0, 0.0));
+        });
+        for sample in &samples[512..] {
+            assert!((sample - 0.5).abs() < 1e-9);
+        }
+    }
+}
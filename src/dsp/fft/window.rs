@@ -0,0 +1,47 @@
+use crate::dsp::DspFloat;
+
+/// Window functions for spectral analysis. The windows are periodic, i.e. a window of size N
+/// is the first N points of a symmetric window of size N + 1, which is what is needed for
+/// overlap-add processing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+    /// 4-term Blackman-Harris, with sidelobes below -92 dB
+    BlackmanHarris,
+}
+
+impl Window {
+    /// The value of the window at `index`, for a window of length `size`
+    pub fn value<T: DspFloat>(&self, index: usize, size: usize) -> T {
+        let x = std::f64::consts::TAU * index as f64 / size as f64;
+        let value = match self {
+            Self::Rectangular => 1.0,
+            Self::Hann => 0.5 - 0.5 * x.cos(),
+            Self::Hamming => 0.54 - 0.46 * x.cos(),
+            Self::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+            Self::BlackmanHarris => {
+                0.35875 - 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos() - 0.01168 * (3.0 * x).cos()
+            }
+        };
+        T::from_f64(value)
+    }
+
+    /// Fill `window` with the window function
+    pub fn fill<T: DspFloat>(&self, window: &mut [T]) {
+        let size = window.len();
+        for (i, value) in window.iter_mut().enumerate() {
+            *value = self.value(i, size);
+        }
+    }
+
+    /// Multiply `samples` with the window function
+    pub fn apply<T: DspFloat>(&self, samples: &mut [T]) {
+        let size = samples.len();
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample = *sample * self.value(i, size);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use num::Complex;
+
+use super::{FilterType, FrequencyResponse, unit_delay};
+use crate::dsp::DspFloat;
+
+/// Normalized biquad coefficients (a0 = 1)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiquadCoefficients<T = f32> {
+    pub b0: T,
+    pub b1: T,
+    pub b2: T,
+    pub a1: T,
+    pub a2: T,
+}
+
+impl<T: DspFloat> BiquadCoefficients<T> {
+    /// Coefficients for a filter that passes the signal through unchanged
+    pub fn identity() -> Self {
+        Self {
+            b0: T::one(),
+            b1: T::zero(),
+            b2: T::zero(),
+            a1: T::zero(),
+            a2: T::zero(),
+        }
+    }
+
+    /// Design a filter using the formulas from Robert Bristow-Johnson's Audio EQ Cookbook.
+    /// `gain_db` is only used by the peak and shelving filters. For the shelving filters,
+    /// a `q` of 1/sqrt(2) gives the steepest slope without overshoot.
+    pub fn new(filter_type: FilterType, sample_rate: T, frequency: T, q: T, gain_db: T) -> Self {
+        let two = T::from_f32(2.0);
+        let frequency = frequency
+            .max(T::zero())
+            .min(T::from_f32(0.4999) * sample_rate);
+        let omega = T::TAU() * frequency / sample_rate;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / (two * q.max(T::from_f32(1.0e-3)));
+        let a = T::from_f32(10.0).powf(gain_db / T::from_f32(40.0));
+
+        let (b0, b1, b2, a0, a1, a2) = match filter_type {
+            FilterType::LowPass => {
+                let b1 = T::one() - cos;
+                (
+                    b1 / two,
+                    b1,
+                    b1 / two,
+                    T::one() + alpha,
+                    -two * cos,
+                    T::one() - alpha,
+                )
+            }
+            FilterType::HighPass => {
+                let b1 = T::one() + cos;
+                (
+                    b1 / two,
+                    -b1,
+                    b1 / two,
+                    T::one() + alpha,
+                    -two * cos,
+                    T::one() - alpha,
+                )
+            }
+            FilterType::BandPass => (
+                alpha,
+                T::zero(),
+                -alpha,
+                T::one() + alpha,
+                -two * cos,
+                T::one() - alpha,
+            ),
+            FilterType::Notch => (
+                T::one(),
+                -two * cos,
+                T::one(),
+                T::one() + alpha,
+                -two * cos,
+                T::one() - alpha,
+            ),
+            FilterType::Peak => (
+                T::one() + alpha * a,
+                -two * cos,
+                T::one() - alpha * a,
+                T::one() + alpha / a,
+                -two * cos,
+                T::one() - alpha / a,
+            ),
+            FilterType::LowShelf => {
+                let sqrt_a_alpha = two * a.sqrt() * alpha;
+                let (ap1, am1) = (a + T::one(), a - T::one());
+                (
+                    a * (ap1 - am1 * cos + sqrt_a_alpha),
+                    two * a * (am1 - ap1 * cos),
+                    a * (ap1 - am1 * cos - sqrt_a_alpha),
+                    ap1 + am1 * cos + sqrt_a_alpha,
+                    -two * (am1 + ap1 * cos),
+                    ap1 + am1 * cos - sqrt_a_alpha,
+                )
+            }
+            FilterType::HighShelf => {
+                let sqrt_a_alpha = two * a.sqrt() * alpha;
+                let (ap1, am1) = (a + T::one(), a - T::one());
+                (
+                    a * (ap1 + am1 * cos + sqrt_a_alpha),
+                    -two * a * (am1 + ap1 * cos),
+                    a * (ap1 + am1 * cos - sqrt_a_alpha),
+                    ap1 - am1 * cos + sqrt_a_alpha,
+                    two * (am1 - ap1 * cos),
+                    ap1 - am1 * cos - sqrt_a_alpha,
+                )
+            }
+            FilterType::AllPass => (
+                T::one() - alpha,
+                -two * cos,
+                T::one() + alpha,
+                T::one() + alpha,
+                -two * cos,
+                T::one() - alpha,
+            ),
+        };
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    pub fn low_pass(sample_rate: T, frequency: T, q: T) -> Self {
+        Self::new(FilterType::LowPass, sample_rate, frequency, q, T::zero())
+    }
+
+    pub fn high_pass(sample_rate: T, frequency: T, q: T) -> Self {
+        Self::new(FilterType::HighPass, sample_rate, frequency, q, T::zero())
+    }
+
+    pub fn band_pass(sample_rate: T, frequency: T, q: T) -> Self {
+        Self::new(FilterType::BandPass, sample_rate, frequency, q, T::zero())
+    }
+
+    pub fn notch(sample_rate: T, frequency: T, q: T) -> Self {
+        Self::new(FilterType::Notch, sample_rate, frequency, q, T::zero())
+    }
+
+    pub fn peak(sample_rate: T, frequency: T, q: T, gain_db: T) -> Self {
+        Self::new(FilterType::Peak, sample_rate, frequency, q, gain_db)
+    }
+
+    pub fn low_shelf(sample_rate: T, frequency: T, q: T, gain_db: T) -> Self {
+        Self::new(FilterType::LowShelf, sample_rate, frequency, q, gain_db)
+    }
+
+    pub fn high_shelf(sample_rate: T, frequency: T, q: T, gain_db: T) -> Self {
+        Self::new(FilterType::HighShelf, sample_rate, frequency, q, gain_db)
+    }
+
+    pub fn all_pass(sample_rate: T, frequency: T, q: T) -> Self {
+        Self::new(FilterType::AllPass, sample_rate, frequency, q, T::zero())
+    }
+}
+
+impl<T: DspFloat> Default for BiquadCoefficients<T> {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl<T: DspFloat> FrequencyResponse<T> for BiquadCoefficients<T> {
+    fn frequency_response(&self, frequency: T, sample_rate: T) -> Complex<T> {
+        let z1 = unit_delay(frequency, sample_rate);
+        let z2 = z1 * z1;
+        let one = Complex::new(T::one(), T::zero());
+        (one * self.b0 + z1 * self.b1 + z2 * self.b2) / (one + z1 * self.a1 + z2 * self.a2)
+    }
+}
+
+/// Biquad filter, implemented in the transposed direct form II.
+#[derive(Debug, Clone)]
+pub struct Biquad<T = f32> {
+    coefficients: BiquadCoefficients<T>,
+    s1: T,
+    s2: T,
+}
+
+impl<T: DspFloat> Biquad<T> {
+    pub fn new(coefficients: BiquadCoefficients<T>) -> Self {
+        Self {
+            coefficients,
+            s1: T::zero(),
+            s2: T::zero(),
+        }
+    }
+
+    pub fn coefficients(&self) -> &BiquadCoefficients<T> {
+        &self.coefficients
+    }
+
+    /// Update the coefficients, keeping the filter state. Changing the coefficients
+    /// rapidly can cause artifacts, use a [`StateVariableFilter`](super::StateVariableFilter)
+    /// for audio rate modulation.
+    pub fn set_coefficients(&mut self, coefficients: BiquadCoefficients<T>) {
+        self.coefficients = coefficients;
+    }
+
+    pub fn reset(&mut self) {
+        self.s1 = T::zero();
+        self.s2 = T::zero();
+    }
+
+    #[inline]
+    pub fn tick(&mut self, input: T) -> T {
+        let c = &self.coefficients;
+        let output = c.b0 * input + self.s1;
+        self.s1 = c.b1 * input - c.a1 * output + self.s2;
+        self.s2 = c.b2 * input - c.a2 * output;
+        output
+    }
+
+    pub fn process(&mut self, samples: &mut [T]) {
+        for sample in samples.iter_mut() {
+            *sample = self.tick(*sample);
+        }
+    }
+}
+
+impl<T: DspFloat> FrequencyResponse<T> for Biquad<T> {
+    fn frequency_response(&self, frequency: T, sample_rate: T) -> Complex<T> {
+        self.coefficients.frequency_response(frequency, sample_rate)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dsp::gain_to_db;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+    const SQRT_HALF: f64 = std::f64::consts::FRAC_1_SQRT_2;
+
+    fn assert_db(coefficients: &BiquadCoefficients<f64>, frequency: f64, expected_db: f64) {
+        let db = coefficients.magnitude_db(frequency, SAMPLE_RATE);
+        assert!(
+            (db - expected_db).abs() < 0.01,
+            "Expected {expected_db} dB at {frequency} Hz, got {db} dB"
+        );
+    }
+
+    #[test]
+    fn low_and_high_pass() {
+        let low_pass = BiquadCoefficients::low_pass(SAMPLE_RATE, 1000.0, SQRT_HALF);
+        assert_db(&low_pass, 0.0, 0.0);
+        assert_db(&low_pass, 1000.0, gain_to_db(SQRT_HALF));
+        assert!(low_pass.magnitude_db(20000.0, SAMPLE_RATE) < -50.0);
+
+        let high_pass = BiquadCoefficients::high_pass(SAMPLE_RATE, 1000.0, SQRT_HALF);
+        assert_db(&high_pass, 24000.0, 0.0);
+        assert_db(&high_pass, 1000.0, gain_to_db(SQRT_HALF));
+        assert!(high_pass.magnitude_db(20.0, SAMPLE_RATE) < -50.0);
+    }
+
+    #[test]
+    fn band_pass_and_notch() {
+        let band_pass = BiquadCoefficients::band_pass(SAMPLE_RATE, 2000.0, 2.0);
+        assert_db(&band_pass, 2000.0, 0.0);
+        assert!(band_pass.magnitude_db(200.0, SAMPLE_RATE) < -20.0);
+
+        let notch = BiquadCoefficients::notch(SAMPLE_RATE, 2000.0, 2.0);
+        assert!(notch.magnitude_db(2000.0, SAMPLE_RATE) < -100.0);
+        assert_db(&notch, 0.0, 0.0);
+    }
+
+    #[test]
+    fn peak_and_shelves() {
+        let peak = BiquadCoefficients::peak(SAMPLE_RATE, 3000.0, 1.0, 6.0);
+        assert_db(&peak, 3000.0, 6.0);
+        assert_db(&peak, 0.0, 0.0);
+
+        let low_shelf = BiquadCoefficients::low_shelf(SAMPLE_RATE, 300.0, SQRT_HALF, -9.0);
+        assert_db(&low_shelf, 0.0, -9.0);
+        assert_db(&low_shelf, 300.0, -4.5);
+        assert_db(&low_shelf, 24000.0, 0.0);
+
+        let high_shelf = BiquadCoefficients::high_shelf(SAMPLE_RATE, 5000.0, SQRT_HALF, 4.0);
+        assert_db(&high_shelf, 0.0, 0.0);
+        assert_db(&high_shelf, 5000.0, 2.0);
+        assert_db(&high_shelf, 24000.0, 4.0);
+    }
+
+    #[test]
+    fn all_pass_has_unit_magnitude() {
+        let all_pass = BiquadCoefficients::all_pass(SAMPLE_RATE, 1000.0, 0.5);
+        for frequency in [10.0, 100.0, 1000.0, 10000.0] {
+            assert_db(&all_pass, frequency, 0.0);
+        }
+        assert!((all_pass.phase(1000.0, SAMPLE_RATE).abs() - std::f64::consts::PI).abs() < 1e-6);
+    }
+
+    #[test]
+    fn processing_matches_response() {
+        let coefficients = BiquadCoefficients::peak(SAMPLE_RATE, 1000.0, 2.0, -12.0);
+        let mut filter = Biquad::new(coefficients);
+        // Steady state response to a sine at a bin-aligned frequency
+        let frequency = 1000.0;
+        let output: Vec<f64> = (0..9600)
+            .map(|i| {
+                filter.tick((std::f64::consts::TAU * frequency * i as f64 / SAMPLE_RATE).sin())
+            })
+            .collect();
+        let peak = output[4800..]
+            .iter()
+            .fold(0.0f64, |acc, x| acc.max(x.abs()));
+        assert!((peak - coefficients.magnitude(frequency, SAMPLE_RATE)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn cascaded_response() {
+        let filters = [
+            BiquadCoefficients::peak(SAMPLE_RATE, 1000.0, 1.0, 3.0),
+            BiquadCoefficients::peak(SAMPLE_RATE, 1000.0, 1.0, 3.0),
+        ];
+        let db = filters.as_slice().magnitude_db(1000.0, SAMPLE_RATE);
+        assert!((db - 6.0).abs() < 1e-6);
+    }
+}
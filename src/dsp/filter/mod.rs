@@ -0,0 +1,65 @@
+mod biquad;
+mod svf;
+
+pub use biquad::{Biquad, BiquadCoefficients};
+pub use svf::{StateVariableFilter, SvfOutput};
+
+use num::Complex;
+
+use super::{DspFloat, gain_to_db};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    LowPass,
+    HighPass,
+    /// Band pass with 0 dB gain at the center frequency
+    BandPass,
+    Notch,
+    /// Peaking (bell) EQ
+    Peak,
+    LowShelf,
+    HighShelf,
+    AllPass,
+}
+
+impl FilterType {
+    /// True if the filter type uses the gain parameter
+    pub fn has_gain(&self) -> bool {
+        matches!(self, Self::Peak | Self::LowShelf | Self::HighShelf)
+    }
+}
+
+/// Evaluation of the frequency response of a filter, e.g. for drawing EQ curves in the editor.
+pub trait FrequencyResponse<T: DspFloat> {
+    /// The complex response at `frequency` (in Hz), for a filter running at `sample_rate`
+    fn frequency_response(&self, frequency: T, sample_rate: T) -> Complex<T>;
+
+    fn magnitude(&self, frequency: T, sample_rate: T) -> T {
+        self.frequency_response(frequency, sample_rate).norm()
+    }
+
+    fn magnitude_db(&self, frequency: T, sample_rate: T) -> T {
+        gain_to_db(self.magnitude(frequency, sample_rate))
+    }
+
+    /// Phase response in radians
+    fn phase(&self, frequency: T, sample_rate: T) -> T {
+        self.frequency_response(frequency, sample_rate).arg()
+    }
+}
+
+/// The response of a chain of filters is the product of the individual responses
+impl<T: DspFloat, F: FrequencyResponse<T>> FrequencyResponse<T> for [F] {
+    fn frequency_response(&self, frequency: T, sample_rate: T) -> Complex<T> {
+        self.iter()
+            .fold(Complex::new(T::one(), T::zero()), |acc, filter| {
+                acc * filter.frequency_response(frequency, sample_rate)
+            })
+    }
+}
+
+/// The point on the unit circle, z^-1, corresponding to `frequency`
+fn unit_delay<T: DspFloat>(frequency: T, sample_rate: T) -> Complex<T> {
+    let omega = T::TAU() * frequency / sample_rate;
+    Complex::new(omega.cos(), -omega.sin())
+}
@@ -0,0 +1,443 @@
+use std::fmt::Display;
+
+use crate::Transport;
+use crate::dsp::DspFloat;
+
+/// Tempo used for synced rates when the host does not report one
+const DEFAULT_TEMPO: f64 = 120.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    /// Rising saw
+    Saw,
+    Square,
+    /// A new random value at the start of every cycle, held for the whole cycle
+    SampleAndHold,
+    /// Random values smoothly interpolated over the cycle
+    SmoothRandom,
+}
+
+/// Length of a note, in fractions of a whole note
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteValue {
+    FourWholes,
+    TwoWholes,
+    Whole,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+}
+
+impl NoteValue {
+    pub const ALL: [NoteValue; 8] = [
+        NoteValue::FourWholes,
+        NoteValue::TwoWholes,
+        NoteValue::Whole,
+        NoteValue::Half,
+        NoteValue::Quarter,
+        NoteValue::Eighth,
+        NoteValue::Sixteenth,
+        NoteValue::ThirtySecond,
+    ];
+
+    /// Length of the note, in quarter notes
+    pub fn quarters(&self) -> f64 {
+        match self {
+            NoteValue::FourWholes => 16.0,
+            NoteValue::TwoWholes => 8.0,
+            NoteValue::Whole => 4.0,
+            NoteValue::Half => 2.0,
+            NoteValue::Quarter => 1.0,
+            NoteValue::Eighth => 0.5,
+            NoteValue::Sixteenth => 0.25,
+            NoteValue::ThirtySecond => 0.125,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            NoteValue::FourWholes => "4/1",
+            NoteValue::TwoWholes => "2/1",
+            NoteValue::Whole => "1/1",
+            NoteValue::Half => "1/2",
+            NoteValue::Quarter => "1/4",
+            NoteValue::Eighth => "1/8",
+            NoteValue::Sixteenth => "1/16",
+            NoteValue::ThirtySecond => "1/32",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteModifier {
+    Straight,
+    /// One and a half times as long as the straight note
+    Dotted,
+    /// Three notes in the time of two straight notes
+    Triplet,
+}
+
+impl NoteModifier {
+    fn factor(&self) -> f64 {
+        match self {
+            NoteModifier::Straight => 1.0,
+            NoteModifier::Dotted => 1.5,
+            NoteModifier::Triplet => 2.0 / 3.0,
+        }
+    }
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            NoteModifier::Straight => "",
+            NoteModifier::Dotted => "D",
+            NoteModifier::Triplet => "T",
+        }
+    }
+}
+
+/// A tempo synced note length, e.g. a dotted eighth note. Displayed as "1/8D".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteDivision {
+    pub value: NoteValue,
+    pub modifier: NoteModifier,
+}
+
+impl NoteDivision {
+    /// Number of divisions in [`NoteDivision::ALL`]
+    pub const COUNT: usize = NoteValue::ALL.len() * 3;
+
+    /// All divisions, from the longest to the shortest
+    pub const ALL: [NoteDivision; Self::COUNT] = {
+        const MODIFIERS: [NoteModifier; 3] = [
+            NoteModifier::Dotted,
+            NoteModifier::Straight,
+            NoteModifier::Triplet,
+        ];
+        let mut all = [NoteDivision::new(NoteValue::Quarter, NoteModifier::Straight); Self::COUNT];
+        let mut i = 0;
+        while i < Self::COUNT {
+            all[i] = NoteDivision::new(NoteValue::ALL[i / 3], MODIFIERS[i % 3]);
+            i += 1;
+        }
+        all
+    };
+
+    pub const fn new(value: NoteValue, modifier: NoteModifier) -> Self {
+        Self { value, modifier }
+    }
+
+    pub const fn straight(value: NoteValue) -> Self {
+        Self::new(value, NoteModifier::Straight)
+    }
+
+    pub const fn dotted(value: NoteValue) -> Self {
+        Self::new(value, NoteModifier::Dotted)
+    }
+
+    pub const fn triplet(value: NoteValue) -> Self {
+        Self::new(value, NoteModifier::Triplet)
+    }
+
+    /// Length of the division, in quarter notes
+    pub fn quarters(&self) -> f64 {
+        self.value.quarters() * self.modifier.factor()
+    }
+
+    /// Index of the division in [`NoteDivision::ALL`]
+    pub fn index(&self) -> usize {
+        Self::ALL.iter().position(|x| x == self).unwrap()
+    }
+
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+}
+
+impl Display for NoteDivision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.value.name(), self.modifier.suffix())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LfoRate {
+    /// Frequency in Hz
+    Free(f64),
+    /// One cycle per note division, following the host tempo
+    Synced(NoteDivision),
+}
+
+/// Low frequency oscillator for modulation, with an output in the range [-1, 1].
+///
+/// Call [`Lfo::update_transport`] once per block, before calling [`Lfo::tick`] for the
+/// samples of the block. The phase is reset when the host transport starts. Synced rates
+/// follow the host tempo, and while playing their phase is locked to the song position,
+/// so the LFO stays in time when the host loops or the playhead is moved.
+#[derive(Debug, Clone)]
+pub struct Lfo<T = f32> {
+    shape: LfoShape,
+    rate: LfoRate,
+    sample_rate: f64,
+    samples_per_quarter: f64,
+    phase: f64,
+    increment: f64,
+    playing: bool,
+    last_position: Option<f64>,
+    samples_since_update: usize,
+    random_state: u32,
+    previous_random: T,
+    current_random: T,
+}
+
+impl<T: DspFloat> Lfo<T> {
+    pub fn new(sample_rate: f64, shape: LfoShape, rate: LfoRate) -> Self {
+        let mut this = Self {
+            shape,
+            rate,
+            sample_rate,
+            samples_per_quarter: 60.0 * sample_rate / DEFAULT_TEMPO,
+            phase: 0.0,
+            increment: 0.0,
+            playing: false,
+            last_position: None,
+            samples_since_update: 0,
+            random_state: 0x9E37_79B9,
+            previous_random: T::zero(),
+            current_random: T::zero(),
+        };
+        this.update_increment();
+        this.reset();
+        this
+    }
+
+    pub fn shape(&self) -> LfoShape {
+        self.shape
+    }
+
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    pub fn rate(&self) -> LfoRate {
+        self.rate
+    }
+
+    pub fn set_rate(&mut self, rate: LfoRate) {
+        self.rate = rate;
+        self.update_increment();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.samples_per_quarter *= sample_rate / self.sample_rate;
+        self.sample_rate = sample_rate;
+        self.update_increment();
+    }
+
+    /// The current frequency in Hz
+    pub fn frequency(&self) -> f64 {
+        self.increment * self.sample_rate
+    }
+
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    /// Set the phase, in the range [0, 1)
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    /// Reset the phase, and start a new random cycle
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+        self.next_random();
+        self.next_random();
+    }
+
+    /// Update the tempo and position from the host. Should be called at the start of
+    /// every block.
+    pub fn update_transport(&mut self, transport: &Transport) {
+        let started = transport.playing && !self.playing;
+        self.playing = transport.playing;
+
+        if let LfoRate::Synced(division) = self.rate
+            && transport.playing
+            && let Some(position) = transport.position_quarters
+        {
+            // Only resync when the position jumps, to not disturb the random shapes
+            let expected = self
+                .last_position
+                .map(|last| last + self.samples_since_update as f64 / self.samples_per_quarter);
+            let jumped = expected.is_none_or(|expected| {
+                (position - expected).abs() * self.samples_per_quarter > 1.0
+            });
+            if started || jumped {
+                self.reset();
+                self.set_phase(position / division.quarters());
+            }
+        } else if started {
+            self.reset();
+        }
+
+        self.last_position = transport.position_quarters.filter(|_| transport.playing);
+        self.samples_since_update = 0;
+        let tempo = transport.tempo.unwrap_or(DEFAULT_TEMPO);
+        self.samples_per_quarter = 60.0 * self.sample_rate / tempo;
+        self.update_increment();
+    }
+
+    #[inline]
+    pub fn tick(&mut self) -> T {
+        let value = self.value();
+        self.phase += self.increment;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.fract();
+            self.next_random();
+        }
+        self.samples_since_update += 1;
+        value
+    }
+
+    pub fn process(&mut self, output: &mut [T]) {
+        for sample in output.iter_mut() {
+            *sample = self.tick();
+        }
+    }
+
+    fn value(&self) -> T {
+        let phase = T::from_f64(self.phase);
+        let one = T::one();
+        let two = T::from_f32(2.0);
+        match self.shape {
+            LfoShape::Sine => (T::TAU() * phase).sin(),
+            LfoShape::Triangle => {
+                one - T::from_f32(4.0)
+                    * ((phase + T::from_f32(0.25)).fract() - T::from_f32(0.5)).abs()
+            }
+            LfoShape::Saw => two * phase - one,
+            LfoShape::Square => {
+                if phase < T::from_f32(0.5) {
+                    one
+                } else {
+                    -one
+                }
+            }
+            LfoShape::SampleAndHold => self.current_random,
+            LfoShape::SmoothRandom => {
+                let t = (one - (T::PI() * phase).cos()) / two;
+                self.previous_random + (self.current_random - self.previous_random) * t
+            }
+        }
+    }
+
+    fn update_increment(&mut self) {
+        let frequency = match self.rate {
+            LfoRate::Free(frequency) => frequency,
+            LfoRate::Synced(division) => {
+                self.sample_rate / (self.samples_per_quarter * division.quarters())
+            }
+        };
+        self.increment = (frequency / self.sample_rate).clamp(0.0, 0.5);
+    }
+
+    fn next_random(&mut self) {
+        // xorshift32, plenty random for modulation
+        let mut x = self.random_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.random_state = x;
+        self.previous_random = self.current_random;
+        self.current_random = T::from_f64(x as f64 / u32::MAX as f64 * 2.0 - 1.0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn note_divisions() {
+        assert_eq!(NoteDivision::ALL.len(), 24);
+        let dotted_eighth = NoteDivision::dotted(NoteValue::Eighth);
+        assert_eq!(dotted_eighth.quarters(), 0.75);
+        assert_eq!(dotted_eighth.to_string(), "1/8D");
+        assert_eq!(
+            NoteDivision::from_index(dotted_eighth.index()),
+            Some(dotted_eighth)
+        );
+        let quarter_triplet = NoteDivision::triplet(NoteValue::Quarter);
+        assert!((quarter_triplet.quarters() - 2.0 / 3.0).abs() < 1e-12);
+        assert_eq!(quarter_triplet.to_string(), "1/4T");
+    }
+
+    #[test]
+    fn shapes() {
+        let mut lfo = Lfo::<f64>::new(4.0, LfoShape::Triangle, LfoRate::Free(1.0));
+        let mut output = [0.0; 4];
+        lfo.process(&mut output);
+        assert_eq!(output, [0.0, 1.0, 0.0, -1.0]);
+
+        lfo.set_shape(LfoShape::Saw);
+        lfo.process(&mut output);
+        assert_eq!(output, [-1.0, -0.5, 0.0, 0.5]);
+
+        lfo.set_shape(LfoShape::Square);
+        lfo.process(&mut output);
+        assert_eq!(output, [1.0, 1.0, -1.0, -1.0]);
+
+        lfo.set_shape(LfoShape::SampleAndHold);
+        lfo.process(&mut output);
+        assert!(output.iter().all(|&x| x == output[0] && x.abs() <= 1.0));
+    }
+
+    #[test]
+    fn synced_rate_follows_tempo_and_position() {
+        let rate = LfoRate::Synced(NoteDivision::straight(NoteValue::Half));
+        let mut lfo = Lfo::<f32>::new(48000.0, LfoShape::Sine, rate);
+        let mut transport = Transport {
+            tempo: Some(120.0),
+            ..Default::default()
+        };
+        lfo.update_transport(&transport);
+        assert!((lfo.frequency() - 1.0).abs() < 1e-9);
+
+        for _ in 0..100 {
+            lfo.tick();
+        }
+        transport.playing = true;
+        transport.position_quarters = Some(4.5);
+        lfo.update_transport(&transport);
+        assert!((lfo.phase() - 0.25).abs() < 1e-9);
+
+        // Following the expected position does not resync
+        for _ in 0..1000 {
+            lfo.tick();
+        }
+        transport.position_quarters = Some(4.5 + 1000.0 / 24000.0);
+        lfo.update_transport(&transport);
+        assert!((lfo.phase() - (0.25 + 1000.0 / 48000.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn free_rate_resets_on_transport_start() {
+        let mut lfo = Lfo::<f32>::new(48000.0, LfoShape::Saw, LfoRate::Free(2.0));
+        for _ in 0..100 {
+            lfo.tick();
+        }
+        assert!(lfo.phase() > 0.0);
+        let transport = Transport {
+            playing: true,
+            ..Default::default()
+        };
+        lfo.update_transport(&transport);
+        assert_eq!(lfo.phase(), 0.0);
+        lfo.tick();
+        lfo.update_transport(&transport);
+        assert!(lfo.phase() > 0.0);
+    }
+}
@@ -1,10 +1,32 @@
+mod delay;
+mod dynamics;
 mod envelope;
+mod fft;
+mod filter;
+mod lfo;
+mod oscillator;
+mod oversampling;
+mod resampler;
 
+pub use delay::{DelayLine, DelayTap, Interpolation};
+pub use dynamics::{
+    Compressor, CompressorParameters, Detector, EnvelopeFollower, Gate, GateParameters, Limiter,
+    LimiterParameters,
+};
 pub use envelope::{ADSREnvelope, ADSRParameters, AREnvelope, ARParameters};
-use num::Float;
+pub use fft::{Fft, RealFft, Stft, Window};
+pub use filter::{
+    Biquad, BiquadCoefficients, FilterType, FrequencyResponse, StateVariableFilter, SvfOutput,
+};
+pub use lfo::{Lfo, LfoRate, LfoShape, NoteDivision, NoteModifier, NoteValue};
+use num::{Float, traits::FloatConst};
+pub use oscillator::{PolyBlepOscillator, Waveform, Wavetable, WavetableOscillator};
+pub use oversampling::{Oversampler, OversamplingFactor, OversamplingFilter};
+pub use resampler::{Resampler, ResamplerQuality};
 
-pub trait DspFloat: Float {
+pub trait DspFloat: Float + FloatConst {
     fn from_f32(value: f32) -> Self;
+    fn from_f64(value: f64) -> Self;
 }
 
 impl DspFloat for f32 {
@@ -12,6 +34,11 @@ impl DspFloat for f32 {
     fn from_f32(value: f32) -> Self {
         value
     }
+
+    #[inline(always)]
+    fn from_f64(value: f64) -> Self {
+        value as _
+    }
 }
 
 impl DspFloat for f64 {
@@ -19,4 +46,39 @@ impl DspFloat for f64 {
     fn from_f32(value: f32) -> Self {
         value as _
     }
+
+    #[inline(always)]
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+#[inline]
+pub fn db_to_gain<T: DspFloat>(db: T) -> T {
+    T::from_f32(10.0).powf(db / T::from_f32(20.0))
+}
+
+#[inline]
+pub fn gain_to_db<T: DspFloat>(gain: T) -> T {
+    T::from_f32(20.0) * gain.max(T::min_positive_value()).log10()
+}
+
+/// Kaiser window at `x` in [-1, 1]
+fn kaiser_window(x: f64, beta: f64) -> f64 {
+    bessel_i0(beta * (1.0 - x * x).max(0.0).sqrt()) / bessel_i0(beta)
+}
+
+/// Modified Bessel function of the first kind, order zero
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    for k in 1..50 {
+        term *= half_x / k as f64;
+        sum += term * term;
+        if term * term < sum * 1e-17 {
+            break;
+        }
+    }
+    sum
 }
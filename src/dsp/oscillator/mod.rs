@@ -0,0 +1,94 @@
+mod polyblep;
+mod wavetable;
+
+pub use polyblep::PolyBlepOscillator;
+pub use wavetable::{Wavetable, WavetableOscillator};
+
+use super::DspFloat;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Saw,
+    /// Pulse wave. The duty cycle is controlled by the pulse width of the oscillator,
+    /// a pulse width of 0.5 gives a square wave.
+    Square,
+    Triangle,
+}
+
+/// Phase accumulator in the range [0, 1)
+#[derive(Debug, Clone, Copy)]
+struct Phasor<T> {
+    phase: T,
+    increment: T,
+}
+
+impl<T: DspFloat> Phasor<T> {
+    fn new() -> Self {
+        Self {
+            phase: T::zero(),
+            increment: T::zero(),
+        }
+    }
+
+    fn set_frequency(&mut self, sample_rate: T, frequency: T) {
+        self.increment = (frequency / sample_rate).abs().min(T::from_f32(0.5));
+    }
+
+    fn set_phase(&mut self, phase: T) {
+        self.phase = wrap_phase(phase);
+    }
+
+    #[inline]
+    fn advance(&mut self) {
+        self.phase = self.phase + self.increment;
+        if self.phase >= T::one() {
+            self.phase = self.phase - T::one();
+        }
+    }
+}
+
+#[inline]
+fn wrap_phase<T: DspFloat>(phase: T) -> T {
+    phase - phase.floor()
+}
+
+#[cfg(test)]
+mod test_util {
+    /// Magnitude spectrum (bins 0..=N/2) of a signal, scaled so that a sine with
+    /// amplitude A gives a peak of A in its bin.
+    pub fn magnitude_spectrum(signal: &[f64]) -> Vec<f64> {
+        let n = signal.len();
+        let (cos, sin): (Vec<f64>, Vec<f64>) = (0..n)
+            .map(|i| {
+                let angle = std::f64::consts::TAU * i as f64 / n as f64;
+                (angle.cos(), angle.sin())
+            })
+            .unzip();
+        (0..=n / 2)
+            .map(|k| {
+                let (mut re, mut im) = (0.0, 0.0);
+                for (i, x) in signal.iter().enumerate() {
+                    let index = (k * i) % n;
+                    re += x * cos[index];
+                    im -= x * sin[index];
+                }
+                2.0 * re.hypot(im) / n as f64
+            })
+            .collect()
+    }
+
+    /// Ratio (in dB) between the energy in the bins that are not harmonics of
+    /// `fundamental_bin` and the energy in the harmonic bins. DC is ignored.
+    pub fn aliasing_db(spectrum: &[f64], fundamental_bin: usize) -> f64 {
+        let (mut harmonic, mut other) = (0.0, 0.0);
+        for (bin, magnitude) in spectrum.iter().enumerate().skip(1) {
+            if bin % fundamental_bin == 0 {
+                harmonic += magnitude * magnitude;
+            } else {
+                other += magnitude * magnitude;
+            }
+        }
+        10.0 * (other / harmonic).log10()
+    }
+}
@@ -0,0 +1,213 @@
+use super::{Phasor, Waveform, wrap_phase};
+use crate::dsp::DspFloat;
+
+/// Oscillator producing the classic analog waveforms, with the discontinuities
+/// smoothed by polynomial band-limited steps (PolyBLEP) and ramps (PolyBLAMP)
+/// to suppress aliasing. The output is in the range [-1, 1].
+#[derive(Debug, Clone)]
+pub struct PolyBlepOscillator<T = f32> {
+    waveform: Waveform,
+    sample_rate: T,
+    frequency: T,
+    pulse_width: T,
+    phasor: Phasor<T>,
+}
+
+impl<T: DspFloat> PolyBlepOscillator<T> {
+    pub fn new(sample_rate: T, waveform: Waveform) -> Self {
+        Self {
+            waveform,
+            sample_rate,
+            frequency: T::zero(),
+            pulse_width: T::from_f32(0.5),
+            phasor: Phasor::new(),
+        }
+    }
+
+    pub fn waveform(&self) -> Waveform {
+        self.waveform
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.phasor.set_frequency(sample_rate, self.frequency);
+    }
+
+    pub fn frequency(&self) -> T {
+        self.frequency
+    }
+
+    /// Set the frequency (in Hz). Can be called for every sample for frequency modulation.
+    #[inline]
+    pub fn set_frequency(&mut self, frequency: T) {
+        self.frequency = frequency;
+        self.phasor.set_frequency(self.sample_rate, frequency);
+    }
+
+    /// Set the duty cycle of the [`Waveform::Square`] waveform, in the range (0, 1).
+    /// Can be called for every sample for pulse width modulation.
+    #[inline]
+    pub fn set_pulse_width(&mut self, pulse_width: T) {
+        // Keep both edges at least a sample apart, otherwise the BLEP:s will overlap
+        let min_width = self.phasor.increment.max(T::from_f32(1.0e-3));
+        self.pulse_width = pulse_width.max(min_width).min(T::one() - min_width);
+    }
+
+    pub fn phase(&self) -> T {
+        self.phasor.phase
+    }
+
+    /// Set the phase, in the range [0, 1)
+    pub fn set_phase(&mut self, phase: T) {
+        self.phasor.set_phase(phase);
+    }
+
+    pub fn reset(&mut self) {
+        self.phasor.set_phase(T::zero());
+    }
+
+    pub fn tick(&mut self) -> T {
+        let t = self.phasor.phase;
+        let dt = self.phasor.increment;
+        let value = match self.waveform {
+            Waveform::Sine => (T::TAU() * t).sin(),
+            Waveform::Saw => {
+                let two = T::from_f32(2.0);
+                two * t - T::one() - poly_blep(t, dt)
+            }
+            Waveform::Square => {
+                let naive = if t < self.pulse_width {
+                    T::one()
+                } else {
+                    -T::one()
+                };
+                naive + poly_blep(t, dt) - poly_blep(wrap_phase(t - self.pulse_width), dt)
+            }
+            Waveform::Triangle => {
+                let half = T::from_f32(0.5);
+                let four = T::from_f32(4.0);
+                // The slope changes by 8 (per unit phase) at the corners
+                let slope_change = T::from_f32(8.0) * dt;
+                let naive = T::one() - four * (t - half).abs();
+                naive + slope_change * (poly_blamp(t, dt) - poly_blamp(wrap_phase(t - half), dt))
+            }
+        };
+        self.phasor.advance();
+        value
+    }
+}
+
+/// Residual for a band-limited step of height 2, centered at t = 0
+#[inline]
+fn poly_blep<T: DspFloat>(t: T, dt: T) -> T {
+    if t < dt {
+        let x = t / dt - T::one();
+        -x * x
+    } else if t > T::one() - dt {
+        let x = (t - T::one()) / dt + T::one();
+        x * x
+    } else {
+        T::zero()
+    }
+}
+
+/// Residual for a band-limited ramp with a unit change of slope (per sample), centered at t = 0
+#[inline]
+fn poly_blamp<T: DspFloat>(t: T, dt: T) -> T {
+    let sixth = T::from_f64(1.0 / 6.0);
+    if t < dt {
+        let x = T::one() - t / dt;
+        sixth * x * x * x
+    } else if t > T::one() - dt {
+        let x = (t - T::one()) / dt + T::one();
+        sixth * x * x * x
+    } else {
+        T::zero()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dsp::oscillator::test_util::{aliasing_db, magnitude_spectrum};
+
+    const SAMPLE_RATE: f64 = 48000.0;
+    // Gives a bin width of 10 Hz
+    const N: usize = 4800;
+    // Chosen so that the aliased harmonics do not end up on harmonic bins
+    const FREQUENCY: f64 = 2630.0;
+    const FUNDAMENTAL_BIN: usize = 263;
+
+    fn render(waveform: Waveform, pulse_width: f64) -> Vec<f64> {
+        let mut osc = PolyBlepOscillator::new(SAMPLE_RATE, waveform);
+        osc.set_frequency(FREQUENCY);
+        osc.set_pulse_width(pulse_width);
+        (0..N).map(|_| osc.tick()).collect()
+    }
+
+    fn render_naive(f: impl Fn(f64) -> f64) -> Vec<f64> {
+        (0..N)
+            .map(|i| f((i as f64 * FREQUENCY / SAMPLE_RATE).fract()))
+            .collect()
+    }
+
+    #[test]
+    fn saw_spectrum() {
+        let spectrum = magnitude_spectrum(&render(Waveform::Saw, 0.5));
+        // PolyBLEP rolls off the upper harmonics slightly, so only check the lower ones
+        for k in 1..=2 {
+            let expected = 2.0 / (std::f64::consts::PI * k as f64);
+            assert!((spectrum[k * FUNDAMENTAL_BIN] - expected).abs() < 0.05 * expected);
+        }
+
+        let naive_spectrum = magnitude_spectrum(&render_naive(|t| 2.0 * t - 1.0));
+        let aliasing = aliasing_db(&spectrum, FUNDAMENTAL_BIN);
+        let naive_aliasing = aliasing_db(&naive_spectrum, FUNDAMENTAL_BIN);
+        assert!(aliasing < naive_aliasing - 10.0);
+    }
+
+    #[test]
+    fn square_spectrum() {
+        let spectrum = magnitude_spectrum(&render(Waveform::Square, 0.5));
+        let expected = 4.0 / std::f64::consts::PI;
+        assert!((spectrum[FUNDAMENTAL_BIN] - expected).abs() < 0.05 * expected);
+        // A square wave only contains odd harmonics
+        assert!(spectrum[2 * FUNDAMENTAL_BIN] < 1.0e-3);
+
+        let naive_spectrum =
+            magnitude_spectrum(&render_naive(|t| if t < 0.5 { 1.0 } else { -1.0 }));
+        let aliasing = aliasing_db(&spectrum, FUNDAMENTAL_BIN);
+        let naive_aliasing = aliasing_db(&naive_spectrum, FUNDAMENTAL_BIN);
+        assert!(aliasing < naive_aliasing - 10.0);
+    }
+
+    #[test]
+    fn pulse_width_modulates_duty_cycle() {
+        let signal = render(Waveform::Square, 0.25);
+        let mean = signal.iter().sum::<f64>() / N as f64;
+        assert!((mean - (2.0 * 0.25 - 1.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn triangle_spectrum() {
+        let spectrum = magnitude_spectrum(&render(Waveform::Triangle, 0.5));
+        let expected = 8.0 / (std::f64::consts::PI * std::f64::consts::PI);
+        assert!((spectrum[FUNDAMENTAL_BIN] - expected).abs() < 0.05 * expected);
+
+        let naive_spectrum = magnitude_spectrum(&render_naive(|t| 1.0 - 4.0 * (t - 0.5).abs()));
+        let aliasing = aliasing_db(&spectrum, FUNDAMENTAL_BIN);
+        let naive_aliasing = aliasing_db(&naive_spectrum, FUNDAMENTAL_BIN);
+        assert!(aliasing < naive_aliasing - 10.0);
+    }
+
+    #[test]
+    fn sine_is_pure() {
+        let spectrum = magnitude_spectrum(&render(Waveform::Sine, 0.5));
+        assert!((spectrum[FUNDAMENTAL_BIN] - 1.0).abs() < 1.0e-6);
+        assert!(aliasing_db(&spectrum, FUNDAMENTAL_BIN) < -100.0);
+    }
+}
@@ -0,0 +1,301 @@
+use std::sync::Arc;
+
+use super::{Phasor, wrap_phase};
+use crate::dsp::DspFloat;
+
+/// A single cycle waveform, stored as a set of mip-mapped tables where each level
+/// contains half the number of harmonics of the previous level. The oscillator picks the
+/// level with the most harmonics that does not alias for the current frequency.
+///
+/// Building a wavetable is expensive, so it should be done outside of the audio thread
+/// and shared between oscillators (e.g. between voices in a synth).
+#[derive(Debug, Clone)]
+pub struct Wavetable<T = f32> {
+    table_size: usize,
+    /// Tables with the number of harmonics in each, ordered from most to fewest harmonics.
+    /// Each table has a guard sample at the end, equal to the first sample.
+    levels: Vec<(usize, Box<[T]>)>,
+}
+
+impl<T: DspFloat> Wavetable<T> {
+    /// Create a wavetable from its Fourier series. `harmonic` is called with the harmonic
+    /// number k (starting at 1), and should return the amplitudes of the cosine and sine
+    /// partials `(a_k, b_k)`. At most `table_size / 2 - 1` harmonics are used.
+    pub fn from_harmonics(table_size: usize, harmonic: impl Fn(usize) -> (T, T)) -> Self {
+        assert!(table_size >= 4, "Wavetable size must be at least 4");
+        let max_harmonics = table_size / 2 - 1;
+        let coefficients: Vec<(T, T)> = (1..=max_harmonics).map(harmonic).collect();
+        Self::from_coefficients(table_size, T::zero(), &coefficients)
+    }
+
+    /// Create a wavetable from one cycle of a waveform. The number of samples
+    /// is used as the table size.
+    pub fn from_cycle(samples: &[T]) -> Self {
+        let table_size = samples.len();
+        assert!(table_size >= 4, "Wavetable size must be at least 4");
+        let (cos, sin) = trig_tables::<T>(table_size);
+        let scale = T::from_f64(2.0 / table_size as f64);
+
+        let dc = samples.iter().fold(T::zero(), |acc, &x| acc + x) / T::from_f64(table_size as f64);
+        let coefficients: Vec<(T, T)> = (1..table_size / 2)
+            .map(|k| {
+                samples
+                    .iter()
+                    .enumerate()
+                    .fold((T::zero(), T::zero()), |(a, b), (n, &x)| {
+                        let index = (k * n) % table_size;
+                        (a + x * cos[index], b + x * sin[index])
+                    })
+            })
+            .map(|(a, b)| (a * scale, b * scale))
+            .collect();
+        Self::from_coefficients(table_size, dc, &coefficients)
+    }
+
+    /// Create a wavetable from a function evaluated at phases in [0, 1)
+    pub fn from_fn(table_size: usize, f: impl Fn(T) -> T) -> Self {
+        let samples: Vec<T> = (0..table_size)
+            .map(|i| f(T::from_f64(i as f64 / table_size as f64)))
+            .collect();
+        Self::from_cycle(&samples)
+    }
+
+    pub fn sine(table_size: usize) -> Self {
+        Self::from_harmonics(table_size, |k| {
+            (T::zero(), if k == 1 { T::one() } else { T::zero() })
+        })
+    }
+
+    /// Rising sawtooth, going from -1 to 1 over the cycle
+    pub fn saw(table_size: usize) -> Self {
+        Self::from_harmonics(table_size, |k| {
+            (
+                T::zero(),
+                -T::from_f64(2.0 / (std::f64::consts::PI * k as f64)),
+            )
+        })
+    }
+
+    pub fn square(table_size: usize) -> Self {
+        Self::from_harmonics(table_size, |k| {
+            let b = if k % 2 == 1 {
+                4.0 / (std::f64::consts::PI * k as f64)
+            } else {
+                0.0
+            };
+            (T::zero(), T::from_f64(b))
+        })
+    }
+
+    pub fn triangle(table_size: usize) -> Self {
+        use std::f64::consts::PI;
+        Self::from_harmonics(table_size, |k| {
+            let a = if k % 2 == 1 {
+                -8.0 / (PI * PI * (k * k) as f64)
+            } else {
+                0.0
+            };
+            (T::from_f64(a), T::zero())
+        })
+    }
+
+    fn from_coefficients(table_size: usize, dc: T, coefficients: &[(T, T)]) -> Self {
+        let (cos, sin) = trig_tables::<T>(table_size);
+        // Only keep the harmonics up to the last one that is audible (above -120 dB)
+        let magnitudes = coefficients.iter().map(|&(a, b)| a.hypot(b));
+        let threshold = magnitudes.clone().fold(T::zero(), T::max) * T::from_f32(1.0e-6);
+        let harmonic_count = magnitudes
+            .collect::<Vec<_>>()
+            .iter()
+            .rposition(|&magnitude| magnitude > threshold)
+            .map_or(1, |i| i + 1);
+
+        let mut levels = Vec::new();
+        let mut harmonics = harmonic_count;
+        loop {
+            let mut table: Vec<T> = (0..table_size)
+                .map(|n| {
+                    coefficients[..harmonics]
+                        .iter()
+                        .enumerate()
+                        .fold(dc, |acc, (k, &(a, b))| {
+                            let index = ((k + 1) * n) % table_size;
+                            acc + a * cos[index] + b * sin[index]
+                        })
+                })
+                .collect();
+            table.push(table[0]);
+            levels.push((harmonics, table.into_boxed_slice()));
+
+            if harmonics == 1 {
+                break;
+            }
+            harmonics /= 2;
+        }
+
+        Self { table_size, levels }
+    }
+
+    pub fn table_size(&self) -> usize {
+        self.table_size
+    }
+
+    /// Index of the level to use for a given phase increment (frequency / sample rate)
+    fn level_for_increment(&self, increment: T) -> usize {
+        let max_harmonics = if increment > T::zero() {
+            (T::from_f32(0.5) / increment)
+                .to_usize()
+                .unwrap_or(usize::MAX)
+        } else {
+            usize::MAX
+        };
+        self.levels
+            .iter()
+            .position(|&(harmonics, _)| harmonics <= max_harmonics)
+            .unwrap_or(self.levels.len() - 1)
+    }
+
+    #[inline]
+    fn sample(&self, level: usize, phase: T) -> T {
+        let table = &self.levels[level].1;
+        let position = phase * T::from_f64(self.table_size as f64);
+        let index = position.to_usize().unwrap_or(0).min(self.table_size - 1);
+        let fraction = position - T::from_f64(index as f64);
+        table[index] + fraction * (table[index + 1] - table[index])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WavetableOscillator<T = f32> {
+    wavetable: Arc<Wavetable<T>>,
+    sample_rate: T,
+    frequency: T,
+    phasor: Phasor<T>,
+    level: usize,
+}
+
+impl<T: DspFloat> WavetableOscillator<T> {
+    pub fn new(sample_rate: T, wavetable: Arc<Wavetable<T>>) -> Self {
+        Self {
+            wavetable,
+            sample_rate,
+            frequency: T::zero(),
+            phasor: Phasor::new(),
+            level: 0,
+        }
+    }
+
+    pub fn wavetable(&self) -> &Arc<Wavetable<T>> {
+        &self.wavetable
+    }
+
+    pub fn set_wavetable(&mut self, wavetable: Arc<Wavetable<T>>) {
+        self.wavetable = wavetable;
+        self.level = self.wavetable.level_for_increment(self.phasor.increment);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.set_frequency(self.frequency);
+    }
+
+    pub fn frequency(&self) -> T {
+        self.frequency
+    }
+
+    /// Set the frequency (in Hz). Can be called for every sample for frequency modulation.
+    #[inline]
+    pub fn set_frequency(&mut self, frequency: T) {
+        self.frequency = frequency;
+        self.phasor.set_frequency(self.sample_rate, frequency);
+        self.level = self.wavetable.level_for_increment(self.phasor.increment);
+    }
+
+    pub fn phase(&self) -> T {
+        self.phasor.phase
+    }
+
+    /// Set the phase, in the range [0, 1)
+    pub fn set_phase(&mut self, phase: T) {
+        self.phasor.set_phase(wrap_phase(phase));
+    }
+
+    pub fn reset(&mut self) {
+        self.phasor.set_phase(T::zero());
+    }
+
+    pub fn tick(&mut self) -> T {
+        let value = self.wavetable.sample(self.level, self.phasor.phase);
+        self.phasor.advance();
+        value
+    }
+}
+
+fn trig_tables<T: DspFloat>(size: usize) -> (Vec<T>, Vec<T>) {
+    (0..size)
+        .map(|i| {
+            let angle = std::f64::consts::TAU * i as f64 / size as f64;
+            (T::from_f64(angle.cos()), T::from_f64(angle.sin()))
+        })
+        .unzip()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dsp::oscillator::test_util::{aliasing_db, magnitude_spectrum};
+
+    const SAMPLE_RATE: f64 = 48000.0;
+    const N: usize = 4800;
+
+    fn render(wavetable: Wavetable<f64>, frequency: f64) -> Vec<f64> {
+        let mut osc = WavetableOscillator::new(SAMPLE_RATE, Arc::new(wavetable));
+        osc.set_frequency(frequency);
+        (0..N).map(|_| osc.tick()).collect()
+    }
+
+    #[test]
+    fn from_cycle_matches_harmonics() {
+        use std::f64::consts::TAU;
+        let from_fn =
+            Wavetable::<f64>::from_fn(256, |t| (TAU * t).sin() + 0.5 * (3.0 * TAU * t).cos());
+        let from_harmonics = Wavetable::<f64>::from_harmonics(256, |k| match k {
+            1 => (0.0, 1.0),
+            3 => (0.5, 0.0),
+            _ => (0.0, 0.0),
+        });
+        assert_eq!(from_fn.levels.len(), from_harmonics.levels.len());
+        for (level_a, level_b) in from_fn.levels.iter().zip(from_harmonics.levels.iter()) {
+            assert_eq!(level_a.0, level_b.0);
+            for (a, b) in level_a.1.iter().zip(level_b.1.iter()) {
+                assert!((a - b).abs() < 1.0e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn mip_levels() {
+        let wavetable = Wavetable::<f64>::saw(2048);
+        let harmonics: Vec<usize> = wavetable.levels.iter().map(|(h, _)| *h).collect();
+        assert_eq!(harmonics.first(), Some(&1023));
+        assert_eq!(harmonics.last(), Some(&1));
+
+        // A sine only needs a single level
+        assert_eq!(Wavetable::<f64>::sine(2048).levels.len(), 1);
+    }
+
+    #[test]
+    fn saw_does_not_alias() {
+        // Bin 263 (2630 Hz) at a bin width of 10 Hz
+        let spectrum = magnitude_spectrum(&render(Wavetable::saw(2048), 2630.0));
+        let expected = 2.0 / std::f64::consts::PI;
+        assert!((spectrum[263] - expected).abs() < 0.02 * expected);
+        assert!(aliasing_db(&spectrum, 263) < -40.0);
+    }
+
+    #[test]
+    fn square_does_not_alias() {
+        let spectrum = magnitude_spectrum(&render(Wavetable::square(2048), 4410.0));
+        assert!(aliasing_db(&spectrum, 441) < -40.0);
+    }
+}
@@ -0,0 +1,143 @@
+//! Linear phase half-band FIR filters, designed with the windowed-sinc method.
+//!
+//! A half-band filter of length 2c + 1 (with c odd) has every other tap equal to zero, except
+//! for the center tap which is 0.5. When interpolating, the odd output samples are therefore
+//! just delayed input samples, and only the even output samples need to be filtered.
+
+use crate::dsp::kaiser_window;
+
+/// Stopband attenuation of the designed filters, in dB
+const ATTENUATION_DB: f64 = 100.0;
+
+/// The nonzero taps of a half-band filter with center `center` (must be odd), h[2j] for
+/// j in 0..=center, scaled by 2 so that they sum to one.
+fn design(center: usize) -> Box<[f32]> {
+    debug_assert!(center % 2 == 1);
+    let beta = 0.1102 * (ATTENUATION_DB - 8.7);
+    let length = 2 * center + 1;
+    let taps: Vec<f64> = (0..=center)
+        .map(|j| {
+            let k = 2 * j;
+            let x = (k as f64 - center as f64) / 2.0;
+            let sinc = (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x);
+            let r = 2.0 * k as f64 / (length - 1) as f64 - 1.0;
+            sinc * kaiser_window(r, beta)
+        })
+        .collect();
+    let sum: f64 = taps.iter().sum();
+    taps.iter().map(|tap| (tap / sum) as f32).collect()
+}
+
+/// Filter history, stored twice so that the most recent samples are always available as a
+/// contiguous slice, newest first
+#[derive(Debug, Clone)]
+struct History {
+    buffer: Box<[f32]>,
+    position: usize,
+}
+
+impl History {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; 2 * len].into_boxed_slice(),
+            position: 0,
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, sample: f32) {
+        let len = self.buffer.len() / 2;
+        self.position = if self.position == 0 {
+            len - 1
+        } else {
+            self.position - 1
+        };
+        self.buffer[self.position] = sample;
+        self.buffer[self.position + len] = sample;
+    }
+
+    #[inline]
+    fn samples(&self) -> &[f32] {
+        let len = self.buffer.len() / 2;
+        &self.buffer[self.position..self.position + len]
+    }
+
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+    }
+}
+
+#[inline]
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Upsampler {
+    taps: Box<[f32]>,
+    history: History,
+}
+
+impl Upsampler {
+    pub fn new(center: usize) -> Self {
+        Self {
+            taps: design(center),
+            history: History::new(center + 1),
+        }
+    }
+
+    /// Delay in samples, at the oversampled rate
+    pub fn latency(&self) -> f64 {
+        (self.taps.len() - 1) as f64
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        let delay = (self.taps.len() - 2) / 2;
+        for (&x, y) in input.iter().zip(output.chunks_exact_mut(2)) {
+            self.history.push(x);
+            let history = self.history.samples();
+            y[0] = dot(&self.taps, history);
+            y[1] = history[delay];
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.history.reset();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Downsampler {
+    taps: Box<[f32]>,
+    even: History,
+    odd: History,
+}
+
+impl Downsampler {
+    pub fn new(center: usize) -> Self {
+        Self {
+            taps: design(center),
+            even: History::new(center + 1),
+            odd: History::new(center.div_ceil(2)),
+        }
+    }
+
+    /// Delay in samples, at the oversampled rate
+    pub fn latency(&self) -> f64 {
+        (self.taps.len() - 1) as f64
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        let delay = (self.taps.len() - 2) / 2;
+        for (x, y) in input.chunks_exact(2).zip(output.iter_mut()) {
+            self.even.push(x[0]);
+            *y = 0.5 * (dot(&self.taps, self.even.samples()) + self.odd.samples()[delay]);
+            self.odd.push(x[1]);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.even.reset();
+        self.odd.reset();
+    }
+}
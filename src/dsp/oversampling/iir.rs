@@ -0,0 +1,155 @@
+//! Polyphase half-band IIR filters, made of two parallel chains of first order allpass
+//! sections. The coefficients are computed with the elliptic design by Laurent de Soras
+//! (from the HIIR library). Much cheaper and lower latency than the FIR filters, at the cost
+//! of a nonlinear phase response.
+
+use std::f64::consts::PI;
+
+/// Compute the allpass coefficients for a half-band filter with `count` coefficients, and
+/// a transition band of `transition` relative to the oversampled rate, in (0, 0.5).
+fn design(count: usize, transition: f64) -> Box<[f32]> {
+    let mut k = ((1.0 - transition * 2.0) * PI / 4.0).tan();
+    k *= k;
+    let kk_sqrt = (1.0 - k * k).powf(0.25);
+    let e = 0.5 * (1.0 - kk_sqrt) / (1.0 + kk_sqrt);
+    let e2 = e * e;
+    let e4 = e2 * e2;
+    let q = e * (1.0 + e4 * (2.0 + e4 * (15.0 + 150.0 * e4)));
+    let order = (count * 2 + 1) as f64;
+
+    (0..count)
+        .map(|index| {
+            let c = (index + 1) as f64;
+
+            let mut numerator = 0.0;
+            let mut sign = 1.0;
+            for i in 0.. {
+                let term = q.powi(i * (i + 1)) * ((i * 2 + 1) as f64 * c * PI / order).sin() * sign;
+                numerator += term;
+                sign = -sign;
+                if term.abs() <= 1e-100 {
+                    break;
+                }
+            }
+            numerator *= q.powf(0.25);
+
+            let mut denominator = 0.0;
+            let mut sign = -1.0;
+            for i in 1.. {
+                let term = q.powi(i * i) * ((i * 2) as f64 * c * PI / order).cos() * sign;
+                denominator += term;
+                sign = -sign;
+                if term.abs() <= 1e-100 {
+                    break;
+                }
+            }
+            denominator += 0.5;
+
+            let ww = numerator / denominator;
+            let ww2 = ww * ww;
+            let x = ((1.0 - ww2 * k) * (1.0 - ww2 / k)).sqrt() / (1.0 + ww2);
+            ((1.0 - x) / (1.0 + x)) as f32
+        })
+        .collect()
+}
+
+/// The two allpass chains. Even coefficients belong to the first chain, odd to the second.
+#[derive(Debug, Clone)]
+struct AllPassChains {
+    coefficients: Box<[f32]>,
+    x: Box<[f32]>,
+    y: Box<[f32]>,
+}
+
+impl AllPassChains {
+    fn new(count: usize, transition: f64) -> Self {
+        Self {
+            coefficients: design(count, transition),
+            x: vec![0.0; count].into_boxed_slice(),
+            y: vec![0.0; count].into_boxed_slice(),
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, mut a: f32, mut b: f32) -> (f32, f32) {
+        for (i, &coefficient) in self.coefficients.iter().enumerate() {
+            let sample = if i % 2 == 0 { &mut a } else { &mut b };
+            let output = (*sample - self.y[i]) * coefficient + self.x[i];
+            self.x[i] = *sample;
+            self.y[i] = output;
+            *sample = output;
+        }
+        (a, b)
+    }
+
+    /// Group delay at DC, in samples at the oversampled rate
+    fn latency(&self) -> f64 {
+        // Each section is (c + z^-2) / (1 + c z^-2) at the oversampled rate, and the second
+        // chain is delayed by one sample. The filter output is the average of the chains.
+        let sections: f64 = self
+            .coefficients
+            .iter()
+            .map(|&c| {
+                let c = c as f64;
+                2.0 * (1.0 - c) / (1.0 + c)
+            })
+            .sum();
+        (sections + 1.0) / 2.0
+    }
+
+    fn reset(&mut self) {
+        self.x.fill(0.0);
+        self.y.fill(0.0);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Upsampler(AllPassChains);
+
+impl Upsampler {
+    pub fn new(count: usize, transition: f64) -> Self {
+        Self(AllPassChains::new(count, transition))
+    }
+
+    /// Delay in samples, at the oversampled rate
+    pub fn latency(&self) -> f64 {
+        self.0.latency()
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        for (&x, y) in input.iter().zip(output.chunks_exact_mut(2)) {
+            (y[0], y[1]) = self.0.process(x, x);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Downsampler(AllPassChains);
+
+impl Downsampler {
+    pub fn new(count: usize, transition: f64) -> Self {
+        Self(AllPassChains::new(count, transition))
+    }
+
+    /// Delay in samples, at the oversampled rate
+    pub fn latency(&self) -> f64 {
+        // The output is aligned with the odd input samples, which are one sample
+        // newer than the even ones
+        self.0.latency() - 1.0
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        for (x, y) in input.chunks_exact(2).zip(output.iter_mut()) {
+            let (a, b) = self.0.process(x[1], x[0]);
+            *y = 0.5 * (a + b);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.0.reset();
+    }
+}
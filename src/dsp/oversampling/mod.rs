@@ -0,0 +1,431 @@
+mod fir;
+mod iir;
+
+use crate::{AudioBuffer, OwnedAudioBuffer};
+
+use super::{DelayLine, DelayTap, Interpolation};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversamplingFactor {
+    X2,
+    X4,
+    X8,
+    X16,
+}
+
+impl OversamplingFactor {
+    pub fn factor(&self) -> usize {
+        1 << self.stages()
+    }
+
+    /// Number of 2x stages
+    fn stages(&self) -> usize {
+        match self {
+            Self::X2 => 1,
+            Self::X4 => 2,
+            Self::X8 => 3,
+            Self::X16 => 4,
+        }
+    }
+}
+
+/// The half-band filters used for up and down-sampling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversamplingFilter {
+    /// Polyphase allpass IIR filters. Cheap and low latency, but with a nonlinear phase
+    /// response close to the Nyquist frequency.
+    Iir,
+    /// Linear phase FIR filters. More expensive and with a higher latency.
+    Fir,
+}
+
+enum Upsampler {
+    Fir(fir::Upsampler),
+    Iir(iir::Upsampler),
+}
+
+impl Upsampler {
+    fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        match self {
+            Self::Fir(upsampler) => upsampler.process(input, output),
+            Self::Iir(upsampler) => upsampler.process(input, output),
+        }
+    }
+
+    fn latency(&self) -> f64 {
+        match self {
+            Self::Fir(upsampler) => upsampler.latency(),
+            Self::Iir(upsampler) => upsampler.latency(),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            Self::Fir(upsampler) => upsampler.reset(),
+            Self::Iir(upsampler) => upsampler.reset(),
+        }
+    }
+}
+
+enum Downsampler {
+    Fir(fir::Downsampler),
+    Iir(iir::Downsampler),
+}
+
+impl Downsampler {
+    fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        match self {
+            Self::Fir(downsampler) => downsampler.process(input, output),
+            Self::Iir(downsampler) => downsampler.process(input, output),
+        }
+    }
+
+    fn latency(&self) -> f64 {
+        match self {
+            Self::Fir(downsampler) => downsampler.latency(),
+            Self::Iir(downsampler) => downsampler.latency(),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            Self::Fir(downsampler) => downsampler.reset(),
+            Self::Iir(downsampler) => downsampler.reset(),
+        }
+    }
+}
+
+/// Creates the up and down-sampler for a stage. The first stage, closest to the base sample
+/// rate, needs the steepest filters; later stages only have to remove the images above the
+/// (already filtered) band of the previous stage, so they can be much shorter.
+fn create_stage(filter: OversamplingFilter, stage: usize) -> (Upsampler, Downsampler) {
+    match filter {
+        OversamplingFilter::Fir => {
+            let center = if stage == 0 { 63 } else { 15 };
+            (
+                Upsampler::Fir(fir::Upsampler::new(center)),
+                Downsampler::Fir(fir::Downsampler::new(center)),
+            )
+        }
+        OversamplingFilter::Iir => {
+            let (count, transition) = if stage == 0 { (12, 0.025) } else { (4, 0.2) };
+            (
+                Upsampler::Iir(iir::Upsampler::new(count, transition)),
+                Downsampler::Iir(iir::Downsampler::new(count, transition)),
+            )
+        }
+    }
+}
+
+struct OversamplerChannel {
+    upsamplers: Vec<Upsampler>,
+    downsamplers: Vec<Downsampler>,
+    /// The stages alternate between this buffer and the channel of the oversampled buffer
+    scratch: Box<[f32]>,
+    delay: DelayLine<f32>,
+}
+
+/// Up-samples an [`AudioBuffer`] by 2, 4, 8 or 16 times with a cascade of half-band filters,
+/// lets a closure process the oversampled buffer, and down-samples the result again.
+/// Use it to reduce aliasing from nonlinear processing, like saturation.
+///
+/// Buffers are allocated in [`Oversampler::prepare`], which should be called from
+/// [`Plugin::prepare`](crate::Plugin::prepare). The latency is padded to a whole number of
+/// samples, and should be added to [`Plugin::latency_samples`](crate::Plugin::latency_samples).
+pub struct Oversampler {
+    factor: OversamplingFactor,
+    filter: OversamplingFilter,
+    max_buffer_size: usize,
+    latency_samples: usize,
+    delay_tap: DelayTap<f32>,
+    channels: Vec<OversamplerChannel>,
+    oversampled: OwnedAudioBuffer<f32>,
+}
+
+impl Oversampler {
+    /// Create an oversampler, [`Oversampler::prepare`] must be called before use
+    pub fn new(factor: OversamplingFactor, filter: OversamplingFilter) -> Self {
+        Self {
+            factor,
+            filter,
+            max_buffer_size: 0,
+            latency_samples: 0,
+            delay_tap: DelayTap::new(Interpolation::None),
+            channels: Vec::new(),
+            oversampled: OwnedAudioBuffer::new(),
+        }
+    }
+
+    pub fn factor(&self) -> OversamplingFactor {
+        self.factor
+    }
+
+    pub fn filter(&self) -> OversamplingFilter {
+        self.filter
+    }
+
+    /// Change the factor or filter type. Takes effect at the next call to
+    /// [`Oversampler::prepare`], since it changes the latency.
+    pub fn set_oversampling(&mut self, factor: OversamplingFactor, filter: OversamplingFilter) {
+        self.factor = factor;
+        self.filter = filter;
+    }
+
+    /// Allocate the filters and buffers for `num_channels` channels and blocks of up
+    /// to `max_buffer_size` samples
+    pub fn prepare(&mut self, num_channels: usize, max_buffer_size: usize) {
+        let stages = self.factor.stages();
+        let factor = self.factor.factor();
+        self.max_buffer_size = max_buffer_size;
+
+        // Each stage adds the delay of the up and down-sampler at its rate. The FIR filters have
+        // a delay of a whole number of samples at their rate, so padding at the highest rate
+        // makes the total latency exact. For the IIR filters it is the group delay at DC.
+        let (upsampler, downsampler) = create_stage(self.filter, 0);
+        let mut latency = (upsampler.latency() + downsampler.latency()) / 2.0;
+        for stage in 1..stages {
+            let (upsampler, downsampler) = create_stage(self.filter, stage);
+            latency += (upsampler.latency() + downsampler.latency()) / (2 << stage) as f64;
+        }
+        self.latency_samples = latency.ceil() as usize;
+        let padding = ((self.latency_samples as f64 - latency) * factor as f64).round() as usize;
+        self.delay_tap.set_delay(padding as f32);
+
+        self.channels = (0..num_channels)
+            .map(|_| {
+                let (upsamplers, downsamplers) = (0..stages)
+                    .map(|stage| create_stage(self.filter, stage))
+                    .unzip();
+                let mut delay = DelayLine::new();
+                delay.prepare_samples(padding, 1);
+                OversamplerChannel {
+                    upsamplers,
+                    downsamplers,
+                    scratch: vec![0.0; max_buffer_size * factor].into_boxed_slice(),
+                    delay,
+                }
+            })
+            .collect();
+        self.oversampled
+            .prepare(num_channels, max_buffer_size * factor);
+    }
+
+    /// Total delay of up and down-sampling, at the base sample rate
+    pub fn latency_samples(&self) -> usize {
+        self.latency_samples
+    }
+
+    pub fn reset(&mut self) {
+        for channel in self.channels.iter_mut() {
+            channel.upsamplers.iter_mut().for_each(Upsampler::reset);
+            channel.downsamplers.iter_mut().for_each(Downsampler::reset);
+            channel.delay.reset();
+        }
+    }
+
+    /// Up-sample `input`, run `f` on the oversampled buffer, and down-sample the result
+    /// to `output`
+    pub fn process(
+        &mut self,
+        input: &AudioBuffer,
+        output: &mut AudioBuffer,
+        f: impl FnOnce(&mut AudioBuffer),
+    ) {
+        let num_samples = input.samples().min(output.samples());
+        for (index, channel) in self.channels.iter_mut().enumerate().take(input.channels()) {
+            channel.upsample(
+                &input.channel(index).as_slice()[..num_samples],
+                self.oversampled.channel_mut(index),
+            );
+        }
+        self.process_oversampled(num_samples, f);
+        for (index, channel) in self.channels.iter_mut().enumerate().take(output.channels()) {
+            let mut samples = output.channel_mut(index);
+            channel.downsample(
+                &mut samples.as_mut_slice()[..num_samples],
+                self.oversampled.channel_mut(index),
+                &mut self.delay_tap,
+            );
+        }
+    }
+
+    /// Same as [`Oversampler::process`], but with the output written back to `buffer`
+    pub fn process_in_place(&mut self, buffer: &mut AudioBuffer, f: impl FnOnce(&mut AudioBuffer)) {
+        let num_samples = buffer.samples();
+        for (index, channel) in self.channels.iter_mut().enumerate().take(buffer.channels()) {
+            channel.upsample(
+                buffer.channel(index).as_slice(),
+                self.oversampled.channel_mut(index),
+            );
+        }
+        self.process_oversampled(num_samples, f);
+        for (index, channel) in self.channels.iter_mut().enumerate().take(buffer.channels()) {
+            let mut samples = buffer.channel_mut(index);
+            channel.downsample(
+                samples.as_mut_slice(),
+                self.oversampled.channel_mut(index),
+                &mut self.delay_tap,
+            );
+        }
+    }
+
+    fn process_oversampled(&mut self, num_samples: usize, f: impl FnOnce(&mut AudioBuffer)) {
+        assert!(num_samples <= self.max_buffer_size);
+        f(&mut self
+            .oversampled
+            .as_audio_buffer(num_samples * self.factor.factor()));
+    }
+}
+
+/// The buffers that the stages alternate between, starting with the one the first stage
+/// up-samples to. Ordered so that the last stage up-samples to `oversampled`.
+fn stage_buffers<'a>(
+    stages: usize,
+    scratch: &'a mut [f32],
+    oversampled: &'a mut [f32],
+) -> (&'a mut [f32], &'a mut [f32]) {
+    if stages % 2 == 1 {
+        (oversampled, scratch)
+    } else {
+        (scratch, oversampled)
+    }
+}
+
+impl OversamplerChannel {
+    fn upsample(&mut self, input: &[f32], oversampled: &mut [f32]) {
+        let mut len = input.len();
+        let (a, b) = stage_buffers(self.upsamplers.len(), &mut self.scratch, oversampled);
+        self.upsamplers[0].process(input, &mut a[..2 * len]);
+        for (stage, upsampler) in self.upsamplers.iter_mut().enumerate().skip(1) {
+            let (from, to) = if stage % 2 == 1 {
+                (&*a, &mut *b)
+            } else {
+                (&*b, &mut *a)
+            };
+            len *= 2;
+            upsampler.process(&from[..len], &mut to[..2 * len]);
+        }
+    }
+
+    fn downsample(
+        &mut self,
+        output: &mut [f32],
+        oversampled: &mut [f32],
+        delay_tap: &mut DelayTap<f32>,
+    ) {
+        let stages = self.downsamplers.len();
+        let mut len = output.len() << stages;
+        if delay_tap.delay() > 0.0 {
+            for sample in oversampled[..len].iter_mut() {
+                *sample = self.delay.tick(*sample, delay_tap);
+            }
+        }
+
+        let (a, b) = stage_buffers(stages, &mut self.scratch, oversampled);
+
+        for stage in (1..stages).rev() {
+            let (from, to) = if stage % 2 == 1 {
+                (&*b, &mut *a)
+            } else {
+                (&*a, &mut *b)
+            };
+            self.downsamplers[stage].process(&from[..len], &mut to[..len / 2]);
+            len /= 2;
+        }
+        self.downsamplers[0].process(&a[..len], output);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FACTORS: [OversamplingFactor; 4] = [
+        OversamplingFactor::X2,
+        OversamplingFactor::X4,
+        OversamplingFactor::X8,
+        OversamplingFactor::X16,
+    ];
+
+    /// Magnitude of the DFT of `signal` at `frequency` (relative to the sample rate), scaled
+    /// so that a sine with amplitude A gives A
+    fn magnitude_at(signal: &[f64], frequency: f64) -> f64 {
+        let (re, im) = signal
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (i, x)| {
+                let angle = std::f64::consts::TAU * frequency * i as f64;
+                (re + x * angle.cos(), im - x * angle.sin())
+            });
+        2.0 * (re * re + im * im).sqrt() / signal.len() as f64
+    }
+
+    fn sine(frequency: f64, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (std::f64::consts::TAU * frequency * i as f64).sin() as f32)
+            .collect()
+    }
+
+    #[test]
+    fn latency_is_exact() {
+        const LEN: usize = 512;
+        for filter in [OversamplingFilter::Fir, OversamplingFilter::Iir] {
+            for factor in FACTORS {
+                let mut oversampler = Oversampler::new(factor, filter);
+                oversampler.prepare(1, LEN);
+                let latency = oversampler.latency_samples();
+
+                // A low frequency sine should come out delayed by exactly the latency
+                let input = sine(0.01, 4 * LEN);
+                let mut output = vec![0.0f32; 4 * LEN];
+                for (input, output) in input.chunks(LEN).zip(output.chunks_mut(LEN)) {
+                    let mut input_block: [f32; LEN] = input.try_into().unwrap();
+                    let mut output_block = [0.0f32; LEN];
+                    let mut input_channels = [&mut input_block];
+                    let mut output_channels = [&mut output_block];
+                    let input_buffer = AudioBuffer::from_slice(&mut input_channels);
+                    let mut output_buffer = AudioBuffer::from_slice(&mut output_channels);
+                    oversampler.process(&input_buffer, &mut output_buffer, |buffer| {
+                        assert_eq!(buffer.samples(), LEN * factor.factor());
+                    });
+                    output.copy_from_slice(&output_block);
+                }
+
+                // The IIR filters only have a constant delay at low frequencies, and the latency
+                // is rounded to the closest sample at the highest rate
+                let tolerance = match filter {
+                    OversamplingFilter::Fir => 1e-4,
+                    OversamplingFilter::Iir => 2e-2,
+                };
+                for i in 2 * LEN..4 * LEN {
+                    let error = (output[i] - input[i - latency]).abs();
+                    assert!(error < tolerance, "{filter:?} {factor:?}: {error}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn images_are_rejected() {
+        // Up-sample a sine close to the base rate Nyquist, the image on the other side of it
+        // should be removed
+        const LEN: usize = 4800;
+        for filter in [OversamplingFilter::Fir, OversamplingFilter::Iir] {
+            let mut oversampler = Oversampler::new(OversamplingFactor::X2, filter);
+            oversampler.prepare(1, LEN);
+            let mut block: [f32; LEN] = sine(0.4, LEN).try_into().unwrap();
+            let mut channels = [&mut block];
+            let mut buffer = AudioBuffer::from_slice(&mut channels);
+            let mut oversampled = Vec::new();
+            oversampler.process_in_place(&mut buffer, |buffer| {
+                oversampled.extend_from_slice(buffer.channel(0).as_slice());
+            });
+
+            let signal: Vec<f64> = oversampled[1000..].iter().map(|&x| x as f64).collect();
+            // The sine is at 0.2 and the image at 0.3 of the oversampled rate
+            assert!(magnitude_at(&signal, 0.2) > 0.9);
+            let image = magnitude_at(&signal, 0.3);
+            assert!(image < 1e-4, "{filter:?}: {image}");
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use crate::AudioBuffer;
+
+use super::kaiser_window;
+
+/// Number of table entries per zero crossing of the sinc kernel. Values in between are
+/// linearly interpolated.
+const TABLE_RESOLUTION: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResamplerQuality {
+    /// 16 taps, for previews and other cases where CPU matters more than quality
+    Low,
+    /// 32 taps
+    Medium,
+    /// 64 taps, with a stopband attenuation of about 100 dB
+    High,
+}
+
+impl ResamplerQuality {
+    /// Zero crossings on each side of the kernel, the Kaiser window beta and the cutoff
+    /// relative to the Nyquist frequency
+    fn kernel_parameters(&self) -> (usize, f64, f64) {
+        match self {
+            Self::Low => (8, 6.0, 0.85),
+            Self::Medium => (16, 8.0, 0.9),
+            Self::High => (32, 10.0, 0.95),
+        }
+    }
+}
+
+/// Band-limited resampler, using windowed-sinc interpolation. The ratio between the output
+/// and the input sample rates can be arbitrary, and can change over time. When down-sampling,
+/// the kernel is stretched to filter out everything above the new Nyquist frequency.
+///
+/// Processing is streaming, input and output can be passed in chunks of any size, and the
+/// resampler will consume and produce as many samples as it can. All memory is allocated in
+/// [`Resampler::new`].
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    half_width: usize,
+    table: Box<[f32]>,
+    min_ratio: f64,
+    ratio: f64,
+    ratio_increment: f64,
+    ratio_ramp_samples: usize,
+    /// Position of the next output sample, relative to the center of the history window
+    position: f64,
+    /// The max distance, in input samples, from an output sample to the input samples it is
+    /// computed from
+    max_half_width: usize,
+    histories: Vec<History>,
+}
+
+impl Resampler {
+    /// Create a resampler for `num_channels` channels. `min_ratio` is the lowest output/input
+    /// rate ratio that will be used, which determines how much input history must be kept.
+    pub fn new(num_channels: usize, quality: ResamplerQuality, min_ratio: f64) -> Self {
+        assert!(min_ratio > 0.0);
+        let (half_width, beta, cutoff) = quality.kernel_parameters();
+        let table = (0..=half_width * TABLE_RESOLUTION + 1)
+            .map(|i| {
+                let x = i as f64 / TABLE_RESOLUTION as f64;
+                let sinc = if i == 0 {
+                    1.0
+                } else {
+                    let y = std::f64::consts::PI * cutoff * x;
+                    y.sin() / y
+                };
+                (cutoff * sinc * kaiser_window(x / half_width as f64, beta)) as f32
+            })
+            .collect();
+        let max_half_width = (half_width as f64 / min_ratio.min(1.0)).ceil() as usize;
+
+        Self {
+            half_width,
+            table,
+            min_ratio,
+            ratio: 1.0,
+            ratio_increment: 0.0,
+            ratio_ramp_samples: 0,
+            position: 1.0,
+            max_half_width,
+            histories: vec![History::new(2 * max_half_width); num_channels],
+        }
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.histories.len()
+    }
+
+    /// The current output/input sample rate ratio
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    /// Set the output/input sample rate ratio, e.g. 48000 / 44100 to convert from 44.1 kHz
+    /// to 48 kHz. Clamped to the `min_ratio` passed to [`Resampler::new`].
+    pub fn set_ratio(&mut self, ratio: f64) {
+        self.ratio = ratio.max(self.min_ratio);
+        self.ratio_ramp_samples = 0;
+    }
+
+    /// Change the ratio linearly over the next `num_output_samples` samples, for smooth
+    /// pitch changes
+    pub fn ramp_ratio(&mut self, ratio: f64, num_output_samples: usize) {
+        let ratio = ratio.max(self.min_ratio);
+        if num_output_samples == 0 {
+            self.set_ratio(ratio);
+        } else {
+            self.ratio_increment = (ratio - self.ratio) / num_output_samples as f64;
+            self.ratio_ramp_samples = num_output_samples;
+        }
+    }
+
+    /// The delay, in input samples
+    pub fn latency(&self) -> f64 {
+        self.max_half_width as f64
+    }
+
+    pub fn reset(&mut self) {
+        for history in self.histories.iter_mut() {
+            history.reset();
+        }
+        self.position = 1.0;
+        self.ratio_ramp_samples = 0;
+    }
+
+    /// Resample `input` to `output`, with one slice per channel. Returns the number of input
+    /// samples consumed and the number of output samples produced. Processing stops when the
+    /// input runs out or the output is full, the remaining input should be passed again in
+    /// the next call.
+    pub fn process(&mut self, input: &[&[f32]], output: &mut [&mut [f32]]) -> (usize, usize) {
+        let num_channels = self.histories.len();
+        assert!(input.len() >= num_channels && output.len() >= num_channels);
+        let input_len = input[..num_channels]
+            .iter()
+            .map(|channel| channel.len())
+            .min()
+            .unwrap_or(0);
+        let output_len = output[..num_channels]
+            .iter()
+            .map(|channel| channel.len())
+            .min()
+            .unwrap_or(0);
+
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            while self.position >= 1.0 {
+                if consumed == input_len {
+                    return (consumed, produced);
+                }
+                for (history, channel) in self.histories.iter_mut().zip(input) {
+                    history.push(channel[consumed]);
+                }
+                consumed += 1;
+                self.position -= 1.0;
+            }
+            if produced == output_len {
+                return (consumed, produced);
+            }
+
+            let scale = self.ratio.min(1.0);
+            for (history, channel) in self.histories.iter().zip(output.iter_mut()) {
+                channel[produced] = self.interpolate(history.samples(), scale);
+            }
+            produced += 1;
+
+            if self.ratio_ramp_samples > 0 {
+                self.ratio_ramp_samples -= 1;
+                self.ratio = (self.ratio + self.ratio_increment).max(self.min_ratio);
+            }
+            self.position += 1.0 / self.ratio;
+        }
+    }
+
+    /// Same as [`Resampler::process`], but with the channels of [`AudioBuffer`]s
+    pub fn process_buffer(
+        &mut self,
+        input: &AudioBuffer,
+        output: &mut AudioBuffer,
+    ) -> (usize, usize) {
+        let num_channels = self.histories.len();
+        assert!(input.channels() >= num_channels && output.channels() >= num_channels);
+
+        // Up to 8 channels without allocating
+        const MAX_CHANNELS: usize = 8;
+        assert!(num_channels <= MAX_CHANNELS);
+        let input_channels: [&[f32]; MAX_CHANNELS] = std::array::from_fn(|index| {
+            if index < num_channels {
+                input.channel(index).as_slice()
+            } else {
+                &[]
+            }
+        });
+        let mut output_iter = output.channels_iter_mut();
+        let mut output_channels: [&mut [f32]; MAX_CHANNELS] = std::array::from_fn(|index| {
+            if index < num_channels {
+                output_iter.next().unwrap().into_mut_slice()
+            } else {
+                &mut []
+            }
+        });
+        self.process(
+            &input_channels[..num_channels],
+            &mut output_channels[..num_channels],
+        )
+    }
+
+    /// Evaluate the band-limited signal at the current position
+    #[inline]
+    fn interpolate(&self, samples: &[f32], scale: f64) -> f32 {
+        // The output position is between samples[center] and samples[center + 1]
+        let center = self.max_half_width - 1;
+        let half_width =
+            ((self.half_width as f64 / scale).ceil() as usize).min(self.max_half_width);
+        let step = scale * TABLE_RESOLUTION as f64;
+        let max_index = (self.half_width * TABLE_RESOLUTION) as f64;
+
+        let mut sum = 0.0f32;
+        let first = center + 1 - half_width;
+        for (i, &sample) in samples[first..center + 1 + half_width].iter().enumerate() {
+            let distance = ((first + i) as f64 - center as f64 - self.position).abs();
+            let index = distance * step;
+            if index < max_index {
+                let integer = index as usize;
+                let fraction = (index - integer as f64) as f32;
+                let a = self.table[integer];
+                let b = self.table[integer + 1];
+                sum += sample * (a + fraction * (b - a));
+            }
+        }
+        sum * scale as f32
+    }
+}
+
+/// Input history, stored twice so that the window is always available as a contiguous
+/// slice, oldest first
+#[derive(Debug, Clone)]
+struct History {
+    buffer: Box<[f32]>,
+    position: usize,
+}
+
+impl History {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; 2 * len].into_boxed_slice(),
+            position: 0,
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, sample: f32) {
+        let len = self.buffer.len() / 2;
+        self.buffer[self.position] = sample;
+        self.buffer[self.position + len] = sample;
+        self.position = (self.position + 1) % len;
+    }
+
+    #[inline]
+    fn samples(&self) -> &[f32] {
+        let len = self.buffer.len() / 2;
+        &self.buffer[self.position..self.position + len]
+    }
+
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.position = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sine(frequency: f64, sample_rate: f64, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (std::f64::consts::TAU * frequency * i as f64 / sample_rate).sin() as f32)
+            .collect()
+    }
+
+    /// Resample the same input on all channels, returning the output of the first channel
+    fn resample(resampler: &mut Resampler, input: &[f32], output_len: usize) -> Vec<f32> {
+        let num_channels = resampler.num_channels();
+        let mut outputs = vec![vec![0.0; output_len]; num_channels];
+        let mut output_slices: Vec<&mut [f32]> = outputs
+            .iter_mut()
+            .map(|output| output.as_mut_slice())
+            .collect();
+        let (consumed, produced) =
+            resampler.process(&vec![input; num_channels], &mut output_slices);
+        assert!(consumed == input.len() || produced == output_len);
+        let mut output = outputs.swap_remove(0);
+        output.truncate(produced);
+        output
+    }
+
+    #[test]
+    fn sine_is_preserved() {
+        for quality in [
+            ResamplerQuality::Low,
+            ResamplerQuality::Medium,
+            ResamplerQuality::High,
+        ] {
+            for (input_rate, output_rate) in [(44100.0, 48000.0), (48000.0, 44100.0)] {
+                let mut resampler = Resampler::new(1, quality, 0.5);
+                resampler.set_ratio(output_rate / input_rate);
+                let input = sine(1000.0, input_rate, 10000);
+                let output = resample(&mut resampler, &input, 20000);
+
+                // Output sample m is at input time m / ratio - latency
+                let latency = resampler.latency();
+                let mut max_error = 0.0f64;
+                for (m, &y) in output.iter().enumerate().skip(200) {
+                    let t = m as f64 * input_rate / output_rate - latency;
+                    if t > input.len() as f64 - 200.0 {
+                        break;
+                    }
+                    let expected = (std::f64::consts::TAU * 1000.0 * t / input_rate).sin();
+                    max_error = max_error.max((y as f64 - expected).abs());
+                }
+                assert!(
+                    max_error < 1e-3,
+                    "{quality:?} {input_rate} -> {output_rate}: {max_error}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn down_sampling_removes_aliases() {
+        // 18 kHz can't be represented at 24 kHz, and should be filtered out
+        let mut resampler = Resampler::new(1, ResamplerQuality::High, 0.5);
+        resampler.set_ratio(0.5);
+        let input = sine(18000.0, 48000.0, 9600);
+        let output = resample(&mut resampler, &input, 4800);
+        let peak = output[200..]
+            .iter()
+            .fold(0.0f32, |peak, &x| peak.max(x.abs()));
+        assert!(peak < 1e-3, "{peak}");
+    }
+
+    #[test]
+    fn chunked_processing() {
+        let input = sine(440.0, 44100.0, 5000);
+        let mut resampler = Resampler::new(2, ResamplerQuality::Medium, 0.5);
+        resampler.ramp_ratio(0.7, 3000);
+        let mut reference = resampler.clone();
+
+        let expected = resample(&mut reference, &input, 10000);
+
+        // Feed the input in small, irregular chunks, with a small output buffer
+        let mut output = Vec::new();
+        let mut start = 0;
+        let mut left = [0.0f32; 37];
+        let mut right = [0.0f32; 37];
+        for chunk_size in [1, 13, 100, 7].iter().cycle() {
+            let end = (start + chunk_size).min(input.len());
+            let chunk = &input[start..end];
+            let (consumed, produced) =
+                resampler.process(&[chunk, chunk], &mut [&mut left, &mut right]);
+            assert_eq!(left[..produced], right[..produced]);
+            output.extend_from_slice(&left[..produced]);
+            start += consumed;
+            if start == input.len() {
+                break;
+            }
+        }
+        assert_eq!(output, expected[..output.len()]);
+        assert!(output.len() + 2 >= expected.len());
+    }
+}
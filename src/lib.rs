@@ -5,10 +5,14 @@ pub mod dsp;
 mod editor;
 mod event;
 pub mod midi;
+mod owned_audio_buffer;
 pub mod param;
 pub mod platform;
 mod plugin;
 mod processor;
+mod state;
+pub mod testing;
+mod transport;
 pub mod ui;
 pub mod util;
 pub mod views;
@@ -18,5 +22,14 @@ pub use audio_buffer::*;
 pub use audiolayout::*;
 pub use editor::*;
 pub use event::{AnimationFrame, KeyEvent, MouseButton, MouseButtons, MouseEvent};
+pub use owned_audio_buffer::{InterleavedAudioBuffer, OwnedAudioBuffer};
 pub use plugin::*;
+pub use state::{PluginState, StateError};
+pub use transport::*;
 pub use uuid::Uuid;
+
+/// Detects allocations on the audio thread in the tests, see [`util::assert_no_alloc`]. Plugins
+/// install [`util::CheckingAllocator`] themselves.
+#[cfg(test)]
+#[global_allocator]
+static ALLOCATOR: util::CheckingAllocator = util::CheckingAllocator::new();
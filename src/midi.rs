@@ -1,4 +1,4 @@
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Note(pub u8);
 
 fn semitone_freq_ratio() -> f32 {
@@ -24,6 +24,7 @@ impl Note {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NoteEvent {
     NoteOn {
         channel: i16,
@@ -36,3 +37,29 @@ pub enum NoteEvent {
         note: Note,
     },
 }
+
+impl NoteEvent {
+    pub fn sample_offset(&self) -> i32 {
+        match *self {
+            Self::NoteOn { sample_offset, .. } | Self::NoteOff { sample_offset, .. } => {
+                sample_offset
+            }
+        }
+    }
+
+    /// The same event, at another position in the block
+    pub fn with_sample_offset(self, sample_offset: i32) -> Self {
+        match self {
+            Self::NoteOn { channel, note, .. } => Self::NoteOn {
+                channel,
+                sample_offset,
+                note,
+            },
+            Self::NoteOff { channel, note, .. } => Self::NoteOff {
+                channel,
+                sample_offset,
+                note,
+            },
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::alloc::{Layout, alloc, dealloc, handle_alloc_error};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::slice::{ChunksExact, ChunksExactMut};
+
+use crate::dsp::DspFloat;
+use crate::{AudioBuffer, AudioBufferRef};
+
+/// Alignment of the samples in owned buffers, in bytes. Enough for AVX-512 loads, and
+/// makes every channel start on a cache line.
+const ALIGNMENT: usize = 64;
+
+/// Heap allocated, zero initialized samples aligned to [`ALIGNMENT`]
+struct AlignedSamples<T> {
+    ptr: NonNull<T>,
+    len: usize,
+}
+
+impl<T: DspFloat> AlignedSamples<T> {
+    fn new(len: usize) -> Self {
+        if len == 0 {
+            return Self::empty();
+        }
+        let layout = Self::layout(len);
+        let ptr = unsafe { alloc(layout) }.cast::<T>();
+        let Some(ptr) = NonNull::new(ptr) else {
+            handle_alloc_error(layout)
+        };
+        for i in 0..len {
+            unsafe { ptr.add(i).write(T::zero()) };
+        }
+        Self { ptr, len }
+    }
+}
+
+impl<T> AlignedSamples<T> {
+    fn empty() -> Self {
+        Self {
+            ptr: NonNull::dangling(),
+            len: 0,
+        }
+    }
+
+    fn layout(len: usize) -> Layout {
+        Layout::array::<T>(len)
+            .and_then(|layout| layout.align_to(ALIGNMENT))
+            .expect("Audio buffer too large")
+    }
+}
+
+impl<T> Drop for AlignedSamples<T> {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe { dealloc(self.ptr.as_ptr().cast(), Self::layout(self.len)) };
+        }
+    }
+}
+
+impl<T> Deref for AlignedSamples<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for AlignedSamples<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+// Safety: AlignedSamples owns its samples, just like a Box<[T]>
+unsafe impl<T: Send> Send for AlignedSamples<T> {}
+unsafe impl<T: Sync> Sync for AlignedSamples<T> {}
+
+/// Preallocated multichannel buffer, for scratch buffers shaped like the host buffers.
+/// Allocate it in [`crate::Plugin::prepare`], and view it as an [`AudioBuffer`] with the
+/// size of the current block during processing, which does not allocate.
+///
+/// Each channel is stored contiguously, and starts at a 64 byte boundary.
+pub struct OwnedAudioBuffer<T = f32> {
+    samples: AlignedSamples<T>,
+    channel_pointers: Box<[*mut T]>,
+    capacity: usize,
+    stride: usize,
+}
+
+// Safety: The channel pointers point into the owned samples
+unsafe impl<T: Send> Send for OwnedAudioBuffer<T> {}
+
+impl<T: DspFloat> OwnedAudioBuffer<T> {
+    /// Create a buffer without any channels. Call [`OwnedAudioBuffer::prepare`] before using it.
+    pub fn new() -> Self {
+        Self {
+            samples: AlignedSamples::empty(),
+            channel_pointers: Box::new([]),
+            capacity: 0,
+            stride: 0,
+        }
+    }
+
+    pub fn with_capacity(num_channels: usize, max_samples: usize) -> Self {
+        let mut this = Self::new();
+        this.prepare(num_channels, max_samples);
+        this
+    }
+
+    /// Allocate room for `num_channels` channels of `max_samples` samples, and clear the
+    /// buffer. Only allocates if the size has changed.
+    pub fn prepare(&mut self, num_channels: usize, max_samples: usize) {
+        if num_channels == self.channels() && max_samples == self.capacity {
+            self.clear();
+            return;
+        }
+
+        let samples_per_alignment = (ALIGNMENT / size_of::<T>()).max(1);
+        self.stride = max_samples.next_multiple_of(samples_per_alignment);
+        self.capacity = max_samples;
+        self.samples = AlignedSamples::new(num_channels * self.stride);
+        let ptr = self.samples.ptr.as_ptr();
+        self.channel_pointers = (0..num_channels)
+            .map(|index| ptr.wrapping_add(index * self.stride))
+            .collect();
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channel_pointers.len()
+    }
+
+    /// Maximum number of samples per channel
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// All samples of a channel, up to the capacity
+    pub fn channel(&self, index: usize) -> &[T] {
+        &self.samples[index * self.stride..][..self.capacity]
+    }
+
+    pub fn channel_mut(&mut self, index: usize) -> &mut [T] {
+        &mut self.samples[index * self.stride..][..self.capacity]
+    }
+
+    /// View the first `num_samples` samples of every channel as an audio buffer
+    pub fn as_audio_buffer(&mut self, num_samples: usize) -> AudioBuffer<'_, T> {
+        assert!(num_samples <= self.capacity);
+        unsafe {
+            AudioBuffer::from_ptr(
+                self.channel_pointers.as_mut_ptr(),
+                self.channels(),
+                num_samples,
+            )
+        }
+    }
+
+    pub fn as_audio_buffer_ref(&self, num_samples: usize) -> AudioBufferRef<'_, T> {
+        assert!(num_samples <= self.capacity);
+        // The pointer array is never written through, it is only mutable because of the
+        // signature of AudioBuffer::from_ptr
+        AudioBufferRef(unsafe {
+            AudioBuffer::from_ptr(
+                self.channel_pointers.as_ptr().cast_mut(),
+                self.channels(),
+                num_samples,
+            )
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.fill(T::zero());
+    }
+}
+
+impl<T: DspFloat> Default for OwnedAudioBuffer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Preallocated buffer with the channels interleaved, so that the samples of a frame are
+/// adjacent in memory. Useful for processing all channels at once with SIMD. Samples are
+/// moved to and from regular buffers with [`InterleavedAudioBuffer::interleave_from`] and
+/// [`InterleavedAudioBuffer::deinterleave_to`].
+pub struct InterleavedAudioBuffer<T = f32> {
+    samples: AlignedSamples<T>,
+    num_channels: usize,
+    capacity: usize,
+}
+
+impl<T: DspFloat> InterleavedAudioBuffer<T> {
+    /// Create a buffer without any channels. Call [`InterleavedAudioBuffer::prepare`] before
+    /// using it.
+    pub fn new() -> Self {
+        Self {
+            samples: AlignedSamples::empty(),
+            num_channels: 0,
+            capacity: 0,
+        }
+    }
+
+    pub fn with_capacity(num_channels: usize, max_samples: usize) -> Self {
+        let mut this = Self::new();
+        this.prepare(num_channels, max_samples);
+        this
+    }
+
+    /// Allocate room for `max_samples` frames of `num_channels` channels, and clear the
+    /// buffer. Only allocates if the size has changed.
+    pub fn prepare(&mut self, num_channels: usize, max_samples: usize) {
+        if num_channels == self.num_channels && max_samples == self.capacity {
+            self.clear();
+            return;
+        }
+        self.samples = AlignedSamples::new(num_channels * max_samples);
+        self.num_channels = num_channels;
+        self.capacity = max_samples;
+    }
+
+    pub fn channels(&self) -> usize {
+        self.num_channels
+    }
+
+    /// Maximum number of frames
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The interleaved samples of the first `num_samples` frames
+    pub fn as_slice(&self, num_samples: usize) -> &[T] {
+        &self.samples[..num_samples * self.num_channels]
+    }
+
+    pub fn as_mut_slice(&mut self, num_samples: usize) -> &mut [T] {
+        &mut self.samples[..num_samples * self.num_channels]
+    }
+
+    /// Iterate over the first `num_samples` frames, each frame holding one sample per channel
+    pub fn frames(&self, num_samples: usize) -> ChunksExact<'_, T> {
+        let num_channels = self.num_channels.max(1);
+        self.as_slice(num_samples).chunks_exact(num_channels)
+    }
+
+    pub fn frames_mut(&mut self, num_samples: usize) -> ChunksExactMut<'_, T> {
+        let num_channels = self.num_channels.max(1);
+        self.as_mut_slice(num_samples)
+            .chunks_exact_mut(num_channels)
+    }
+
+    /// Interleave the samples of `buffer`. Channels missing from `buffer` are set to zero.
+    pub fn interleave_from(&mut self, buffer: &AudioBuffer<T>) {
+        let num_samples = buffer.samples();
+        assert!(num_samples <= self.capacity);
+        let num_channels = self.num_channels;
+        let samples = self.as_mut_slice(num_samples);
+        for channel in 0..num_channels {
+            if channel < buffer.channels() {
+                let source = buffer.channel(channel);
+                for (i, &sample) in source.as_slice().iter().enumerate() {
+                    samples[i * num_channels + channel] = sample;
+                }
+            } else {
+                for i in 0..num_samples {
+                    samples[i * num_channels + channel] = T::zero();
+                }
+            }
+        }
+    }
+
+    /// Write the first `buffer.samples()` frames back to the channels of `buffer`. Channels
+    /// that only exist in one of the buffers are ignored.
+    pub fn deinterleave_to(&self, buffer: &mut AudioBuffer<T>) {
+        let num_samples = buffer.samples();
+        assert!(num_samples <= self.capacity);
+        let samples = self.as_slice(num_samples);
+        for (channel, mut destination) in buffer
+            .channels_iter_mut()
+            .enumerate()
+            .take(self.num_channels)
+        {
+            for (i, sample) in destination.iter_mut().enumerate() {
+                *sample = samples[i * self.num_channels + channel];
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.fill(T::zero());
+    }
+}
+
+impl<T: DspFloat> Default for InterleavedAudioBuffer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn owned_buffer_view() {
+        let mut owned = OwnedAudioBuffer::<f32>::with_capacity(2, 100);
+        for index in 0..owned.channels() {
+            assert_eq!(owned.channel(index).as_ptr() as usize % ALIGNMENT, 0);
+        }
+
+        let mut buffer = owned.as_audio_buffer(10);
+        assert_eq!((buffer.channels(), buffer.samples()), (2, 10));
+        buffer.channel_mut(1).as_mut_slice().fill(1.0);
+
+        assert_eq!(owned.channel(0), [0.0; 100]);
+        assert_eq!(owned.channel(1)[..10], [1.0; 10]);
+        assert_eq!(owned.channel(1)[10], 0.0);
+        assert_eq!(owned.as_audio_buffer_ref(5).channel(1).as_slice(), [1.0; 5]);
+    }
+
+    #[test]
+    pub fn interleave_round_trip() {
+        let mut left = [1.0f64, 2.0, 3.0];
+        let mut right = [4.0f64, 5.0, 6.0];
+        let mut channels = [&mut left, &mut right];
+        let mut buffer = AudioBuffer::from_slice(&mut channels);
+
+        let mut interleaved = InterleavedAudioBuffer::with_capacity(2, 8);
+        interleaved.interleave_from(&buffer);
+        assert_eq!(interleaved.as_slice(3), [1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+
+        for frame in interleaved.frames_mut(3) {
+            frame.swap(0, 1);
+        }
+        interleaved.deinterleave_to(&mut buffer);
+        assert_eq!(buffer.channel(0).as_slice(), [4.0, 5.0, 6.0]);
+        assert_eq!(buffer.channel(1).as_slice(), [1.0, 2.0, 3.0]);
+    }
+}
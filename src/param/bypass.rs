@@ -5,8 +5,13 @@ use crate::{
     ui::reactive::ReadSignal,
 };
 
-use super::{AnyParameter, NormalizedValue, ParameterId, PlainValue};
+use super::{
+    AnyParameter, NormalizedValue, ParamVisitor, ParameterId, ParameterTraversal, PlainValue,
+};
 
+/// Parameter for bypassing the plugin. The host shows it as the bypass switch of the
+/// plugin, and the wrappers bypass the plugin when it is on, see
+/// [`crate::Plugin::BYPASS_CROSSFADE`].
 pub struct ByPassParameter {
     id: ParameterId,
     value: Cell<bool>,
@@ -120,3 +125,9 @@ impl Parameter for ByPassParameter {
         }
     }
 }
+
+impl ParameterTraversal for ByPassParameter {
+    fn visit<V: ParamVisitor>(&self, visitor: &mut V) {
+        visitor.bypass_parameter(self)
+    }
+}
@@ -5,6 +5,7 @@ mod bypass;
 mod float;
 mod group;
 mod int;
+mod note_division;
 mod parameter_map;
 mod string_list;
 mod traversal;
@@ -14,7 +15,8 @@ pub use bypass::ByPassParameter;
 pub use float::{FloatParameter, FloatRange};
 pub use group::{AnyParameterGroup, ParameterGroup};
 pub use int::{IntParameter, IntRange};
-pub use parameter_map::{AnyParameterMap, ParamRef, ParameterMap, Params};
+pub use note_division::NoteDivisionParameter;
+pub use parameter_map::{AnyParameterMap, OwnedParameterMap, ParamRef, ParameterMap, Params};
 pub use string_list::StringListParameter;
 pub use traversal::{ParamVisitor, ParameterTraversal};
 
@@ -0,0 +1,39 @@
+use std::ops::Deref;
+
+use crate::dsp::NoteDivision;
+
+use super::{ParamVisitor, ParameterId, ParameterTraversal, StringListParameter};
+
+/// Choice of a tempo synced [`NoteDivision`], e.g. for the rate of a synced LFO or delay.
+/// Exposed to the host as a [`StringListParameter`] with one entry per division in
+/// [`NoteDivision::ALL`].
+pub struct NoteDivisionParameter(StringListParameter);
+
+impl NoteDivisionParameter {
+    pub fn new(id: ParameterId, name: &'static str, default: NoteDivision) -> Self {
+        let strings: Vec<String> = NoteDivision::ALL.iter().map(|x| x.to_string()).collect();
+        Self(StringListParameter::new(id, name, strings, default.index()))
+    }
+
+    pub fn value(&self) -> NoteDivision {
+        NoteDivision::ALL[self.0.value()]
+    }
+
+    pub fn set_value(&self, value: NoteDivision) {
+        self.0.set_value(value.index());
+    }
+}
+
+impl Deref for NoteDivisionParameter {
+    type Target = StringListParameter;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ParameterTraversal for NoteDivisionParameter {
+    fn visit<V: ParamVisitor>(&self, visitor: &mut V) {
+        visitor.string_list_parameter(&self.0)
+    }
+}
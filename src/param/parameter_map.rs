@@ -1,4 +1,4 @@
-use std::{ptr::NonNull, rc::Rc};
+use std::{ops::Deref, ptr::NonNull, rc::Rc};
 
 use rustc_hash::FxHashMap;
 
@@ -10,7 +10,7 @@ use super::{
     traversal::ParameterTraversal,
 };
 
-pub trait Params: ParameterTraversal {
+pub trait Params: ParameterTraversal + Send {
     fn new() -> Self;
 }
 
@@ -135,6 +135,7 @@ struct GatherParamPtrsVisitor<'a> {
     params_vec: &'a mut Vec<(Option<GroupId>, ParamPtr)>,
     params_map: &'a mut FxHashMap<ParameterId, ParamPtr>,
     groups_vec: &'a mut Vec<(Option<GroupId>, *const dyn AnyParameterGroup)>,
+    bypass: &'a mut Option<NonNull<ByPassParameter>>,
 }
 
 impl GatherParamPtrsVisitor<'_> {
@@ -150,6 +151,7 @@ impl ParamVisitor for GatherParamPtrsVisitor<'_> {
     }
 
     fn bypass_parameter(&mut self, p: &super::ByPassParameter) {
+        self.bypass.get_or_insert(NonNull::from_ref(p));
         self.add_param_ptr(p.id(), ParamPtr::ByPass(NonNull::from_ref(p)));
     }
 
@@ -181,6 +183,7 @@ pub struct ParameterMap<P: Params> {
     params_vec: Vec<(Option<GroupId>, ParamPtr)>,
     params_map: FxHashMap<ParameterId, ParamPtr>,
     groups_vec: Vec<(Option<GroupId>, *const dyn AnyParameterGroup)>,
+    bypass: Option<NonNull<ByPassParameter>>,
 }
 
 impl<P: Params> ParameterMap<P> {
@@ -191,6 +194,7 @@ impl<P: Params> ParameterMap<P> {
             params_vec: Vec::new(),
             params_map: FxHashMap::default(),
             groups_vec: Vec::new(),
+            bypass: None,
         });
 
         let this_ref = Rc::get_mut(&mut this).unwrap();
@@ -199,6 +203,7 @@ impl<P: Params> ParameterMap<P> {
             params_vec: &mut this_ref.params_vec,
             params_map: &mut this_ref.params_map,
             groups_vec: &mut this_ref.groups_vec,
+            bypass: &mut this_ref.bypass,
         };
         this_ref.parameters.visit(&mut visitor);
 
@@ -209,6 +214,11 @@ impl<P: Params> ParameterMap<P> {
         &self.parameters
     }
 
+    /// The first bypass parameter of the plugin, if it has one
+    pub fn bypass_parameter(&self) -> Option<&ByPassParameter> {
+        self.bypass.map(|p| unsafe { p.as_ref() })
+    }
+
     pub fn iter(&self) -> ParamIter<'_> {
         ParamIter {
             inner_iter: self.params_vec.as_slice().iter(),
@@ -216,6 +226,28 @@ impl<P: Params> ParameterMap<P> {
     }
 }
 
+/// A [`ParameterMap`] that is never shared, so that it can be moved to another thread. Used
+/// for copies of the parameters that live on the audio thread.
+pub struct OwnedParameterMap<P: Params>(Rc<ParameterMap<P>>);
+
+impl<P: Params> OwnedParameterMap<P> {
+    pub fn new(parameters: P) -> Self {
+        Self(ParameterMap::new(parameters))
+    }
+}
+
+impl<P: Params> Deref for OwnedParameterMap<P> {
+    type Target = ParameterMap<P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// Safety: the Rc is never cloned, so only the owner can access the map. The pointers in the
+// map point into the parameters that it owns.
+unsafe impl<P: Params> Send for OwnedParameterMap<P> {}
+
 impl<P: Params> AnyParameterMap for ParameterMap<P> {
     fn get_by_id<'a>(&'a self, id: ParameterId) -> Option<ParamRef<'a>> {
         self.params_map
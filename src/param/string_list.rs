@@ -2,7 +2,10 @@ use std::cell::Cell;
 
 use crate::param::Parameter;
 
-use super::{AnyParameter, NormalizedValue, ParamRef, ParameterId, PlainValue};
+use super::{
+    AnyParameter, NormalizedValue, ParamRef, ParamVisitor, ParameterId, ParameterTraversal,
+    ParseError, PlainValue,
+};
 
 pub struct StringListParameter {
     id: ParameterId,
@@ -24,7 +27,7 @@ impl StringListParameter {
             name,
             strings: strings.into(),
             default_index,
-            index: Cell::new(0),
+            index: Cell::new(default_index),
         }
     }
 
@@ -36,6 +39,10 @@ impl StringListParameter {
         self.strings.iter().position(|x| x == key)
     }
 
+    pub fn strings(&self) -> &[String] {
+        &self.strings
+    }
+
     pub fn value(&self) -> usize {
         self.index.get()
     }
@@ -61,13 +68,16 @@ impl AnyParameter for StringListParameter {
     }
 
     fn normalize(&self, value: PlainValue) -> NormalizedValue {
-        NormalizedValue(
-            value.0.clamp(0.0, self.string_count() as f64) / (self.string_count() as f64),
-        )
+        let steps = self.step_count();
+        if steps == 0 {
+            NormalizedValue(0.0)
+        } else {
+            NormalizedValue(value.0.clamp(0.0, steps as f64) / steps as f64)
+        }
     }
 
     fn denormalize(&self, value: NormalizedValue) -> PlainValue {
-        PlainValue(value.0 * (self.string_count() as f64))
+        PlainValue(value.0 * self.step_count() as f64)
     }
 
     fn step_count(&self) -> usize {
@@ -78,12 +88,16 @@ impl AnyParameter for StringListParameter {
         }
     }
 
-    fn value_from_string(&self, _str: &str) -> Result<NormalizedValue, super::ParseError> {
-        todo!()
+    fn value_from_string(&self, str: &str) -> Result<NormalizedValue, ParseError> {
+        let index = self.index_of(str.trim()).ok_or(ParseError)?;
+        Ok(self.normalize(PlainValue::new(index as _)))
     }
 
-    fn string_from_value(&self, _value: NormalizedValue) -> String {
-        todo!()
+    fn string_from_value(&self, value: NormalizedValue) -> String {
+        self.strings
+            .get(self.value_from_normalized(value))
+            .cloned()
+            .unwrap_or_default()
     }
 
     fn min_value(&self) -> PlainValue {
@@ -107,11 +121,11 @@ impl Parameter for StringListParameter {
     }
 
     fn value_from_plain(&self, value: PlainValue) -> Self::Value {
-        todo!()
+        value.0.round().clamp(0.0, self.step_count() as f64) as _
     }
 
     fn value_from_normalized(&self, value: NormalizedValue) -> Self::Value {
-        todo!()
+        self.value_from_plain(self.denormalize(value))
     }
 
     fn downcast_param_ref<'s>(param_ref: ParamRef<'s>) -> Option<&'s Self> {
@@ -121,3 +135,9 @@ impl Parameter for StringListParameter {
         }
     }
 }
+
+impl ParameterTraversal for StringListParameter {
+    fn visit<V: ParamVisitor>(&self, visitor: &mut V) {
+        visitor.string_list_parameter(self)
+    }
+}
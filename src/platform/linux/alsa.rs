@@ -0,0 +1,190 @@
+//! Functions of libasound, which is loaded at runtime so that ALSA is not needed to build, and
+//! the standalone wrapper still starts without it.
+
+use std::ffi::{CStr, c_char, c_int, c_long, c_short, c_uint, c_ulong, c_void};
+use std::sync::OnceLock;
+
+use libloading::Library;
+
+use super::Error;
+
+#[allow(non_camel_case_types)]
+pub type snd_pcm_t = c_void;
+#[allow(non_camel_case_types)]
+pub type snd_pcm_hw_params_t = c_void;
+#[allow(non_camel_case_types)]
+pub type snd_seq_t = c_void;
+#[allow(non_camel_case_types)]
+pub type snd_seq_client_info_t = c_void;
+#[allow(non_camel_case_types)]
+pub type snd_seq_port_info_t = c_void;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct snd_seq_addr_t {
+    pub client: u8,
+    pub port: u8,
+}
+
+/// Sequencer event. The data is a union, whose layout depends on the type of the event.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct snd_seq_event_t {
+    pub r#type: u8,
+    pub flags: u8,
+    pub tag: u8,
+    pub queue: u8,
+    pub time: [u32; 2],
+    pub source: snd_seq_addr_t,
+    pub dest: snd_seq_addr_t,
+    pub data: [u8; 12],
+}
+
+pub const SND_PCM_STREAM_PLAYBACK: c_int = 0;
+pub const SND_PCM_STREAM_CAPTURE: c_int = 1;
+pub const SND_PCM_NONBLOCK: c_int = 1;
+pub const SND_PCM_ACCESS_RW_INTERLEAVED: c_int = 3;
+pub const SND_PCM_FORMAT_S16_LE: c_int = 2;
+pub const SND_PCM_FORMAT_S32_LE: c_int = 10;
+pub const SND_PCM_FORMAT_FLOAT_LE: c_int = 14;
+
+pub const SND_SEQ_OPEN_INPUT: c_int = 2;
+pub const SND_SEQ_NONBLOCK: c_int = 1;
+pub const SND_SEQ_PORT_CAP_READ: c_uint = 1 << 0;
+pub const SND_SEQ_PORT_CAP_WRITE: c_uint = 1 << 1;
+pub const SND_SEQ_PORT_CAP_SUBS_READ: c_uint = 1 << 5;
+pub const SND_SEQ_PORT_CAP_SUBS_WRITE: c_uint = 1 << 6;
+pub const SND_SEQ_PORT_CAP_NO_EXPORT: c_uint = 1 << 7;
+pub const SND_SEQ_PORT_TYPE_MIDI_GENERIC: c_uint = 1 << 1;
+pub const SND_SEQ_PORT_TYPE_APPLICATION: c_uint = 1 << 20;
+pub const SND_SEQ_EVENT_NOTEON: u8 = 6;
+pub const SND_SEQ_EVENT_NOTEOFF: u8 = 7;
+
+macro_rules! alsa_functions {
+    ($($name: ident: fn($($arg: ty),*) -> $ret: ty;)*) => {
+        #[allow(non_snake_case)]
+        pub struct Alsa {
+            $(pub $name: unsafe extern "C" fn($($arg),*) -> $ret,)*
+            _library: Library,
+        }
+
+        impl Alsa {
+            fn load() -> Result<Self, libloading::Error> {
+                let library = unsafe { Library::new("libasound.so.2") }?;
+                Ok(Self {
+                    $($name: *unsafe {
+                        library.get(concat!(stringify!($name), "\0").as_bytes())
+                    }?,)*
+                    _library: library,
+                })
+            }
+        }
+    };
+}
+
+alsa_functions! {
+    snd_strerror: fn(c_int) -> *const c_char;
+    snd_device_name_hint: fn(c_int, *const c_char, *mut *mut *mut c_void) -> c_int;
+    snd_device_name_get_hint: fn(*const c_void, *const c_char) -> *mut c_char;
+    snd_device_name_free_hint: fn(*mut *mut c_void) -> c_int;
+    snd_pcm_open: fn(*mut *mut snd_pcm_t, *const c_char, c_int, c_int) -> c_int;
+    snd_pcm_close: fn(*mut snd_pcm_t) -> c_int;
+    snd_pcm_set_params: fn(*mut snd_pcm_t, c_int, c_int, c_uint, c_uint, c_int, c_uint) -> c_int;
+    snd_pcm_get_params: fn(*mut snd_pcm_t, *mut c_ulong, *mut c_ulong) -> c_int;
+    snd_pcm_writei: fn(*mut snd_pcm_t, *const c_void, c_ulong) -> c_long;
+    snd_pcm_readi: fn(*mut snd_pcm_t, *mut c_void, c_ulong) -> c_long;
+    snd_pcm_recover: fn(*mut snd_pcm_t, c_int, c_int) -> c_int;
+    snd_pcm_hw_params_malloc: fn(*mut *mut snd_pcm_hw_params_t) -> c_int;
+    snd_pcm_hw_params_free: fn(*mut snd_pcm_hw_params_t) -> ();
+    snd_pcm_hw_params_any: fn(*mut snd_pcm_t, *mut snd_pcm_hw_params_t) -> c_int;
+    snd_pcm_hw_params_get_rate_min: fn(*const snd_pcm_hw_params_t, *mut c_uint, *mut c_int) -> c_int;
+    snd_pcm_hw_params_get_rate_max: fn(*const snd_pcm_hw_params_t, *mut c_uint, *mut c_int) -> c_int;
+    snd_pcm_hw_params_get_channels_max: fn(*const snd_pcm_hw_params_t, *mut c_uint) -> c_int;
+    snd_seq_open: fn(*mut *mut snd_seq_t, *const c_char, c_int, c_int) -> c_int;
+    snd_seq_close: fn(*mut snd_seq_t) -> c_int;
+    snd_seq_client_id: fn(*mut snd_seq_t) -> c_int;
+    snd_seq_set_client_name: fn(*mut snd_seq_t, *const c_char) -> c_int;
+    snd_seq_create_simple_port: fn(*mut snd_seq_t, *const c_char, c_uint, c_uint) -> c_int;
+    snd_seq_connect_from: fn(*mut snd_seq_t, c_int, c_int, c_int) -> c_int;
+    snd_seq_event_input: fn(*mut snd_seq_t, *mut *mut snd_seq_event_t) -> c_int;
+    snd_seq_poll_descriptors_count: fn(*mut snd_seq_t, c_short) -> c_int;
+    snd_seq_poll_descriptors: fn(*mut snd_seq_t, *mut pollfd, c_uint, c_short) -> c_int;
+    snd_seq_client_info_malloc: fn(*mut *mut snd_seq_client_info_t) -> c_int;
+    snd_seq_client_info_free: fn(*mut snd_seq_client_info_t) -> ();
+    snd_seq_client_info_set_client: fn(*mut snd_seq_client_info_t, c_int) -> ();
+    snd_seq_client_info_get_client: fn(*const snd_seq_client_info_t) -> c_int;
+    snd_seq_client_info_get_name: fn(*mut snd_seq_client_info_t) -> *const c_char;
+    snd_seq_query_next_client: fn(*mut snd_seq_t, *mut snd_seq_client_info_t) -> c_int;
+    snd_seq_port_info_malloc: fn(*mut *mut snd_seq_port_info_t) -> c_int;
+    snd_seq_port_info_free: fn(*mut snd_seq_port_info_t) -> ();
+    snd_seq_port_info_set_client: fn(*mut snd_seq_port_info_t, c_int) -> ();
+    snd_seq_port_info_set_port: fn(*mut snd_seq_port_info_t, c_int) -> ();
+    snd_seq_port_info_get_port: fn(*const snd_seq_port_info_t) -> c_int;
+    snd_seq_port_info_get_name: fn(*const snd_seq_port_info_t) -> *const c_char;
+    snd_seq_port_info_get_capability: fn(*const snd_seq_port_info_t) -> c_uint;
+    snd_seq_port_info_get_type: fn(*const snd_seq_port_info_t) -> c_uint;
+    snd_seq_query_next_port: fn(*mut snd_seq_t, *mut snd_seq_port_info_t) -> c_int;
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct pollfd {
+    pub fd: c_int,
+    pub events: c_short,
+    pub revents: c_short,
+}
+
+pub const POLLIN: c_short = 1;
+
+unsafe extern "C" {
+    /// Strings returned by `snd_device_name_get_hint` are allocated with malloc
+    fn free(ptr: *mut c_void);
+    /// Used to wait for sequencer events with a timeout
+    pub fn poll(fds: *mut pollfd, nfds: c_ulong, timeout: c_int) -> c_int;
+}
+
+impl Alsa {
+    /// Convert a negative return value to an error
+    pub fn check(&self, result: c_int, what: &str) -> Result<c_int, Error> {
+        if result < 0 {
+            Err(Error::from_reason(format!(
+                "{what} failed: {}",
+                self.error_string(result)
+            )))
+        } else {
+            Ok(result)
+        }
+    }
+
+    pub fn error_string(&self, error: c_int) -> String {
+        let message = unsafe { (self.snd_strerror)(error) };
+        if message.is_null() {
+            return format!("error {error}");
+        }
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Take ownership of a string returned by libasound
+    pub fn take_string(&self, string: *mut c_char) -> Option<String> {
+        if string.is_null() {
+            return None;
+        }
+        let result = unsafe { CStr::from_ptr(string) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { free(string.cast()) };
+        Some(result)
+    }
+}
+
+/// The loaded library, or an error if libasound is not installed
+pub fn alsa() -> Result<&'static Alsa, Error> {
+    static ALSA: OnceLock<Result<Alsa, String>> = OnceLock::new();
+    ALSA.get_or_init(|| Alsa::load().map_err(|err| err.to_string()))
+        .as_ref()
+        .map_err(|err| Error::from_reason(format!("Could not load libasound: {err}")))
+}
@@ -1,27 +0,0 @@
-use super::Error;
-
-pub struct AudioHost;
-
-impl AudioHost {
-    pub fn devices() -> Result<Vec<Device>, Error> {
-        todo!()
-    }
-
-    pub fn default_output_device() -> Result<Device, Error> {
-        todo!()
-    }
-}
-
-pub struct Device {
-    
-}
-
-impl Device {
-    pub fn name(&self) -> Result<String, Error> {
-        todo!()
-    }
-
-    pub fn sample_rate(&self) -> Result<f64, Error> {
-        todo!()
-    }
-}
\ No newline at end of file
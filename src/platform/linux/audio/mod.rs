@@ -0,0 +1,340 @@
+//! Audio devices for the standalone wrapper. Sound cards are accessed through ALSA, which also
+//! reaches PipeWire, PulseAudio and JACK through their ALSA plugins (usually the `default`
+//! device). The null and WAV file devices don't need any audio hardware, for running in CI.
+
+mod pcm;
+mod stream;
+
+use std::ffi::{CStr, c_void};
+use std::path::{Path, PathBuf};
+use std::ptr::null_mut;
+
+pub use stream::Stream;
+
+use super::Error;
+use super::alsa::alsa;
+use crate::platform::StreamConfig;
+use crate::util::{SampleFormat, WavReader};
+use pcm::{Direction, Pcm};
+
+/// Used when a device does not have a preferred sample rate
+pub const DEFAULT_SAMPLE_RATE: f64 = 48000.0;
+/// The sample rates offered for devices that support a range of rates
+const COMMON_SAMPLE_RATES: [u32; 6] = [44100, 48000, 88200, 96000, 176400, 192000];
+
+pub struct AudioHost;
+
+impl AudioHost {
+    /// The ALSA PCM devices, followed by the null device. Only the null device is returned
+    /// if libasound is not available.
+    pub fn devices() -> Result<Vec<Device>, Error> {
+        let mut devices = match alsa_devices() {
+            Ok(devices) => devices,
+            Err(err) => {
+                log::warn!("{err}");
+                Vec::new()
+            }
+        };
+        devices.push(Device::null(DEFAULT_SAMPLE_RATE));
+        Ok(devices)
+    }
+
+    /// The ALSA `default` device, or the null device if ALSA is not available
+    pub fn default_output_device() -> Result<Device, Error> {
+        Ok(Self::default_alsa_device(true, false))
+    }
+
+    pub fn default_input_device() -> Result<Device, Error> {
+        Ok(Self::default_alsa_device(false, true))
+    }
+
+    fn default_alsa_device(output: bool, input: bool) -> Device {
+        match alsa() {
+            Ok(_) => Device {
+                kind: DeviceKind::Alsa {
+                    id: "default".to_string(),
+                    description: "Default".to_string(),
+                    input,
+                    output,
+                },
+            },
+            Err(err) => {
+                log::warn!("{err}, using the null device");
+                Device::null(DEFAULT_SAMPLE_RATE)
+            }
+        }
+    }
+
+    /// Start calling `callback` from an audio thread, with input from `input` and output to
+    /// `output`. Without an input device, the input is silent.
+    pub fn open_stream(
+        output: &Device,
+        input: Option<&Device>,
+        config: StreamConfig,
+        callback: impl FnMut(&crate::AudioBuffer, &mut crate::AudioBuffer) + Send + 'static,
+    ) -> Result<Stream, Error> {
+        Stream::open(output, input, config, Box::new(callback))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum DeviceKind {
+    Alsa {
+        id: String,
+        description: String,
+        input: bool,
+        output: bool,
+    },
+    /// Discards the output and records silence, in real time
+    Null { sample_rate: f64 },
+    /// Plays a WAV file as input. The stream ends at the end of the file.
+    WavInput { path: PathBuf },
+    /// Records the output to a WAV file, as fast as possible
+    WavOutput {
+        path: PathBuf,
+        sample_format: SampleFormat,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    kind: DeviceKind,
+}
+
+impl Device {
+    /// A device that discards its output, and whose input is silent. It runs in real time,
+    /// like a sound card.
+    pub fn null(sample_rate: f64) -> Self {
+        Self {
+            kind: DeviceKind::Null { sample_rate },
+        }
+    }
+
+    /// An input device that plays a WAV file, and stops the stream at its end
+    pub fn wav_input(path: impl Into<PathBuf>) -> Self {
+        Self {
+            kind: DeviceKind::WavInput { path: path.into() },
+        }
+    }
+
+    /// An output device that records to a WAV file. Streams with a WAV output don't wait for
+    /// a sound card, so files are processed faster than real time.
+    pub fn wav_output(path: impl Into<PathBuf>, sample_format: SampleFormat) -> Self {
+        Self {
+            kind: DeviceKind::WavOutput {
+                path: path.into(),
+                sample_format,
+            },
+        }
+    }
+
+    /// Identifier to open the device again, e.g. from saved settings
+    pub fn id(&self) -> Result<String, Error> {
+        Ok(match &self.kind {
+            DeviceKind::Alsa { id, .. } => id.clone(),
+            DeviceKind::Null { .. } => "null".to_string(),
+            DeviceKind::WavInput { path } | DeviceKind::WavOutput { path, .. } => {
+                path.display().to_string()
+            }
+        })
+    }
+
+    pub fn name(&self) -> Result<String, Error> {
+        Ok(match &self.kind {
+            DeviceKind::Alsa {
+                id, description, ..
+            } => {
+                // The description has the card name on the first line, and the device below
+                let name = description.lines().collect::<Vec<_>>().join(", ");
+                if name.is_empty() { id.clone() } else { name }
+            }
+            DeviceKind::Null { .. } => "No audio device".to_string(),
+            DeviceKind::WavInput { path } | DeviceKind::WavOutput { path, .. } => {
+                path.file_name().map_or_else(
+                    || path.display().to_string(),
+                    |name| name.to_string_lossy().into_owned(),
+                )
+            }
+        })
+    }
+
+    pub fn is_input(&self) -> bool {
+        match &self.kind {
+            DeviceKind::Alsa { input, .. } => *input,
+            DeviceKind::Null { .. } | DeviceKind::WavInput { .. } => true,
+            DeviceKind::WavOutput { .. } => false,
+        }
+    }
+
+    pub fn is_output(&self) -> bool {
+        match &self.kind {
+            DeviceKind::Alsa { output, .. } => *output,
+            DeviceKind::Null { .. } | DeviceKind::WavOutput { .. } => true,
+            DeviceKind::WavInput { .. } => false,
+        }
+    }
+
+    /// The preferred sample rate of the device. For sound cards, this is 48 kHz or 44.1 kHz
+    /// if supported.
+    pub fn sample_rate(&self) -> Result<f64, Error> {
+        match &self.kind {
+            DeviceKind::Alsa { id, output, .. } => {
+                let capabilities = Pcm::capabilities(id, alsa_direction(*output))?;
+                let supported =
+                    |rate: u32| (capabilities.min_rate..=capabilities.max_rate).contains(&rate);
+                Ok([48000, 44100]
+                    .into_iter()
+                    .find(|rate| supported(*rate))
+                    .unwrap_or(capabilities.max_rate) as f64)
+            }
+            DeviceKind::Null { sample_rate } => Ok(*sample_rate),
+            DeviceKind::WavInput { path } => Ok(wav_spec(path)?.sample_rate as f64),
+            DeviceKind::WavOutput { .. } => Ok(DEFAULT_SAMPLE_RATE),
+        }
+    }
+
+    /// The common sample rates supported by the device
+    pub fn sample_rates(&self) -> Result<Vec<f64>, Error> {
+        let rates = match &self.kind {
+            DeviceKind::Alsa { id, output, .. } => {
+                let capabilities = Pcm::capabilities(id, alsa_direction(*output))?;
+                COMMON_SAMPLE_RATES
+                    .into_iter()
+                    .filter(|rate| (capabilities.min_rate..=capabilities.max_rate).contains(rate))
+                    .collect()
+            }
+            DeviceKind::Null { .. } | DeviceKind::WavOutput { .. } => COMMON_SAMPLE_RATES.to_vec(),
+            DeviceKind::WavInput { path } => vec![wav_spec(path)?.sample_rate],
+        };
+        Ok(rates.into_iter().map(f64::from).collect())
+    }
+
+    /// Maximum number of channels in the direction(s) of the device
+    pub fn max_channels(&self) -> Result<usize, Error> {
+        match &self.kind {
+            DeviceKind::Alsa { id, output, .. } => {
+                Ok(Pcm::capabilities(id, alsa_direction(*output))?.max_channels as usize)
+            }
+            DeviceKind::Null { .. } | DeviceKind::WavOutput { .. } => Ok(usize::MAX),
+            DeviceKind::WavInput { path } => Ok(wav_spec(path)?.channels as usize),
+        }
+    }
+}
+
+fn alsa_direction(output: bool) -> Direction {
+    if output {
+        Direction::Playback
+    } else {
+        Direction::Capture
+    }
+}
+
+fn wav_spec(path: &Path) -> Result<crate::util::WavSpec, Error> {
+    let file = std::fs::File::open(path).map_err(Error::from_reason)?;
+    let reader = WavReader::new(std::io::BufReader::new(file)).map_err(Error::from_reason)?;
+    Ok(reader.spec())
+}
+
+/// List the PCM devices from the ALSA configuration
+fn alsa_devices() -> Result<Vec<Device>, Error> {
+    let alsa = alsa()?;
+    let mut hints: *mut *mut c_void = null_mut();
+    alsa.check(
+        unsafe { (alsa.snd_device_name_hint)(-1, c"pcm".as_ptr(), &mut hints) },
+        "Listing devices",
+    )?;
+
+    let mut devices = Vec::new();
+    let get_hint = |hint, id: &CStr| {
+        alsa.take_string(unsafe { (alsa.snd_device_name_get_hint)(hint, id.as_ptr()) })
+    };
+    let mut hint = hints;
+    while !unsafe { *hint }.is_null() {
+        let current = unsafe { *hint };
+        hint = unsafe { hint.add(1) };
+        let Some(id) = get_hint(current, c"NAME") else {
+            continue;
+        };
+        // Covered by our own null device
+        if id == "null" {
+            continue;
+        }
+        let description = get_hint(current, c"DESC").unwrap_or_default();
+        // No IOID means both directions
+        let io = get_hint(current, c"IOID");
+        devices.push(Device {
+            kind: DeviceKind::Alsa {
+                id,
+                description,
+                input: io.as_deref() != Some("Output"),
+                output: io.as_deref() != Some("Input"),
+            },
+        });
+    }
+    unsafe { (alsa.snd_device_name_free_hint)(hints) };
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::WavFile;
+
+    #[test]
+    fn wav_devices_process_file() {
+        let dir = std::env::temp_dir().join(format!("audioplug-audio-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input_path = dir.join("input.wav");
+        let output_path = dir.join("output.wav");
+        let samples: Vec<f32> = (0..1000).map(|i| i as f32 / 1000.0).collect();
+        WavFile::new(44100, SampleFormat::Float32, vec![samples.clone()])
+            .save(&input_path)
+            .unwrap();
+
+        let config = StreamConfig {
+            sample_rate: 44100.0,
+            block_size: 64,
+            input_channels: 2,
+            output_channels: 2,
+        };
+        let stream = AudioHost::open_stream(
+            &Device::wav_output(&output_path, SampleFormat::Float32),
+            Some(&Device::wav_input(&input_path)),
+            config,
+            |input, output| {
+                output.copy_from(input);
+                output.channel_mut(1).as_mut_slice().fill(0.25);
+            },
+        )
+        .unwrap();
+        stream.wait().unwrap();
+
+        let output = WavFile::open(&output_path).unwrap();
+        assert_eq!(output.sample_rate, 44100);
+        assert_eq!(output.channels, [samples, vec![0.25; 1000]]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn null_device_runs_in_real_time() {
+        let config = StreamConfig {
+            sample_rate: 48000.0,
+            block_size: 480,
+            input_channels: 0,
+            output_channels: 2,
+        };
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let start = std::time::Instant::now();
+        let stream =
+            AudioHost::open_stream(&Device::null(48000.0), None, config, move |_, output| {
+                let _ = sender.send(output.samples());
+            })
+            .unwrap();
+        for _ in 0..5 {
+            assert_eq!(receiver.recv().unwrap(), 480);
+        }
+        // Four blocks of 10 ms have been waited for
+        assert!(start.elapsed() >= std::time::Duration::from_millis(40));
+        stream.stop().unwrap();
+    }
+}
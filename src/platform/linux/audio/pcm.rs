@@ -0,0 +1,283 @@
+use std::ffi::{CString, c_int, c_uint, c_ulong};
+use std::ptr::null_mut;
+
+use super::super::Error;
+use super::super::alsa::{
+    Alsa, SND_PCM_ACCESS_RW_INTERLEAVED, SND_PCM_FORMAT_FLOAT_LE, SND_PCM_FORMAT_S16_LE,
+    SND_PCM_FORMAT_S32_LE, SND_PCM_NONBLOCK, SND_PCM_STREAM_CAPTURE, SND_PCM_STREAM_PLAYBACK, alsa,
+    snd_pcm_t,
+};
+use crate::{AudioBuffer, InterleavedAudioBuffer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Playback,
+    Capture,
+}
+
+impl Direction {
+    fn stream(self) -> c_int {
+        match self {
+            Self::Playback => SND_PCM_STREAM_PLAYBACK,
+            Self::Capture => SND_PCM_STREAM_CAPTURE,
+        }
+    }
+}
+
+/// Sample formats to try, in order of preference. The plug devices convert to anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Float,
+    Int32,
+    Int16,
+}
+
+impl Format {
+    const ALL: [Self; 3] = [Self::Float, Self::Int32, Self::Int16];
+
+    fn alsa_format(self) -> c_int {
+        match self {
+            Self::Float => SND_PCM_FORMAT_FLOAT_LE,
+            Self::Int32 => SND_PCM_FORMAT_S32_LE,
+            Self::Int16 => SND_PCM_FORMAT_S16_LE,
+        }
+    }
+
+    fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::Float | Self::Int32 => 4,
+            Self::Int16 => 2,
+        }
+    }
+}
+
+/// Sample rates and channels supported by a device
+pub struct Capabilities {
+    pub min_rate: u32,
+    pub max_rate: u32,
+    pub max_channels: u32,
+}
+
+/// An open ALSA PCM, with interleaved read/write access
+pub struct Pcm {
+    alsa: &'static Alsa,
+    pcm: *mut snd_pcm_t,
+    format: Format,
+    buffer: InterleavedAudioBuffer,
+    bytes: Vec<u8>,
+}
+
+// Safety: the PCM is only used from one thread at a time
+unsafe impl Send for Pcm {}
+
+impl Pcm {
+    fn open_raw(
+        alsa: &'static Alsa,
+        name: &str,
+        direction: Direction,
+        mode: c_int,
+    ) -> Result<*mut snd_pcm_t, Error> {
+        let c_name = CString::new(name).map_err(Error::from_reason)?;
+        let mut pcm = null_mut();
+        alsa.check(
+            unsafe { (alsa.snd_pcm_open)(&mut pcm, c_name.as_ptr(), direction.stream(), mode) },
+            &format!("Opening {name}"),
+        )?;
+        Ok(pcm)
+    }
+
+    /// Open the device `name` for blocking reads or writes of `max_frames` frames.
+    /// The latency is kept at about two blocks.
+    pub fn open(
+        name: &str,
+        direction: Direction,
+        channels: usize,
+        sample_rate: u32,
+        max_frames: usize,
+    ) -> Result<Self, Error> {
+        let alsa = alsa()?;
+        let pcm = Self::open_raw(alsa, name, direction, 0)?;
+        let latency_us = (2 * max_frames) as u64 * 1_000_000 / sample_rate.max(1) as u64;
+
+        let mut result = Err(Error::from_reason("No supported sample format"));
+        for format in Format::ALL {
+            let error = unsafe {
+                (alsa.snd_pcm_set_params)(
+                    pcm,
+                    format.alsa_format(),
+                    SND_PCM_ACCESS_RW_INTERLEAVED,
+                    channels as c_uint,
+                    sample_rate,
+                    1,
+                    latency_us as c_uint,
+                )
+            };
+            result = alsa
+                .check(error, &format!("Configuring {name}"))
+                .map(|_| format);
+            if result.is_ok() {
+                break;
+            }
+        }
+        let format = match result {
+            Ok(format) => format,
+            Err(err) => {
+                unsafe { (alsa.snd_pcm_close)(pcm) };
+                return Err(err);
+            }
+        };
+
+        let mut buffer_size: c_ulong = 0;
+        let mut period_size: c_ulong = 0;
+        if unsafe { (alsa.snd_pcm_get_params)(pcm, &mut buffer_size, &mut period_size) } >= 0 {
+            log::info!(
+                "Opened {name} ({direction:?}, {format:?}), buffer {buffer_size} frames, period {period_size} frames"
+            );
+        }
+
+        Ok(Self {
+            alsa,
+            pcm,
+            format,
+            buffer: InterleavedAudioBuffer::with_capacity(channels, max_frames),
+            bytes: vec![0; channels * max_frames * format.bytes_per_sample()],
+        })
+    }
+
+    /// Query the supported sample rates and channels, without keeping the device open
+    pub fn capabilities(name: &str, direction: Direction) -> Result<Capabilities, Error> {
+        let alsa = alsa()?;
+        let pcm = Self::open_raw(alsa, name, direction, SND_PCM_NONBLOCK)?;
+        let mut params = null_mut();
+        let result = (|| {
+            alsa.check(
+                unsafe { (alsa.snd_pcm_hw_params_malloc)(&mut params) },
+                "Allocating hardware parameters",
+            )?;
+            alsa.check(
+                unsafe { (alsa.snd_pcm_hw_params_any)(pcm, params) },
+                "Reading hardware parameters",
+            )?;
+            let mut capabilities = Capabilities {
+                min_rate: 0,
+                max_rate: 0,
+                max_channels: 0,
+            };
+            let mut dir = 0;
+            unsafe {
+                (alsa.snd_pcm_hw_params_get_rate_min)(params, &mut capabilities.min_rate, &mut dir);
+                (alsa.snd_pcm_hw_params_get_rate_max)(params, &mut capabilities.max_rate, &mut dir);
+                (alsa.snd_pcm_hw_params_get_channels_max)(params, &mut capabilities.max_channels);
+            }
+            Ok(capabilities)
+        })();
+        unsafe {
+            if !params.is_null() {
+                (alsa.snd_pcm_hw_params_free)(params);
+            }
+            (alsa.snd_pcm_close)(pcm);
+        }
+        result
+    }
+
+    /// Write the output, blocking until there is room in the device buffer
+    pub fn write(&mut self, output: &AudioBuffer) -> Result<(), Error> {
+        let num_frames = output.samples();
+        self.buffer.interleave_from(output);
+        let bytes_per_frame = self.buffer.channels() * self.format.bytes_per_sample();
+        self.encode(num_frames);
+
+        let mut written = 0;
+        while written < num_frames {
+            let result = unsafe {
+                (self.alsa.snd_pcm_writei)(
+                    self.pcm,
+                    self.bytes[written * bytes_per_frame..].as_ptr().cast(),
+                    (num_frames - written) as c_ulong,
+                )
+            };
+            if result < 0 {
+                self.recover(result as c_int)?;
+            } else {
+                written += result as usize;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill `input`, blocking until enough frames have been captured
+    pub fn read(&mut self, input: &mut AudioBuffer) -> Result<(), Error> {
+        let num_frames = input.samples();
+        let bytes_per_frame = self.buffer.channels() * self.format.bytes_per_sample();
+
+        let mut read = 0;
+        while read < num_frames {
+            let result = unsafe {
+                (self.alsa.snd_pcm_readi)(
+                    self.pcm,
+                    self.bytes[read * bytes_per_frame..].as_mut_ptr().cast(),
+                    (num_frames - read) as c_ulong,
+                )
+            };
+            if result < 0 {
+                self.recover(result as c_int)?;
+            } else {
+                read += result as usize;
+            }
+        }
+
+        self.decode(num_frames);
+        self.buffer.deinterleave_to(input);
+        Ok(())
+    }
+
+    /// Recover from under- and overruns
+    fn recover(&self, error: c_int) -> Result<(), Error> {
+        log::warn!("ALSA: {}", self.alsa.error_string(error));
+        self.alsa
+            .check(
+                unsafe { (self.alsa.snd_pcm_recover)(self.pcm, error, 1) },
+                "Recovering",
+            )
+            .map(|_| ())
+    }
+
+    fn encode(&mut self, num_frames: usize) {
+        let samples = self.buffer.as_slice(num_frames);
+        let size = self.format.bytes_per_sample();
+        for (bytes, &sample) in self.bytes.chunks_exact_mut(size).zip(samples) {
+            match self.format {
+                Format::Float => bytes.copy_from_slice(&sample.to_le_bytes()),
+                Format::Int32 => {
+                    let value = (sample as f64 * 2147483648.0).clamp(-2147483648.0, 2147483647.0);
+                    bytes.copy_from_slice(&(value as i32).to_le_bytes())
+                }
+                Format::Int16 => {
+                    let value = (sample * 32768.0).clamp(-32768.0, 32767.0);
+                    bytes.copy_from_slice(&(value as i16).to_le_bytes())
+                }
+            }
+        }
+    }
+
+    fn decode(&mut self, num_frames: usize) {
+        let size = self.format.bytes_per_sample();
+        let format = self.format;
+        let samples = self.buffer.as_mut_slice(num_frames);
+        for (sample, bytes) in samples.iter_mut().zip(self.bytes.chunks_exact(size)) {
+            *sample = match format {
+                Format::Float => f32::from_le_bytes(bytes.try_into().unwrap()),
+                Format::Int32 => {
+                    (i32::from_le_bytes(bytes.try_into().unwrap()) as f64 / 2147483648.0) as f32
+                }
+                Format::Int16 => i16::from_le_bytes(bytes.try_into().unwrap()) as f32 / 32768.0,
+            };
+        }
+    }
+}
+
+impl Drop for Pcm {
+    fn drop(&mut self) {
+        unsafe { (self.alsa.snd_pcm_close)(self.pcm) };
+    }
+}
@@ -0,0 +1,309 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::super::Error;
+use super::DeviceKind;
+use super::pcm::{Direction, Pcm};
+use crate::platform::StreamConfig;
+use crate::util::{WavReader, WavSpec, WavWriter};
+use crate::{AudioBuffer, OwnedAudioBuffer};
+
+pub(super) type Callback = Box<dyn FnMut(&AudioBuffer, &mut AudioBuffer) + Send>;
+
+/// Where the input comes from
+enum Source {
+    Silence,
+    Alsa(Pcm),
+    Wav {
+        reader: WavReader<BufReader<File>>,
+        frame: Vec<f32>,
+    },
+}
+
+impl Source {
+    fn open(device: Option<&super::Device>, config: &StreamConfig) -> Result<Self, Error> {
+        let Some(device) = device else {
+            return Ok(Self::Silence);
+        };
+        match &device.kind {
+            DeviceKind::Alsa { id, .. } => Ok(Self::Alsa(Pcm::open(
+                id,
+                Direction::Capture,
+                config.input_channels,
+                config.sample_rate.round() as u32,
+                config.block_size,
+            )?)),
+            DeviceKind::Null { .. } => Ok(Self::Silence),
+            DeviceKind::WavInput { path } => {
+                let file = File::open(path).map_err(Error::from_reason)?;
+                let reader = WavReader::new(BufReader::new(file)).map_err(Error::from_reason)?;
+                if reader.spec().sample_rate as f64 != config.sample_rate {
+                    log::warn!(
+                        "{} has a sample rate of {} Hz, but the stream runs at {} Hz",
+                        path.display(),
+                        reader.spec().sample_rate,
+                        config.sample_rate
+                    );
+                }
+                let frame = vec![0.0; reader.spec().channels as usize];
+                Ok(Self::Wav { reader, frame })
+            }
+            DeviceKind::WavOutput { .. } => Err(Error::from_reason(
+                "WAV output devices can't be used for input",
+            )),
+        }
+    }
+
+    /// Fill `input`, and return the number of frames read. Less than a full block is only
+    /// returned at the end of the input.
+    fn read(&mut self, input: &mut AudioBuffer) -> Result<usize, Error> {
+        match self {
+            Self::Silence => {
+                input.clear();
+                Ok(input.samples())
+            }
+            Self::Alsa(pcm) => {
+                pcm.read(input)?;
+                Ok(input.samples())
+            }
+            Self::Wav { reader, frame } => {
+                for i in 0..input.samples() {
+                    if !reader.read_frame(frame).map_err(Error::from_reason)? {
+                        return Ok(i);
+                    }
+                    for (channel, mut samples) in input.channels_iter_mut().enumerate() {
+                        // Mono files are played on all channels
+                        let sample = match frame.len() {
+                            1 => frame[0],
+                            _ => frame.get(channel).copied().unwrap_or(0.0),
+                        };
+                        samples.as_mut_slice()[i] = sample;
+                    }
+                }
+                Ok(input.samples())
+            }
+        }
+    }
+}
+
+/// Where the output goes, and what sets the pace of the stream
+enum Sink {
+    /// Sleeps to run in real time
+    Null {
+        next_block: Instant,
+        block_duration: Duration,
+    },
+    Alsa(Pcm),
+    Wav {
+        writer: WavWriter<BufWriter<File>>,
+        /// Preallocated for a block, since writing runs on the audio thread
+        interleaved: Vec<f32>,
+    },
+}
+
+impl Sink {
+    fn open(device: &super::Device, config: &StreamConfig) -> Result<Self, Error> {
+        match &device.kind {
+            DeviceKind::Alsa { id, .. } => Ok(Self::Alsa(Pcm::open(
+                id,
+                Direction::Playback,
+                config.output_channels,
+                config.sample_rate.round() as u32,
+                config.block_size,
+            )?)),
+            DeviceKind::Null { .. } => Ok(Self::Null {
+                next_block: Instant::now(),
+                block_duration: Duration::from_secs_f64(
+                    config.block_size as f64 / config.sample_rate,
+                ),
+            }),
+            DeviceKind::WavOutput {
+                path,
+                sample_format,
+            } => {
+                let spec = WavSpec {
+                    channels: config.output_channels as u16,
+                    sample_rate: config.sample_rate.round() as u32,
+                    sample_format: *sample_format,
+                };
+                let file = File::create(path).map_err(Error::from_reason)?;
+                let mut writer =
+                    WavWriter::new(BufWriter::new(file), spec).map_err(Error::from_reason)?;
+                writer.reserve(config.block_size);
+                let interleaved = Vec::with_capacity(config.block_size * config.output_channels);
+                Ok(Self::Wav {
+                    writer,
+                    interleaved,
+                })
+            }
+            DeviceKind::WavInput { .. } => Err(Error::from_reason(
+                "WAV input devices can't be used for output",
+            )),
+        }
+    }
+
+    fn write(&mut self, output: &AudioBuffer) -> Result<(), Error> {
+        match self {
+            Self::Null {
+                next_block,
+                block_duration,
+            } => {
+                *next_block += *block_duration;
+                let now = Instant::now();
+                if *next_block > now {
+                    std::thread::sleep(*next_block - now);
+                } else if now - *next_block > *block_duration {
+                    // Don't try to catch up after falling behind
+                    *next_block = now;
+                }
+                Ok(())
+            }
+            Self::Alsa(pcm) => pcm.write(output),
+            Self::Wav {
+                writer,
+                interleaved,
+            } => {
+                interleaved.clear();
+                for i in 0..output.samples() {
+                    for channel in 0..writer.spec().channels as usize {
+                        let sample = if channel < output.channels() {
+                            output.channel(channel).as_slice()[i]
+                        } else {
+                            0.0
+                        };
+                        interleaved.push(sample);
+                    }
+                }
+                writer
+                    .write_interleaved(interleaved)
+                    .map_err(Error::from_reason)
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if let Self::Wav { writer, .. } = self {
+            writer.finalize().map_err(Error::from_reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// A running stream. Dropping it stops the audio thread.
+pub struct Stream {
+    config: StreamConfig,
+    running: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<(), Error>>>,
+}
+
+impl Stream {
+    pub(super) fn open(
+        output: &super::Device,
+        input: Option<&super::Device>,
+        config: StreamConfig,
+        mut callback: Callback,
+    ) -> Result<Self, Error> {
+        if config.block_size == 0 || config.sample_rate <= 0.0 {
+            return Err(Error::from_reason(format!(
+                "Invalid stream configuration: {config:?}"
+            )));
+        }
+        // Open the devices here, so that errors are returned to the caller
+        let mut source = Source::open(input, &config)?;
+        let mut sink = Sink::open(output, &config)?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let finished = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::Builder::new()
+            .name("audio".to_string())
+            .spawn({
+                let running = running.clone();
+                let finished = finished.clone();
+                move || {
+                    let mut input =
+                        OwnedAudioBuffer::with_capacity(config.input_channels, config.block_size);
+                    let mut output =
+                        OwnedAudioBuffer::with_capacity(config.output_channels, config.block_size);
+                    let mut result = Ok(());
+                    while running.load(Ordering::Acquire) {
+                        let num_frames =
+                            match source.read(&mut input.as_audio_buffer(config.block_size)) {
+                                Ok(num_frames) => num_frames,
+                                Err(err) => {
+                                    result = Err(err);
+                                    break;
+                                }
+                            };
+                        if num_frames > 0 {
+                            callback(
+                                &input.as_audio_buffer_ref(num_frames),
+                                &mut output.as_audio_buffer(num_frames),
+                            );
+                            if let Err(err) = sink.write(&output.as_audio_buffer_ref(num_frames)) {
+                                result = Err(err);
+                                break;
+                            }
+                        }
+                        if num_frames < config.block_size {
+                            break;
+                        }
+                    }
+                    let result = result.and(sink.finish());
+                    if let Err(err) = &result {
+                        log::error!("Audio stream stopped: {err}");
+                    }
+                    finished.store(true, Ordering::Release);
+                    result
+                }
+            })
+            .map_err(Error::from_reason)?;
+
+        Ok(Self {
+            config,
+            running,
+            finished,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn config(&self) -> &StreamConfig {
+        &self.config
+    }
+
+    /// Whether the stream has stopped by itself, at the end of the input or after an error
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    /// Wait until the stream has finished by itself
+    pub fn wait(mut self) -> Result<(), Error> {
+        self.join()
+    }
+
+    /// Stop the audio thread, and wait for it to finish
+    pub fn stop(mut self) -> Result<(), Error> {
+        self.running.store(false, Ordering::Release);
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<(), Error> {
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .map_err(|_| Error::from_reason("The audio thread panicked"))?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        let _ = self.join();
+    }
+}
@@ -0,0 +1,350 @@
+//! MIDI input through the ALSA sequencer. Hardware MIDI ports are exposed to the sequencer by
+//! the kernel, and other applications (and PipeWire) connect to it too, so raw MIDI devices
+//! don't need to be opened separately.
+
+use std::ffi::{CStr, CString, c_char, c_int, c_uint, c_ulong};
+use std::ptr::null_mut;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use super::Error;
+use super::alsa::{
+    Alsa, POLLIN, SND_SEQ_EVENT_NOTEOFF, SND_SEQ_EVENT_NOTEON, SND_SEQ_NONBLOCK,
+    SND_SEQ_OPEN_INPUT, SND_SEQ_PORT_CAP_NO_EXPORT, SND_SEQ_PORT_CAP_READ,
+    SND_SEQ_PORT_CAP_SUBS_READ, SND_SEQ_PORT_CAP_SUBS_WRITE, SND_SEQ_PORT_CAP_WRITE,
+    SND_SEQ_PORT_TYPE_APPLICATION, SND_SEQ_PORT_TYPE_MIDI_GENERIC, alsa, poll, pollfd,
+    snd_seq_event_t, snd_seq_t,
+};
+use crate::midi::{Note, NoteEvent};
+
+/// How often the input thread checks if it should stop, in milliseconds
+const POLL_TIMEOUT_MS: c_int = 100;
+
+pub struct MidiHost;
+
+impl MidiHost {
+    /// The sequencer ports that can be read from, except for the system ports
+    pub fn devices() -> Result<Vec<MidiDevice>, Error> {
+        let sequencer = Sequencer::open()?;
+        sequencer.readable_ports()
+    }
+
+    /// The first port of a device or another application. The through port of the kernel
+    /// only echoes what is sent to it, so it is skipped.
+    pub fn default_input_device() -> Result<Option<MidiDevice>, Error> {
+        Ok(Self::devices()?
+            .into_iter()
+            .find(|device| !device.name.starts_with("Midi Through")))
+    }
+
+    /// Create a sequencer client named `client_name`, with an input port that other
+    /// applications can connect to, and connect `device` to it. `callback` is called from
+    /// the MIDI thread with the time at which each event was received.
+    pub fn open_input(
+        client_name: &str,
+        device: Option<&MidiDevice>,
+        callback: impl FnMut(Instant, NoteEvent) + Send + 'static,
+    ) -> Result<MidiInput, Error> {
+        let sequencer = Sequencer::open()?;
+        let alsa = sequencer.alsa;
+        let name = CString::new(client_name).map_err(Error::from_reason)?;
+        alsa.check(
+            unsafe { (alsa.snd_seq_set_client_name)(sequencer.seq, name.as_ptr()) },
+            "Naming the sequencer client",
+        )?;
+        let port = alsa.check(
+            unsafe {
+                (alsa.snd_seq_create_simple_port)(
+                    sequencer.seq,
+                    c"MIDI In".as_ptr(),
+                    SND_SEQ_PORT_CAP_WRITE | SND_SEQ_PORT_CAP_SUBS_WRITE,
+                    SND_SEQ_PORT_TYPE_MIDI_GENERIC | SND_SEQ_PORT_TYPE_APPLICATION,
+                )
+            },
+            "Creating the MIDI input port",
+        )?;
+        if let Some(device) = device {
+            alsa.check(
+                unsafe {
+                    (alsa.snd_seq_connect_from)(sequencer.seq, port, device.client, device.port)
+                },
+                &format!("Connecting {}", device.name),
+            )?;
+        }
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = std::thread::Builder::new()
+            .name("midi".to_string())
+            .spawn({
+                let running = running.clone();
+                move || sequencer.run(&running, callback)
+            })
+            .map_err(Error::from_reason)?;
+        Ok(MidiInput {
+            running,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// A port of another sequencer client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MidiDevice {
+    client: c_int,
+    port: c_int,
+    name: String,
+}
+
+impl MidiDevice {
+    /// Identifier to open the device again, e.g. from saved settings. Only the name is used,
+    /// since client numbers change when devices are plugged in.
+    pub fn id(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn name(&self) -> Result<String, Error> {
+        Ok(self.name.clone())
+    }
+}
+
+/// Receives MIDI events on a thread, until it is stopped or dropped
+pub struct MidiInput {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MidiInput {
+    pub fn stop(mut self) {
+        self.join();
+    }
+
+    fn join(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for MidiInput {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+struct Sequencer {
+    alsa: &'static Alsa,
+    seq: *mut snd_seq_t,
+}
+
+// Safety: the sequencer is only used from one thread at a time
+unsafe impl Send for Sequencer {}
+
+impl Sequencer {
+    fn open() -> Result<Self, Error> {
+        let alsa = alsa()?;
+        let mut seq = null_mut();
+        alsa.check(
+            unsafe {
+                (alsa.snd_seq_open)(
+                    &mut seq,
+                    c"default".as_ptr(),
+                    SND_SEQ_OPEN_INPUT,
+                    SND_SEQ_NONBLOCK,
+                )
+            },
+            "Opening the ALSA sequencer",
+        )?;
+        Ok(Self { alsa, seq })
+    }
+
+    fn readable_ports(&self) -> Result<Vec<MidiDevice>, Error> {
+        let alsa = self.alsa;
+        let own_client = unsafe { (alsa.snd_seq_client_id)(self.seq) };
+        let mut client_info = null_mut();
+        let mut port_info = null_mut();
+        let result = (|| {
+            alsa.check(
+                unsafe { (alsa.snd_seq_client_info_malloc)(&mut client_info) },
+                "Allocating client info",
+            )?;
+            alsa.check(
+                unsafe { (alsa.snd_seq_port_info_malloc)(&mut port_info) },
+                "Allocating port info",
+            )?;
+
+            let mut devices = Vec::new();
+            unsafe { (alsa.snd_seq_client_info_set_client)(client_info, -1) };
+            while unsafe { (alsa.snd_seq_query_next_client)(self.seq, client_info) } >= 0 {
+                let client = unsafe { (alsa.snd_seq_client_info_get_client)(client_info) };
+                // Client 0 is the system client, with the timer and announcement ports
+                if client == 0 || client == own_client {
+                    continue;
+                }
+                let client_name =
+                    borrowed_string(unsafe { (alsa.snd_seq_client_info_get_name)(client_info) });
+                unsafe {
+                    (alsa.snd_seq_port_info_set_client)(port_info, client);
+                    (alsa.snd_seq_port_info_set_port)(port_info, -1);
+                }
+                while unsafe { (alsa.snd_seq_query_next_port)(self.seq, port_info) } >= 0 {
+                    let capabilities =
+                        unsafe { (alsa.snd_seq_port_info_get_capability)(port_info) };
+                    let port_type = unsafe { (alsa.snd_seq_port_info_get_type)(port_info) };
+                    if !is_readable_midi_port(capabilities, port_type) {
+                        continue;
+                    }
+                    let port_name =
+                        borrowed_string(unsafe { (alsa.snd_seq_port_info_get_name)(port_info) });
+                    devices.push(MidiDevice {
+                        client,
+                        port: unsafe { (alsa.snd_seq_port_info_get_port)(port_info) },
+                        name: if port_name == client_name {
+                            port_name
+                        } else {
+                            format!("{client_name}: {port_name}")
+                        },
+                    });
+                }
+            }
+            Ok(devices)
+        })();
+        unsafe {
+            if !client_info.is_null() {
+                (alsa.snd_seq_client_info_free)(client_info);
+            }
+            if !port_info.is_null() {
+                (alsa.snd_seq_port_info_free)(port_info);
+            }
+        }
+        result
+    }
+
+    /// Read events until `running` is cleared
+    fn run(self, running: &AtomicBool, mut callback: impl FnMut(Instant, NoteEvent)) {
+        let alsa = self.alsa;
+        let count = unsafe { (alsa.snd_seq_poll_descriptors_count)(self.seq, POLLIN) };
+        let mut fds: Vec<pollfd> = (0..count.max(0))
+            .map(|_| pollfd {
+                fd: -1,
+                events: 0,
+                revents: 0,
+            })
+            .collect();
+        let count = unsafe {
+            (alsa.snd_seq_poll_descriptors)(self.seq, fds.as_mut_ptr(), fds.len() as c_uint, POLLIN)
+        };
+        fds.truncate(count.max(0) as usize);
+
+        while running.load(Ordering::Acquire) {
+            let ready = unsafe { poll(fds.as_mut_ptr(), fds.len() as c_ulong, POLL_TIMEOUT_MS) };
+            if ready <= 0 {
+                continue;
+            }
+            loop {
+                let mut event: *mut snd_seq_event_t = null_mut();
+                let result = unsafe { (alsa.snd_seq_event_input)(self.seq, &mut event) };
+                // -EAGAIN when all events have been read, or -ENOSPC after an overrun
+                if result < 0 || event.is_null() {
+                    break;
+                }
+                let time = Instant::now();
+                if let Some(event) = note_event(unsafe { &*event }) {
+                    callback(time, event);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Sequencer {
+    fn drop(&mut self) {
+        unsafe { (self.alsa.snd_seq_close)(self.seq) };
+    }
+}
+
+fn is_readable_midi_port(capabilities: c_uint, port_type: c_uint) -> bool {
+    let readable = SND_SEQ_PORT_CAP_READ | SND_SEQ_PORT_CAP_SUBS_READ;
+    capabilities & readable == readable
+        && capabilities & SND_SEQ_PORT_CAP_NO_EXPORT == 0
+        && port_type & SND_SEQ_PORT_TYPE_MIDI_GENERIC != 0
+}
+
+fn borrowed_string(string: *const c_char) -> String {
+    if string.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(string) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Convert note on and off events. The data of note events starts with the channel, the
+/// note and the velocity.
+fn note_event(event: &snd_seq_event_t) -> Option<NoteEvent> {
+    let [channel, note, velocity, ..] = event.data;
+    let channel = channel as i16;
+    let note = Note::from_midi(note);
+    match event.r#type {
+        // A note on with zero velocity is a note off
+        SND_SEQ_EVENT_NOTEON if velocity > 0 => Some(NoteEvent::NoteOn {
+            channel,
+            sample_offset: 0,
+            note,
+        }),
+        SND_SEQ_EVENT_NOTEON | SND_SEQ_EVENT_NOTEOFF => Some(NoteEvent::NoteOff {
+            channel,
+            sample_offset: 0,
+            note,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::alsa::snd_seq_addr_t;
+    use super::*;
+
+    #[test]
+    fn decodes_note_events() {
+        // The layout must match `snd_seq_event_t` in alsa/seq_event.h
+        assert_eq!(std::mem::size_of::<snd_seq_event_t>(), 28);
+
+        let event = |r#type, velocity| snd_seq_event_t {
+            r#type,
+            flags: 0,
+            tag: 0,
+            queue: 0,
+            time: [0; 2],
+            source: snd_seq_addr_t {
+                client: 20,
+                port: 0,
+            },
+            dest: snd_seq_addr_t {
+                client: 128,
+                port: 0,
+            },
+            data: [3, 60, velocity, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        };
+        let note_off = Some(NoteEvent::NoteOff {
+            channel: 3,
+            sample_offset: 0,
+            note: Note(60),
+        });
+        assert_eq!(
+            note_event(&event(SND_SEQ_EVENT_NOTEON, 100)),
+            Some(NoteEvent::NoteOn {
+                channel: 3,
+                sample_offset: 0,
+                note: Note(60),
+            })
+        );
+        assert_eq!(note_event(&event(SND_SEQ_EVENT_NOTEON, 0)), note_off);
+        assert_eq!(note_event(&event(SND_SEQ_EVENT_NOTEOFF, 64)), note_off);
+        // Controllers are ignored
+        assert_eq!(note_event(&event(10, 64)), None);
+    }
+}
@@ -1,6 +1,8 @@
+mod alsa;
 mod application;
 mod audio;
 mod executor;
+mod midi;
 mod text;
 mod wayland;
 mod window;
@@ -9,8 +11,9 @@ mod x11;
 use std::fmt::Display;
 
 pub use application::Application;
-pub use audio::{AudioHost, Device};
+pub use audio::{AudioHost, DEFAULT_SAMPLE_RATE, Device, Stream};
 pub use executor::Executor;
+pub use midi::{MidiDevice, MidiHost, MidiInput};
 use raw_window_handle::{HandleError, RawDisplayHandle, RawWindowHandle};
 pub use text::{NativeFont, NativeTextLayout};
 pub use window::Window;
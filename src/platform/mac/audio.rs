@@ -1,15 +1,40 @@
 use super::Error;
+use crate::platform::StreamConfig;
 use objc2_core_audio::AudioDeviceID;
 
+/// Core Audio is not supported yet. There are no devices, and streams can't be opened.
 pub struct AudioHost;
 
+const NOT_IMPLEMENTED: Error = Error::NotImplemented("The audio backend");
+
 impl AudioHost {
     pub fn devices() -> Result<Vec<Device>, Error> {
-        todo!()
+        Ok(Vec::new())
     }
 
     pub fn default_output_device() -> Result<Device, Error> {
-        todo!()
+        Err(NOT_IMPLEMENTED)
+    }
+
+    pub fn default_input_device() -> Result<Device, Error> {
+        Err(NOT_IMPLEMENTED)
+    }
+
+    pub fn open_stream(
+        _output: &Device,
+        _input: Option<&Device>,
+        _config: StreamConfig,
+        _callback: impl FnMut(&crate::AudioBuffer, &mut crate::AudioBuffer) + Send + 'static,
+    ) -> Result<Stream, Error> {
+        Err(NOT_IMPLEMENTED)
+    }
+}
+
+pub struct Stream;
+
+impl Stream {
+    pub fn stop(self) -> Result<(), Error> {
+        Ok(())
     }
 }
 
@@ -19,10 +44,30 @@ pub struct Device {
 
 impl Device {
     pub fn name(&self) -> Result<String, Error> {
-        todo!()
+        Err(NOT_IMPLEMENTED)
+    }
+
+    pub fn id(&self) -> Result<String, Error> {
+        Err(NOT_IMPLEMENTED)
     }
 
     pub fn sample_rate(&self) -> Result<f64, Error> {
-        todo!()
+        Err(NOT_IMPLEMENTED)
+    }
+
+    pub fn sample_rates(&self) -> Result<Vec<f64>, Error> {
+        Ok(Vec::new())
+    }
+
+    pub fn is_input(&self) -> bool {
+        false
+    }
+
+    pub fn is_output(&self) -> bool {
+        false
+    }
+
+    pub fn max_channels(&self) -> Result<usize, Error> {
+        Ok(0)
     }
 }
@@ -1,2 +1,18 @@
 #[derive(Debug)]
-pub struct Error;
+pub enum Error {
+    /// A call to a macOS API failed
+    Platform,
+    /// The feature has not been implemented on macOS yet
+    NotImplemented(&'static str),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Platform => f.write_str("macOS platform error"),
+            Self::NotImplemented(what) => write!(f, "{what} is not implemented on this platform"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -14,7 +14,7 @@ impl Bitmap {
     pub fn from_file(path: &Path) -> Result<Self, Error> {
         let path_str = NSString::from_str(path.to_str().unwrap());
         let ns_image = NSImage::initWithContentsOfFile(NSImage::alloc(), &path_str);
-        ns_image.map(|ns_image| Self(ns_image)).ok_or(Error)
+        ns_image.map(|ns_image| Self(ns_image)).ok_or(Error::Platform)
     }
 
     pub fn size(&self) -> Size {
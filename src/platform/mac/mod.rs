@@ -10,17 +10,21 @@ mod executor;
 mod handle;
 mod image;
 mod keyboard;
+mod midi;
 mod text;
+mod timer;
 mod util;
 pub(crate) mod view;
 mod window;
 
 pub(crate) use application::Application;
-pub(crate) use audio::AudioHost;
+pub(crate) use audio::{AudioHost, Device, Stream};
 pub use error::Error;
 pub(crate) use executor::Executor;
 pub(crate) use handle::Handle;
+pub use midi::{MidiDevice, MidiHost, MidiInput};
 pub use image::Bitmap;
 pub(crate) use text::{NativeFont, NativeTextLayout};
+pub(crate) use timer::Timer;
 pub(crate) use util::*;
 pub(crate) use window::Window;
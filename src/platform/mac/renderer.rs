@@ -485,14 +485,14 @@ impl NativeGeometry {
     ) -> Result<Self, Error> {
         let path = unsafe { CGMutablePath::new() };
         let builder = f(NativeGeometryBuilder(path));
-        let dd = builder.0.downcast().map_err(|_| Error)?;
+        let dd = builder.0.downcast().map_err(|_| Error::Platform)?;
         Ok(Self(dd))
     }
 
     pub fn transform(&self, transform: Transform) -> Result<Self, Error> {
         let path =
             unsafe { CGPath::new_copy_by_transforming_path(Some(&self.0), &transform.into()) }
-                .ok_or(Error)?;
+                .ok_or(Error::Platform)?;
         Ok(Self(path))
     }
 
@@ -0,0 +1,33 @@
+use std::{ptr::NonNull, time::Duration};
+
+use block2::RcBlock;
+use objc2::rc::Retained;
+use objc2_foundation::NSTimer;
+
+use super::Error;
+
+/// Calls a callback periodically on the run loop of the thread that created it. The timer
+/// is stopped when dropped.
+pub struct Timer {
+    timer: Retained<NSTimer>,
+}
+
+impl Timer {
+    pub fn new(interval: Duration, callback: impl Fn() + 'static) -> Result<Self, Error> {
+        let block = RcBlock::new(move |_timer: NonNull<NSTimer>| callback());
+        let timer = unsafe {
+            NSTimer::scheduledTimerWithTimeInterval_repeats_block(
+                interval.as_secs_f64(),
+                true,
+                &block,
+            )
+        };
+        Ok(Self { timer })
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        unsafe { self.timer.invalidate() };
+    }
+}
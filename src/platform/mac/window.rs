@@ -77,7 +77,7 @@ impl Window {
                     view.setFrameSize(size);
                     Ok(())
                 } else {
-                    Err(Error)
+                    Err(Error::Platform)
                 }
             }
         }
@@ -92,7 +92,7 @@ impl Window {
                 if let Some(view) = view.load() {
                     Ok(view.frame())
                 } else {
-                    Err(Error)
+                    Err(Error::Platform)
                 }
             }
         }?;
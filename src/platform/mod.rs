@@ -17,5 +17,5 @@ pub use linux::*;
 
 mod shared;
 mod text;
-pub use shared::{WindowEvent, WindowHandler};
+pub use shared::{StreamConfig, WindowEvent, WindowHandler};
 pub use text::{Font, TextLayout};
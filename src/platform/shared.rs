@@ -29,3 +29,13 @@ pub enum WindowEvent {
     ScaleFactorChanged(ScaleFactor),
     ThemeChanged(WindowTheme),
 }
+
+/// Format of an audio stream. The devices are opened with this format, and convert if needed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamConfig {
+    pub sample_rate: f64,
+    /// Number of frames passed to the callback each time
+    pub block_size: usize,
+    pub input_channels: usize,
+    pub output_channels: usize,
+}
@@ -3,6 +3,7 @@ use std::{cell::OnceCell, sync::OnceLock, thread::JoinHandle};
 use windows::{
     Win32::{
         Devices::Properties,
+        Foundation::{CloseHandle, E_NOTIMPL},
         Media::Audio,
         System::Com::{self, StructuredStorage::PropVariantGetStringElem},
         System::Threading::{
@@ -14,25 +15,26 @@ use windows::{
 };
 
 use super::com;
+use crate::platform::StreamConfig;
 
 pub struct AudioHost;
 
 impl AudioHost {
-    pub fn default_input_device() -> Result<AudioDevice> {
+    pub fn default_input_device() -> Result<Device> {
         let device = unsafe {
             device_enumerator().GetDefaultAudioEndpoint(Audio::eCapture, Audio::eConsole)?
         };
-        Ok(AudioDevice::new(device))
+        Ok(Device::new(device))
     }
 
-    pub fn default_output_device() -> Result<AudioDevice> {
+    pub fn default_output_device() -> Result<Device> {
         let device = unsafe {
             device_enumerator().GetDefaultAudioEndpoint(Audio::eRender, Audio::eConsole)?
         };
-        Ok(AudioDevice::new(device))
+        Ok(Device::new(device))
     }
 
-    pub fn devices() -> Result<Vec<AudioDevice>> {
+    pub fn devices() -> Result<Vec<Device>> {
         let endpoints = unsafe {
             device_enumerator().EnumAudioEndpoints(Audio::eAll, Audio::DEVICE_STATE_ACTIVE)?
         };
@@ -40,18 +42,27 @@ impl AudioHost {
         (0..count)
             .map(|i| {
                 let device = unsafe { endpoints.Item(i)? };
-                Ok(AudioDevice::new(device))
+                Ok(Device::new(device))
             })
             .collect()
     }
+
+    pub fn open_stream(
+        _output: &Device,
+        _input: Option<&Device>,
+        _config: StreamConfig,
+        _callback: impl FnMut(&crate::AudioBuffer, &mut crate::AudioBuffer) + Send + 'static,
+    ) -> Result<Stream> {
+        Err(not_implemented())
+    }
 }
 
-pub struct AudioDevice {
+pub struct Device {
     device: Audio::IMMDevice,
     audio_client: OnceCell<Audio::IAudioClient>,
 }
 
-impl AudioDevice {
+impl Device {
     pub fn id(&self) -> Result<String> {
         let id = unsafe { self.device.GetId()? };
         Ok(unsafe { id.to_string()? })
@@ -86,8 +97,30 @@ impl AudioDevice {
         Ok(unsafe { &*mix_format })
     }
 
-    pub fn sample_rate(&self) -> Result<u32> {
-        self.get_mix_format().map(|x| x.nSamplesPerSec)
+    pub fn is_input(&self) -> bool {
+        self.data_flow().is_ok_and(|flow| flow == Audio::eCapture)
+    }
+
+    pub fn is_output(&self) -> bool {
+        self.data_flow().is_ok_and(|flow| flow == Audio::eRender)
+    }
+
+    fn data_flow(&self) -> Result<Audio::EDataFlow> {
+        let endpoint: Audio::IMMEndpoint = self.device.cast()?;
+        unsafe { endpoint.GetDataFlow() }
+    }
+
+    pub fn sample_rate(&self) -> Result<f64> {
+        self.get_mix_format().map(|x| x.nSamplesPerSec as f64)
+    }
+
+    /// Shared mode streams always run at the rate of the mix format
+    pub fn sample_rates(&self) -> Result<Vec<f64>> {
+        Ok(vec![self.sample_rate()?])
+    }
+
+    pub fn max_channels(&self) -> Result<usize> {
+        self.get_mix_format().map(|x| x.nChannels as usize)
     }
 
     pub fn new(device: Audio::IMMDevice) -> Self {
@@ -125,7 +158,8 @@ impl AudioDevice {
         };
         unsafe { audio_client.SetEventHandle(sample_ready_event) }?;
 
-        todo!()
+        unsafe { CloseHandle(sample_ready_event) }?;
+        Err(not_implemented())
     }
 
     /*pub fn new2() -> Result<Self> {
@@ -159,6 +193,14 @@ impl AudioDevice {
     }*/
 }
 
+/// Devices can be listed, but streams can't be opened yet
+fn not_implemented() -> Error {
+    Error::new(
+        E_NOTIMPL,
+        "The audio backend is not implemented on this platform",
+    )
+}
+
 struct Enumerator(Audio::IMMDeviceEnumerator);
 unsafe impl Sync for Enumerator {}
 unsafe impl Send for Enumerator {}
@@ -178,8 +220,19 @@ pub struct Stream {
     thread_handle: Option<JoinHandle<()>>,
 }
 
+impl Stream {
+    pub fn stop(mut self) -> Result<()> {
+        if let Some(thread_handle) = self.thread_handle.take() {
+            let _ = thread_handle.join();
+        }
+        Ok(())
+    }
+}
+
 impl Drop for Stream {
     fn drop(&mut self) {
-        self.thread_handle.take().unwrap().join().unwrap()
+        if let Some(thread_handle) = self.thread_handle.take() {
+            thread_handle.join().unwrap()
+        }
     }
 }
@@ -0,0 +1,49 @@
+use std::time::Instant;
+
+use windows::core::Result;
+
+use crate::midi::NoteEvent;
+
+pub struct MidiHost;
+
+impl MidiHost {
+    pub fn devices() -> Result<Vec<MidiDevice>> {
+        Ok(Vec::new())
+    }
+
+    pub fn default_input_device() -> Result<Option<MidiDevice>> {
+        Ok(None)
+    }
+
+    /// MIDI input is not implemented yet on this platform. The returned input never
+    /// receives any events.
+    pub fn open_input(
+        _client_name: &str,
+        _device: Option<&MidiDevice>,
+        _callback: impl FnMut(Instant, NoteEvent) + Send + 'static,
+    ) -> Result<MidiInput> {
+        log::warn!("MIDI input is not supported on this platform yet");
+        Ok(MidiInput)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MidiDevice {
+    name: String,
+}
+
+impl MidiDevice {
+    pub fn id(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn name(&self) -> Result<String> {
+        Ok(self.name.clone())
+    }
+}
+
+pub struct MidiInput;
+
+impl MidiInput {
+    pub fn stop(self) {}
+}
@@ -7,15 +7,19 @@ mod cursors;
 mod executor;
 mod handle;
 mod keyboard;
+mod midi;
 mod text;
+mod timer;
 mod util;
 mod window;
 
 pub(crate) use application::Application;
-pub(crate) use audio::{AudioDevice, AudioHost};
+pub(crate) use audio::{AudioHost, Device, Stream};
 pub(crate) use bitmap::Bitmap;
 pub(crate) use executor::Executor;
 pub(crate) use handle::Handle;
+pub use midi::{MidiDevice, MidiHost, MidiInput};
 pub(crate) use text::{NativeFont, NativeTextLayout};
+pub(crate) use timer::Timer;
 pub(crate) use window::Window;
 pub use windows::core::Error;
@@ -1,3 +1,45 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+
+use windows::{
+    Win32::{
+        Foundation::HWND,
+        UI::WindowsAndMessaging::{KillTimer, SetTimer},
+    },
+    core::{Error, Result},
+};
+
+thread_local! {
+    static CALLBACKS: RefCell<HashMap<usize, Rc<dyn Fn()>>> = RefCell::new(HashMap::new());
+}
+
+/// Calls a callback periodically on the thread that created it, which needs to run a
+/// message loop. The timer is stopped when dropped.
 pub struct Timer {
-    
-}
\ No newline at end of file
+    id: usize,
+}
+
+impl Timer {
+    pub fn new(interval: Duration, callback: impl Fn() + 'static) -> Result<Self> {
+        let id = unsafe { SetTimer(None, 0, interval.as_millis() as u32, Some(timer_proc)) };
+        if id == 0 {
+            return Err(Error::from_thread());
+        }
+        CALLBACKS.with_borrow_mut(|callbacks| callbacks.insert(id, Rc::new(callback)));
+        Ok(Self { id })
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let _ = unsafe { KillTimer(None, self.id) };
+        CALLBACKS.with_borrow_mut(|callbacks| callbacks.remove(&self.id));
+    }
+}
+
+unsafe extern "system" fn timer_proc(_hwnd: HWND, _message: u32, id: usize, _time: u32) {
+    // The callback may drop the timer, so it can not be called while borrowing the callbacks
+    let callback = CALLBACKS.with_borrow(|callbacks| callbacks.get(&id).cloned());
+    if let Some(callback) = callback {
+        callback();
+    }
+}
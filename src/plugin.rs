@@ -7,7 +7,12 @@ use crate::midi::NoteEvent;
 use crate::param::Params;
 use crate::wrapper::clap::ClapFeature;
 use crate::wrapper::vst3::VST3Categories;
-use crate::{AudioBuffer, AudioLayout};
+use crate::{AudioBuffer, AudioLayout, Transport};
+
+/// Number of samples converted at a time by the default [`Plugin::process_f64`]
+pub const F64_CONVERSION_BLOCK: usize = 64;
+/// The most channels of a bus, see [`crate::ChannelType`]
+const MAX_BUS_CHANNELS: usize = 2;
 
 pub struct PluginInfo {
     pub name: &'static str,
@@ -32,15 +37,29 @@ pub struct Preset<P: Params> {
     pub parameters: P,
 }
 
+/// How the host is processing the plugin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProcessMode {
+    /// Processing in realtime, e.g. during playback
+    #[default]
+    Realtime,
+    /// Processing ahead of time, but still at least as fast as realtime. Only used by VST3 hosts.
+    Prefetch,
+    /// Rendering offline (a.k.a. bouncing), where processing is allowed to be slower than
+    /// realtime. A good time to switch to higher quality algorithms.
+    Offline,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct ProcessInfo {
     pub rendering_offline: bool,
     pub sample_rate: f64,
+    pub transport: Transport,
 }
 
-pub struct ProcessContext<'a> {
-    pub input: &'a AudioBuffer,
-    pub output: &'a mut AudioBuffer,
+pub struct ProcessContext<'a, 'b, T = f32> {
+    pub input: &'a AudioBuffer<'b, T>,
+    pub output: &'a mut AudioBuffer<'b, T>,
     pub info: ProcessInfo,
 }
 
@@ -70,6 +89,14 @@ pub trait Plugin: Send + 'static {
     const ACCEPTS_MIDI: bool = false;
     /// True if the plugin produces output midi messages
     const PRODUCES_MIDI: bool = false;
+    /// True if the plugin can process double precision samples. Hosts that request 64-bit
+    /// processing will then call [`Plugin::process_f64`] instead of [`Plugin::process`].
+    const SUPPORTS_F64: bool = false;
+    /// Length of the crossfade between the processed and the dry signal when the
+    /// [`crate::param::ByPassParameter`] of the plugin is toggled. While bypassed, the plugin
+    /// is not processed, and the input is passed through, delayed by
+    /// [`Plugin::latency_samples`] to stay aligned with the processed signal.
+    const BYPASS_CROSSFADE: Duration = Duration::ZERO;
 
     fn new(info: HostInfo) -> Self;
 
@@ -80,6 +107,13 @@ pub trait Plugin: Send + 'static {
 
     fn process(&mut self, context: ProcessContext, parameters: &Self::Parameters);
 
+    /// Double precision version of [`Plugin::process`]. Only called if [`Plugin::SUPPORTS_F64`]
+    /// is true. The default converts the samples to single precision and calls
+    /// [`Plugin::process`], in blocks of up to [`F64_CONVERSION_BLOCK`] samples.
+    fn process_f64(&mut self, context: ProcessContext<f64>, parameters: &Self::Parameters) {
+        process_f64_as_f32(self, context, parameters)
+    }
+
     fn process_midi(
         &mut self,
         _context: &mut MidiProcessContext,
@@ -91,6 +125,18 @@ pub trait Plugin: Send + 'static {
     /// Called when the plugin should reset internal buffers and voices (???)
     fn reset(&mut self) {}
 
+    /// Called when the host changes the process mode. Hosts usually change the mode before
+    /// calling [`Plugin::prepare`], but CLAP hosts can change it at any time, in which case
+    /// this is called from the audio thread before processing the next block.
+    fn process_mode_changed(&mut self, _mode: ProcessMode) {}
+
+    /// Called when the host starts processing, after [`Plugin::prepare`]. Can be called
+    /// from the audio thread.
+    fn start_processing(&mut self) {}
+
+    /// Called when the host stops processing. Can be called from the audio thread.
+    fn stop_processing(&mut self) {}
+
     fn presets(&self) -> Vec<Preset<Self::Parameters>> {
         Vec::new()
     }
@@ -98,15 +144,28 @@ pub trait Plugin: Send + 'static {
     /// Length of the tail of the signal from the plugin. This can be thought of as the
     /// time it takes for the plugin to go silent when no more input is given. A good example
     /// for a plugin type with a tail is a reverb, which will reverberate for some time for
-    /// each input value.
+    /// each input value. Return [`Duration::MAX`] for an infinite tail.
     fn tail_time(&self) -> Duration {
         Duration::ZERO
     }
 
-    /// The latency (in number of samples) that the plugin imposes
+    /// The latency (in number of samples) that the plugin imposes. The latency may change,
+    /// e.g. when the oversampling factor is changed. The wrappers check the latency after
+    /// every processed block, and let the host know when it has changed. The host will then
+    /// restart processing, calling [`Plugin::prepare`] again.
     fn latency_samples(&self) -> usize {
         0
     }
+
+    /// State of the plugin that is not held by the parameters, such as a loaded sample. It
+    /// is saved with the values of the parameters, see [`crate::PluginState`].
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restore the state from [`Plugin::save_state`]. The parameters have already been set
+    /// when this is called. `state` may come from an older version of the plugin.
+    fn load_state(&mut self, _state: &[u8]) {}
 }
 
 pub trait VST3Plugin: Plugin {
@@ -118,3 +177,143 @@ pub trait VST3Plugin: Plugin {
 pub trait ClapPlugin: Plugin {
     const CLAP_FEATURES: &'static [ClapFeature];
 }
+
+/// Process double precision buffers with [`Plugin::process`]. The samples are converted in
+/// buffers on the stack, since this runs on the audio thread.
+fn process_f64_as_f32<P: Plugin + ?Sized>(
+    plugin: &mut P,
+    context: ProcessContext<f64>,
+    parameters: &P::Parameters,
+) {
+    let ProcessContext {
+        input,
+        output,
+        info,
+    } = context;
+    let num_samples = input.samples().max(output.samples());
+    let num_inputs = input.channels().min(MAX_BUS_CHANNELS);
+    let num_outputs = output.channels().min(MAX_BUS_CHANNELS);
+    let mut input_samples = [[0.0f32; F64_CONVERSION_BLOCK]; MAX_BUS_CHANNELS];
+    let mut output_samples = [[0.0f32; F64_CONVERSION_BLOCK]; MAX_BUS_CHANNELS];
+
+    for start in (0..num_samples).step_by(F64_CONVERSION_BLOCK) {
+        let end = (start + F64_CONVERSION_BLOCK).min(num_samples);
+        let len = end - start;
+        for (channel, samples) in input_samples.iter_mut().enumerate().take(num_inputs) {
+            let source = &input.channel(channel).as_slice()[start..end];
+            for (sample, &source) in samples.iter_mut().zip(source) {
+                *sample = source as f32;
+            }
+        }
+
+        let mut input_ptrs = input_samples.each_mut().map(|samples| samples.as_mut_ptr());
+        let mut output_ptrs = output_samples
+            .each_mut()
+            .map(|samples| samples.as_mut_ptr());
+        // Safety: the pointers point to MAX_BUS_CHANNELS channels of F64_CONVERSION_BLOCK
+        // samples, which are only accessed through the buffers while they exist
+        let block_input =
+            unsafe { AudioBuffer::from_ptr(input_ptrs.as_mut_ptr(), num_inputs, len) };
+        let mut block_output =
+            unsafe { AudioBuffer::from_ptr(output_ptrs.as_mut_ptr(), num_outputs, len) };
+        let mut transport = info.transport;
+        transport.position_samples = transport
+            .position_samples
+            .map(|position| position + start as i64);
+        transport.position_quarters = info.transport.position_quarters_at(start, info.sample_rate);
+        plugin.process(
+            ProcessContext {
+                input: &block_input,
+                output: &mut block_output,
+                info: ProcessInfo { transport, ..info },
+            },
+            parameters,
+        );
+
+        for (channel, samples) in output_samples.iter().enumerate().take(num_outputs) {
+            let mut destination = output.channel_mut(channel);
+            let destination = &mut destination.as_mut_slice()[start..end];
+            for (sample, &source) in destination.iter_mut().zip(samples) {
+                *sample = source as f64;
+            }
+        }
+    }
+    // Buses have at most MAX_BUS_CHANNELS channels, anything else is silent
+    for channel in num_outputs..output.channels() {
+        output.channel_mut(channel).as_mut_slice().fill(0.0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Bus, ChannelType, GenericEditor};
+
+    struct HalfGain;
+
+    impl Plugin for HalfGain {
+        const NAME: &'static str = "Half gain";
+        const VENDOR: &'static str = "audioplug";
+        const URL: &'static str = "";
+        const EMAIL: &'static str = "";
+        const AUDIO_LAYOUT: AudioLayout = AudioLayout {
+            main_input: Some(Bus {
+                name: "Input",
+                channel: ChannelType::Stereo,
+            }),
+            main_output: Some(Bus {
+                name: "Output",
+                channel: ChannelType::Stereo,
+            }),
+        };
+        const SUPPORTS_F64: bool = true;
+        type Editor = GenericEditor<()>;
+        type Parameters = ();
+
+        fn new(_info: HostInfo) -> Self {
+            Self
+        }
+
+        fn prepare(&mut self, _sample_rate: f64, _max_buffer_size: usize) {}
+
+        fn process(&mut self, context: ProcessContext, _parameters: &()) {
+            context.output.copy_from(context.input);
+            context.output.apply_gain(0.5);
+        }
+    }
+
+    #[test]
+    fn default_process_f64_converts_to_f32() {
+        const SAMPLES: usize = F64_CONVERSION_BLOCK * 2 + 3;
+        let mut left = [0.0f64; SAMPLES];
+        let mut right = [0.0f64; SAMPLES];
+        for (i, (left, right)) in left.iter_mut().zip(right.iter_mut()).enumerate() {
+            *left = i as f64;
+            *right = -(i as f64);
+        }
+        let mut input_channels = [&mut left, &mut right];
+        let input = AudioBuffer::from_slice(&mut input_channels);
+        let mut output_left = [1.0f64; SAMPLES];
+        let mut output_right = [1.0f64; SAMPLES];
+        let mut output_channels = [&mut output_left, &mut output_right];
+        let mut output = AudioBuffer::from_slice(&mut output_channels);
+
+        let mut plugin = HalfGain;
+        plugin.process_f64(
+            ProcessContext {
+                input: &input,
+                output: &mut output,
+                info: ProcessInfo {
+                    rendering_offline: false,
+                    sample_rate: 48000.0,
+                    transport: Transport::default(),
+                },
+            },
+            &(),
+        );
+        for i in 0..SAMPLES {
+            assert_eq!(output.channel(0).as_slice()[i], i as f64 * 0.5);
+            assert_eq!(output.channel(1).as_slice()[i], -(i as f64) * 0.5);
+        }
+    }
+}
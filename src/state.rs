@@ -0,0 +1,203 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use thiserror::Error;
+
+use crate::param::{AnyParameterMap, ParameterId, PlainValue};
+
+/// Identifies a serialized [`PluginState`]
+const MAGIC: &[u8; 4] = b"APST";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Not a plugin state")]
+    NotState,
+    #[error("Unsupported state version {0}")]
+    UnsupportedVersion(u32),
+}
+
+/// The state of a plugin, as saved by the hosts and the standalone wrapper: the values of
+/// the parameters, and the custom state from [`crate::Plugin::save_state`].
+///
+/// Parameters are stored by id with their plain value, so that states stay valid when
+/// parameters are added or their ranges change. Parameters that are not in the state keep
+/// their value when it is applied, and unknown parameters are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PluginState {
+    pub parameters: Vec<(ParameterId, PlainValue)>,
+    pub custom: Vec<u8>,
+}
+
+impl PluginState {
+    /// The current values of `parameters`, with the custom state of the plugin
+    pub fn new(parameters: &dyn AnyParameterMap, custom: Vec<u8>) -> Self {
+        let parameters = (0..parameters.count())
+            .filter_map(|index| parameters.get_by_index(index))
+            .map(|(_, param_ref)| (param_ref.id(), param_ref.plain_value()))
+            .collect();
+        Self { parameters, custom }
+    }
+
+    /// Set the parameters in `parameters` to their value in the state. Returns the ids of
+    /// the parameters that were set.
+    pub fn apply_parameters(&self, parameters: &dyn AnyParameterMap) -> Vec<ParameterId> {
+        self.parameters
+            .iter()
+            .filter_map(|&(id, value)| {
+                let param_ref = parameters.get_by_id(id)?;
+                param_ref.set_value_plain(value);
+                Some(id)
+            })
+            .collect()
+    }
+
+    /// Serialize the state. All numbers are little-endian.
+    pub fn write(&self, mut writer: impl Write) -> Result<(), StateError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(self.parameters.len() as u32).to_le_bytes())?;
+        for (id, value) in &self.parameters {
+            writer.write_all(&id.0.to_le_bytes())?;
+            writer.write_all(&value.0.to_le_bytes())?;
+        }
+        writer.write_all(&(self.custom.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.custom)?;
+        Ok(())
+    }
+
+    pub fn read(mut reader: impl Read) -> Result<Self, StateError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic).map_err(not_state)?;
+        if &magic != MAGIC {
+            return Err(StateError::NotState);
+        }
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let count = read_u32(&mut reader)?;
+        let mut parameters = Vec::new();
+        for _ in 0..count {
+            let id = ParameterId(read_u32(&mut reader)?);
+            let mut value = [0; 8];
+            reader.read_exact(&mut value).map_err(not_state)?;
+            parameters.push((id, PlainValue::new(f64::from_le_bytes(value))));
+        }
+
+        let len = read_u32(&mut reader)? as u64;
+        let mut custom = Vec::new();
+        reader.by_ref().take(len).read_to_end(&mut custom)?;
+        if custom.len() as u64 != len {
+            return Err(StateError::NotState);
+        }
+        Ok(Self { parameters, custom })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes)
+            .expect("Writing to a Vec does not fail");
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StateError> {
+        Self::read(bytes)
+    }
+
+    /// Read a state file, as written by [`PluginState::save`]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, StateError> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), StateError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, StateError> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes).map_err(not_state)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// A state that ends early is not a valid state
+fn not_state(err: io::Error) -> StateError {
+    if err.kind() == io::ErrorKind::UnexpectedEof {
+        StateError::NotState
+    } else {
+        err.into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::param::{FloatParameter, IntParameter, ParameterMap, Params};
+    use crate::params;
+
+    params!(
+        struct TestParams {
+            gain: FloatParameter,
+            steps: IntParameter,
+        }
+    );
+
+    impl Params for TestParams {
+        fn new() -> Self {
+            Self {
+                gain: FloatParameter::new(ParameterId(1), "Gain")
+                    .with_linear_range(-60.0, 12.0)
+                    .with_default(0.0),
+                steps: IntParameter::new(ParameterId(2), "Steps").with_range(1..=16),
+            }
+        }
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let saved = ParameterMap::new(TestParams::new());
+        saved
+            .get_by_id(ParameterId(1))
+            .unwrap()
+            .set_value_plain(PlainValue::new(-6.0));
+        saved
+            .get_by_id(ParameterId(2))
+            .unwrap()
+            .set_value_plain(PlainValue::new(9.0));
+        let state = PluginState::new(&*saved, vec![1, 2, 3]);
+        let bytes = state.to_bytes();
+        let loaded_state = PluginState::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded_state, state);
+
+        let loaded = ParameterMap::new(TestParams::new());
+        let mut with_unknown = loaded_state.clone();
+        with_unknown
+            .parameters
+            .push((ParameterId(99), PlainValue::new(1.0)));
+        assert_eq!(
+            with_unknown.apply_parameters(&*loaded),
+            [ParameterId(1), ParameterId(2)]
+        );
+        assert_eq!(loaded.parameters_ref().gain.value(), -6.0);
+        assert_eq!(loaded.parameters_ref().steps.value(), 9);
+
+        assert!(matches!(
+            PluginState::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(StateError::NotState)
+        ));
+        assert!(matches!(
+            PluginState::from_bytes(b"RIFF"),
+            Err(StateError::NotState)
+        ));
+    }
+}
@@ -0,0 +1,329 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use num::Complex;
+
+use crate::dsp::{RealFft, Window, gain_to_db};
+use crate::util::{SampleFormat, WavFile};
+
+/// Set this environment variable to overwrite the golden files with the current output
+pub const UPDATE_GOLDEN_ENV: &str = "AUDIOPLUG_UPDATE_GOLDEN";
+
+const SPECTRUM_SIZE: usize = 2048;
+
+/// Maximum differences accepted by [`assert_golden`]. Use `f32::INFINITY` to disable a check.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Largest difference of a single sample
+    pub max_abs_error: f32,
+    /// RMS of the difference signal
+    pub rms_error: f32,
+    /// Largest difference in dB between the average magnitude spectra
+    pub spectral_error_db: f32,
+    /// Spectrum bins below this level, in dBFS, are ignored in both signals
+    pub spectral_floor_db: f32,
+}
+
+impl Tolerance {
+    /// Only accept identical signals
+    pub const EXACT: Self = Self {
+        max_abs_error: 0.0,
+        rms_error: 0.0,
+        spectral_error_db: 0.0,
+        spectral_floor_db: -100.0,
+    };
+
+    pub const fn with_max_abs_error(mut self, max_abs_error: f32) -> Self {
+        self.max_abs_error = max_abs_error;
+        self
+    }
+
+    pub const fn with_rms_error(mut self, rms_error: f32) -> Self {
+        self.rms_error = rms_error;
+        self
+    }
+
+    pub const fn with_spectral_error_db(mut self, spectral_error_db: f32) -> Self {
+        self.spectral_error_db = spectral_error_db;
+        self
+    }
+
+    pub const fn with_spectral_floor_db(mut self, spectral_floor_db: f32) -> Self {
+        self.spectral_floor_db = spectral_floor_db;
+        self
+    }
+}
+
+impl Default for Tolerance {
+    /// Small enough to catch audible changes, but accepts rounding differences
+    fn default() -> Self {
+        Self {
+            max_abs_error: 1e-4,
+            rms_error: 1e-5,
+            spectral_error_db: 0.1,
+            spectral_floor_db: -100.0,
+        }
+    }
+}
+
+/// Differences between two signals, see [`compare`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub expected_channels: usize,
+    pub actual_channels: usize,
+    pub expected_len: usize,
+    pub actual_len: usize,
+    pub max_abs_error: f32,
+    pub rms_error: f32,
+    pub spectral_error_db: f32,
+}
+
+impl Comparison {
+    /// Whether the shapes of the signals match and all errors are within the tolerance
+    pub fn is_within(&self, tolerance: &Tolerance) -> bool {
+        self.expected_channels == self.actual_channels
+            && self.expected_len == self.actual_len
+            && self.max_abs_error <= tolerance.max_abs_error
+            && self.rms_error <= tolerance.rms_error
+            && self.spectral_error_db <= tolerance.spectral_error_db
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.expected_channels != self.actual_channels {
+            writeln!(
+                f,
+                "channels: expected {}, got {}",
+                self.expected_channels, self.actual_channels
+            )?;
+        }
+        if self.expected_len != self.actual_len {
+            writeln!(
+                f,
+                "length: expected {} samples, got {}",
+                self.expected_len, self.actual_len
+            )?;
+        }
+        writeln!(f, "max abs error: {:e}", self.max_abs_error)?;
+        writeln!(f, "rms error: {:e}", self.rms_error)?;
+        write!(f, "spectral error: {} dB", self.spectral_error_db)
+    }
+}
+
+/// Compare two signals with one `Vec` per channel. Missing channels and samples are treated
+/// as silence in the error measurements.
+pub fn compare(expected: &[Vec<f32>], actual: &[Vec<f32>], tolerance: &Tolerance) -> Comparison {
+    let len = |channels: &[Vec<f32>]| channels.iter().map(Vec::len).max().unwrap_or(0);
+    let num_channels = expected.len().max(actual.len());
+    let num_samples = len(expected).max(len(actual));
+
+    let mut max_abs_error = 0.0f32;
+    let mut squared_error = 0.0f64;
+    let mut spectral_error_db = 0.0f32;
+    for channel in 0..num_channels {
+        let expected = padded_channel(expected, channel, num_samples);
+        let actual = padded_channel(actual, channel, num_samples);
+        for (expected, actual) in expected.iter().zip(&actual) {
+            let error = (actual - expected).abs();
+            max_abs_error = max_abs_error.max(error);
+            squared_error += error as f64 * error as f64;
+        }
+
+        let expected_spectrum = average_spectrum_db(&expected);
+        let actual_spectrum = average_spectrum_db(&actual);
+        for (expected, actual) in expected_spectrum.iter().zip(&actual_spectrum) {
+            if expected.max(*actual) > tolerance.spectral_floor_db {
+                let expected = expected.max(tolerance.spectral_floor_db);
+                let actual = actual.max(tolerance.spectral_floor_db);
+                spectral_error_db = spectral_error_db.max((actual - expected).abs());
+            }
+        }
+    }
+
+    let total_samples = (num_channels * num_samples).max(1);
+    Comparison {
+        expected_channels: expected.len(),
+        actual_channels: actual.len(),
+        expected_len: len(expected),
+        actual_len: len(actual),
+        max_abs_error,
+        rms_error: (squared_error / total_samples as f64).sqrt() as f32,
+        spectral_error_db,
+    }
+}
+
+/// Compare `actual` with the golden file at `path`, and panic if the difference is larger than
+/// `tolerance`. On failure, the output and the difference to the golden file are written next
+/// to it, as `<name>.actual.wav` and `<name>.diff.wav`.
+///
+/// A missing golden file is created from `actual`, and the test fails so that it can be
+/// checked and committed. Set the `AUDIOPLUG_UPDATE_GOLDEN` environment variable to overwrite
+/// the golden files.
+#[track_caller]
+pub fn assert_golden(
+    path: impl AsRef<Path>,
+    actual: &[Vec<f32>],
+    sample_rate: u32,
+    tolerance: &Tolerance,
+) {
+    let path = path.as_ref();
+    let actual_wav = WavFile::new(sample_rate, SampleFormat::Float32, actual.to_vec());
+    let update = std::env::var_os(UPDATE_GOLDEN_ENV).is_some();
+    if update || !path.exists() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        actual_wav
+            .save(path)
+            .unwrap_or_else(|err| panic!("Could not write {}: {err}", path.display()));
+        if update {
+            return;
+        }
+        panic!(
+            "Golden file {} did not exist and has been created",
+            path.display()
+        );
+    }
+
+    let expected = WavFile::open(path)
+        .unwrap_or_else(|err| panic!("Could not read {}: {err}", path.display()));
+    let comparison = compare(&expected.channels, actual, tolerance);
+    if expected.sample_rate == sample_rate && comparison.is_within(tolerance) {
+        return;
+    }
+
+    let diff: Vec<Vec<f32>> = (0..comparison.expected_channels.max(comparison.actual_channels))
+        .map(|channel| {
+            let num_samples = comparison.expected_len.max(comparison.actual_len);
+            let expected = padded_channel(&expected.channels, channel, num_samples);
+            let actual = padded_channel(actual, channel, num_samples);
+            actual.iter().zip(&expected).map(|(a, e)| a - e).collect()
+        })
+        .collect();
+    let actual_path = sibling_path(path, "actual");
+    let diff_path = sibling_path(path, "diff");
+    let _ = actual_wav.save(&actual_path);
+    let _ = WavFile::new(sample_rate, SampleFormat::Float32, diff).save(&diff_path);
+
+    let sample_rate_mismatch = if expected.sample_rate != sample_rate {
+        format!(
+            "sample rate: expected {}, got {}\n",
+            expected.sample_rate, sample_rate
+        )
+    } else {
+        String::new()
+    };
+    panic!(
+        "Output does not match golden file {}\n{sample_rate_mismatch}{comparison}\n\
+         output written to {}, difference to {}",
+        path.display(),
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+fn padded_channel(channels: &[Vec<f32>], channel: usize, num_samples: usize) -> Vec<f32> {
+    let mut samples = channels.get(channel).cloned().unwrap_or_default();
+    samples.resize(num_samples, 0.0);
+    samples
+}
+
+/// `<dir>/<stem>.<suffix>.wav`
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}.{suffix}.wav"))
+}
+
+/// Magnitude spectrum in dBFS, averaged over Hann windowed frames with 50% overlap
+fn average_spectrum_db(samples: &[f32]) -> Vec<f32> {
+    let size = samples.len().next_power_of_two().clamp(2, SPECTRUM_SIZE);
+    let hop_size = size / 2;
+    let mut fft = RealFft::new(size);
+    let mut frame = vec![0.0; size];
+    let mut spectrum = vec![Complex::new(0.0, 0.0); fft.spectrum_size()];
+    let mut power = vec![0.0f64; fft.spectrum_size()];
+
+    let mut num_frames = 0;
+    let mut start = 0;
+    while start < samples.len() || num_frames == 0 {
+        let end = (start + size).min(samples.len());
+        frame.fill(0.0);
+        frame[..end - start].copy_from_slice(&samples[start..end]);
+        Window::Hann.apply(&mut frame);
+        fft.forward(&frame, &mut spectrum);
+        for (power, bin) in power.iter_mut().zip(&spectrum) {
+            *power += bin.norm_sqr() as f64;
+        }
+        num_frames += 1;
+        start += hop_size;
+    }
+
+    // A full scale sine has a peak of size / 4 with a Hann window
+    let scale = 4.0 / size as f64;
+    power
+        .iter()
+        .map(|power| gain_to_db(((power / num_frames as f64).sqrt() * scale) as f32))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sine(frequency: f32, gain: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| gain * (std::f32::consts::TAU * frequency * i as f32 / 48000.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn measures_errors() {
+        let expected = vec![sine(1000.0, 0.5, 4800)];
+        let comparison = compare(&expected, &expected, &Tolerance::EXACT);
+        assert!(comparison.is_within(&Tolerance::EXACT));
+
+        // 0.5 dB louder
+        let actual = vec![sine(1000.0, 0.5 * 1.059_254, 4800)];
+        let comparison = compare(&expected, &actual, &Tolerance::default());
+        assert!((comparison.max_abs_error - 0.0296).abs() < 1e-3);
+        assert!((comparison.rms_error - 0.0296 / 2.0f32.sqrt()).abs() < 1e-3);
+        assert!((comparison.spectral_error_db - 0.5).abs() < 1e-2);
+        assert!(!comparison.is_within(&Tolerance::default()));
+        assert!(
+            comparison.is_within(
+                &Tolerance::default()
+                    .with_max_abs_error(0.03)
+                    .with_rms_error(0.03)
+                    .with_spectral_error_db(0.6)
+            )
+        );
+
+        let comparison = compare(&expected, &[], &Tolerance::default());
+        assert_eq!(comparison.actual_channels, 0);
+        assert!(!comparison.is_within(&Tolerance::default().with_max_abs_error(1.0)));
+    }
+
+    #[test]
+    fn failing_golden_test_writes_diff() {
+        let dir = std::env::temp_dir().join(format!("audioplug-golden-{}", std::process::id()));
+        let path = dir.join("sine.wav");
+        let expected = vec![sine(440.0, 0.5, 1000)];
+        std::fs::create_dir_all(&dir).unwrap();
+        WavFile::new(48000, SampleFormat::Float32, expected.clone())
+            .save(&path)
+            .unwrap();
+
+        assert_golden(&path, &expected, 48000, &Tolerance::EXACT);
+
+        let actual = vec![sine(440.0, 0.25, 1000)];
+        let result = std::panic::catch_unwind(|| {
+            assert_golden(&path, &actual, 48000, &Tolerance::default())
+        });
+        assert!(result.is_err());
+        let diff = WavFile::open(dir.join("sine.diff.wav")).unwrap();
+        assert_eq!(diff.channels[0][1], actual[0][1] - expected[0][1]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
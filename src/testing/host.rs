@@ -0,0 +1,467 @@
+use std::rc::Rc;
+
+use crate::midi::{Note, NoteEvent};
+use crate::param::{
+    AnyParameterMap, NormalizedValue, ParameterId, ParameterMap, Params, PlainValue,
+};
+use crate::util::assert_no_alloc;
+use crate::wrapper::bypass::Bypass;
+use crate::{
+    HostInfo, MidiProcessContext, OwnedAudioBuffer, Plugin, ProcessInfo, ProcessMode, Transport,
+};
+
+/// Name reported to plugins in [`HostInfo`]
+pub const TEST_HOST_NAME: &str = "audioplug test host";
+
+enum ParameterValue {
+    Normalized(NormalizedValue),
+    Plain(PlainValue),
+}
+
+/// Something that happens at a given sample position
+enum ScheduledEvent {
+    Parameter(ParameterId, ParameterValue),
+    Note(NoteEvent),
+}
+
+/// In-process host for testing plugins, without a DAW, audio device or window.
+///
+/// The host calls the plugin like the wrappers do: parameter changes are applied between
+/// blocks, note events are passed to [`Plugin::process_midi`] with their offset into the block,
+/// and the plugin is bypassed by its [`crate::param::ByPassParameter`]. Blocks are split at
+/// scheduled parameter changes, so automation is applied at the exact sample.
+///
+/// Like the wrappers, the host checks that `process` and `process_midi` don't allocate when
+/// the real-time checks are compiled in, see [`crate::util::assert_no_alloc`]. Unlike the
+/// wrappers, it doesn't require processing to be faster than real time.
+///
+/// ```ignore
+/// let mut host = TestHost::<MyPlugin>::new(48000.0, 512);
+/// host.schedule_parameter_plain(24000, ParameterId(1), PlainValue::new(0.0));
+/// let output = host.process(&[vec![1.0; 48000], vec![1.0; 48000]]);
+/// ```
+pub struct TestHost<P: Plugin> {
+    plugin: P,
+    parameters: Rc<ParameterMap<P::Parameters>>,
+    bypass: Bypass<f32>,
+    sample_rate: f64,
+    max_block_size: usize,
+    input: OwnedAudioBuffer,
+    output: OwnedAudioBuffer,
+    transport: Transport,
+    process_mode: ProcessMode,
+    check_realtime: bool,
+    /// Pending events, ordered by their sample position
+    events: Vec<(usize, ScheduledEvent)>,
+    position: usize,
+}
+
+impl<P: Plugin> TestHost<P> {
+    /// Create the plugin, and prepare it for processing
+    pub fn new(sample_rate: f64, max_block_size: usize) -> Self {
+        let plugin = P::new(HostInfo {
+            name: TEST_HOST_NAME.to_string(),
+        });
+        let mut this = Self {
+            plugin,
+            parameters: ParameterMap::new(P::Parameters::new()),
+            bypass: Bypass::new(),
+            sample_rate,
+            max_block_size,
+            input: OwnedAudioBuffer::new(),
+            output: OwnedAudioBuffer::new(),
+            transport: Transport::default(),
+            process_mode: ProcessMode::Realtime,
+            check_realtime: true,
+            events: Vec::new(),
+            position: 0,
+        };
+        this.prepare(sample_rate, max_block_size);
+        this
+    }
+
+    /// Prepare the plugin again, e.g. to change the sample rate. Pending events are kept.
+    pub fn prepare(&mut self, sample_rate: f64, max_block_size: usize) {
+        assert!(max_block_size > 0);
+        self.sample_rate = sample_rate;
+        self.max_block_size = max_block_size;
+        self.plugin.prepare(sample_rate, max_block_size);
+        self.bypass
+            .prepare_for_plugin(&self.plugin, sample_rate, max_block_size);
+        let num_inputs = P::AUDIO_LAYOUT
+            .main_input
+            .map_or(0, |bus| bus.channel.size() as usize);
+        let num_outputs = P::AUDIO_LAYOUT
+            .main_output
+            .map_or(0, |bus| bus.channel.size() as usize);
+        self.input.prepare(num_inputs, max_block_size);
+        self.output.prepare(num_outputs, max_block_size);
+    }
+
+    pub fn plugin(&self) -> &P {
+        &self.plugin
+    }
+
+    pub fn plugin_mut(&mut self) -> &mut P {
+        &mut self.plugin
+    }
+
+    pub fn parameters(&self) -> &P::Parameters {
+        self.parameters.parameters_ref()
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Number of samples processed so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The latency currently reported by the plugin
+    pub fn latency_samples(&self) -> usize {
+        self.plugin.latency_samples()
+    }
+
+    pub fn input_channels(&self) -> usize {
+        self.input.channels()
+    }
+
+    pub fn output_channels(&self) -> usize {
+        self.output.channels()
+    }
+
+    /// Set the transport state passed to the plugin. While playing, the position is advanced
+    /// after every processed block.
+    pub fn set_transport(&mut self, transport: Transport) {
+        self.transport = transport;
+    }
+
+    pub fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    pub fn set_process_mode(&mut self, mode: ProcessMode) {
+        if mode != self.process_mode {
+            self.process_mode = mode;
+            self.plugin.process_mode_changed(mode);
+        }
+    }
+
+    /// Turn the allocation checks around the calls to the plugin off or on. They are on by
+    /// default.
+    pub fn set_check_realtime(&mut self, enabled: bool) {
+        self.check_realtime = enabled;
+    }
+
+    /// Set a parameter immediately, like a host would do between two blocks
+    pub fn set_parameter_normalized(&mut self, id: ParameterId, value: NormalizedValue) {
+        self.apply_parameter(id, ParameterValue::Normalized(value));
+    }
+
+    pub fn set_parameter_plain(&mut self, id: ParameterId, value: PlainValue) {
+        self.apply_parameter(id, ParameterValue::Plain(value));
+    }
+
+    /// Change a parameter when the host reaches the absolute sample position `sample`
+    pub fn schedule_parameter_normalized(
+        &mut self,
+        sample: usize,
+        id: ParameterId,
+        value: NormalizedValue,
+    ) {
+        self.schedule(
+            sample,
+            ScheduledEvent::Parameter(id, ParameterValue::Normalized(value)),
+        );
+    }
+
+    pub fn schedule_parameter_plain(&mut self, sample: usize, id: ParameterId, value: PlainValue) {
+        self.schedule(
+            sample,
+            ScheduledEvent::Parameter(id, ParameterValue::Plain(value)),
+        );
+    }
+
+    /// Schedule a note event at the absolute sample position `sample`. The sample offset of
+    /// the event is replaced with the offset into the block that contains `sample`.
+    pub fn schedule_note_event(&mut self, sample: usize, event: NoteEvent) {
+        self.schedule(sample, ScheduledEvent::Note(event));
+    }
+
+    pub fn note_on(&mut self, sample: usize, channel: i16, note: Note) {
+        self.schedule_note_event(
+            sample,
+            NoteEvent::NoteOn {
+                channel,
+                sample_offset: 0,
+                note,
+            },
+        );
+    }
+
+    pub fn note_off(&mut self, sample: usize, channel: i16, note: Note) {
+        self.schedule_note_event(
+            sample,
+            NoteEvent::NoteOff {
+                channel,
+                sample_offset: 0,
+                note,
+            },
+        );
+    }
+
+    /// Process `input`, with one slice per input channel, and return the output with one
+    /// `Vec` per output channel. Missing input channels are silent, and the number of samples
+    /// is given by the longest input channel.
+    pub fn process<S: AsRef<[f32]>>(&mut self, input: &[S]) -> Vec<Vec<f32>> {
+        let num_samples = input
+            .iter()
+            .map(|channel| channel.as_ref().len())
+            .max()
+            .unwrap_or(0);
+        let mut output = vec![Vec::with_capacity(num_samples); self.output.channels()];
+
+        let mut start = 0;
+        while start < num_samples {
+            let block_size = self.next_block_size(num_samples - start);
+            for channel in 0..self.input.channels() {
+                let destination = &mut self.input.channel_mut(channel)[..block_size];
+                let source = input.get(channel).map_or(&[][..], |source| source.as_ref());
+                let source = source.get(start..).unwrap_or(&[]);
+                let copied = source.len().min(block_size);
+                destination[..copied].copy_from_slice(&source[..copied]);
+                destination[copied..].fill(0.0);
+            }
+
+            self.process_block(block_size);
+
+            for (channel, output) in output.iter_mut().enumerate() {
+                output.extend_from_slice(&self.output.channel(channel)[..block_size]);
+            }
+            start += block_size;
+        }
+        output
+    }
+
+    /// Process `num_samples` samples of silence, e.g. for instruments or to collect a tail
+    pub fn process_silence(&mut self, num_samples: usize) -> Vec<Vec<f32>> {
+        let input = vec![vec![0.0; num_samples]; self.input.channels()];
+        if input.is_empty() {
+            // Still need something to define the length
+            return self.process(&[vec![0.0; num_samples]]);
+        }
+        self.process(&input)
+    }
+
+    fn schedule(&mut self, sample: usize, event: ScheduledEvent) {
+        // Keep events at the same position in the order they were scheduled
+        let index = self.events.partition_point(|(other, _)| *other <= sample);
+        self.events.insert(index, (sample, event));
+    }
+
+    fn apply_parameter(&self, id: ParameterId, value: ParameterValue) {
+        let param_ref = self
+            .parameters
+            .get_by_id(id)
+            .unwrap_or_else(|| panic!("The plugin has no parameter with id {}", id.0));
+        match value {
+            ParameterValue::Normalized(value) => param_ref.set_value_normalized(value),
+            ParameterValue::Plain(value) => param_ref.set_value_plain(value),
+        }
+    }
+
+    /// Size of the next block, which ends at the next parameter change
+    fn next_block_size(&self, remaining: usize) -> usize {
+        let end = self.position + remaining.min(self.max_block_size);
+        let next_change = self
+            .events
+            .iter()
+            .filter(|(_, event)| matches!(event, ScheduledEvent::Parameter(..)))
+            .map(|&(sample, _)| sample)
+            .find(|&sample| sample > self.position);
+        next_change.map_or(end, |sample| sample.min(end)) - self.position
+    }
+
+    fn process_block(&mut self, num_samples: usize) {
+        let block_end = self.position + num_samples;
+        let info = ProcessInfo {
+            rendering_offline: self.process_mode == ProcessMode::Offline,
+            sample_rate: self.sample_rate,
+            transport: self.transport,
+        };
+
+        let due = self
+            .events
+            .partition_point(|(sample, _)| *sample < block_end);
+        let check_realtime = self.check_realtime;
+        let guarded = |f: &mut dyn FnMut()| {
+            if check_realtime {
+                assert_no_alloc(f)
+            } else {
+                f()
+            }
+        };
+        let mut context = MidiProcessContext { info };
+        let events: Vec<_> = self.events.drain(..due).collect();
+        for (sample, event) in events {
+            match event {
+                ScheduledEvent::Parameter(id, value) => self.apply_parameter(id, value),
+                ScheduledEvent::Note(event) => {
+                    let offset = sample.saturating_sub(self.position) as i32;
+                    let event = event.with_sample_offset(offset);
+                    if P::ACCEPTS_MIDI {
+                        let parameters = self.parameters.parameters_ref();
+                        guarded(&mut || self.plugin.process_midi(&mut context, parameters, event));
+                    }
+                }
+            }
+        }
+
+        let bypassed = self
+            .parameters
+            .bypass_parameter()
+            .is_some_and(|bypass| bypass.value());
+        let parameters = self.parameters.parameters_ref();
+        let plugin = &mut self.plugin;
+        let input = self.input.as_audio_buffer_ref(num_samples);
+        let mut output = self.output.as_audio_buffer(num_samples);
+        self.bypass
+            .process(bypassed, &input, &mut output, info, |context| {
+                let mut context = Some(context);
+                guarded(&mut || plugin.process(context.take().unwrap(), parameters))
+            });
+
+        self.position = block_end;
+        self.advance_transport(num_samples);
+    }
+
+    fn advance_transport(&mut self, num_samples: usize) {
+        if !self.transport.playing {
+            return;
+        }
+        if let Some(position) = self.transport.position_samples.as_mut() {
+            *position += num_samples as i64;
+        }
+        if let Some(position) = self
+            .transport
+            .position_quarters_at(num_samples, self.sample_rate)
+        {
+            self.transport.position_quarters = Some(position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::param::{ByPassParameter, FloatParameter};
+    use crate::{AudioLayout, Bus, ChannelType, GenericEditor, ProcessContext, params};
+
+    params!(
+        struct GainParams {
+            gain: FloatParameter,
+            bypass: ByPassParameter,
+        }
+    );
+
+    impl Params for GainParams {
+        fn new() -> Self {
+            Self {
+                gain: FloatParameter::new(ParameterId(1), "Gain")
+                    .with_linear_range(0.0, 1.0)
+                    .with_default(1.0),
+                bypass: ByPassParameter::new(ParameterId(2)),
+            }
+        }
+    }
+
+    struct Gain {
+        notes: Vec<NoteEvent>,
+    }
+
+    impl Plugin for Gain {
+        const NAME: &'static str = "Gain";
+        const VENDOR: &'static str = "audioplug";
+        const URL: &'static str = "";
+        const EMAIL: &'static str = "";
+        const AUDIO_LAYOUT: AudioLayout = AudioLayout {
+            main_input: Some(Bus {
+                name: "Input",
+                channel: ChannelType::Mono,
+            }),
+            main_output: Some(Bus {
+                name: "Output",
+                channel: ChannelType::Mono,
+            }),
+        };
+        const ACCEPTS_MIDI: bool = true;
+        type Editor = GenericEditor<GainParams>;
+        type Parameters = GainParams;
+
+        fn new(_info: HostInfo) -> Self {
+            Self {
+                notes: Vec::with_capacity(16),
+            }
+        }
+
+        fn prepare(&mut self, _sample_rate: f64, _max_buffer_size: usize) {}
+
+        fn process(&mut self, context: ProcessContext, parameters: &GainParams) {
+            context.output.copy_from(context.input);
+            context.output.apply_gain(parameters.gain.value() as f32);
+        }
+
+        fn process_midi(
+            &mut self,
+            _context: &mut MidiProcessContext,
+            _parameters: &GainParams,
+            event: NoteEvent,
+        ) {
+            self.notes.push(event);
+        }
+    }
+
+    #[test]
+    fn parameter_automation_is_sample_accurate() {
+        let mut host = TestHost::<Gain>::new(48000.0, 16);
+        host.schedule_parameter_plain(10, ParameterId(1), PlainValue::new(0.5));
+        host.schedule_parameter_normalized(
+            20,
+            ParameterId(2),
+            NormalizedValue::from_f64_unchecked(1.0),
+        );
+
+        let output = host.process(&[[1.0; 40]]);
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0][..10], [1.0; 10]);
+        // The bypass is not crossfaded, but switches after the first sample of the block
+        assert_eq!(output[0][10..21], [0.5; 11]);
+        assert_eq!(output[0][21..], [1.0; 19]);
+        assert_eq!(host.position(), 40);
+    }
+
+    #[test]
+    fn note_events_have_block_offsets() {
+        let mut host = TestHost::<Gain>::new(48000.0, 16);
+        host.note_on(20, 0, Note(60));
+        host.note_off(3, 0, Note(60));
+        host.process_silence(32);
+        assert_eq!(
+            host.plugin().notes,
+            [
+                NoteEvent::NoteOff {
+                    channel: 0,
+                    sample_offset: 3,
+                    note: Note(60)
+                },
+                NoteEvent::NoteOn {
+                    channel: 0,
+                    sample_offset: 4,
+                    note: Note(60)
+                }
+            ]
+        );
+    }
+}
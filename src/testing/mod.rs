@@ -0,0 +1,5 @@
+mod golden;
+mod host;
+
+pub use golden::{Comparison, Tolerance, UPDATE_GOLDEN_ENV, assert_golden, compare};
+pub use host::{TEST_HOST_NAME, TestHost};
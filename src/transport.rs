@@ -0,0 +1,91 @@
+/// Time signature, e.g. 6/8 has a numerator of 6 and a denominator of 8
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSignature {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl TimeSignature {
+    /// Length of a bar, in quarter notes
+    pub fn quarters_per_bar(&self) -> f64 {
+        self.numerator as f64 * 4.0 / self.denominator as f64
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self {
+            numerator: 4,
+            denominator: 4,
+        }
+    }
+}
+
+/// Range of the loop (a.k.a. cycle), in quarter notes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+/// State of the host transport, and the musical position at the start of the processed
+/// block. Not all hosts provide all of the information, so most of it is optional.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Transport {
+    pub playing: bool,
+    pub recording: bool,
+    /// Tempo in beats (quarter notes) per minute
+    pub tempo: Option<f64>,
+    pub time_signature: Option<TimeSignature>,
+    /// Position in the project timeline, in samples
+    pub position_samples: Option<i64>,
+    /// Position in the project timeline, in quarter notes (a.k.a. PPQ position)
+    pub position_quarters: Option<f64>,
+    /// Start of the current bar, in quarter notes
+    pub bar_start_quarters: Option<f64>,
+    /// The loop range, if looping is active
+    pub loop_range: Option<LoopRange>,
+}
+
+impl Transport {
+    /// Length of a quarter note, in samples
+    pub fn samples_per_quarter(&self, sample_rate: f64) -> Option<f64> {
+        self.tempo.map(|tempo| 60.0 * sample_rate / tempo)
+    }
+
+    /// Position in quarter notes at `sample_offset` samples into the block, assuming a
+    /// constant tempo within the block
+    pub fn position_quarters_at(&self, sample_offset: usize, sample_rate: f64) -> Option<f64> {
+        let position = self.position_quarters?;
+        let samples_per_quarter = self.samples_per_quarter(sample_rate)?;
+        Some(position + sample_offset as f64 / samples_per_quarter)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn position_is_extrapolated_from_tempo() {
+        let transport = Transport {
+            tempo: Some(120.0),
+            position_quarters: Some(4.0),
+            ..Default::default()
+        };
+        assert_eq!(transport.samples_per_quarter(48000.0), Some(24000.0));
+        assert_eq!(transport.position_quarters_at(12000, 48000.0), Some(4.5));
+        assert_eq!(
+            Transport::default().position_quarters_at(12000, 48000.0),
+            None
+        );
+        assert_eq!(
+            TimeSignature {
+                numerator: 6,
+                denominator: 8
+            }
+            .quarters_per_bar(),
+            3.0
+        );
+    }
+}
@@ -67,7 +67,6 @@ impl AppState {
         self.host_handle = host_handle;
     }
 
-    #[allow(dead_code)]
     pub(crate) fn set_plain_parameter_value_from_host(
         &mut self,
         id: ParameterId,
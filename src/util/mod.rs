@@ -1,3 +1,11 @@
 mod bounded_vec;
+mod realtime;
+mod wav;
 
 pub use bounded_vec::*;
+pub use realtime::{
+    CheckingAllocator, REALTIME_CHECKS, ViolationAction, assert_no_alloc, assert_realtime,
+    deadline_action, permit_alloc, realtime_checks_enabled, set_deadline_action,
+    set_realtime_checks_enabled, set_violation_action, violation_action,
+};
+pub use wav::{SampleFormat, WavError, WavFile, WavReader, WavSpec, WavWriter};
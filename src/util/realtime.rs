@@ -0,0 +1,296 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+/// Whether the real-time checks are compiled in. They are only used in debug builds, with the
+/// `assert_process_allocs` feature, and in the tests of this crate.
+pub const REALTIME_CHECKS: bool = cfg!(all(
+    debug_assertions,
+    any(test, feature = "assert_process_allocs")
+));
+
+static CHECKS_ENABLED: AtomicBool = AtomicBool::new(true);
+static VIOLATION_ACTION: AtomicU8 = AtomicU8::new(ViolationAction::Panic as u8);
+static DEADLINE_ACTION: AtomicU8 = AtomicU8::new(ViolationAction::Log as u8);
+
+thread_local! {
+    /// Number of nested [`assert_no_alloc`] calls on this thread
+    static GUARD_DEPTH: Cell<u32> = const { Cell::new(0) };
+    /// Number of nested [`permit_alloc`] calls on this thread
+    static PERMIT_DEPTH: Cell<u32> = const { Cell::new(0) };
+    /// The first violation in the current guard
+    static VIOLATION: RefCell<Option<Violation>> = const { RefCell::new(None) };
+}
+
+/// What to do when the audio thread allocates or misses its deadline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum ViolationAction {
+    #[default]
+    Panic,
+    /// Log an error, and continue processing
+    Log,
+}
+
+impl ViolationAction {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Panic,
+            _ => Self::Log,
+        }
+    }
+}
+
+pub fn set_violation_action(action: ViolationAction) {
+    VIOLATION_ACTION.store(action as u8, Ordering::Relaxed);
+}
+
+pub fn violation_action() -> ViolationAction {
+    ViolationAction::from_u8(VIOLATION_ACTION.load(Ordering::Relaxed))
+}
+
+/// Set what to do when processing misses its deadline. Debug builds are often too slow to
+/// process in real time, so missed deadlines are only logged by default.
+pub fn set_deadline_action(action: ViolationAction) {
+    DEADLINE_ACTION.store(action as u8, Ordering::Relaxed);
+}
+
+pub fn deadline_action() -> ViolationAction {
+    ViolationAction::from_u8(DEADLINE_ACTION.load(Ordering::Relaxed))
+}
+
+/// Turn the checks off or on at runtime, e.g. for tests that allocate on purpose.
+/// Has no effect if the checks are not compiled in, see [`REALTIME_CHECKS`].
+pub fn set_realtime_checks_enabled(enabled: bool) {
+    CHECKS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn realtime_checks_enabled() -> bool {
+    REALTIME_CHECKS && CHECKS_ENABLED.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ViolationKind {
+    Alloc,
+    Dealloc,
+    Realloc,
+    Deadline {
+        elapsed: Duration,
+        deadline: Duration,
+    },
+}
+
+struct Violation {
+    kind: ViolationKind,
+    size: usize,
+    backtrace: Backtrace,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ViolationKind::Alloc => write!(f, "allocated {} bytes", self.size)?,
+            ViolationKind::Dealloc => write!(f, "freed {} bytes", self.size)?,
+            ViolationKind::Realloc => write!(f, "reallocated to {} bytes", self.size)?,
+            ViolationKind::Deadline { elapsed, deadline } => write!(
+                f,
+                "took {:.3} ms, the block is {:.3} ms long. Is it blocking?",
+                elapsed.as_secs_f64() * 1000.0,
+                deadline.as_secs_f64() * 1000.0
+            )?,
+        }
+        write!(f, "\n{}", self.backtrace)
+    }
+}
+
+/// Global allocator that detects allocations inside [`assert_no_alloc`]. It forwards to
+/// another allocator, the system allocator by default.
+///
+/// A library can't choose the global allocator of the program it ends up in, so the plugin
+/// crate opts in by installing it, e.g. together with the `assert_process_allocs` feature:
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: audioplug::util::CheckingAllocator = audioplug::util::CheckingAllocator::new();
+/// ```
+pub struct CheckingAllocator<A = System> {
+    allocator: A,
+}
+
+impl CheckingAllocator {
+    pub const fn new() -> Self {
+        Self { allocator: System }
+    }
+}
+
+impl Default for CheckingAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A> CheckingAllocator<A> {
+    pub const fn with_allocator(allocator: A) -> Self {
+        Self { allocator }
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CheckingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record_violation(ViolationKind::Alloc, layout.size());
+        unsafe { self.allocator.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record_violation(ViolationKind::Alloc, layout.size());
+        unsafe { self.allocator.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        record_violation(ViolationKind::Dealloc, layout.size());
+        unsafe { self.allocator.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record_violation(ViolationKind::Realloc, new_size);
+        unsafe { self.allocator.realloc(ptr, layout, new_size) }
+    }
+}
+
+/// Called by the allocator. Allocators must not unwind, so the violation is stored, and
+/// reported when the guard ends.
+fn record_violation(kind: ViolationKind, size: usize) {
+    let guarded = GUARD_DEPTH.try_with(Cell::get).unwrap_or(0) > 0
+        && PERMIT_DEPTH.try_with(Cell::get).unwrap_or(1) == 0;
+    if guarded {
+        // Capturing the backtrace allocates
+        permit_alloc(|| {
+            let _ = VIOLATION.try_with(|violation| {
+                if let Ok(mut violation) = violation.try_borrow_mut() {
+                    violation.get_or_insert_with(|| Violation {
+                        kind,
+                        size,
+                        backtrace: Backtrace::force_capture(),
+                    });
+                }
+            });
+        });
+    }
+}
+
+/// Run `f`, and report any allocation or deallocation it does on this thread. Only works
+/// when [`CheckingAllocator`] is the global allocator, and when the checks are enabled, see
+/// [`realtime_checks_enabled`].
+///
+/// Violations make the calling thread panic, or are logged, depending on
+/// [`violation_action`]. The backtrace of the first violation is included.
+pub fn assert_no_alloc<R>(f: impl FnOnce() -> R) -> R {
+    if !realtime_checks_enabled() {
+        return f();
+    }
+    let result = guarded(f);
+    if GUARD_DEPTH.get() == 0 {
+        report_violation("Allocation on the audio thread");
+    }
+    result
+}
+
+/// Run `f`, and report it like [`assert_no_alloc`] if it allocates or takes longer than
+/// `deadline`. This catches blocking calls, like waiting for a lock, which can't be detected
+/// directly. Missed deadlines are reported depending on [`deadline_action`].
+pub fn assert_realtime<R>(deadline: Option<Duration>, f: impl FnOnce() -> R) -> R {
+    if !realtime_checks_enabled() {
+        return f();
+    }
+    let start = Instant::now();
+    let result = guarded(f);
+    let elapsed = start.elapsed();
+    if GUARD_DEPTH.get() == 0 {
+        report_violation("Real-time violation on the audio thread");
+        if let Some(deadline) = deadline.filter(|deadline| elapsed > *deadline) {
+            let violation = Violation {
+                kind: ViolationKind::Deadline { elapsed, deadline },
+                size: 0,
+                backtrace: Backtrace::force_capture(),
+            };
+            report(
+                deadline_action(),
+                "Missed deadline on the audio thread",
+                violation,
+            );
+        }
+    }
+    result
+}
+
+/// Allow allocations in `f`, inside of [`assert_no_alloc`]
+pub fn permit_alloc<R>(f: impl FnOnce() -> R) -> R {
+    let _ = PERMIT_DEPTH.try_with(|depth| depth.set(depth.get() + 1));
+    let result = f();
+    let _ = PERMIT_DEPTH.try_with(|depth| depth.set(depth.get() - 1));
+    result
+}
+
+fn guarded<R>(f: impl FnOnce() -> R) -> R {
+    GUARD_DEPTH.set(GUARD_DEPTH.get() + 1);
+    // Make sure the depth is restored if `f` panics
+    struct Restore;
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            GUARD_DEPTH.set(GUARD_DEPTH.get() - 1);
+        }
+    }
+    let _restore = Restore;
+    f()
+}
+
+fn report_violation(message: &str) {
+    if let Some(violation) = VIOLATION.take() {
+        report(violation_action(), message, violation);
+    }
+}
+
+fn report(action: ViolationAction, message: &str, violation: Violation) {
+    match action {
+        ViolationAction::Panic => panic!("{message}: {violation}"),
+        ViolationAction::Log => log::error!("{message}: {violation}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detects_allocations() {
+        let result = std::panic::catch_unwind(|| assert_no_alloc(|| Box::new(1)));
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.starts_with("Allocation on the audio thread: allocated 4 bytes"));
+
+        let mut vec = Vec::with_capacity(4);
+        assert_no_alloc(|| {
+            vec.push(1);
+            permit_alloc(|| vec![0u8; 16].len());
+        });
+    }
+
+    #[test]
+    fn detects_missed_deadline() {
+        let miss_deadline = || {
+            assert_realtime(Some(Duration::from_millis(1)), || {
+                std::thread::sleep(Duration::from_millis(5))
+            })
+        };
+        // Only logged by default
+        miss_deadline();
+
+        set_deadline_action(ViolationAction::Panic);
+        let result = std::panic::catch_unwind(miss_deadline);
+        set_deadline_action(ViolationAction::Log);
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("Is it blocking?"));
+    }
+}
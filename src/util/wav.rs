@@ -0,0 +1,464 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use thiserror::Error;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Size of the RIFF and fmt chunks written by [`WavWriter`], up to the data chunk size
+const HEADER_SIZE: u64 = 12 + 8 + 18 + 8;
+
+#[derive(Error, Debug)]
+pub enum WavError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Not a RIFF/WAVE file")]
+    NotWave,
+    #[error("Missing {0} chunk")]
+    MissingChunk(&'static str),
+    #[error("Unsupported format: tag {format_tag}, {bits_per_sample} bits per sample")]
+    UnsupportedFormat {
+        format_tag: u16,
+        bits_per_sample: u16,
+    },
+    #[error("Invalid fmt chunk")]
+    InvalidFormat,
+}
+
+/// Sample encodings that can be read and written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleFormat {
+    Int16,
+    Int24,
+    Int32,
+    #[default]
+    Float32,
+}
+
+impl SampleFormat {
+    pub fn bits_per_sample(&self) -> u16 {
+        match self {
+            Self::Int16 => 16,
+            Self::Int24 => 24,
+            Self::Int32 => 32,
+            Self::Float32 => 32,
+        }
+    }
+
+    pub fn bytes_per_sample(&self) -> usize {
+        self.bits_per_sample() as usize / 8
+    }
+
+    fn format_tag(&self) -> u16 {
+        match self {
+            Self::Float32 => WAVE_FORMAT_IEEE_FLOAT,
+            _ => WAVE_FORMAT_PCM,
+        }
+    }
+
+    fn from_tag(format_tag: u16, bits_per_sample: u16) -> Option<Self> {
+        match (format_tag, bits_per_sample) {
+            (WAVE_FORMAT_PCM, 16) => Some(Self::Int16),
+            (WAVE_FORMAT_PCM, 24) => Some(Self::Int24),
+            (WAVE_FORMAT_PCM, 32) => Some(Self::Int32),
+            (WAVE_FORMAT_IEEE_FLOAT, 32) => Some(Self::Float32),
+            _ => None,
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> f32 {
+        match self {
+            Self::Int16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            Self::Int24 => {
+                // Shift into the upper bytes to sign extend
+                let value = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+                value as f32 / 8388608.0
+            }
+            Self::Int32 => {
+                let value = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                (value as f64 / 2147483648.0) as f32
+            }
+            Self::Float32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+
+    fn encode(&self, sample: f32, out: &mut Vec<u8>) {
+        let quantize = |scale: f64| {
+            let value = (sample as f64 * scale).round();
+            value.clamp(-scale, scale - 1.0) as i32
+        };
+        match self {
+            Self::Int16 => out.extend_from_slice(&(quantize(32768.0) as i16).to_le_bytes()),
+            Self::Int24 => out.extend_from_slice(&quantize(8388608.0).to_le_bytes()[..3]),
+            Self::Int32 => out.extend_from_slice(&quantize(2147483648.0).to_le_bytes()),
+            Self::Float32 => out.extend_from_slice(&sample.to_le_bytes()),
+        }
+    }
+}
+
+/// Format of a WAV file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavSpec {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub sample_format: SampleFormat,
+}
+
+impl WavSpec {
+    fn block_align(&self) -> usize {
+        self.channels as usize * self.sample_format.bytes_per_sample()
+    }
+}
+
+/// A WAV file loaded in memory, with the samples of each channel converted to f32 in [-1, 1]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavFile {
+    pub sample_rate: u32,
+    pub sample_format: SampleFormat,
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl WavFile {
+    pub fn new(sample_rate: u32, sample_format: SampleFormat, channels: Vec<Vec<f32>>) -> Self {
+        Self {
+            sample_rate,
+            sample_format,
+            channels,
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, WavError> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read(reader: impl Read) -> Result<Self, WavError> {
+        let mut reader = WavReader::new(reader)?;
+        let spec = reader.spec();
+        let mut channels = vec![Vec::new(); spec.channels as usize];
+        let mut frame = vec![0.0; spec.channels as usize];
+        while reader.read_frame(&mut frame)? {
+            for (channel, sample) in channels.iter_mut().zip(&frame) {
+                channel.push(*sample);
+            }
+        }
+        Ok(Self {
+            sample_rate: spec.sample_rate,
+            sample_format: spec.sample_format,
+            channels,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), WavError> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn write(&self, writer: impl Write + Seek) -> Result<(), WavError> {
+        let mut writer = WavWriter::new(writer, self.spec())?;
+        let channels: Vec<&[f32]> = self.channels.iter().map(|c| c.as_slice()).collect();
+        writer.write(&channels)?;
+        writer.finalize()?;
+        Ok(())
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        WavSpec {
+            channels: self.channels.len() as u16,
+            sample_rate: self.sample_rate,
+            sample_format: self.sample_format,
+        }
+    }
+
+    /// Number of samples per channel
+    pub fn len(&self) -> usize {
+        self.channels.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Reads the samples of a WAV file frame by frame
+pub struct WavReader<R> {
+    reader: R,
+    spec: WavSpec,
+    remaining_bytes: u64,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> WavReader<R> {
+    /// Read the header, up to the start of the sample data
+    pub fn new(mut reader: R) -> Result<Self, WavError> {
+        let mut riff = [0; 12];
+        reader.read_exact(&mut riff)?;
+        if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+            return Err(WavError::NotWave);
+        }
+
+        let mut spec = None;
+        loop {
+            let mut chunk_header = [0; 8];
+            match reader.read_exact(&mut chunk_header) {
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Err(WavError::MissingChunk(if spec.is_none() {
+                        "fmt"
+                    } else {
+                        "data"
+                    }));
+                }
+                result => result?,
+            }
+            let id = &chunk_header[0..4];
+            let size = u32::from_le_bytes(chunk_header[4..8].try_into().unwrap()) as u64;
+
+            if id == b"fmt " {
+                let mut fmt = vec![0; size as usize];
+                reader.read_exact(&mut fmt)?;
+                if size % 2 == 1 {
+                    skip(&mut reader, 1)?;
+                }
+                spec = Some(parse_fmt(&fmt)?);
+            } else if id == b"data" {
+                let spec = spec.ok_or(WavError::MissingChunk("fmt"))?;
+                return Ok(Self {
+                    reader,
+                    spec,
+                    remaining_bytes: size,
+                    buffer: vec![0; spec.block_align()],
+                });
+            } else {
+                // Chunks are padded to an even size
+                skip(&mut reader, size + size % 2)?;
+            }
+        }
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    /// Number of frames left to read
+    pub fn remaining_frames(&self) -> u64 {
+        self.remaining_bytes / self.spec.block_align().max(1) as u64
+    }
+
+    /// Read the next frame, with one sample per channel. Returns false at the end of the data.
+    pub fn read_frame(&mut self, frame: &mut [f32]) -> Result<bool, WavError> {
+        let block_align = self.buffer.len();
+        if block_align == 0 || self.remaining_bytes < block_align as u64 {
+            return Ok(false);
+        }
+        match self.reader.read_exact(&mut self.buffer) {
+            // Accept files where the data size in the header is too large
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                self.remaining_bytes = 0;
+                return Ok(false);
+            }
+            result => result?,
+        }
+        self.remaining_bytes -= block_align as u64;
+
+        let format = self.spec.sample_format;
+        let samples = self.buffer.chunks_exact(format.bytes_per_sample());
+        for (sample, bytes) in frame.iter_mut().zip(samples) {
+            *sample = format.decode(bytes);
+        }
+        Ok(true)
+    }
+}
+
+/// Writes a WAV file incrementally. The sizes in the header are written by
+/// [`WavWriter::finalize`].
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    spec: WavSpec,
+    data_size: u64,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut writer: W, spec: WavSpec) -> Result<Self, WavError> {
+        write_header(&mut writer, &spec, 0)?;
+        Ok(Self {
+            writer,
+            spec,
+            data_size: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    /// Reserve space for encoding blocks of `frames` frames, so that writing them does not
+    /// allocate
+    pub fn reserve(&mut self, frames: usize) {
+        self.buffer.reserve(frames * self.spec.block_align());
+    }
+
+    /// Number of frames written so far
+    pub fn frames_written(&self) -> u64 {
+        self.data_size / self.spec.block_align().max(1) as u64
+    }
+
+    /// Write the samples of each channel. Missing or short channels are padded with silence.
+    pub fn write(&mut self, channels: &[&[f32]]) -> Result<(), WavError> {
+        let num_frames = channels.iter().map(|c| c.len()).max().unwrap_or(0);
+        self.buffer.clear();
+        for i in 0..num_frames {
+            for channel in 0..self.spec.channels as usize {
+                let sample = channels
+                    .get(channel)
+                    .and_then(|c| c.get(i))
+                    .copied()
+                    .unwrap_or(0.0);
+                self.spec.sample_format.encode(sample, &mut self.buffer);
+            }
+        }
+        self.writer.write_all(&self.buffer)?;
+        self.data_size += self.buffer.len() as u64;
+        Ok(())
+    }
+
+    /// Write interleaved samples
+    pub fn write_interleaved(&mut self, samples: &[f32]) -> Result<(), WavError> {
+        self.buffer.clear();
+        for sample in samples {
+            self.spec.sample_format.encode(*sample, &mut self.buffer);
+        }
+        self.writer.write_all(&self.buffer)?;
+        self.data_size += self.buffer.len() as u64;
+        Ok(())
+    }
+
+    /// Update the header and flush, returning the inner writer
+    pub fn finalize(mut self) -> Result<W, WavError> {
+        if self.data_size % 2 == 1 {
+            self.writer.write_all(&[0])?;
+        }
+        self.writer.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.writer, &self.spec, self.data_size)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+fn skip(reader: &mut impl Read, bytes: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.take(bytes), &mut io::sink())?;
+    if skipped < bytes {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn parse_fmt(fmt: &[u8]) -> Result<WavSpec, WavError> {
+    if fmt.len() < 16 {
+        return Err(WavError::InvalidFormat);
+    }
+    let read_u16 = |offset: usize| u16::from_le_bytes([fmt[offset], fmt[offset + 1]]);
+    let mut format_tag = read_u16(0);
+    let channels = read_u16(2);
+    let sample_rate = u32::from_le_bytes(fmt[4..8].try_into().unwrap());
+    let bits_per_sample = read_u16(14);
+    if format_tag == WAVE_FORMAT_EXTENSIBLE {
+        // The format tag is in the first two bytes of the sub format GUID
+        if fmt.len() < 26 {
+            return Err(WavError::InvalidFormat);
+        }
+        format_tag = read_u16(24);
+    }
+    if channels == 0 {
+        return Err(WavError::InvalidFormat);
+    }
+
+    let sample_format =
+        SampleFormat::from_tag(format_tag, bits_per_sample).ok_or(WavError::UnsupportedFormat {
+            format_tag,
+            bits_per_sample,
+        })?;
+    Ok(WavSpec {
+        channels,
+        sample_rate,
+        sample_format,
+    })
+}
+
+fn write_header(writer: &mut impl Write, spec: &WavSpec, data_size: u64) -> io::Result<()> {
+    let padded_size = data_size + data_size % 2;
+    let riff_size = (HEADER_SIZE - 8 + padded_size).min(u32::MAX as u64) as u32;
+    let block_align = spec.block_align() as u16;
+
+    let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&riff_size.to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&18u32.to_le_bytes());
+    header.extend_from_slice(&spec.sample_format.format_tag().to_le_bytes());
+    header.extend_from_slice(&spec.channels.to_le_bytes());
+    header.extend_from_slice(&spec.sample_rate.to_le_bytes());
+    header.extend_from_slice(&(spec.sample_rate * block_align as u32).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&spec.sample_format.bits_per_sample().to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&(data_size.min(u32::MAX as u64) as u32).to_le_bytes());
+    writer.write_all(&header)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn round_trip_all_formats() {
+        let samples = vec![
+            vec![0.0, 0.5, -0.5, 1.0, -1.0, 0.123],
+            vec![0.25, -0.25, 0.0, 0.75, -0.75, 0.0],
+        ];
+        let formats = [
+            (SampleFormat::Int16, 1.0 / 32768.0),
+            (SampleFormat::Int24, 1.0 / 8388608.0),
+            (SampleFormat::Int32, 1e-7),
+            (SampleFormat::Float32, 0.0),
+        ];
+        for (format, tolerance) in formats {
+            let wav = WavFile::new(44100, format, samples.clone());
+            let mut data = Cursor::new(Vec::new());
+            wav.write(&mut data).unwrap();
+            assert_eq!(
+                data.get_ref().len(),
+                HEADER_SIZE as usize + 12 * format.bytes_per_sample()
+            );
+
+            let read = WavFile::read(Cursor::new(data.into_inner())).unwrap();
+            assert_eq!(read.spec(), wav.spec());
+            for (expected, actual) in samples.iter().zip(&read.channels) {
+                for (expected, actual) in expected.iter().zip(actual) {
+                    // Full scale positive values are clipped to the largest integer
+                    assert!((expected - actual).abs() <= tolerance, "{format:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn skips_unknown_chunks() {
+        let wav = WavFile::new(48000, SampleFormat::Int16, vec![vec![0.5, -0.5]]);
+        let mut data = Cursor::new(Vec::new());
+        wav.write(&mut data).unwrap();
+        let mut data = data.into_inner();
+        // Insert an odd sized chunk, which needs a pad byte, before the data chunk
+        let position = HEADER_SIZE as usize - 8;
+        data.splice(position..position, *b"LIST\x03\0\0\0abc\0");
+
+        let read = WavFile::read(Cursor::new(data)).unwrap();
+        assert_eq!(read, wav);
+    }
+}
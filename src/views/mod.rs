@@ -8,6 +8,7 @@ mod key_down;
 mod knob;
 mod label;
 mod menu;
+mod piano;
 mod scroll;
 mod slider;
 mod stateful;
@@ -25,6 +26,7 @@ pub use filled::*;
 pub use image::Image;
 pub use knob::{Knob, KnobWidget, ParameterKnob};
 pub use label::Label;
+pub use piano::{Piano, PianoWidget};
 //pub use scroll::*;
 pub use slider::{ParameterSlider, Slider};
 pub use stateful::Stateful;
@@ -1,30 +1,245 @@
-use crate::app::Widget;
+use crate::{
+    MouseEvent,
+    core::{Color, Point, Rect, Size},
+    event::MouseButton,
+    midi::Note,
+    ui::{
+        BuildContext, CallbackContext, EventContext, EventStatus, MouseEventContext, RenderContext,
+        Scene, StatusChange, View, Widget,
+        style::{AvailableSpace, LayoutMode, Length, Measure, Style},
+    },
+};
 
-use super::View;
+type NoteFn = dyn Fn(&mut CallbackContext, Note);
 
-pub enum PianoKeyEvent {
-    NoteDown,
-    NoteUp,
+/// On-screen keyboard. Clicking a key plays its note until the mouse is released, and
+/// dragging over the keys plays each key in turn.
+pub struct Piano {
+    lowest: Note,
+    highest: Note,
+    on_note_on: Option<Box<NoteFn>>,
+    on_note_off: Option<Box<NoteFn>>,
 }
 
-pub struct Piano {
-    
+impl Piano {
+    /// A keyboard from C3 to C5
+    pub fn new() -> Self {
+        Self {
+            lowest: Note(48),
+            highest: Note(72),
+            on_note_on: None,
+            on_note_off: None,
+        }
+    }
+
+    /// Set the lowest and highest key, which should both be white keys
+    pub fn range(mut self, lowest: Note, highest: Note) -> Self {
+        assert!(lowest <= highest);
+        self.lowest = lowest;
+        self.highest = highest;
+        self
+    }
+
+    pub fn on_note_on(mut self, f: impl Fn(&mut CallbackContext, Note) + 'static) -> Self {
+        self.on_note_on = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_note_off(mut self, f: impl Fn(&mut CallbackContext, Note) + 'static) -> Self {
+        self.on_note_off = Some(Box::new(f));
+        self
+    }
+}
+
+impl Default for Piano {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl View for Piano {
-    type Element = Piano;
+    type Element = PianoWidget;
 
-    fn build(self, ctx: &mut crate::app::BuildContext<Self::Element>) -> Self::Element {
-        todo!()
+    fn build(self, ctx: &mut BuildContext<Self::Element>) -> Self::Element {
+        ctx.set_default_style(Style {
+            size: Size::new(Length::Auto, Length::Px(60.0)),
+            ..Default::default()
+        });
+        PianoWidget {
+            lowest: self.lowest,
+            highest: self.highest,
+            pressed: None,
+            on_note_on: self.on_note_on,
+            on_note_off: self.on_note_off,
+        }
     }
 }
 
-impl Widget for Piano {
+pub struct PianoWidget {
+    lowest: Note,
+    highest: Note,
+    /// The key held down with the mouse
+    pressed: Option<Note>,
+    on_note_on: Option<Box<NoteFn>>,
+    on_note_off: Option<Box<NoteFn>>,
+}
+
+fn is_black_key(note: Note) -> bool {
+    matches!(note.0 % 12, 1 | 3 | 6 | 8 | 10)
+}
+
+impl PianoWidget {
+    fn notes(&self) -> impl Iterator<Item = Note> + use<> {
+        (self.lowest.0..=self.highest.0).map(Note)
+    }
+
+    /// Number of white keys below `note`
+    fn white_keys_below(&self, note: Note) -> usize {
+        (self.lowest.0..note.0)
+            .filter(|&note| !is_black_key(Note(note)))
+            .count()
+    }
+
+    fn white_key_width(&self, bounds: Rect) -> f64 {
+        let white_keys = self.white_keys_below(self.highest) + 1;
+        bounds.width() / white_keys as f64
+    }
+
+    fn key_rect(&self, note: Note, bounds: Rect) -> Rect {
+        let width = self.white_key_width(bounds);
+        let left = bounds.left + self.white_keys_below(note) as f64 * width;
+        if is_black_key(note) {
+            // Black keys sit on the boundary between two white keys
+            let black_width = width * 0.6;
+            Rect::from_xywh(
+                left - black_width / 2.0,
+                bounds.top,
+                black_width,
+                bounds.height() * 0.6,
+            )
+        } else {
+            Rect::from_xywh(left, bounds.top, width, bounds.height())
+        }
+    }
+
+    fn note_at(&self, position: Point, bounds: Rect) -> Option<Note> {
+        if !bounds.contains(position) {
+            return None;
+        }
+        let hit = |note: &Note| self.key_rect(*note, bounds).contains(position);
+        // Black keys are on top of the white keys
+        self.notes()
+            .filter(|&note| is_black_key(note))
+            .find(hit)
+            .or_else(|| self.notes().filter(|&note| !is_black_key(note)).find(hit))
+    }
+
+    fn set_pressed(&mut self, note: Option<Note>, cx: &mut CallbackContext) -> bool {
+        if note == self.pressed {
+            return false;
+        }
+        if let Some(previous) = self.pressed.take()
+            && let Some(f) = self.on_note_off.as_ref()
+        {
+            f(cx, previous);
+        }
+        if let Some(note) = note
+            && let Some(f) = self.on_note_on.as_ref()
+        {
+            f(cx, note);
+        }
+        self.pressed = note;
+        true
+    }
+}
+
+impl Measure for PianoWidget {
+    fn measure(&self, _style: &Style, width: AvailableSpace, height: AvailableSpace) -> Size {
+        let white_keys = self.white_keys_below(self.highest) + 1;
+        Size::new(
+            width.unwrap_or(white_keys as f64 * 16.0),
+            height.unwrap_or(60.0),
+        )
+    }
+}
+
+impl Widget for PianoWidget {
     fn debug_label(&self) -> &'static str {
         "Piano"
     }
 
-    fn render(&mut self, ctx: &mut crate::app::RenderContext) {
-        todo!()
+    fn mouse_event(&mut self, event: MouseEvent, ctx: &mut MouseEventContext) -> EventStatus {
+        match event {
+            MouseEvent::Down {
+                button: MouseButton::LEFT,
+                position,
+                ..
+            } => {
+                let note = self.note_at(position, ctx.bounds());
+                if self.set_pressed(note, &mut ctx.as_callback_context()) {
+                    ctx.request_render();
+                }
+                ctx.capture_mouse();
+                EventStatus::Handled
+            }
+            MouseEvent::Moved { position, .. } if self.pressed.is_some() => {
+                // Keep the last key down when dragging outside of the keyboard
+                if let Some(note) = self.note_at(position, ctx.bounds())
+                    && self.set_pressed(Some(note), &mut ctx.as_callback_context())
+                {
+                    ctx.request_render();
+                }
+                EventStatus::Handled
+            }
+            MouseEvent::Up {
+                button: MouseButton::LEFT,
+                ..
+            } => {
+                if self.set_pressed(None, &mut ctx.as_callback_context()) {
+                    ctx.request_render();
+                }
+                ctx.release_capture();
+                EventStatus::Handled
+            }
+            _ => EventStatus::Ignored,
+        }
     }
-}
\ No newline at end of file
+
+    fn status_change(&mut self, event: StatusChange, ctx: &mut EventContext) {
+        // Don't leave a note hanging
+        if event == StatusChange::MouseCaptureLost
+            && self.set_pressed(None, &mut ctx.as_callback_context())
+        {
+            ctx.request_render();
+        }
+    }
+
+    fn render(&mut self, ctx: &mut RenderContext) -> Scene {
+        let mut scene = Scene::new();
+        let bounds = ctx.content_bounds();
+        let pressed_color = Color::from_rgb8(101, 133, 121);
+        scene.fill(bounds, Color::from_rgb8(0x30, 0x30, 0x30));
+        for note in self.notes().filter(|&note| !is_black_key(note)) {
+            let color = if self.pressed == Some(note) {
+                pressed_color
+            } else {
+                Color::from_rgb8(0xF0, 0xF0, 0xF0)
+            };
+            // Leave a gap between the keys
+            scene.fill(self.key_rect(note, bounds).shrink_x(0.5), color);
+        }
+        for note in self.notes().filter(|&note| is_black_key(note)) {
+            let color = if self.pressed == Some(note) {
+                pressed_color
+            } else {
+                Color::from_rgb8(0x20, 0x20, 0x20)
+            };
+            scene.fill(self.key_rect(note, bounds), color);
+        }
+        scene
+    }
+
+    fn layout_mode(&self) -> LayoutMode<'_> {
+        LayoutMode::Leaf(self)
+    }
+}
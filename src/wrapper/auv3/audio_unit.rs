@@ -10,7 +10,7 @@ use objc2::{AllocAnyThread, extern_class, extern_methods, msg_send, rc::Retained
 use objc2::{ClassType, Encoding, RefEncode, sel};
 use objc2_audio_toolbox::{
     AUAudioFrameCount, AUAudioUnit, AUAudioUnitBusArray, AUAudioUnitBusType, AUAudioUnitStatus,
-    AUParameterTree, AURenderEventType, AURenderPullInputBlock, AudioComponentDescription,
+    AUHostTransportStateFlags, AUParameterTree, AURenderEventType, AURenderPullInputBlock, AudioComponentDescription,
     AudioComponentInstantiationOptions, AudioUnitRenderActionFlags,
 };
 use objc2_avf_audio::AVAudioFormat;
@@ -24,7 +24,9 @@ use uuid::Uuid;
 use super::buffers::create_buffers;
 use super::{buffers::BusBuffer, render_event::AURenderEvent, utils::create_parameter_tree};
 use crate::param::{AnyParameterMap, ParameterId, ParameterMap, Params, PlainValue};
-use crate::{AudioBuffer, Plugin, ProcessContext, ProcessInfo};
+use crate::{
+    AudioBuffer, LoopRange, Plugin, ProcessContext, ProcessInfo, TimeSignature, Transport,
+};
 
 const DEFAULT_SAMPLE_RATE: f64 = 44100.0;
 
@@ -52,6 +54,12 @@ pub type AUInternalRenderRcBlock = RcBlock<
     ) -> AUAudioUnitStatus,
 >;
 
+type MusicalContextRcBlock = RcBlock<
+    dyn Fn(*mut f64, *mut NSInteger, *mut NSInteger, *mut f64, *mut NSInteger, *mut f64) -> Bool,
+>;
+type TransportStateRcBlock =
+    RcBlock<dyn Fn(*mut AUHostTransportStateFlags, *mut f64, *mut f64, *mut f64) -> Bool>;
+
 extern_class!(
     #[unsafe(super(NSObject))]
     pub struct AUAudioUnitViewConfiguration;
@@ -75,6 +83,8 @@ struct Inner<P: Plugin> {
     rendering_offline: bool,
     sample_rate: f64,
     last_sample_time: f64,
+    musical_context_block: Option<MusicalContextRcBlock>,
+    transport_state_block: Option<TransportStateRcBlock>,
 }
 
 impl<P: Plugin> Inner<P> {
@@ -92,9 +102,69 @@ impl<P: Plugin> Inner<P> {
             rendering_offline: false,
             sample_rate: DEFAULT_SAMPLE_RATE,
             last_sample_time: f64::MAX,
+            musical_context_block: None,
+            transport_state_block: None,
         }
     }
 
+    /// Query the host for the transport state, through the blocks provided by the host
+    fn transport(&self) -> Transport {
+        let mut transport = Transport::default();
+
+        if let Some(block) = &self.musical_context_block {
+            let mut tempo = 0.0;
+            let mut numerator: NSInteger = 0;
+            let mut denominator: NSInteger = 0;
+            let mut beat_position = 0.0;
+            let mut samples_to_next_beat: NSInteger = 0;
+            let mut measure_downbeat_position = 0.0;
+            let valid = block.call((
+                &raw mut tempo,
+                &raw mut numerator,
+                &raw mut denominator,
+                &raw mut beat_position,
+                &raw mut samples_to_next_beat,
+                &raw mut measure_downbeat_position,
+            ));
+            if valid.as_bool() {
+                transport.tempo = Some(tempo);
+                transport.time_signature = Some(TimeSignature {
+                    numerator: numerator as _,
+                    denominator: denominator as _,
+                });
+                transport.position_quarters = Some(beat_position);
+                transport.bar_start_quarters = Some(measure_downbeat_position);
+            }
+        }
+
+        if let Some(block) = &self.transport_state_block {
+            let mut flags = AUHostTransportStateFlags(0);
+            let mut sample_position = 0.0;
+            let mut cycle_start = 0.0;
+            let mut cycle_end = 0.0;
+            let valid = block.call((
+                &raw mut flags,
+                &raw mut sample_position,
+                &raw mut cycle_start,
+                &raw mut cycle_end,
+            ));
+            if valid.as_bool() {
+                let has_flag = |flag: AUHostTransportStateFlags| flags.0 & flag.0 != 0;
+                transport.playing = has_flag(AUHostTransportStateFlags::Moving);
+                transport.recording = has_flag(AUHostTransportStateFlags::Recording);
+                transport.position_samples = Some(sample_position as i64);
+                transport.loop_range = has_flag(AUHostTransportStateFlags::Cycling).then_some(
+                    LoopRange {
+                        start: cycle_start,
+                        end: cycle_end,
+                    },
+                );
+            }
+        }
+
+        transport
+    }
+
     fn process_events(&mut self, realtime_event_list_head: *const AURenderEvent) {
         let mut event_list = realtime_event_list_head;
         while !event_list.is_null() {
@@ -143,7 +213,8 @@ impl<P: Plugin> Inner<P> {
 
             let info = ProcessInfo {
                 rendering_offline: self.rendering_offline,
-                sample_rate: DEFAULT_SAMPLE_RATE,
+                sample_rate: self.sample_rate,
+                transport: self.transport(),
             };
 
             let context = ProcessContext {
@@ -158,7 +229,17 @@ impl<P: Plugin> Inner<P> {
         0
     }
 
-    fn allocate_render_resources(&mut self, max_frames_to_render: usize) {
+    fn allocate_render_resources(
+        &mut self,
+        max_frames_to_render: usize,
+        musical_context_block: Option<MusicalContextRcBlock>,
+        transport_state_block: Option<TransportStateRcBlock>,
+    ) {
+        // The host blocks are cached here, as we should not message Objective-C objects
+        // from the render thread
+        self.musical_context_block = musical_context_block;
+        self.transport_state_block = transport_state_block;
+
         self.sample_rate = self
             .input_buffer
             .sample_rate()
@@ -172,6 +253,8 @@ impl<P: Plugin> Inner<P> {
     }
 
     fn deallocate_render_resources(&mut self) {
+        self.musical_context_block = None;
+        self.transport_state_block = None;
         self.input_buffer.deallocate();
         self.output_buffer.deallocate();
     }
@@ -432,10 +515,16 @@ impl<P: Plugin> MyAudioUnit<P> {
 
     unsafe extern "C-unwind" fn allocate_render_resources_and_return_error(this: &AUAudioUnit, _cmd: Sel, error: *mut *mut NSError) -> Bool {
         let max_frames = unsafe { this.maximumFramesToRender() };
+        let musical_context_block = unsafe { RcBlock::copy(this.musicalContextBlock()) };
+        let transport_state_block = unsafe { RcBlock::copy(this.transportStateBlock()) };
         unsafe { Self::get_self(this) }
             .inner
             .borrow_mut()
-            .allocate_render_resources(max_frames as _);
+            .allocate_render_resources(
+                max_frames as _,
+                musical_context_block,
+                transport_state_block,
+            );
         unsafe { msg_send![super(this, AUAudioUnit::class()), allocateRenderResourcesAndReturnError: error] }
     }
 
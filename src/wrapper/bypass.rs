@@ -0,0 +1,259 @@
+use crate::dsp::DspFloat;
+use crate::util::permit_alloc;
+use crate::{AudioBuffer, AudioBufferRef, OwnedAudioBuffer, Plugin, ProcessContext, ProcessInfo};
+
+/// Bypass handling shared by the wrappers. The input is run through a delay matching the
+/// latency of the plugin, so the dry signal lines up with the processed signal, and the
+/// output is crossfaded between the two when the bypass state changes.
+pub(crate) struct Bypass<T> {
+    /// Delayed input for the current block
+    dry: OwnedAudioBuffer<T>,
+    /// Ring buffers holding the last `latency` samples of each input channel
+    delay: OwnedAudioBuffer<T>,
+    /// Copy of the input for hosts that process in place, so that the plugin never sees
+    /// input and output buffers that share samples
+    in_place_input: OwnedAudioBuffer<T>,
+    write_position: usize,
+    latency: usize,
+    num_samples: usize,
+    /// Amount of dry signal in the output, 0 when processing and 1 when bypassed
+    dry_gain: T,
+    target_gain: T,
+    step: T,
+}
+
+impl<T: DspFloat> Bypass<T> {
+    pub fn new() -> Self {
+        Self {
+            dry: OwnedAudioBuffer::new(),
+            delay: OwnedAudioBuffer::new(),
+            in_place_input: OwnedAudioBuffer::new(),
+            write_position: 0,
+            latency: 0,
+            num_samples: 0,
+            dry_gain: T::zero(),
+            target_gain: T::zero(),
+            step: T::one(),
+        }
+    }
+
+    /// Allocate the buffers, and clear the delayed signal. Must be called again when the
+    /// latency changes.
+    pub fn prepare(
+        &mut self,
+        num_channels: usize,
+        max_samples: usize,
+        latency: usize,
+        crossfade_samples: usize,
+    ) {
+        self.dry.prepare(num_channels, max_samples);
+        self.delay.prepare(num_channels, latency);
+        self.write_position = 0;
+        self.latency = latency;
+        self.step = T::one() / T::from_f64(crossfade_samples.max(1) as f64);
+    }
+
+    /// Prepare for processing the main output bus of `plugin`. Should be called after the
+    /// plugin has been prepared, so that it reports the latency for the new settings.
+    pub fn prepare_for_plugin<P: Plugin>(
+        &mut self,
+        plugin: &P,
+        sample_rate: f64,
+        max_samples: usize,
+    ) {
+        let num_channels = P::AUDIO_LAYOUT
+            .main_output
+            .map_or(0, |bus| bus.channel.size() as usize);
+        let num_input_channels = P::AUDIO_LAYOUT
+            .main_input
+            .map_or(0, |bus| bus.channel.size() as usize);
+        self.in_place_input.prepare(num_input_channels, max_samples);
+        let crossfade_samples = (P::BYPASS_CROSSFADE.as_secs_f64() * sample_rate).round() as usize;
+        self.prepare(
+            num_channels,
+            max_samples,
+            plugin.latency_samples(),
+            crossfade_samples,
+        );
+    }
+
+    /// Process a block, calling `process` to process the plugin unless it is completely
+    /// bypassed
+    pub fn process(
+        &mut self,
+        bypassed: bool,
+        input: &AudioBuffer<T>,
+        output: &mut AudioBuffer<T>,
+        info: ProcessInfo,
+        process: impl FnOnce(ProcessContext<T>),
+    ) {
+        // The input has to be read before processing, since it is overwritten when the
+        // host processes in place
+        if self.process_input(bypassed, input, output.samples()) {
+            let input = self.separate_input(input, output);
+            let mut output = output.sub_block_mut(..);
+            process(ProcessContext {
+                input: &input,
+                output: &mut output,
+                info,
+            });
+        }
+        self.process_output(output);
+    }
+
+    /// The input, or a copy of it if it shares samples with the output
+    fn separate_input<'a>(
+        &'a mut self,
+        input: &'a AudioBuffer<T>,
+        output: &AudioBuffer<T>,
+    ) -> AudioBufferRef<'a, T> {
+        if !input.shares_samples_with(output) {
+            return input.sub_block(..);
+        }
+
+        let num_samples = input.samples();
+        if input.channels() != self.in_place_input.channels()
+            || num_samples > self.in_place_input.capacity()
+        {
+            // Only if the host passes other channels or longer blocks than it prepared for
+            let capacity = num_samples.max(self.in_place_input.capacity());
+            permit_alloc(|| self.in_place_input.prepare(input.channels(), capacity));
+        }
+        for (channel, samples) in input.channels_iter().enumerate() {
+            self.in_place_input.channel_mut(channel)[..num_samples]
+                .copy_from_slice(samples.as_slice());
+        }
+        self.in_place_input.as_audio_buffer_ref(num_samples)
+    }
+
+    /// Returns true if the plugin needs to be processed, i.e. unless it is completely bypassed.
+    fn process_input(
+        &mut self,
+        bypassed: bool,
+        input: &AudioBuffer<T>,
+        num_samples: usize,
+    ) -> bool {
+        let num_samples = num_samples.min(self.dry.capacity());
+        self.num_samples = num_samples;
+        for channel in 0..self.dry.channels() {
+            let input = (channel < input.channels()).then(|| input.channel(channel));
+            let input = input.as_ref().map(|input| input.as_slice());
+            let dry = &mut self.dry.channel_mut(channel)[..num_samples];
+            match input {
+                Some(input) => dry.copy_from_slice(&input[..num_samples]),
+                None => dry.fill(T::zero()),
+            }
+            if self.latency > 0 {
+                let delay = self.delay.channel_mut(channel);
+                let mut position = self.write_position;
+                for sample in dry.iter_mut() {
+                    std::mem::swap(sample, &mut delay[position]);
+                    position = (position + 1) % self.latency;
+                }
+            }
+        }
+        if self.latency > 0 {
+            self.write_position = (self.write_position + num_samples) % self.latency;
+        }
+
+        self.target_gain = if bypassed { T::one() } else { T::zero() };
+        !(bypassed && self.dry_gain == T::one())
+    }
+
+    /// Mix the delayed dry signal into the output of the plugin
+    fn process_output(&mut self, output: &mut AudioBuffer<T>) {
+        if self.dry_gain == T::zero() && self.target_gain == T::zero() {
+            return;
+        }
+
+        let num_samples = self.num_samples.min(output.samples());
+        let mut end_gain = self.target_gain;
+        for (channel, mut output) in output.channels_iter_mut().enumerate() {
+            let dry =
+                (channel < self.dry.channels()).then(|| &self.dry.channel(channel)[..num_samples]);
+            let mut gain = self.dry_gain;
+            for (i, sample) in output.as_mut_slice()[..num_samples].iter_mut().enumerate() {
+                let dry = dry.map_or(T::zero(), |dry| dry[i]);
+                // The output is not written by the plugin when it is completely bypassed
+                *sample = if gain == T::one() {
+                    dry
+                } else {
+                    *sample + (dry - *sample) * gain
+                };
+                gain = if self.target_gain > gain {
+                    (gain + self.step).min(self.target_gain)
+                } else {
+                    (gain - self.step).max(self.target_gain)
+                };
+            }
+            end_gain = gain;
+        }
+        self.dry_gain = end_gain;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Transport;
+
+    fn process_block(bypass: &mut Bypass<f32>, bypassed: bool, samples: &mut [f32; 4]) -> bool {
+        let mut input_samples = *samples;
+        let mut input_channels = [&mut input_samples];
+        let input = AudioBuffer::from_slice(&mut input_channels);
+        let mut output_channels = [samples];
+        let mut output = AudioBuffer::from_slice(&mut output_channels);
+        let info = ProcessInfo {
+            rendering_offline: false,
+            sample_rate: 4.0,
+            transport: Transport::default(),
+        };
+        let mut processed = false;
+        bypass.process(bypassed, &input, &mut output, info, |context| {
+            context.output.apply_gain(0.0);
+            processed = true;
+        });
+        processed
+    }
+
+    #[test]
+    fn in_place_input_is_copied() {
+        let mut bypass = Bypass::new();
+        bypass.prepare(1, 4, 0, 4);
+        let mut samples = [1.0f32, 2.0, 3.0, 4.0];
+        let mut channels = [samples.as_mut_ptr()];
+        // The same samples for the input and the output, like a host that processes in place
+        let input = unsafe { AudioBuffer::from_ptr(channels.as_mut_ptr(), 1, 4) };
+        let mut output = unsafe { AudioBuffer::from_ptr(channels.as_mut_ptr(), 1, 4) };
+        let info = ProcessInfo {
+            rendering_offline: false,
+            sample_rate: 4.0,
+            transport: Transport::default(),
+        };
+        bypass.process(false, &input, &mut output, info, |context| {
+            assert!(!context.input.shares_samples_with(context.output));
+            context.output.clear();
+            context.output.add_from(context.input);
+            context.output.apply_gain(2.0);
+        });
+        assert_eq!(output.channel(0).as_slice(), [2.0, 4.0, 6.0, 8.0]);
+    }
+
+    #[test]
+    fn dry_signal_is_delayed_and_crossfaded() {
+        let mut bypass = Bypass::new();
+        bypass.prepare(1, 4, 2, 4);
+
+        let mut samples = [1.0, 2.0, 3.0, 4.0];
+        assert!(process_block(&mut bypass, false, &mut samples));
+        assert_eq!(samples, [0.0; 4]);
+
+        let mut samples = [5.0, 6.0, 7.0, 8.0];
+        assert!(process_block(&mut bypass, true, &mut samples));
+        assert_eq!(samples, [0.0, 0.25 * 4.0, 0.5 * 5.0, 0.75 * 6.0]);
+
+        let mut samples = [9.0, 10.0, 11.0, 12.0];
+        assert!(!process_block(&mut bypass, true, &mut samples));
+        assert_eq!(samples, [7.0, 8.0, 9.0, 10.0]);
+    }
+}
@@ -0,0 +1,69 @@
+use std::marker::PhantomData;
+
+use clap_sys::{
+    ext::audio_ports::{
+        CLAP_AUDIO_PORT_IS_MAIN, CLAP_AUDIO_PORT_REQUIRES_COMMON_SAMPLE_SIZE,
+        CLAP_AUDIO_PORT_SUPPORTS_64BITS, CLAP_PORT_MONO, CLAP_PORT_STEREO, clap_audio_port_info,
+        clap_plugin_audio_ports,
+    },
+    id::CLAP_INVALID_ID,
+    plugin::clap_plugin,
+};
+
+use super::util::strcpy;
+use crate::{Bus, ChannelType, ClapPlugin};
+
+/// Implementation of the audio-ports extension. The ports are static, and described by
+/// [`crate::Plugin::AUDIO_LAYOUT`].
+pub struct AudioPorts<P: ClapPlugin>(PhantomData<P>);
+
+impl<P: ClapPlugin> AudioPorts<P> {
+    pub const VTABLE: &'static clap_plugin_audio_ports = &clap_plugin_audio_ports {
+        count: Some(Self::count),
+        get: Some(Self::get),
+    };
+
+    fn bus(is_input: bool) -> Option<Bus> {
+        if is_input {
+            P::AUDIO_LAYOUT.main_input
+        } else {
+            P::AUDIO_LAYOUT.main_output
+        }
+    }
+
+    unsafe extern "C" fn count(_plugin: *const clap_plugin, is_input: bool) -> u32 {
+        Self::bus(is_input).is_some() as u32
+    }
+
+    unsafe extern "C" fn get(
+        _plugin: *const clap_plugin,
+        index: u32,
+        is_input: bool,
+        info: *mut clap_audio_port_info,
+    ) -> bool {
+        let Some(info) = (unsafe { info.as_mut() }) else {
+            return false;
+        };
+        let Some(bus) = Self::bus(is_input).filter(|_| index == 0) else {
+            return false;
+        };
+
+        info.id = 0;
+        strcpy(bus.name, &mut info.name);
+        info.flags = CLAP_AUDIO_PORT_IS_MAIN;
+        if P::SUPPORTS_F64 {
+            // Input and output must use the same precision, as they are passed to the
+            // plugin in the same process call
+            info.flags |=
+                CLAP_AUDIO_PORT_SUPPORTS_64BITS | CLAP_AUDIO_PORT_REQUIRES_COMMON_SAMPLE_SIZE;
+        }
+        info.channel_count = bus.channel.size() as u32;
+        info.port_type = match bus.channel {
+            ChannelType::Empty => std::ptr::null(),
+            ChannelType::Mono => CLAP_PORT_MONO.as_ptr(),
+            ChannelType::Stereo => CLAP_PORT_STEREO.as_ptr(),
+        };
+        info.in_place_pair = CLAP_INVALID_ID;
+        true
+    }
+}
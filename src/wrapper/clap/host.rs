@@ -1,5 +1,7 @@
 use std::ffi::{CStr, c_void};
 
+use clap_sys::ext::latency::{CLAP_EXT_LATENCY, clap_host_latency};
+use clap_sys::ext::params::{CLAP_EXT_PARAMS, CLAP_PARAM_RESCAN_VALUES, clap_host_params};
 use clap_sys::host::clap_host;
 
 pub struct ClapHost {
@@ -28,7 +30,34 @@ impl ClapHost {
     }
 
     pub fn get_extension(&self, extension_id: &CStr) -> *const c_void {
-        todo!()
-        //unsafe { (*(self.host)).get_extension}
+        match unsafe { (*self.host).get_extension } {
+            Some(get_extension) => unsafe { get_extension(self.host, extension_id.as_ptr()) },
+            None => std::ptr::null(),
+        }
+    }
+
+    /// Ask the host to deactivate and reactivate the plugin. Can be called from any thread.
+    pub fn request_restart(&self) {
+        if let Some(request_restart) = unsafe { (*self.host).request_restart } {
+            unsafe { request_restart(self.host) };
+        }
+    }
+
+    /// Tell the host that the latency has changed. Must be called from the main thread, while
+    /// the plugin is being activated.
+    pub fn latency_changed(&self) {
+        let latency = self.get_extension(CLAP_EXT_LATENCY) as *const clap_host_latency;
+        if let Some(changed) = unsafe { latency.as_ref() }.and_then(|latency| latency.changed) {
+            unsafe { changed(self.host) };
+        }
+    }
+
+    /// Tell the host that the values of the parameters have changed, e.g. when a state has
+    /// been loaded. Must be called from the main thread.
+    pub fn parameter_values_changed(&self) {
+        let params = self.get_extension(CLAP_EXT_PARAMS) as *const clap_host_params;
+        if let Some(rescan) = unsafe { params.as_ref() }.and_then(|params| params.rescan) {
+            unsafe { rescan(self.host, CLAP_PARAM_RESCAN_VALUES) };
+        }
     }
 }
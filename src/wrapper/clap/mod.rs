@@ -1,7 +1,10 @@
+mod audio_ports;
 mod factory;
 mod features;
 mod host;
+mod params;
 mod plugin;
+mod util;
 
 pub use clap_sys::{entry::clap_plugin_entry, version::CLAP_VERSION};
 pub use factory::Factory;
@@ -0,0 +1,186 @@
+use std::ffi::{CStr, c_char};
+use std::marker::PhantomData;
+
+use clap_sys::{
+    events::{
+        CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_PARAM_VALUE, clap_event_param_value,
+        clap_input_events, clap_output_events,
+    },
+    ext::params::{
+        CLAP_PARAM_IS_AUTOMATABLE, CLAP_PARAM_IS_BYPASS, CLAP_PARAM_IS_ENUM, CLAP_PARAM_IS_STEPPED,
+        clap_param_info, clap_plugin_params,
+    },
+    id::clap_id,
+    plugin::clap_plugin,
+};
+
+use super::plugin::PluginInstance;
+use super::util::strcpy;
+use crate::ClapPlugin;
+use crate::param::{AnyParameterMap, ParamRef, ParameterId, PlainValue};
+
+/// Implementation of the params extension. CLAP hosts use plain parameter values.
+pub struct Params<P: ClapPlugin>(PhantomData<P>);
+
+impl<P: ClapPlugin> Params<P> {
+    pub const VTABLE: &'static clap_plugin_params = &clap_plugin_params {
+        count: Some(Self::count),
+        get_info: Some(Self::get_info),
+        get_value: Some(Self::get_value),
+        value_to_text: Some(Self::value_to_text),
+        text_to_value: Some(Self::text_to_value),
+        flush: Some(Self::flush),
+    };
+
+    unsafe fn parameters<'a>(plugin: *const clap_plugin) -> Option<&'a dyn AnyParameterMap> {
+        unsafe { PluginInstance::<P>::use_self(plugin) }.map(|this| this.parameters.as_ref() as _)
+    }
+
+    unsafe extern "C" fn count(plugin: *const clap_plugin) -> u32 {
+        unsafe { Self::parameters(plugin) }.map_or(0, |parameters| parameters.count() as u32)
+    }
+
+    unsafe extern "C" fn get_info(
+        plugin: *const clap_plugin,
+        param_index: u32,
+        param_info: *mut clap_param_info,
+    ) -> bool {
+        let Some(parameters) = (unsafe { Self::parameters(plugin) }) else {
+            return false;
+        };
+        let Some(info) = (unsafe { param_info.as_mut() }) else {
+            return false;
+        };
+        let Some((_, param_ref)) = parameters.get_by_index(param_index as usize) else {
+            return false;
+        };
+
+        info.id = param_ref.id().into();
+        info.flags = CLAP_PARAM_IS_AUTOMATABLE
+            | match param_ref {
+                ParamRef::ByPass(_) => CLAP_PARAM_IS_STEPPED | CLAP_PARAM_IS_BYPASS,
+                ParamRef::Int(_) | ParamRef::Bool(_) => CLAP_PARAM_IS_STEPPED,
+                ParamRef::StringList(_) => CLAP_PARAM_IS_STEPPED | CLAP_PARAM_IS_ENUM,
+                ParamRef::Float(_) => 0,
+            };
+        info.cookie = std::ptr::null_mut();
+        strcpy(param_ref.name(), &mut info.name);
+        strcpy("", &mut info.module);
+        info.min_value = param_ref.info().min_value().into();
+        info.max_value = param_ref.info().max_value().into();
+        info.default_value = param_ref.info().default_value_plain().into();
+        true
+    }
+
+    unsafe extern "C" fn get_value(
+        plugin: *const clap_plugin,
+        param_id: clap_id,
+        out_value: *mut f64,
+    ) -> bool {
+        let Some(parameters) = (unsafe { Self::parameters(plugin) }) else {
+            return false;
+        };
+        let Some(out_value) = (unsafe { out_value.as_mut() }) else {
+            return false;
+        };
+        let Some(param_ref) = parameters.get_by_id(ParameterId(param_id)) else {
+            return false;
+        };
+        *out_value = param_ref.plain_value().into();
+        true
+    }
+
+    unsafe extern "C" fn value_to_text(
+        plugin: *const clap_plugin,
+        param_id: clap_id,
+        value: f64,
+        out_buffer: *mut c_char,
+        out_buffer_capacity: u32,
+    ) -> bool {
+        let Some(parameters) = (unsafe { Self::parameters(plugin) }) else {
+            return false;
+        };
+        let Some(param_ref) = parameters.get_by_id(ParameterId(param_id)) else {
+            return false;
+        };
+        if out_buffer.is_null() || out_buffer_capacity == 0 {
+            return false;
+        }
+
+        let info = param_ref.info();
+        let text = info.string_from_value(info.normalize(PlainValue::new(value)));
+        let out_buffer =
+            unsafe { std::slice::from_raw_parts_mut(out_buffer, out_buffer_capacity as usize) };
+        strcpy(&text, out_buffer);
+        true
+    }
+
+    unsafe extern "C" fn text_to_value(
+        plugin: *const clap_plugin,
+        param_id: clap_id,
+        param_value_text: *const c_char,
+        out_value: *mut f64,
+    ) -> bool {
+        let Some(parameters) = (unsafe { Self::parameters(plugin) }) else {
+            return false;
+        };
+        let Some(param_ref) = parameters.get_by_id(ParameterId(param_id)) else {
+            return false;
+        };
+        let Some(out_value) = (unsafe { out_value.as_mut() }) else {
+            return false;
+        };
+        if param_value_text.is_null() {
+            return false;
+        }
+        let Ok(text) = unsafe { CStr::from_ptr(param_value_text) }.to_str() else {
+            return false;
+        };
+        let Ok(value) = param_ref.info().value_from_string(text) else {
+            return false;
+        };
+        *out_value = param_ref.info().denormalize(value).into();
+        true
+    }
+
+    unsafe extern "C" fn flush(
+        plugin: *const clap_plugin,
+        in_: *const clap_input_events,
+        _out: *const clap_output_events,
+    ) {
+        if let Some(parameters) = unsafe { Self::parameters(plugin) } {
+            unsafe { apply_parameter_events(parameters, in_) };
+        }
+    }
+}
+
+/// Set the parameters changed by the param value events in `events`. Only the last value of
+/// each parameter in the block is used.
+///
+/// # Safety
+///
+/// `events` must be null or point to a valid event list
+pub unsafe fn apply_parameter_events(
+    parameters: &dyn AnyParameterMap,
+    events: *const clap_input_events,
+) {
+    let Some(events) = (unsafe { events.as_ref() }) else {
+        return;
+    };
+    let (Some(size), Some(get)) = (events.size, events.get) else {
+        return;
+    };
+
+    for index in 0..unsafe { size(events) } {
+        let Some(header) = (unsafe { get(events, index).as_ref() }) else {
+            continue;
+        };
+        if header.space_id != CLAP_CORE_EVENT_SPACE_ID || header.type_ != CLAP_EVENT_PARAM_VALUE {
+            continue;
+        }
+        let event = unsafe { &*(header as *const _ as *const clap_event_param_value) };
+        if let Some(param_ref) = parameters.get_by_id(ParameterId(event.param_id)) {
+            param_ref.set_value_plain(PlainValue::new(event.value));
+        }
+    }
+}
@@ -1,12 +1,66 @@
-use std::ffi::{c_char, c_void};
+use std::ffi::{CStr, c_char, c_void};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 use atomic_refcell::AtomicRefCell;
 use clap_sys::{
+    audio_buffer::clap_audio_buffer,
+    events::{
+        CLAP_TRANSPORT_HAS_BEATS_TIMELINE, CLAP_TRANSPORT_HAS_SECONDS_TIMELINE,
+        CLAP_TRANSPORT_HAS_TEMPO, CLAP_TRANSPORT_HAS_TIME_SIGNATURE, CLAP_TRANSPORT_IS_LOOP_ACTIVE,
+        CLAP_TRANSPORT_IS_PLAYING, CLAP_TRANSPORT_IS_RECORDING, clap_event_transport,
+    },
+    ext::{
+        audio_ports::CLAP_EXT_AUDIO_PORTS,
+        latency::{CLAP_EXT_LATENCY, clap_plugin_latency},
+        params::CLAP_EXT_PARAMS,
+        render::{
+            CLAP_EXT_RENDER, CLAP_RENDER_OFFLINE, clap_plugin_render, clap_plugin_render_mode,
+        },
+        state::{CLAP_EXT_STATE, clap_plugin_state},
+        tail::{CLAP_EXT_TAIL, clap_plugin_tail},
+    },
+    fixedpoint::{CLAP_BEATTIME_FACTOR, CLAP_SECTIME_FACTOR},
     plugin::{clap_plugin, clap_plugin_descriptor},
     process::{CLAP_PROCESS_CONTINUE, CLAP_PROCESS_ERROR, clap_process, clap_process_status},
+    stream::{clap_istream, clap_ostream},
 };
 
-use crate::{ClapPlugin, Plugin, wrapper::clap::host::ClapHost};
+use crate::param::{ParameterMap, Params};
+use crate::util::assert_realtime;
+use crate::wrapper::bypass::Bypass;
+use crate::wrapper::clap::params::{self, Params as ClapParams};
+use crate::wrapper::clap::util::{InputStream, OutputStream};
+use crate::wrapper::clap::{audio_ports::AudioPorts, host::ClapHost};
+use crate::wrapper::custom_state::CustomState;
+use crate::wrapper::{process_deadline, tail_samples};
+use crate::{
+    AudioBuffer, ClapPlugin, LoopRange, Plugin, PluginState, ProcessInfo, ProcessMode,
+    TimeSignature, Transport,
+};
+
+struct Inner<P> {
+    plugin: P,
+    sample_rate: f64,
+    bypass: Bypass<f32>,
+    bypass_f64: Bypass<f64>,
+    restart_requested: bool,
+    process_mode: ProcessMode,
+}
+
+impl<P: Plugin> Inner<P> {
+    fn update_process_mode(&mut self, rendering_offline: bool) {
+        let process_mode = if rendering_offline {
+            ProcessMode::Offline
+        } else {
+            ProcessMode::Realtime
+        };
+        if process_mode != self.process_mode {
+            self.process_mode = process_mode;
+            self.plugin.process_mode_changed(process_mode);
+        }
+    }
+}
 
 #[repr(C)]
 pub struct PluginInstance<P: ClapPlugin> {
@@ -14,7 +68,18 @@ pub struct PluginInstance<P: ClapPlugin> {
     // This struct has C representation, so the members will not be reordered.
     raw: clap_plugin,
     host: ClapHost,
-    plugin: AtomicRefCell<P>,
+    inner: AtomicRefCell<Inner<P>>,
+    pub(super) parameters: Rc<ParameterMap<P::Parameters>>,
+    /// Set by the render extension on the main thread, and passed on to the plugin before
+    /// the next block is processed
+    rendering_offline: AtomicBool,
+    /// The latency last reported to the host. Kept outside of `inner`, which is borrowed by
+    /// the audio thread, since the host asks for it on the main thread.
+    latency: AtomicU32,
+    /// The tail in samples, updated when activating and after each block
+    tail: AtomicU32,
+    /// Saves and loads the custom state while `inner` is borrowed by the audio thread
+    custom_state: CustomState,
 }
 
 impl<P: ClapPlugin> PluginInstance<P> {
@@ -37,13 +102,25 @@ impl<P: ClapPlugin> PluginInstance<P> {
             get_extension: Some(Self::clap_get_extension),
             on_main_thread: Some(Self::clap_on_main_thread),
         };
-        let plugin = AtomicRefCell::new(Plugin::new(crate::HostInfo {
+        let plugin = Plugin::new(crate::HostInfo {
             name: host.name().to_str().unwrap().to_string(),
-        }));
+        });
         let this = Box::new(Self {
             raw: plugin_vtbl,
-            plugin,
+            inner: AtomicRefCell::new(Inner {
+                plugin,
+                sample_rate: 0.0,
+                bypass: Bypass::new(),
+                bypass_f64: Bypass::new(),
+                restart_requested: false,
+                process_mode: ProcessMode::default(),
+            }),
+            parameters: ParameterMap::new(P::Parameters::new()),
             host,
+            rendering_offline: AtomicBool::new(false),
+            latency: AtomicU32::new(0),
+            tail: AtomicU32::new(0),
+            custom_state: CustomState::default(),
         });
         let this_ptr = Box::into_raw(this);
         let clap_plugin = &mut unsafe { &mut *this_ptr }.raw;
@@ -55,7 +132,7 @@ impl<P: ClapPlugin> PluginInstance<P> {
         drop(unsafe { Box::from_raw((*plugin).plugin_data) })
     }
 
-    unsafe fn use_self<'a>(plugin: *const clap_plugin) -> Option<&'a Self> {
+    pub(super) unsafe fn use_self<'a>(plugin: *const clap_plugin) -> Option<&'a Self> {
         if plugin.is_null() || unsafe { (*plugin).plugin_data }.is_null() {
             return None;
         }
@@ -81,9 +158,30 @@ impl<P: ClapPlugin> PluginInstance<P> {
         let Some(this) = (unsafe { Self::use_self(plugin) }) else {
             return false;
         };
-        this.plugin
-            .borrow_mut()
-            .prepare(sample_rate, max_frames_count as _);
+        let mut inner = this.inner.borrow_mut();
+        let inner = &mut *inner;
+        let max_samples = max_frames_count as usize;
+        inner.sample_rate = sample_rate;
+        inner.update_process_mode(this.rendering_offline.load(Ordering::Relaxed));
+        inner.plugin.prepare(sample_rate, max_samples);
+        this.custom_state.prepare(sample_rate, max_samples);
+        inner.restart_requested = false;
+        let latency = inner.plugin.latency_samples() as u32;
+        if this.latency.swap(latency, Ordering::Relaxed) != latency {
+            this.host.latency_changed();
+        }
+        this.tail.store(
+            tail_samples(inner.plugin.tail_time(), sample_rate),
+            Ordering::Relaxed,
+        );
+        inner
+            .bypass
+            .prepare_for_plugin(&inner.plugin, sample_rate, max_samples);
+        if P::SUPPORTS_F64 {
+            inner
+                .bypass_f64
+                .prepare_for_plugin(&inner.plugin, sample_rate, max_samples);
+        }
         true
     }
 
@@ -94,31 +192,126 @@ impl<P: ClapPlugin> PluginInstance<P> {
     }
 
     unsafe extern "C" fn clap_start_processing(plugin: *const clap_plugin) -> bool {
-        let Some(_this) = (unsafe { Self::use_self(plugin) }) else {
+        let Some(this) = (unsafe { Self::use_self(plugin) }) else {
+            return false;
+        };
+        this.custom_state.start_processing();
+        let Ok(mut inner) = this.inner.try_borrow_mut() else {
+            // The main thread is saving or loading the state
+            this.custom_state.cancel_start_processing();
             return false;
         };
+        inner.plugin.start_processing();
         true
     }
 
     unsafe extern "C" fn clap_stop_processing(plugin: *const clap_plugin) {
-        let Some(_this) = (unsafe { Self::use_self(plugin) }) else {
+        let Some(this) = (unsafe { Self::use_self(plugin) }) else {
+            return;
+        };
+        // The main thread may still be saving or loading the state if starting failed
+        let Ok(mut inner) = this.inner.try_borrow_mut() else {
+            this.custom_state.stop_processing(None::<&mut P>);
             return;
         };
+        inner.plugin.stop_processing();
+        this.custom_state.stop_processing(Some(&mut inner.plugin));
     }
 
     unsafe extern "C" fn clap_reset(plugin: *const clap_plugin) {
-        let Some(_this) = (unsafe { Self::use_self(plugin) }) else {
+        let Some(this) = (unsafe { Self::use_self(plugin) }) else {
             return;
         };
+        // Skipped if the main thread is saving or loading the state
+        if let Ok(mut inner) = this.inner.try_borrow_mut() {
+            inner.plugin.reset();
+        }
     }
 
     unsafe extern "C" fn clap_process(
         plugin: *const clap_plugin,
         process: *const clap_process,
     ) -> clap_process_status {
-        let Some(_this) = (unsafe { Self::use_self(plugin) }) else {
+        let Some(this) = (unsafe { Self::use_self(plugin) }) else {
             return CLAP_PROCESS_ERROR;
         };
+        let Some(process) = (unsafe { process.as_ref() }) else {
+            return CLAP_PROCESS_ERROR;
+        };
+
+        let input_port = if process.audio_inputs_count > 0 {
+            unsafe { process.audio_inputs.as_ref() }
+        } else {
+            None
+        };
+        let output_port = if process.audio_outputs_count > 0 {
+            unsafe { process.audio_outputs.as_ref() }
+        } else {
+            None
+        };
+        // The ports are flagged as requiring a common sample size, so it is enough to look at
+        // one of them
+        let use_f64 = P::SUPPORTS_F64
+            && output_port
+                .or(input_port)
+                .is_some_and(|port| !port.data64.is_null());
+
+        unsafe { params::apply_parameter_events(this.parameters.as_ref(), process.in_events) };
+
+        let Ok(mut inner) = this.inner.try_borrow_mut() else {
+            // Only happens if the host processes without starting to process first
+            unsafe { clear_output(output_port, process.frames_count as usize, use_f64) };
+            return CLAP_PROCESS_CONTINUE;
+        };
+        let inner = &mut *inner;
+        inner.update_process_mode(this.rendering_offline.load(Ordering::Relaxed));
+        this.custom_state.exchange(&mut inner.plugin);
+        let info = ProcessInfo {
+            rendering_offline: inner.process_mode == ProcessMode::Offline,
+            sample_rate: inner.sample_rate,
+            transport: unsafe { process.transport.as_ref() }
+                .map(|transport| transport_from_event(transport, inner.sample_rate))
+                .unwrap_or_default(),
+        };
+        let num_samples = process.frames_count as usize;
+        let deadline = process_deadline(num_samples, &info);
+        let parameters = this.parameters.parameters_ref();
+        let bypassed = this
+            .parameters
+            .bypass_parameter()
+            .is_some_and(|bypass| bypass.value());
+        let plugin = &mut inner.plugin;
+
+        if use_f64 {
+            let input = unsafe { port_audio_buffer(input_port, num_samples, |port| port.data64) };
+            let mut output =
+                unsafe { port_audio_buffer(output_port, num_samples, |port| port.data64) };
+            inner
+                .bypass_f64
+                .process(bypassed, &input, &mut output, info, |context| {
+                    assert_realtime(deadline, || plugin.process_f64(context, parameters))
+                });
+        } else {
+            let input = unsafe { port_audio_buffer(input_port, num_samples, |port| port.data32) };
+            let mut output =
+                unsafe { port_audio_buffer(output_port, num_samples, |port| port.data32) };
+            inner
+                .bypass
+                .process(bypassed, &input, &mut output, info, |context| {
+                    assert_realtime(deadline, || plugin.process(context, parameters))
+                });
+        }
+
+        // The latency can only change while activating, so ask the host to reactivate
+        this.tail.store(
+            tail_samples(inner.plugin.tail_time(), inner.sample_rate),
+            Ordering::Relaxed,
+        );
+        let latency = inner.plugin.latency_samples() as u32;
+        if latency != this.latency.load(Ordering::Relaxed) && !inner.restart_requested {
+            inner.restart_requested = true;
+            this.host.request_restart();
+        }
 
         CLAP_PROCESS_CONTINUE
     }
@@ -127,8 +320,187 @@ impl<P: ClapPlugin> PluginInstance<P> {
         plugin: *const clap_plugin,
         id: *const c_char,
     ) -> *const c_void {
-        todo!()
+        if unsafe { Self::use_self(plugin) }.is_none() || id.is_null() {
+            return std::ptr::null();
+        }
+
+        let id = unsafe { CStr::from_ptr(id) };
+        if id == CLAP_EXT_AUDIO_PORTS {
+            AudioPorts::<P>::VTABLE as *const _ as *const c_void
+        } else if id == CLAP_EXT_PARAMS {
+            ClapParams::<P>::VTABLE as *const _ as *const c_void
+        } else if id == CLAP_EXT_LATENCY {
+            Self::LATENCY as *const _ as *const c_void
+        } else if id == CLAP_EXT_TAIL {
+            Self::TAIL as *const _ as *const c_void
+        } else if id == CLAP_EXT_RENDER {
+            Self::RENDER as *const _ as *const c_void
+        } else if id == CLAP_EXT_STATE {
+            Self::STATE as *const _ as *const c_void
+        } else {
+            std::ptr::null()
+        }
     }
 
     unsafe extern "C" fn clap_on_main_thread(plugin: *const clap_plugin) {}
+
+    const LATENCY: &'static clap_plugin_latency = &clap_plugin_latency {
+        get: Some(Self::clap_latency_get),
+    };
+
+    const TAIL: &'static clap_plugin_tail = &clap_plugin_tail {
+        get: Some(Self::clap_tail_get),
+    };
+
+    const RENDER: &'static clap_plugin_render = &clap_plugin_render {
+        has_hard_realtime_requirement: Some(Self::clap_render_has_hard_realtime_requirement),
+        set: Some(Self::clap_render_set),
+    };
+
+    const STATE: &'static clap_plugin_state = &clap_plugin_state {
+        save: Some(Self::clap_state_save),
+        load: Some(Self::clap_state_load),
+    };
+
+    unsafe extern "C" fn clap_render_has_hard_realtime_requirement(
+        _plugin: *const clap_plugin,
+    ) -> bool {
+        false
+    }
+
+    unsafe extern "C" fn clap_render_set(
+        plugin: *const clap_plugin,
+        mode: clap_plugin_render_mode,
+    ) -> bool {
+        let Some(this) = (unsafe { Self::use_self(plugin) }) else {
+            return false;
+        };
+        this.rendering_offline
+            .store(mode == CLAP_RENDER_OFFLINE, Ordering::Relaxed);
+        true
+    }
+
+    unsafe extern "C" fn clap_state_save(
+        plugin: *const clap_plugin,
+        stream: *const clap_ostream,
+    ) -> bool {
+        let Some(this) = (unsafe { Self::use_self(plugin) }) else {
+            return false;
+        };
+        let Some(stream) = (unsafe { stream.as_ref() }) else {
+            return false;
+        };
+        let Some(custom) = this
+            .custom_state
+            .save(|| Some(this.inner.try_borrow().ok()?.plugin.save_state()))
+        else {
+            return false;
+        };
+        PluginState::new(&*this.parameters, custom)
+            .write(OutputStream(stream))
+            .is_ok()
+    }
+
+    unsafe extern "C" fn clap_state_load(
+        plugin: *const clap_plugin,
+        stream: *const clap_istream,
+    ) -> bool {
+        let Some(this) = (unsafe { Self::use_self(plugin) }) else {
+            return false;
+        };
+        let Some(stream) = (unsafe { stream.as_ref() }) else {
+            return false;
+        };
+        let Ok(state) = PluginState::read(InputStream(stream)) else {
+            return false;
+        };
+        state.apply_parameters(&*this.parameters);
+        this.custom_state.load(state.custom, |custom| {
+            let Ok(mut inner) = this.inner.try_borrow_mut() else {
+                return false;
+            };
+            inner.plugin.load_state(custom);
+            true
+        });
+        this.host.parameter_values_changed();
+        true
+    }
+
+    unsafe extern "C" fn clap_latency_get(plugin: *const clap_plugin) -> u32 {
+        let Some(this) = (unsafe { Self::use_self(plugin) }) else {
+            return 0;
+        };
+        this.latency.load(Ordering::Relaxed)
+    }
+
+    unsafe extern "C" fn clap_tail_get(plugin: *const clap_plugin) -> u32 {
+        let Some(this) = (unsafe { Self::use_self(plugin) }) else {
+            return 0;
+        };
+        this.tail.load(Ordering::Relaxed)
+    }
+}
+
+fn transport_from_event(transport: &clap_event_transport, sample_rate: f64) -> Transport {
+    let has_flag = |flag: u32| transport.flags & flag != 0;
+    let beats = |time: i64| time as f64 / CLAP_BEATTIME_FACTOR as f64;
+    let has_beats_timeline = has_flag(CLAP_TRANSPORT_HAS_BEATS_TIMELINE);
+    Transport {
+        playing: has_flag(CLAP_TRANSPORT_IS_PLAYING),
+        recording: has_flag(CLAP_TRANSPORT_IS_RECORDING),
+        tempo: has_flag(CLAP_TRANSPORT_HAS_TEMPO).then_some(transport.tempo),
+        time_signature: has_flag(CLAP_TRANSPORT_HAS_TIME_SIGNATURE).then_some(TimeSignature {
+            numerator: transport.tsig_num as _,
+            denominator: transport.tsig_denom as _,
+        }),
+        position_samples: has_flag(CLAP_TRANSPORT_HAS_SECONDS_TIMELINE).then(|| {
+            let seconds = transport.song_pos_seconds as f64 / CLAP_SECTIME_FACTOR as f64;
+            (seconds * sample_rate).round() as i64
+        }),
+        position_quarters: has_beats_timeline.then(|| beats(transport.song_pos_beats)),
+        bar_start_quarters: has_beats_timeline.then(|| beats(transport.bar_start)),
+        loop_range: (has_beats_timeline && has_flag(CLAP_TRANSPORT_IS_LOOP_ACTIVE)).then(|| {
+            LoopRange {
+                start: beats(transport.loop_start_beats),
+                end: beats(transport.loop_end_beats),
+            }
+        }),
+    }
+}
+
+/// Creates an audio buffer for the channels of a port, or an empty buffer if there is no port.
+/// The input and output share samples when the host processes in place, so they are passed
+/// through [`Bypass::process`], which gives the plugin a copy of the input in that case.
+///
+/// # Safety
+///
+/// The channel pointers returned by `channel_buffers` must be valid for `num_samples` samples.
+unsafe fn port_audio_buffer<'a, T>(
+    port: Option<&clap_audio_buffer>,
+    num_samples: usize,
+    channel_buffers: impl FnOnce(&clap_audio_buffer) -> *mut *mut T,
+) -> AudioBuffer<'a, T> {
+    match port {
+        Some(port) => unsafe {
+            AudioBuffer::from_ptr(
+                channel_buffers(port),
+                port.channel_count as usize,
+                num_samples,
+            )
+        },
+        None => AudioBuffer::empty(),
+    }
+}
+
+/// Silences the output port.
+///
+/// # Safety
+///
+/// Same as [`port_audio_buffer`].
+unsafe fn clear_output(port: Option<&clap_audio_buffer>, num_samples: usize, use_f64: bool) {
+    if use_f64 {
+        unsafe { port_audio_buffer(port, num_samples, |port| port.data64) }.clear();
+    } else {
+        unsafe { port_audio_buffer(port, num_samples, |port| port.data32) }.clear();
+    }
 }
@@ -0,0 +1,42 @@
+use std::ffi::c_char;
+use std::io;
+
+use clap_sys::stream::{clap_istream, clap_ostream};
+
+pub fn strcpy(src: &str, dst: &mut [c_char]) {
+    let src = src.as_bytes();
+    let src = unsafe { &*(src as *const [u8] as *const [c_char]) };
+    let len = std::cmp::min(dst.len() - 1, src.len());
+    dst[..len].copy_from_slice(&src[..len]);
+    dst[len] = 0;
+}
+
+/// Reads the stream that the host passes when restoring state
+pub struct InputStream<'a>(pub &'a clap_istream);
+
+impl io::Read for InputStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(read) = self.0.read else {
+            return Err(io::Error::other("The host stream can't be read"));
+        };
+        let read = unsafe { read(self.0, buf.as_mut_ptr().cast(), buf.len() as u64) };
+        usize::try_from(read).map_err(|_| io::Error::other("Failed to read from the host stream"))
+    }
+}
+
+/// Writes the stream that the host passes when saving state
+pub struct OutputStream<'a>(pub &'a clap_ostream);
+
+impl io::Write for OutputStream<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(write) = self.0.write else {
+            return Err(io::Error::other("The host stream can't be written"));
+        };
+        let written = unsafe { write(self.0, buf.as_ptr().cast(), buf.len() as u64) };
+        usize::try_from(written).map_err(|_| io::Error::other("Failed to write to the host stream"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::Plugin;
+use crate::util::permit_alloc;
+
+/// How many blocks saving waits for the audio thread to provide the custom state
+const SAVE_TIMEOUT_BLOCKS: f64 = 4.0;
+
+/// Passes the custom state of the plugin (see [`Plugin::save_state`]) between the main thread,
+/// where hosts save and load it, and the audio thread, which has the plugin borrowed while
+/// processing. The audio thread only try-locks it.
+#[derive(Default)]
+pub(crate) struct CustomState {
+    exchange: Mutex<Exchange>,
+    saved: Condvar,
+    /// True between starting and stopping processing, while the plugin belongs to the audio
+    /// thread
+    processing: AtomicBool,
+    save_requested: AtomicBool,
+}
+
+#[derive(Default)]
+struct Exchange {
+    /// The state that was last saved or loaded, used when the audio thread does not save
+    /// the state in time
+    last: Option<Vec<u8>>,
+    /// Set by the audio thread when it has saved `last` on request
+    saved: bool,
+    to_load: Option<Vec<u8>>,
+    save_timeout: Duration,
+}
+
+impl CustomState {
+    /// Called with the block size whenever the plugin is prepared
+    pub fn prepare(&self, sample_rate: f64, max_samples: usize) {
+        self.exchange.lock().unwrap().save_timeout =
+            Duration::from_secs_f64(SAVE_TIMEOUT_BLOCKS * max_samples as f64 / sample_rate);
+    }
+
+    /// Called by the audio thread before it borrows the plugin to start processing
+    pub fn start_processing(&self) {
+        self.processing.store(true, Ordering::SeqCst);
+    }
+
+    /// Called by the audio thread if it could not borrow the plugin after
+    /// [`Self::start_processing`]. A state that was queued in the meantime is loaded once
+    /// it processes again.
+    pub fn cancel_start_processing(&self) {
+        let _exchange = self.exchange.lock().unwrap();
+        self.processing.store(false, Ordering::SeqCst);
+        self.saved.notify_all();
+    }
+
+    /// Called by the audio thread when it stops processing, with the plugin if it could be
+    /// borrowed. Loads and saves the state that the main thread is still waiting for.
+    pub fn stop_processing<P: Plugin>(&self, plugin: Option<&mut P>) {
+        let mut exchange = self.exchange.lock().unwrap();
+        if let Some(plugin) = plugin {
+            self.exchange_locked(&mut exchange, plugin);
+        }
+        self.processing.store(false, Ordering::SeqCst);
+        self.saved.notify_all();
+    }
+
+    fn is_processing(&self) -> bool {
+        self.processing.load(Ordering::SeqCst)
+    }
+
+    /// Save the custom state of the plugin. `save_directly` tries to borrow the plugin on the
+    /// calling thread, which fails while the audio thread has it. In that case the audio
+    /// thread is asked to save it within a few blocks, and if it doesn't, the state that was
+    /// last loaded or saved is used. Returns `None` if there is no such state either.
+    pub fn save(&self, save_directly: impl FnOnce() -> Option<Vec<u8>>) -> Option<Vec<u8>> {
+        if !self.is_processing()
+            && let Some(state) = save_directly()
+        {
+            self.exchange.lock().unwrap().last = Some(state.clone());
+            return Some(state);
+        }
+
+        let mut exchange = self.exchange.lock().unwrap();
+        exchange.saved = false;
+        self.save_requested.store(true, Ordering::SeqCst);
+        let timeout = exchange.save_timeout;
+        let (exchange, _) = self
+            .saved
+            .wait_timeout_while(exchange, timeout, |exchange| {
+                !exchange.saved && self.is_processing()
+            })
+            .unwrap();
+        self.save_requested.store(false, Ordering::SeqCst);
+        if exchange.saved {
+            return exchange.last.clone();
+        }
+
+        // The plugin will have the queued state once the audio thread gets to it
+        let state = exchange
+            .to_load
+            .as_ref()
+            .or(exchange.last.as_ref())
+            .cloned();
+        if state.is_some() {
+            log::warn!("The plugin did not save its state in time, using the last state instead");
+        } else {
+            log::warn!("The plugin did not save its state in time");
+        }
+        state
+    }
+
+    /// Load the custom state into the plugin. `load_directly` tries to borrow the plugin on
+    /// the calling thread and returns false if it can't, in which case the audio thread loads
+    /// the state before the next block.
+    pub fn load(&self, state: Vec<u8>, load_directly: impl FnOnce(&[u8]) -> bool) {
+        let mut exchange = self.exchange.lock().unwrap();
+        exchange.to_load = None;
+        if !self.is_processing() && load_directly(&state) {
+            exchange.last = Some(state);
+        } else {
+            exchange.to_load = Some(state);
+        }
+    }
+
+    /// Called by the audio thread with the plugin borrowed for each block, to load and save the
+    /// state that was asked for by the main thread. Never blocks.
+    pub fn exchange<P: Plugin>(&self, plugin: &mut P) {
+        if let Ok(mut exchange) = self.exchange.try_lock() {
+            self.exchange_locked(&mut exchange, plugin);
+        }
+    }
+
+    /// Loading and saving may allocate, this only happens when the host loads or saves a state
+    fn exchange_locked<P: Plugin>(&self, exchange: &mut Exchange, plugin: &mut P) {
+        permit_alloc(|| {
+            if let Some(state) = exchange.to_load.take() {
+                plugin.load_state(&state);
+                exchange.last = Some(state);
+            }
+            if self.save_requested.swap(false, Ordering::SeqCst) {
+                exchange.last = Some(plugin.save_state());
+                exchange.saved = true;
+                self.saved.notify_all();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use atomic_refcell::AtomicRefCell;
+
+    use super::*;
+    use crate::{AudioLayout, GenericEditor, HostInfo, ProcessContext};
+
+    #[derive(Default)]
+    struct StatefulPlugin {
+        state: Vec<u8>,
+    }
+
+    impl Plugin for StatefulPlugin {
+        const NAME: &'static str = "Stateful";
+        const VENDOR: &'static str = "audioplug";
+        const URL: &'static str = "";
+        const EMAIL: &'static str = "";
+        const AUDIO_LAYOUT: AudioLayout = AudioLayout::EMPTY;
+        type Editor = GenericEditor<()>;
+        type Parameters = ();
+
+        fn new(_info: HostInfo) -> Self {
+            Self::default()
+        }
+
+        fn prepare(&mut self, _sample_rate: f64, _max_buffer_size: usize) {}
+
+        fn process(&mut self, _context: ProcessContext, _parameters: &()) {}
+
+        fn save_state(&self) -> Vec<u8> {
+            self.state.clone()
+        }
+
+        fn load_state(&mut self, state: &[u8]) {
+            self.state = state.to_vec();
+        }
+    }
+
+    #[test]
+    fn save_while_processing() {
+        let plugin = Arc::new(AtomicRefCell::new(StatefulPlugin::default()));
+        let custom_state = Arc::new(CustomState::default());
+        custom_state.prepare(1000.0, 100);
+        let save_directly = || Some(plugin.try_borrow().ok()?.save_state());
+        let load_directly = |state: &[u8]| {
+            let Ok(mut plugin) = plugin.try_borrow_mut() else {
+                return false;
+            };
+            plugin.load_state(state);
+            true
+        };
+
+        custom_state.load(vec![1], load_directly);
+        assert_eq!(custom_state.save(save_directly), Some(vec![1]));
+
+        custom_state.start_processing();
+        let (started_sender, started) = std::sync::mpsc::channel();
+        let (stop, stop_receiver) = std::sync::mpsc::channel::<()>();
+        let audio_thread = {
+            let plugin = plugin.clone();
+            let custom_state = custom_state.clone();
+            std::thread::spawn(move || {
+                // Borrowed like the wrappers borrow the plugin while processing a block
+                let mut plugin = plugin.borrow_mut();
+                started_sender.send(()).unwrap();
+                while stop_receiver.try_recv().is_err() {
+                    custom_state.exchange(&mut *plugin);
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                custom_state.stop_processing(Some(&mut *plugin));
+            })
+        };
+        started.recv().unwrap();
+
+        assert!(plugin.try_borrow().is_err());
+        custom_state.load(vec![1, 2, 3], load_directly);
+        assert_eq!(custom_state.save(save_directly), Some(vec![1, 2, 3]));
+
+        // A state that is loaded just before processing stops is not lost
+        custom_state.load(vec![4], load_directly);
+        stop.send(()).unwrap();
+        audio_thread.join().unwrap();
+        assert_eq!(custom_state.save(save_directly), Some(vec![4]));
+    }
+
+    #[test]
+    fn save_falls_back_to_last_state() {
+        let mut plugin = StatefulPlugin::default();
+        let custom_state = CustomState::default();
+        custom_state.prepare(48000.0, 480);
+        custom_state.start_processing();
+        // The audio thread has the plugin, but does not process
+        assert_eq!(custom_state.save(|| None), None);
+
+        custom_state.stop_processing(Some(&mut plugin));
+        custom_state.load(vec![1], |state| {
+            plugin.load_state(state);
+            true
+        });
+        custom_state.start_processing();
+        assert_eq!(custom_state.save(|| None), Some(vec![1]));
+        custom_state.load(vec![2], |_| unreachable!());
+        assert_eq!(custom_state.save(|| None), Some(vec![2]));
+
+        custom_state.stop_processing(None::<&mut StatefulPlugin>);
+        assert_eq!(
+            custom_state.save(|| Some(plugin.save_state())),
+            Some(vec![1])
+        );
+    }
+}
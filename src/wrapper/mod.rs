@@ -1,10 +1,30 @@
+use std::time::Duration;
+
+use crate::ProcessInfo;
+
 #[cfg(target_os = "macos")]
 pub mod auv3;
 
+pub(crate) mod bypass;
 pub mod clap;
+pub(crate) mod custom_state;
 pub mod standalone;
 pub mod vst3;
 
+/// Converts the tail time of a plugin to samples. Tails that are too long to be represented,
+/// such as [`Duration::MAX`], become `u32::MAX`, which means an infinite tail to both VST3
+/// and CLAP hosts.
+pub(crate) fn tail_samples(tail_time: Duration, sample_rate: f64) -> u32 {
+    (tail_time.as_secs_f64() * sample_rate).ceil() as u32
+}
+
+/// Time available for processing a block in real time, used to detect blocking calls when
+/// the real-time checks are enabled. There is no deadline when rendering offline.
+pub(crate) fn process_deadline(num_samples: usize, info: &ProcessInfo) -> Option<Duration> {
+    (!info.rendering_offline && info.sample_rate > 0.0)
+        .then(|| Duration::from_secs_f64(num_samples as f64 / info.sample_rate))
+}
+
 #[macro_export]
 #[cfg(any(target_os = "windows", target_os = "linux"))]
 macro_rules! audioplug_auv3_plugin {
@@ -0,0 +1,440 @@
+use rtrb::Consumer;
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Instant,
+};
+
+use super::{
+    AudioSettings, FilePlayer, NoteEventReceiver, NoteEventSender, ParameterUpdate, Recorder,
+    RecordingSender, Settings, TimedNoteEvent,
+};
+use crate::{
+    AudioBuffer, MidiProcessContext, OwnedAudioBuffer, Plugin, PluginState, ProcessInfo, Transport,
+    param::{AnyParameterMap, OwnedParameterMap, ParameterId, ParameterMap, Params},
+    platform::{self, AudioHost, MidiHost, StreamConfig},
+    util::{WavError, WavFile, assert_no_alloc, assert_realtime},
+    wrapper::{bypass::Bypass, process_deadline},
+};
+
+/// Runs the plugin on the audio devices chosen in the settings, and forwards the chosen MIDI
+/// device to it. Lives on the main thread.
+pub struct AudioEngine<P: Plugin> {
+    /// The parameters of the editor. The processor has a copy, which follows these.
+    parameters: Rc<ParameterMap<P::Parameters>>,
+    /// Shared with the callback of the stream, so that it can be started again with other
+    /// settings
+    processor: Arc<Mutex<AudioProcessor<P>>>,
+    /// Shared with the MIDI input thread, for the same reason
+    midi_input_events: Arc<Mutex<NoteEventSender>>,
+    /// Shared with the processor
+    player: Arc<Mutex<FilePlayer>>,
+    recording: Arc<Mutex<Option<RecordingSender>>>,
+    recorder: Option<Recorder>,
+    stream: Option<platform::Stream>,
+    midi_input: Option<platform::MidiInput>,
+    /// The settings that the stream and the MIDI input were last opened with
+    settings: Option<Settings>,
+}
+
+impl<P: Plugin> AudioEngine<P> {
+    pub fn new(processor: AudioProcessor<P>, midi_input_events: NoteEventSender) -> Self {
+        Self {
+            parameters: ParameterMap::new(P::Parameters::new()),
+            player: processor.player.clone(),
+            recording: processor.recording.clone(),
+            recorder: None,
+            processor: Arc::new(Mutex::new(processor)),
+            midi_input_events: Arc::new(Mutex::new(midi_input_events)),
+            stream: None,
+            midi_input: None,
+            settings: None,
+        }
+    }
+
+    /// The parameters for the editor, which are sent to the processor when changed through the
+    /// host handle of the editor
+    pub fn parameters(&self) -> &Rc<ParameterMap<P::Parameters>> {
+        &self.parameters
+    }
+
+    /// The settings that were last applied
+    pub fn settings(&self) -> Option<&Settings> {
+        self.settings.as_ref()
+    }
+
+    /// The sample rate and block size of the running stream
+    pub fn stream_config(&self) -> Option<&StreamConfig> {
+        self.stream.as_ref().map(platform::Stream::config)
+    }
+
+    /// Open the stream and the MIDI input again if their settings changed. Audio errors are
+    /// returned, MIDI errors are only logged since the keyboard can still be used.
+    pub fn apply(&mut self, settings: &Settings) -> Result<(), platform::Error> {
+        let previous = self.settings.replace(settings.clone());
+        if P::ACCEPTS_MIDI
+            && previous
+                .as_ref()
+                .is_none_or(|previous| previous.midi_device != settings.midi_device)
+        {
+            self.stop_midi_input();
+            self.midi_input = self
+                .open_midi_input(settings.midi_device.as_deref())
+                .inspect_err(|err| log::warn!("MIDI input is not available: {err}"))
+                .ok();
+        }
+        if previous.is_none_or(|previous| previous.audio != settings.audio) {
+            self.stop_stream();
+            self.start_stream(&settings.audio)?;
+        }
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_stream();
+        self.stop_midi_input();
+        self.settings = None;
+    }
+
+    /// The file player, which replaces the audio input while it has a file
+    pub fn player(&self) -> MutexGuard<'_, FilePlayer> {
+        self.player.lock().unwrap()
+    }
+
+    /// Play `path` instead of the audio input, once started with [`FilePlayer::play`]
+    pub fn load_file(&self, path: &Path) -> Result<(), WavError> {
+        let file = WavFile::open(path)?;
+        let previous = self.player().load(Some(file));
+        // Freed here, after the player has been unlocked
+        drop(previous);
+        Ok(())
+    }
+
+    /// Go back to the audio input
+    pub fn unload_file(&self) {
+        let previous = self.player().load(None);
+        drop(previous);
+    }
+
+    /// Set the parameters, and restore the custom state of the plugin. Returns the ids of
+    /// the parameters that were set, so that the editor can be notified.
+    pub fn load_state(&self, state: &PluginState) -> Vec<ParameterId> {
+        let ids = state.apply_parameters(&*self.parameters);
+        let mut processor = self.processor.lock().unwrap();
+        processor.copy_parameters(&*self.parameters);
+        processor.plugin.load_state(&state.custom);
+        ids
+    }
+
+    pub fn state(&self) -> PluginState {
+        let custom = self.processor.lock().unwrap().plugin.save_state();
+        PluginState::new(&*self.parameters, custom)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Record the output of the plugin to a WAV file at `path`, until the recording is
+    /// stopped or the stream is opened with other settings
+    pub fn start_recording(&mut self, path: impl Into<PathBuf>) -> Result<(), WavError> {
+        let Some(config) = self.stream_config() else {
+            return Err(std::io::Error::other("The audio stream is not running").into());
+        };
+        let config = *config;
+        self.stop_recording();
+        let (recorder, sender) = Recorder::start(path, config.sample_rate, config.output_channels)?;
+        *self.recording.lock().unwrap() = Some(sender);
+        self.recorder = Some(recorder);
+        Ok(())
+    }
+
+    /// Stop recording, and return the path of the recorded file
+    pub fn stop_recording(&mut self) -> Option<Result<PathBuf, WavError>> {
+        let recorder = self.recorder.take()?;
+        let sender = self.recording.lock().unwrap().take();
+        if sender.is_some_and(|sender| sender.dropped_samples()) {
+            log::warn!(
+                "Samples were dropped while recording {}",
+                recorder.path().display()
+            );
+        }
+        Some(recorder.finish())
+    }
+
+    fn start_stream(&mut self, settings: &AudioSettings) -> Result<(), platform::Error> {
+        let output_device = find_device(settings.output_device.as_deref(), true)?;
+        let input_map = settings.input_channel_map(input_channels::<P>());
+        let input_device = if input_map.is_empty() {
+            None
+        } else {
+            Some(find_device(settings.input_device.as_deref(), false)?)
+        };
+        let sample_rate = match settings.sample_rate {
+            Some(sample_rate) => sample_rate,
+            None => output_device.sample_rate()?,
+        };
+        let config = StreamConfig {
+            sample_rate,
+            block_size: settings.block_size,
+            input_channels: input_map.iter().max().map_or(0, |channel| channel + 1),
+            output_channels: P::AUDIO_LAYOUT
+                .main_output
+                .map_or(0, |bus| bus.channel.size() as usize),
+        };
+
+        let mut processor = self.processor.lock().unwrap();
+        // Changes are dropped while the stream is stopped and the ring buffer is full
+        processor.copy_parameters(&*self.parameters);
+        processor.start(sample_rate, settings.block_size, input_map);
+        drop(processor);
+        let processor = self.processor.clone();
+        let stream = AudioHost::open_stream(
+            &output_device,
+            input_device.as_ref(),
+            config,
+            move |input, output| match processor.try_lock() {
+                Ok(mut processor) => processor.process(input, output),
+                // Only locked by the main thread while the stream is stopped
+                Err(_) => output.clear(),
+            },
+        );
+        match stream {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                Ok(())
+            }
+            Err(err) => {
+                self.processor.lock().unwrap().stop();
+                Err(err)
+            }
+        }
+    }
+
+    fn stop_stream(&mut self) {
+        // The file would get the wrong sample rate or number of channels
+        match self.stop_recording() {
+            Some(Ok(path)) => log::info!("Recorded {}", path.display()),
+            Some(Err(err)) => log::error!("Error while recording: {err}"),
+            None => {}
+        }
+        if let Some(stream) = self.stream.take()
+            && let Err(err) = stream.stop()
+        {
+            log::error!("Error while stopping the audio stream: {err}");
+        }
+        self.processor.lock().unwrap().stop();
+    }
+
+    /// Forward the events of the MIDI device to the audio thread. Other applications can also
+    /// connect to the MIDI input.
+    fn open_midi_input(
+        &self,
+        device_id: Option<&str>,
+    ) -> Result<platform::MidiInput, platform::Error> {
+        let device = match device_id {
+            Some(id) => {
+                let device = MidiHost::devices()?
+                    .into_iter()
+                    .find(|device| device.id() == id);
+                if device.is_none() {
+                    log::warn!("MIDI device {id} is not available");
+                }
+                device
+            }
+            None => MidiHost::default_input_device()?,
+        };
+        if let Some(device) = &device {
+            log::info!("Receiving MIDI from {}", device.name()?);
+        }
+        let sender = self.midi_input_events.clone();
+        MidiHost::open_input(P::NAME, device.as_ref(), move |time, event| {
+            let mut sender = sender.lock().unwrap();
+            if sender.push(TimedNoteEvent { time, event }).is_err() {
+                log::warn!("Dropped a note event, the audio thread is not processing");
+            }
+        })
+    }
+
+    fn stop_midi_input(&mut self) {
+        if let Some(midi_input) = self.midi_input.take() {
+            midi_input.stop();
+        }
+    }
+}
+
+impl<P: Plugin> Drop for AudioEngine<P> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The number of channels of the main input of the plugin
+pub fn input_channels<P: Plugin>() -> usize {
+    P::AUDIO_LAYOUT
+        .main_input
+        .map_or(0, |bus| bus.channel.size() as usize)
+}
+
+/// The device with the given id, or the default device if there is no id or the device is
+/// not available
+pub fn find_device(id: Option<&str>, output: bool) -> Result<platform::Device, platform::Error> {
+    if let Some(id) = id {
+        for device in AudioHost::devices()? {
+            if device.id()? == id {
+                return Ok(device);
+            }
+        }
+        log::warn!("Audio device {id} is not available, using the default device");
+    }
+    if output {
+        AudioHost::default_output_device()
+    } else {
+        AudioHost::default_input_device()
+    }
+}
+
+/// The audio side of the standalone wrapper, which runs the plugin on the audio thread.
+///
+/// It has its own copy of the parameters, like the audio processor of a VST3 plugin. Changes
+/// made in the editor are sent through a ring buffer, and applied before each block. Other
+/// changes are copied from the parameters of the engine while the processor is locked.
+pub struct AudioProcessor<P: Plugin> {
+    plugin: P,
+    parameters: OwnedParameterMap<P::Parameters>,
+    parameter_updates: Consumer<ParameterUpdate>,
+    note_events: NoteEventReceiver,
+    player: Arc<Mutex<FilePlayer>>,
+    recording: Arc<Mutex<Option<RecordingSender>>>,
+    bypass: Bypass<f32>,
+    /// The plugin input, taken from the device channels in `input_map`
+    input: OwnedAudioBuffer,
+    input_map: Vec<usize>,
+    sample_rate: f64,
+    processing: bool,
+}
+
+impl<P: Plugin> AudioProcessor<P> {
+    pub fn new(
+        parameter_updates: Consumer<ParameterUpdate>,
+        note_events: NoteEventReceiver,
+    ) -> Self {
+        Self {
+            plugin: P::new(crate::HostInfo {
+                name: "Standalone".to_string(),
+            }),
+            parameters: OwnedParameterMap::new(P::Parameters::new()),
+            parameter_updates,
+            note_events,
+            player: Arc::new(Mutex::new(FilePlayer::new())),
+            recording: Arc::new(Mutex::new(None)),
+            bypass: Bypass::new(),
+            input: OwnedAudioBuffer::new(),
+            input_map: Vec::new(),
+            sample_rate: 0.0,
+            processing: false,
+        }
+    }
+
+    /// Prepare the plugin and start processing. Plugin input channel `i` gets device input
+    /// channel `input_map[i]`.
+    fn start(&mut self, sample_rate: f64, max_block_size: usize, input_map: Vec<usize>) {
+        self.sample_rate = sample_rate;
+        self.input.prepare(input_map.len(), max_block_size);
+        self.input_map = input_map;
+        self.plugin.prepare(sample_rate, max_block_size);
+        self.bypass
+            .prepare_for_plugin(&self.plugin, sample_rate, max_block_size);
+        self.plugin.start_processing();
+        self.processing = true;
+    }
+
+    /// Set the parameters to the values in `parameters`. Updates that are still in the ring
+    /// buffer are older, so they are dropped.
+    fn copy_parameters(&mut self, parameters: &dyn AnyParameterMap) {
+        while self.parameter_updates.pop().is_ok() {}
+        for param_ref in self.parameters.iter() {
+            if let Some(source) = parameters.get_by_id(param_ref.id()) {
+                param_ref.set_value_normalized(source.normalized_value());
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        if self.processing {
+            self.plugin.stop_processing();
+            self.processing = false;
+        }
+    }
+
+    fn process(&mut self, device_input: &AudioBuffer, output: &mut AudioBuffer) {
+        let now = Instant::now();
+        while let Ok(update) = self.parameter_updates.pop() {
+            if let Some(param_ref) = self.parameters.get_by_id(update.id) {
+                param_ref.set_value_normalized(update.value);
+            }
+        }
+
+        let num_samples = output.samples();
+        let mut plugin_input = self.input.as_audio_buffer(num_samples);
+        match self.player.try_lock() {
+            Ok(mut player) if player.has_file() => player.read(&mut plugin_input, self.sample_rate),
+            Ok(_) => {
+                for (channel, &device_channel) in self.input_map.iter().enumerate() {
+                    let mut samples = plugin_input.channel_mut(channel);
+                    if device_channel < device_input.channels() {
+                        samples
+                            .as_mut_slice()
+                            .copy_from_slice(device_input.channel(device_channel).as_slice());
+                    } else {
+                        samples.as_mut_slice().fill(0.0);
+                    }
+                }
+            }
+            // Only locked by the main thread for a moment, e.g. to change the transport
+            Err(_) => plugin_input.clear(),
+        }
+
+        let info = ProcessInfo {
+            rendering_offline: false,
+            sample_rate: self.sample_rate,
+            transport: Transport::default(),
+        };
+        let deadline = process_deadline(num_samples, &info);
+        let parameters = self.parameters.parameters_ref();
+        if P::ACCEPTS_MIDI {
+            let mut context = MidiProcessContext { info };
+            let plugin = &mut self.plugin;
+            self.note_events
+                .receive(now, num_samples, self.sample_rate, |event| {
+                    assert_no_alloc(|| plugin.process_midi(&mut context, parameters, event))
+                });
+        }
+
+        let bypassed = self
+            .parameters
+            .bypass_parameter()
+            .is_some_and(|bypass| bypass.value());
+        let plugin = &mut self.plugin;
+        self.bypass.process(
+            bypassed,
+            &self.input.as_audio_buffer_ref(num_samples),
+            output,
+            info,
+            |context| assert_realtime(deadline, || plugin.process(context, parameters)),
+        );
+
+        if let Ok(mut recording) = self.recording.try_lock()
+            && let Some(sender) = recording.as_mut()
+        {
+            sender.send(output);
+        }
+    }
+}
+
+impl<P: Plugin> Drop for AudioProcessor<P> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
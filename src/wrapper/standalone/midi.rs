@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::midi::NoteEvent;
+
+/// Number of note events that can be waiting for the audio thread, per source
+const NOTE_EVENT_CAPACITY: usize = 1024;
+
+/// A note event, and when it was received
+pub struct TimedNoteEvent {
+    pub time: Instant,
+    pub event: NoteEvent,
+}
+
+pub type NoteEventSender = Producer<TimedNoteEvent>;
+
+/// Receives the note events of the MIDI input and the on-screen keyboard on the audio thread.
+///
+/// Events are delayed by one block, and placed in the block at the same distance from each
+/// other as they were received. This keeps the timing between events, at the cost of one
+/// block of latency.
+pub struct NoteEventReceiver {
+    midi_input: Consumer<TimedNoteEvent>,
+    keyboard: Consumer<TimedNoteEvent>,
+}
+
+impl NoteEventReceiver {
+    /// Create the receiver, and the senders for the MIDI input and the keyboard
+    pub fn new() -> (Self, NoteEventSender, NoteEventSender) {
+        let (midi_input_sender, midi_input) = RingBuffer::new(NOTE_EVENT_CAPACITY);
+        let (keyboard_sender, keyboard) = RingBuffer::new(NOTE_EVENT_CAPACITY);
+        let receiver = Self {
+            midi_input,
+            keyboard,
+        };
+        (receiver, midi_input_sender, keyboard_sender)
+    }
+
+    /// Call `f` with the events received before `now`, in the order they were received, with
+    /// their offset into a block of `num_samples` samples that ends at `now`
+    pub fn receive(
+        &mut self,
+        now: Instant,
+        num_samples: usize,
+        sample_rate: f64,
+        mut f: impl FnMut(NoteEvent),
+    ) {
+        let block_start = now
+            .checked_sub(Duration::from_secs_f64(num_samples as f64 / sample_rate))
+            .unwrap_or(now);
+        let last_sample = num_samples.saturating_sub(1);
+        while let Some(source) = self.next_source(now) {
+            let Ok(TimedNoteEvent { time, event }) = source.pop() else {
+                break;
+            };
+            let offset = time.saturating_duration_since(block_start).as_secs_f64() * sample_rate;
+            let offset = (offset.round() as usize).min(last_sample);
+            f(event.with_sample_offset(offset as i32));
+        }
+    }
+
+    /// The source with the earliest event before `now`
+    fn next_source(&mut self, now: Instant) -> Option<&mut Consumer<TimedNoteEvent>> {
+        let time = |source: &Consumer<TimedNoteEvent>| {
+            source
+                .peek()
+                .ok()
+                .map(|event| event.time)
+                .filter(|&time| time <= now)
+        };
+        match (time(&self.midi_input), time(&self.keyboard)) {
+            (Some(midi_input), Some(keyboard)) if keyboard < midi_input => Some(&mut self.keyboard),
+            (Some(_), _) => Some(&mut self.midi_input),
+            (None, Some(_)) => Some(&mut self.keyboard),
+            (None, None) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::midi::Note;
+
+    #[test]
+    fn events_keep_their_timing_within_the_block() {
+        let (mut receiver, mut midi_input, mut keyboard) = NoteEventReceiver::new();
+        let start = Instant::now();
+        let note_on = |note| NoteEvent::NoteOn {
+            channel: 0,
+            sample_offset: 0,
+            note: Note(note),
+        };
+        let at_ms = |ms| start + Duration::from_millis(ms);
+        let send = |sender: &mut NoteEventSender, ms, note| {
+            let event = TimedNoteEvent {
+                time: at_ms(ms),
+                event: note_on(note),
+            };
+            assert!(sender.push(event).is_ok());
+        };
+        send(&mut midi_input, 2, 60);
+        send(&mut midi_input, 8, 62);
+        send(&mut keyboard, 5, 61);
+        // Received after the end of the block
+        send(&mut keyboard, 12, 63);
+
+        // A block of 10 ms
+        let mut events = Vec::new();
+        receiver.receive(at_ms(10), 480, 48000.0, |event| events.push(event));
+        assert_eq!(
+            events,
+            [
+                note_on(60).with_sample_offset(96),
+                note_on(61).with_sample_offset(240),
+                note_on(62).with_sample_offset(384),
+            ]
+        );
+
+        events.clear();
+        receiver.receive(at_ms(20), 480, 48000.0, |event| events.push(event));
+        assert_eq!(events, [note_on(63).with_sample_offset(96)]);
+    }
+}
@@ -1,14 +1,31 @@
-use rtrb::{Consumer, Producer, RingBuffer};
-use std::{cell::RefCell, rc::Rc};
+mod engine;
+mod midi;
+mod player;
+mod render;
+mod settings;
+mod settings_view;
+
+use rtrb::{Producer, RingBuffer};
+use std::{cell::RefCell, path::PathBuf, rc::Rc, time::Instant};
 
 use crate::{
-    Editor, Plugin,
-    param::{AnyParameter, NormalizedValue, ParameterId, ParameterMap, Params},
-    platform::{self, AudioHost},
+    Editor, Plugin, PluginState, StateError,
+    midi::NoteEvent,
+    param::{AnyParameter, NormalizedValue, ParameterId, ParameterMap},
+    platform,
     ui::{App, AppState, HostHandle, Window},
+    views::{Column, Piano},
 };
+pub use engine::{AudioEngine, AudioProcessor};
+pub use midi::{NoteEventReceiver, NoteEventSender, TimedNoteEvent};
+pub use player::{FilePlayer, Recorder, RecordingSender};
+pub use render::render_file;
+use settings::last_state_path;
+pub use settings::{AudioSettings, Settings};
+use settings_view::{apply_settings, settings_view};
 
-const SAMPLES_PER_BLOCK: usize = 128;
+/// Number of parameter changes that can be waiting for the audio thread
+const PARAMETER_UPDATE_CAPACITY: usize = 1024;
 
 pub struct ParameterUpdate {
     id: ParameterId,
@@ -17,6 +34,19 @@ pub struct ParameterUpdate {
 
 struct AppInner {
     parameter_updates: Producer<ParameterUpdate>,
+    keyboard_events: NoteEventSender,
+}
+
+impl AppInner {
+    fn send_note_event(&mut self, event: NoteEvent) {
+        let event = TimedNoteEvent {
+            time: Instant::now(),
+            event,
+        };
+        if self.keyboard_events.push(event).is_err() {
+            log::warn!("Dropped a note event, the audio thread is not processing");
+        }
+    }
 }
 
 struct StandaloneHostHandle {
@@ -30,32 +60,40 @@ impl HostHandle for StandaloneHostHandle {
 
     fn perform_edit(&self, info: &dyn AnyParameter, value: crate::param::NormalizedValue) {
         let mut app_inner = RefCell::borrow_mut(&self.app_inner);
-        app_inner
-            .parameter_updates
-            .push(ParameterUpdate {
-                id: info.id(),
-                value,
-            })
-            .unwrap();
+        let update = ParameterUpdate {
+            id: info.id(),
+            value,
+        };
+        // The buffer only fills up if the audio thread is not running
+        if app_inner.parameter_updates.push(update).is_err() {
+            log::warn!("Dropped a parameter change, the audio thread is not processing");
+        }
     }
 }
 
 pub struct StandaloneApp<P: Plugin> {
     app: App,
     editor: P::Editor,
+    parameters: Rc<ParameterMap<P::Parameters>>,
+    app_inner: Rc<RefCell<AppInner>>,
 }
 
 impl<P: Plugin> StandaloneApp<P> {
+    /// `parameters` are the parameters of the engine, see [`AudioEngine::parameters`]
     pub fn new(
+        parameters: Rc<ParameterMap<P::Parameters>>,
         parameter_updates: Producer<ParameterUpdate>,
+        keyboard_events: NoteEventSender,
         _executor: Rc<platform::Executor>,
     ) -> Self {
-        let app_inner = Rc::new(RefCell::new(AppInner { parameter_updates }));
+        let app_inner = Rc::new(RefCell::new(AppInner {
+            parameter_updates,
+            keyboard_events,
+        }));
         let host_handle = StandaloneHostHandle {
             app_inner: app_inner.clone(),
         };
-        let parameters = ParameterMap::new(P::Parameters::new());
-        let mut app_state = AppState::new(parameters);
+        let mut app_state = AppState::new(parameters.clone());
         app_state.set_host_handle(Some(Box::new(host_handle)));
 
         let editor = P::Editor::new(&mut crate::EditorContext {
@@ -65,51 +103,145 @@ impl<P: Plugin> StandaloneApp<P> {
         let state = Rc::new(RefCell::new(app_state));
         let app = App::new_with_app_state(state);
 
-        Self { app, editor }
+        Self {
+            app,
+            editor,
+            parameters,
+            app_inner,
+        }
     }
 
-    pub fn run(mut self) {
-        let editor = self.editor;
-        let _ = Window::open(&mut self.app, {
-            let p = P::Parameters::new();
-            editor.view(&p)
-        });
+    /// Open the editor window, and run until it is closed. The audio and MIDI settings are
+    /// above the editor, and plugins that accept MIDI get an on-screen keyboard below it.
+    /// `status` describes the result of applying `settings` to `engine`.
+    pub fn run(mut self, engine: Rc<RefCell<AudioEngine<P>>>, settings: Settings, status: String) {
+        let view = Column::new((
+            settings_view(engine, settings, status),
+            self.editor.view(self.parameters.parameters_ref()),
+            P::ACCEPTS_MIDI.then(|| self.keyboard()),
+        ));
+        let _ = Window::open(&mut self.app, view);
         self.app.run()
     }
+
+    fn keyboard(&self) -> Piano {
+        let note_on_inner = self.app_inner.clone();
+        let note_off_inner = self.app_inner.clone();
+        Piano::new()
+            .on_note_on(move |_, note| {
+                note_on_inner
+                    .borrow_mut()
+                    .send_note_event(NoteEvent::NoteOn {
+                        channel: 0,
+                        sample_offset: 0,
+                        note,
+                    })
+            })
+            .on_note_off(move |_, note| {
+                note_off_inner
+                    .borrow_mut()
+                    .send_note_event(NoteEvent::NoteOff {
+                        channel: 0,
+                        sample_offset: 0,
+                        note,
+                    })
+            })
+    }
 }
 
-pub struct AudioProcessor<P> {
-    plugin: P,
-    parameter_updates: Consumer<ParameterUpdate>,
+const USAGE: &str = "Usage: [--render <input.wav> <output.wav> [--block-size <samples>]]";
+
+/// Arguments of the offline render mode
+struct RenderArgs {
+    input: PathBuf,
+    output: PathBuf,
+    block_size: usize,
 }
 
-impl<P: Plugin> AudioProcessor<P> {
-    pub fn new(parameter_updates: Consumer<ParameterUpdate>) -> Self {
-        Self {
-            plugin: P::new(crate::HostInfo {
-                name: "Standalone".to_string(),
-            }),
-            parameter_updates,
-        }
+/// Parse the command line. Returns `None` to open the window.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<RenderArgs>, String> {
+    let Some(command) = args.next() else {
+        return Ok(None);
+    };
+    if command != "--render" {
+        return Err(format!("Unknown argument {command}"));
     }
-
-    pub fn start(mut self) {
-        for device in AudioHost::devices().unwrap() {
-            println!("{:?}", device.name())
+    let (Some(input), Some(output)) = (args.next(), args.next()) else {
+        return Err("--render needs an input and an output file".to_string());
+    };
+    let mut block_size = AudioSettings::default().block_size;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--block-size" => {
+                block_size = args
+                    .next()
+                    .and_then(|size| size.parse().ok())
+                    .filter(|&size| size > 0)
+                    .ok_or("--block-size needs a number of samples")?;
+            }
+            _ => return Err(format!("Unknown argument {arg}")),
         }
-
-        let output_device = AudioHost::default_output_device().unwrap();
-        let sample_rate = output_device.sample_rate().unwrap();
-
-        self.plugin.prepare(sample_rate as f64, SAMPLES_PER_BLOCK);
     }
+    Ok(Some(RenderArgs {
+        input: input.into(),
+        output: output.into(),
+        block_size,
+    }))
 }
 
+/// Run the plugin in a window, or render a file through it with
+/// `--render <input.wav> <output.wav>`, without opening a window. The window starts with the
+/// state of the plugin from when it was last closed.
 pub fn standalone_main<P: Plugin>() {
+    match parse_args(std::env::args().skip(1)) {
+        Ok(None) => {}
+        Ok(Some(args)) => {
+            if let Err(err) = render_file::<P>(&args.input, &args.output, args.block_size) {
+                eprintln!("Could not render {}: {err}", args.input.display());
+                std::process::exit(1);
+            }
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            std::process::exit(2);
+        }
+    }
+
     let executor = Rc::new(platform::Executor::new().unwrap());
-    let (producer, consumer) = RingBuffer::new(1024);
-    let processor = AudioProcessor::<P>::new(consumer);
-    let app = StandaloneApp::<P>::new(producer, executor);
-    processor.start();
-    app.run();
+    let (producer, consumer) = RingBuffer::new(PARAMETER_UPDATE_CAPACITY);
+    let (note_events, midi_input_events, keyboard_events) = NoteEventReceiver::new();
+    let processor = AudioProcessor::<P>::new(consumer, note_events);
+    let engine = Rc::new(RefCell::new(AudioEngine::new(processor, midi_input_events)));
+    let settings = Settings::load(P::NAME);
+    // Errors are shown in the settings, so that the editor stays usable without audio, e.g.
+    // when the device is busy
+    let status = apply_settings(&mut engine.borrow_mut(), &settings);
+    let parameters = engine.borrow().parameters().clone();
+    let app = StandaloneApp::<P>::new(parameters, producer, keyboard_events, executor);
+    let state_path = last_state_path(P::NAME);
+    if let Some(path) = state_path.as_deref().filter(|path| path.exists()) {
+        match PluginState::load(path) {
+            // Before the window is opened, so the editor doesn't need to be notified
+            Ok(state) => {
+                engine.borrow().load_state(&state);
+            }
+            Err(err) => log::warn!("Could not restore the state from {}: {err}", path.display()),
+        }
+    }
+
+    app.run(engine.clone(), settings, status);
+    engine.borrow_mut().stop();
+
+    if let Some(path) = state_path {
+        let state = engine.borrow().state();
+        let saved = match path.parent() {
+            Some(dir) => std::fs::create_dir_all(dir).map_err(StateError::from),
+            None => Ok(()),
+        }
+        .and_then(|()| state.save(&path));
+        if let Err(err) = saved {
+            log::error!("Could not save the state to {}: {err}", path.display());
+        }
+    }
 }
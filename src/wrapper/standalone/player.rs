@@ -0,0 +1,257 @@
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::{
+    AudioBuffer,
+    util::{SampleFormat, WavError, WavFile, WavSpec, WavWriter},
+};
+
+/// Seconds of audio that can be waiting for the recording thread
+const RECORDING_BUFFER_SECONDS: f64 = 2.0;
+/// How long the recording thread sleeps when it has written everything
+const RECORDING_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Plays a WAV file as the input of the plugin. Shared by the audio thread and the main
+/// thread, which loads files and controls the transport.
+pub struct FilePlayer {
+    file: Option<WavFile>,
+    /// Position in samples of the file, which has its own sample rate
+    position: f64,
+    playing: bool,
+    looping: bool,
+}
+
+impl FilePlayer {
+    pub fn new() -> Self {
+        Self {
+            file: None,
+            position: 0.0,
+            playing: false,
+            looping: true,
+        }
+    }
+
+    /// Replace the file, and return the previous one so that it isn't dropped while the
+    /// player is locked
+    pub fn load(&mut self, file: Option<WavFile>) -> Option<WavFile> {
+        self.position = 0.0;
+        self.playing = false;
+        std::mem::replace(&mut self.file, file)
+    }
+
+    pub fn has_file(&self) -> bool {
+        self.file.is_some()
+    }
+
+    pub fn play(&mut self) {
+        self.playing = self.file.is_some();
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Pause, and go back to the start of the file
+    pub fn stop(&mut self) {
+        self.playing = false;
+        self.position = 0.0;
+    }
+
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// Fill `output` with the next samples of the file, played at `sample_rate`. Mono files
+    /// are played on all channels, and the output is silent while paused.
+    pub fn read(&mut self, output: &mut AudioBuffer, sample_rate: f64) {
+        output.clear();
+        let Some(file) = &self.file else {
+            return;
+        };
+        let len = file.len();
+        if !self.playing || len == 0 || file.channels.is_empty() {
+            return;
+        }
+
+        let step = file.sample_rate as f64 / sample_rate;
+        for mut frame in output.frames_mut() {
+            if self.position >= len as f64 {
+                if self.looping {
+                    self.position %= len as f64;
+                } else {
+                    self.stop();
+                    return;
+                }
+            }
+            // Linear interpolation, which is good enough for auditioning
+            let index = self.position as usize;
+            let fraction = (self.position - index as f64) as f32;
+            let next = if index + 1 < len {
+                index + 1
+            } else if self.looping {
+                0
+            } else {
+                index
+            };
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let samples = &file.channels[channel % file.channels.len()];
+                let current = samples.get(index).copied().unwrap_or(0.0);
+                let next = samples.get(next).copied().unwrap_or(0.0);
+                *sample = current + (next - current) * fraction;
+            }
+            self.position += step;
+        }
+    }
+}
+
+impl Default for FilePlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The audio thread side of a recording, which sends the samples to the recording thread
+pub struct RecordingSender {
+    samples: Producer<f32>,
+    dropped_samples: bool,
+}
+
+impl RecordingSender {
+    /// Send the samples of `buffer`, interleaved. Blocks that don't fit are dropped, when
+    /// the recording thread can't keep up.
+    pub fn send(&mut self, buffer: &AudioBuffer) {
+        if self.samples.slots() < buffer.samples() * buffer.channels() {
+            self.dropped_samples = true;
+            return;
+        }
+        for frame in buffer.frames() {
+            for sample in frame.iter() {
+                let _ = self.samples.push(sample);
+            }
+        }
+    }
+
+    /// Whether samples were dropped since the recording started
+    pub fn dropped_samples(&self) -> bool {
+        self.dropped_samples
+    }
+}
+
+/// Writes the output of the plugin to a WAV file, from a thread that is fed by the audio
+/// thread
+pub struct Recorder {
+    path: PathBuf,
+    thread: JoinHandle<Result<(), WavError>>,
+}
+
+impl Recorder {
+    /// Create the file, and start the thread that writes to it. The samples for the file are
+    /// sent through the returned sender, and the recording ends when it is dropped.
+    pub fn start(
+        path: impl Into<PathBuf>,
+        sample_rate: f64,
+        channels: usize,
+    ) -> Result<(Self, RecordingSender), WavError> {
+        let path = path.into();
+        let spec = WavSpec {
+            channels: channels as u16,
+            sample_rate: sample_rate.round() as u32,
+            sample_format: SampleFormat::Float32,
+        };
+        let writer = WavWriter::new(BufWriter::new(File::create(&path)?), spec)?;
+        let capacity = (sample_rate * RECORDING_BUFFER_SECONDS) as usize * channels.max(1);
+        let (samples, receiver) = RingBuffer::new(capacity);
+        let sender = RecordingSender {
+            samples,
+            dropped_samples: false,
+        };
+        let thread = std::thread::Builder::new()
+            .name("recording".to_string())
+            .spawn(move || write_recording(writer, receiver))?;
+        Ok((Self { path, thread }, sender))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Wait until everything has been written. The sender must have been dropped.
+    pub fn finish(self) -> Result<PathBuf, WavError> {
+        match self.thread.join() {
+            Ok(result) => result.map(|()| self.path),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+fn write_recording(
+    mut writer: WavWriter<BufWriter<File>>,
+    mut receiver: Consumer<f32>,
+) -> Result<(), WavError> {
+    let channels = writer.spec().channels.max(1) as usize;
+    loop {
+        // Checked first, so that the samples sent before the sender was dropped are written
+        let abandoned = receiver.is_abandoned();
+        // Read whole frames, so that the channels stay in place
+        let available = receiver.slots() / channels * channels;
+        if available == 0 {
+            if abandoned {
+                break;
+            }
+            std::thread::sleep(RECORDING_POLL_INTERVAL);
+            continue;
+        }
+        let chunk = receiver
+            .read_chunk(available)
+            .expect("The samples are available");
+        let (first, second) = chunk.as_slices();
+        writer.write_interleaved(first)?;
+        writer.write_interleaved(second)?;
+        chunk.commit_all();
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::OwnedAudioBuffer;
+
+    #[test]
+    fn player_loops_and_resamples() {
+        let mut player = FilePlayer::new();
+        let file = WavFile::new(24000, SampleFormat::Float32, vec![vec![0.0, 1.0, 2.0, 3.0]]);
+        player.load(Some(file));
+        let mut buffer = OwnedAudioBuffer::with_capacity(2, 6);
+
+        // Silent until played
+        player.read(&mut buffer.as_audio_buffer(6), 48000.0);
+        assert_eq!(buffer.channel(0), [0.0; 6]);
+
+        // Played at twice the sample rate of the file, with the mono file on both channels
+        player.play();
+        player.read(&mut buffer.as_audio_buffer(6), 48000.0);
+        assert_eq!(buffer.channel(0), [0.0, 0.5, 1.0, 1.5, 2.0, 2.5]);
+        assert_eq!(buffer.channel(1), buffer.channel(0));
+        // The last sample is interpolated with the start of the file when looping
+        player.read(&mut buffer.as_audio_buffer(4), 48000.0);
+        assert_eq!(buffer.channel(0)[..4], [3.0, 1.5, 0.0, 0.5]);
+
+        player.set_looping(false);
+        player.stop();
+        player.play();
+        player.read(&mut buffer.as_audio_buffer(6), 48000.0);
+        player.read(&mut buffer.as_audio_buffer(6), 48000.0);
+        assert_eq!(buffer.channel(0), [3.0, 3.0, 0.0, 0.0, 0.0, 0.0]);
+        // Stopped at the end of the file
+        player.read(&mut buffer.as_audio_buffer(6), 48000.0);
+        assert_eq!(buffer.channel(0), [0.0; 6]);
+    }
+}
@@ -0,0 +1,116 @@
+use std::{path::Path, time::Duration};
+
+use crate::{
+    Plugin, ProcessMode,
+    testing::TestHost,
+    util::{WavError, WavFile},
+};
+
+/// The longest tail that is rendered after the end of the input, for plugins with a long or
+/// infinite tail
+const MAX_TAIL: Duration = Duration::from_secs(10);
+
+/// Process the WAV file at `input` with the plugin, offline and as fast as possible, and write
+/// the result to `output` in the same format. The output is compensated for the latency of
+/// the plugin, and includes its tail.
+pub fn render_file<P: Plugin>(
+    input: &Path,
+    output: &Path,
+    block_size: usize,
+) -> Result<(), WavError> {
+    let file = WavFile::open(input)?;
+    let rendered = render::<P>(&file, block_size);
+    WavFile::new(file.sample_rate, file.sample_format, rendered).save(output)
+}
+
+fn render<P: Plugin>(file: &WavFile, block_size: usize) -> Vec<Vec<f32>> {
+    let sample_rate = file.sample_rate as f64;
+    let mut host = TestHost::<P>::new(sample_rate, block_size);
+    host.set_process_mode(ProcessMode::Offline);
+    // Offline processing may allocate
+    host.set_check_realtime(false);
+
+    // Mono files are played on all channels. Plugins without inputs still get one channel
+    // of the file, to define the length of the output.
+    let num_inputs = host.input_channels().max(1);
+    let input: Vec<&[f32]> = if file.channels.is_empty() {
+        Vec::new()
+    } else {
+        (0..num_inputs)
+            .map(|channel| file.channels[channel % file.channels.len()].as_slice())
+            .collect()
+    };
+    let mut output = host.process(&input);
+
+    let tail = host.plugin().tail_time().min(MAX_TAIL);
+    let latency = host.latency_samples();
+    let extra = latency + (tail.as_secs_f64() * sample_rate).ceil() as usize;
+    let rest = host.process_silence(extra);
+    for (channel, rest) in output.iter_mut().zip(rest) {
+        channel.extend(rest);
+        channel.drain(..latency.min(channel.len()));
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        AudioLayout, Bus, ChannelType, GenericEditor, HostInfo, ProcessContext, util::SampleFormat,
+    };
+
+    /// Delays the input by two samples, and reports it as latency
+    struct Delay {
+        delayed: [f32; 2],
+    }
+
+    impl Plugin for Delay {
+        const NAME: &'static str = "Delay";
+        const VENDOR: &'static str = "audioplug";
+        const URL: &'static str = "";
+        const EMAIL: &'static str = "";
+        const AUDIO_LAYOUT: AudioLayout = AudioLayout {
+            main_input: Some(Bus {
+                name: "Input",
+                channel: ChannelType::Mono,
+            }),
+            main_output: Some(Bus {
+                name: "Output",
+                channel: ChannelType::Mono,
+            }),
+        };
+        type Editor = GenericEditor<()>;
+        type Parameters = ();
+
+        fn new(_info: HostInfo) -> Self {
+            Self { delayed: [0.0; 2] }
+        }
+
+        fn prepare(&mut self, _sample_rate: f64, _max_buffer_size: usize) {}
+
+        fn process(&mut self, context: ProcessContext, _parameters: &()) {
+            context.output.copy_from(context.input);
+            for sample in context.output.channel_mut(0).iter_mut() {
+                let input = *sample;
+                *sample = self.delayed[0];
+                self.delayed = [self.delayed[1], input];
+            }
+        }
+
+        fn latency_samples(&self) -> usize {
+            2
+        }
+
+        fn tail_time(&self) -> Duration {
+            Duration::from_secs_f64(3.0 / 48000.0)
+        }
+    }
+
+    #[test]
+    fn render_compensates_latency_and_adds_the_tail() {
+        let file = WavFile::new(48000, SampleFormat::Float32, vec![vec![1.0, 2.0, 3.0, 4.0]]);
+        let output = render::<Delay>(&file, 3);
+        assert_eq!(output, [[1.0, 2.0, 3.0, 4.0, 0.0, 0.0, 0.0]]);
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+/// The block sizes offered in the settings
+pub const BLOCK_SIZES: [usize; 7] = [32, 64, 128, 256, 512, 1024, 2048];
+const DEFAULT_BLOCK_SIZE: usize = 128;
+
+/// The settings of the audio stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioSettings {
+    /// Id of the output device, or the default device if `None`
+    pub output_device: Option<String>,
+    /// Id of the input device, or the default device if `None`
+    pub input_device: Option<String>,
+    /// The preferred sample rate of the output device is used if `None`
+    pub sample_rate: Option<f64>,
+    pub block_size: usize,
+    /// The input device channel of each input channel of the plugin. Channels that are not in
+    /// the list get the device channel with the same index.
+    pub input_channels: Vec<usize>,
+}
+
+impl AudioSettings {
+    /// The device channel of each of the `num_channels` plugin input channels
+    pub fn input_channel_map(&self, num_channels: usize) -> Vec<usize> {
+        (0..num_channels)
+            .map(|channel| self.input_channels.get(channel).copied().unwrap_or(channel))
+            .collect()
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            output_device: None,
+            input_device: None,
+            sample_rate: None,
+            block_size: DEFAULT_BLOCK_SIZE,
+            input_channels: Vec::new(),
+        }
+    }
+}
+
+/// The settings of the standalone wrapper, which are kept between runs.
+///
+/// They are saved as `key = value` lines. Unknown keys are ignored, so that files written by
+/// other versions can still be read.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    pub audio: AudioSettings,
+    /// Name of the MIDI input device, or the default device if `None`
+    pub midi_device: Option<String>,
+}
+
+impl Settings {
+    /// Load the settings of the plugin from the config directory. The defaults are used if
+    /// there are no saved settings.
+    pub fn load(plugin_name: &str) -> Self {
+        let Some(path) = settings_path(plugin_name) else {
+            return Self::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                log::warn!("Could not read {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, plugin_name: &str) -> std::io::Result<()> {
+        let path = settings_path(plugin_name).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "The config directory is not known",
+            )
+        })?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_text())
+    }
+
+    fn parse(text: &str) -> Self {
+        let mut settings = Self::default();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                log::warn!("Invalid line in the settings: {line}");
+                continue;
+            };
+            let value = value.trim();
+            let audio = &mut settings.audio;
+            let valid = match key.trim() {
+                "output_device" => set(&mut audio.output_device, Some(Some(value.to_string()))),
+                "input_device" => set(&mut audio.input_device, Some(Some(value.to_string()))),
+                "sample_rate" => set(&mut audio.sample_rate, value.parse().ok().map(Some)),
+                "block_size" => set(
+                    &mut audio.block_size,
+                    value.parse().ok().filter(|&size| size > 0),
+                ),
+                "input_channels" => set(
+                    &mut audio.input_channels,
+                    value
+                        .split(',')
+                        .map(|channel| channel.trim().parse().ok())
+                        .collect(),
+                ),
+                "midi_device" => set(&mut settings.midi_device, Some(Some(value.to_string()))),
+                _ => true,
+            };
+            if !valid {
+                log::warn!("Invalid value in the settings: {line}");
+            }
+        }
+        settings
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        let mut line = |key: &str, value: &dyn std::fmt::Display| {
+            let _ = writeln!(text, "{key} = {value}");
+        };
+        let audio = &self.audio;
+        if let Some(device) = &audio.output_device {
+            line("output_device", device);
+        }
+        if let Some(device) = &audio.input_device {
+            line("input_device", device);
+        }
+        if let Some(sample_rate) = audio.sample_rate {
+            line("sample_rate", &sample_rate);
+        }
+        line("block_size", &audio.block_size);
+        if !audio.input_channels.is_empty() {
+            let channels: Vec<String> = audio.input_channels.iter().map(usize::to_string).collect();
+            line("input_channels", &channels.join(", "));
+        }
+        if let Some(device) = &self.midi_device {
+            line("midi_device", device);
+        }
+        text
+    }
+}
+
+/// Set `field` to `value` if it was parsed, and return whether it was
+fn set<T>(field: &mut T, value: Option<T>) -> bool {
+    match value {
+        Some(value) => {
+            *field = value;
+            true
+        }
+        None => false,
+    }
+}
+
+fn settings_path(plugin_name: &str) -> Option<PathBuf> {
+    Some(plugin_config_dir(plugin_name)?.join("standalone.conf"))
+}
+
+/// The state of the plugin when the standalone app was last closed
+pub fn last_state_path(plugin_name: &str) -> Option<PathBuf> {
+    Some(plugin_config_dir(plugin_name)?.join("last.state"))
+}
+
+fn plugin_config_dir(plugin_name: &str) -> Option<PathBuf> {
+    // Keep the name usable as a directory name
+    let dir_name: String = plugin_name
+        .chars()
+        .map(|c| if std::path::is_separator(c) { '_' } else { c })
+        .collect();
+    Some(config_dir()?.join(dir_name))
+}
+
+/// The directory for the configuration files of the user
+fn config_dir() -> Option<PathBuf> {
+    let env_dir = |name| {
+        std::env::var_os(name)
+            .map(PathBuf::from)
+            .filter(|path: &PathBuf| path.is_absolute())
+    };
+    if cfg!(target_os = "windows") {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        Some(env_dir("HOME")?.join(Path::new("Library/Application Support")))
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| Some(env_dir("HOME")?.join(".config")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip() {
+        let settings = Settings {
+            audio: AudioSettings {
+                output_device: Some("hw:CARD=PCH,DEV=0".to_string()),
+                input_device: None,
+                sample_rate: Some(44100.0),
+                block_size: 256,
+                input_channels: vec![1, 0],
+            },
+            midi_device: Some("Keystation: MIDI 1".to_string()),
+        };
+        assert_eq!(Settings::parse(&settings.to_text()), settings);
+        assert_eq!(settings.audio.input_channel_map(3), [1, 0, 2]);
+
+        // Invalid values and unknown keys keep the defaults
+        let parsed =
+            Settings::parse("block_size = many\ncolor = blue\n# comment\nsample_rate=96000");
+        assert_eq!(
+            parsed,
+            Settings {
+                audio: AudioSettings {
+                    sample_rate: Some(96000.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        );
+    }
+}
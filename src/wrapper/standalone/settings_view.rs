@@ -0,0 +1,472 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use super::{
+    AudioEngine, Settings,
+    engine::{find_device, input_channels},
+    settings::BLOCK_SIZES,
+};
+use crate::{
+    Plugin, PluginState,
+    core::{Color, Size},
+    platform::{AudioHost, MidiHost},
+    ui::{
+        CallbackContext, View,
+        prelude::*,
+        style::{Length, UiRect},
+    },
+    views::{Button, Column, Dropdown, Label, Row, Stateful, TextBox},
+};
+
+/// The most device channels offered for each input channel of the plugin
+const MAX_DEVICE_CHANNELS: usize = 32;
+
+/// The settings are applied to the engine and saved as soon as they are changed
+struct SettingsModel<P: Plugin> {
+    engine: Rc<RefCell<AudioEngine<P>>>,
+    settings: Var<Settings>,
+    status: Var<String>,
+    recording: Var<bool>,
+}
+
+impl<P: Plugin> Clone for SettingsModel<P> {
+    fn clone(&self) -> Self {
+        Self {
+            engine: self.engine.clone(),
+            settings: self.settings,
+            status: self.status,
+            recording: self.recording,
+        }
+    }
+}
+
+impl<P: Plugin> SettingsModel<P> {
+    fn change(&self, cx: &mut CallbackContext, f: impl FnOnce(&mut Settings)) {
+        let mut settings = self.settings.get_untracked(cx);
+        f(&mut settings);
+        let status = apply_settings(&mut self.engine.borrow_mut(), &settings);
+        self.status.set(cx, status);
+        // Opening the stream again stops the recording
+        self.recording.set(cx, self.engine.borrow().is_recording());
+        if let Err(err) = settings.save(P::NAME) {
+            log::error!("Could not save the settings: {err}");
+        }
+        self.settings.set(cx, settings);
+    }
+
+    /// The settings the engine is running with. Menus are built outside of a reactive
+    /// context, so they can't read the settings variable.
+    fn current(&self) -> Settings {
+        self.engine.borrow().settings().cloned().unwrap_or_default()
+    }
+
+    /// A dropdown showing `label`, with an item for each of `items` that applies its change
+    fn dropdown<L, I, F>(&self, label: L, items: I) -> impl View + use<P, L, I, F>
+    where
+        L: Fn(&Settings) -> String + 'static,
+        I: Fn(&Settings) -> Vec<(String, F)> + 'static,
+        F: Fn(&mut Settings) + Clone + 'static,
+    {
+        let model = self.clone();
+        Dropdown::new(Label::new(self.settings.map(label)), move || {
+            let items = items(&model.current())
+                .into_iter()
+                .map(|(name, f)| {
+                    let model = model.clone();
+                    Button::new(Label::new(name)).on_click(move |cx| model.change(cx, f.clone()))
+                })
+                .collect::<Vec<_>>();
+            Column::new(items).spacing(Length::Px(2.0)).style(|s, _| {
+                s.corner_radius(Size::splat(5.0))
+                    .background(Color::from_rgb8(0x50, 0x50, 0x50))
+                    .padding(UiRect::all_px(5.0));
+            })
+        })
+    }
+}
+
+/// Open the stream and the MIDI input with `settings`, and describe the result
+pub fn apply_settings<P: Plugin>(engine: &mut AudioEngine<P>, settings: &Settings) -> String {
+    match engine.apply(settings) {
+        Ok(()) => match engine.stream_config() {
+            Some(config) => format!("{} Hz, {} samples", config.sample_rate, config.block_size),
+            None => "Not running".to_string(),
+        },
+        Err(err) => {
+            log::error!("Could not start the audio stream: {err}");
+            format!("Audio is not available: {err}")
+        }
+    }
+}
+
+/// A button that opens the settings of the audio devices and the MIDI input, with the status
+/// of the audio stream next to it
+pub fn settings_view<P: Plugin>(
+    engine: Rc<RefCell<AudioEngine<P>>>,
+    settings: Settings,
+    status: String,
+) -> impl View {
+    Stateful::new(move |cx| {
+        let open = Var::new(cx, false);
+        let model = SettingsModel {
+            engine,
+            settings: Var::new(cx, settings),
+            status: Var::new(cx, status),
+            recording: Var::new(cx, false),
+        };
+        let file_path = Var::new(cx, String::new());
+        let looping = Var::new(cx, true);
+        let recording_path = Var::new(cx, format!("{} recording.wav", P::NAME));
+        let state_path = Var::new(cx, format!("{}.state", P::NAME));
+
+        let output_device = model.dropdown(
+            |settings| device_label(settings.audio.output_device.as_deref()),
+            |_| {
+                device_items(true)
+                    .into_iter()
+                    .map(|(name, id)| {
+                        (name, move |s: &mut Settings| {
+                            s.audio.output_device = id.clone()
+                        })
+                    })
+                    .collect()
+            },
+        );
+        let input_device = model.dropdown(
+            |settings| device_label(settings.audio.input_device.as_deref()),
+            |_| {
+                device_items(false)
+                    .into_iter()
+                    .map(|(name, id)| {
+                        (name, move |s: &mut Settings| {
+                            s.audio.input_device = id.clone()
+                        })
+                    })
+                    .collect()
+            },
+        );
+        let sample_rate = model.dropdown(
+            |settings| match settings.audio.sample_rate {
+                Some(sample_rate) => format!("{sample_rate} Hz"),
+                None => "Device default".to_string(),
+            },
+            |settings| {
+                let rates = find_device(settings.audio.output_device.as_deref(), true)
+                    .and_then(|device| device.sample_rates())
+                    .unwrap_or_else(|err| {
+                        log::warn!("Could not get the sample rates: {err}");
+                        Vec::new()
+                    });
+                std::iter::once(None)
+                    .chain(rates.into_iter().map(Some))
+                    .map(|rate| {
+                        let name =
+                            rate.map_or("Device default".to_string(), |rate| format!("{rate} Hz"));
+                        (name, move |s: &mut Settings| s.audio.sample_rate = rate)
+                    })
+                    .collect()
+            },
+        );
+        let block_size = model.dropdown(
+            |settings| format!("{} samples", settings.audio.block_size),
+            |_| {
+                BLOCK_SIZES
+                    .into_iter()
+                    .map(|size| {
+                        (format!("{size} samples"), move |s: &mut Settings| {
+                            s.audio.block_size = size
+                        })
+                    })
+                    .collect()
+            },
+        );
+        let midi_device = model.dropdown(
+            |settings| {
+                settings
+                    .midi_device
+                    .clone()
+                    .unwrap_or_else(|| "Default".to_string())
+            },
+            |_| {
+                let devices = MidiHost::devices().unwrap_or_else(|err| {
+                    log::warn!("Could not list the MIDI devices: {err}");
+                    Vec::new()
+                });
+                std::iter::once(None)
+                    .chain(devices.iter().map(|device| Some(device.id())))
+                    .map(|id| {
+                        let name = id.clone().unwrap_or_else(|| "Default".to_string());
+                        (name, move |s: &mut Settings| s.midi_device = id.clone())
+                    })
+                    .collect()
+            },
+        );
+
+        let has_input = input_channels::<P>() > 0;
+        let input_channel_rows: Vec<_> = (0..input_channels::<P>())
+            .map(|channel| {
+                setting_row(
+                    format!("Input {}", channel + 1),
+                    model.dropdown(
+                        move |settings| {
+                            let device_channel =
+                                settings.audio.input_channel_map(channel + 1)[channel];
+                            format!("Device channel {}", device_channel + 1)
+                        },
+                        move |settings| {
+                            let num_channels =
+                                find_device(settings.audio.input_device.as_deref(), false)
+                                    .and_then(|device| device.max_channels())
+                                    .unwrap_or_else(|err| {
+                                        log::warn!("Could not get the input channels: {err}");
+                                        0
+                                    });
+                            (0..num_channels.min(MAX_DEVICE_CHANNELS))
+                                .map(|device_channel| {
+                                    (
+                                        format!("Device channel {}", device_channel + 1),
+                                        move |s: &mut Settings| {
+                                            let num_channels = input_channels::<P>();
+                                            let mut map = s.audio.input_channel_map(num_channels);
+                                            map[channel] = device_channel;
+                                            s.audio.input_channels = map;
+                                        },
+                                    )
+                                })
+                                .collect()
+                        },
+                    ),
+                )
+            })
+            .collect();
+
+        Column::new((
+            Row::new((
+                state_menu(&model, state_path),
+                Button::new(Label::new("Audio settings"))
+                    .on_click(move |cx| open.update(cx, |_, open| *open = !*open)),
+                Label::new(model.status),
+            ))
+            .v_align_center()
+            .spacing(Length::Px(10.0)),
+            has_input.then(|| player_controls(&model, file_path, looping)),
+            recorder_controls(&model, recording_path),
+            Column::new((
+                setting_row("Output device", output_device),
+                has_input.then(|| setting_row("Input device", input_device)),
+                input_channel_rows,
+                setting_row("Sample rate", sample_rate),
+                setting_row("Block size", block_size),
+                P::ACCEPTS_MIDI.then(|| setting_row("MIDI input", midi_device)),
+            ))
+            .spacing(Length::Px(5.0))
+            .style(move |s, _| {
+                s.hidden(open.map(|open| !open));
+            }),
+        ))
+        .spacing(Length::Px(5.0))
+        .style(|s, _| {
+            s.padding(UiRect::all_px(5.0));
+        })
+    })
+}
+
+/// A File menu that opens and saves the state of the plugin, from and to the file in the text
+/// box next to it
+fn state_menu<P: Plugin>(model: &SettingsModel<P>, path: Var<String>) -> impl View + use<P> {
+    let model = model.clone();
+    let menu = Dropdown::new(Label::new("File"), move || {
+        let open = {
+            let model = model.clone();
+            move |cx: &mut CallbackContext| {
+                let path = path.get_untracked(cx);
+                let status = match PluginState::load(&path) {
+                    Ok(state) => {
+                        for id in model.engine.borrow().load_state(&state) {
+                            cx.write_context().notify_parameter_subscribers(id);
+                        }
+                        format!("Opened {path}")
+                    }
+                    Err(err) => format!("Could not open {path}: {err}"),
+                };
+                model.status.set(cx, status);
+            }
+        };
+        let save = {
+            let model = model.clone();
+            move |cx: &mut CallbackContext| {
+                let path = path.get_untracked(cx);
+                let state = model.engine.borrow().state();
+                let status = match state.save(&path) {
+                    Ok(()) => format!("Saved {path}"),
+                    Err(err) => format!("Could not save {path}: {err}"),
+                };
+                model.status.set(cx, status);
+            }
+        };
+        Column::new((
+            Button::new(Label::new("Open")).on_click(open),
+            Button::new(Label::new("Save")).on_click(save),
+        ))
+        .spacing(Length::Px(2.0))
+        .style(|s, _| {
+            s.corner_radius(Size::splat(5.0))
+                .background(Color::from_rgb8(0x50, 0x50, 0x50))
+                .padding(UiRect::all_px(5.0));
+        })
+    });
+
+    Row::new((
+        menu,
+        TextBox::new(move |cx, text| path.set(cx, text.to_string()))
+            .value(path)
+            .style(|s, _| {
+                s.width(Length::Px(200.0));
+            }),
+    ))
+    .v_align_center()
+    .spacing(Length::Px(5.0))
+}
+
+/// Load a WAV file to play instead of the audio input, with transport controls
+fn player_controls<P: Plugin>(
+    model: &SettingsModel<P>,
+    path: Var<String>,
+    looping: Var<bool>,
+) -> impl View + use<P> {
+    let transport_button = |label: &str, f: fn(&mut AudioEngine<P>, &str) -> Option<String>| {
+        let model = model.clone();
+        Button::new(Label::new(label)).on_click(move |cx| {
+            let path = path.get_untracked(cx);
+            if let Some(status) = f(&mut model.engine.borrow_mut(), &path) {
+                model.status.set(cx, status);
+            }
+        })
+    };
+
+    Row::new((
+        Label::new("File"),
+        TextBox::new(move |cx, text| path.set(cx, text.to_string()))
+            .placeholder("WAV file to play")
+            .style(|s, _| {
+                s.width(Length::Px(300.0));
+            }),
+        transport_button("Load", |engine, path| {
+            Some(match engine.load_file(Path::new(path)) {
+                Ok(()) => format!("Loaded {path}"),
+                Err(err) => format!("Could not load {path}: {err}"),
+            })
+        }),
+        transport_button("Play", |engine, _| {
+            let mut player = engine.player();
+            player.play();
+            (!player.has_file()).then(|| "Load a file to play".to_string())
+        }),
+        transport_button("Pause", |engine, _| {
+            engine.player().pause();
+            None
+        }),
+        transport_button("Stop", |engine, _| {
+            engine.player().stop();
+            None
+        }),
+        Button::new(Label::new(looping.map(|&looping| {
+            if looping { "Loop: on" } else { "Loop: off" }.to_string()
+        })))
+        .on_click({
+            let engine = model.engine.clone();
+            move |cx| {
+                let looping_now = !looping.get_untracked(cx);
+                engine.borrow().player().set_looping(looping_now);
+                looping.set(cx, looping_now);
+            }
+        }),
+        transport_button("Audio input", |engine, _| {
+            engine.unload_file();
+            Some("Playing the audio input".to_string())
+        }),
+    ))
+    .v_align_center()
+    .spacing(Length::Px(5.0))
+}
+
+/// Record the output of the plugin to a WAV file
+fn recorder_controls<P: Plugin>(model: &SettingsModel<P>, path: Var<String>) -> impl View + use<P> {
+    let recording = model.recording;
+    let model = model.clone();
+    Row::new((
+        Label::new("Record to"),
+        TextBox::new(move |cx, text| path.set(cx, text.to_string()))
+            .value(path)
+            .style(|s, _| {
+                s.width(Length::Px(300.0));
+            }),
+        Button::new(Label::new(recording.map(|&recording| {
+            if recording {
+                "Stop recording"
+            } else {
+                "Record"
+            }
+            .to_string()
+        })))
+        .on_click(move |cx| {
+            let mut engine = model.engine.borrow_mut();
+            let status = match engine.stop_recording() {
+                Some(Ok(path)) => format!("Recorded {}", path.display()),
+                Some(Err(err)) => format!("Error while recording: {err}"),
+                None => {
+                    let path = path.get_untracked(cx);
+                    match engine.start_recording(&path) {
+                        Ok(()) => format!("Recording to {path}"),
+                        Err(err) => format!("Could not record to {path}: {err}"),
+                    }
+                }
+            };
+            model.status.set(cx, status);
+            model.recording.set(cx, engine.is_recording());
+        }),
+    ))
+    .v_align_center()
+    .spacing(Length::Px(5.0))
+}
+
+fn setting_row(label: impl Into<String>, control: impl View) -> impl View {
+    Row::new((
+        Label::new(label.into()).style(|s, _| {
+            s.width(Length::Px(120.0));
+        }),
+        control,
+    ))
+    .v_align_center()
+}
+
+fn device_label(id: Option<&str>) -> String {
+    let Some(id) = id else {
+        return "Default".to_string();
+    };
+    match AudioHost::devices() {
+        Ok(devices) => devices
+            .into_iter()
+            .find(|device| device.id().is_ok_and(|device_id| device_id == id))
+            .and_then(|device| device.name().ok())
+            .unwrap_or_else(|| id.to_string()),
+        Err(_) => id.to_string(),
+    }
+}
+
+/// The name and id of the devices in one direction, after the default device
+fn device_items(output: bool) -> Vec<(String, Option<String>)> {
+    let devices = AudioHost::devices().unwrap_or_else(|err| {
+        log::warn!("Could not list the audio devices: {err}");
+        Vec::new()
+    });
+    let mut items = vec![("Default".to_string(), None)];
+    for device in devices {
+        if (output && !device.is_output()) || (!output && !device.is_input()) {
+            continue;
+        }
+        if let (Ok(name), Ok(id)) = (device.name(), device.id()) {
+            items.push((name, Some(id)));
+        }
+    }
+    items
+}
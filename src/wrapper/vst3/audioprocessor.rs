@@ -2,38 +2,59 @@ use atomic_refcell::AtomicRefCell;
 use std::mem::MaybeUninit;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 use vst3::ComRef;
 use vst3::Steinberg::Vst::BusInfo_::BusFlags_;
 use vst3::Steinberg::Vst::Event_::EventTypes_;
+use vst3::Steinberg::Vst::ProcessContext_::StatesAndFlags_;
 use vst3::Steinberg::Vst::{
-    BusDirection, BusDirections_, BusInfo, BusTypes_, IAttributeListTrait, IAudioProcessor,
-    IAudioProcessorTrait, IComponent, IComponentTrait, IConnectionPoint, IConnectionPointTrait,
-    IEventListTrait, IMessage, IMessageTrait, IParamValueQueueTrait as _, IParameterChangesTrait,
-    IoMode, MediaType, MediaTypes_, ProcessData, ProcessModes_, ProcessSetup, RoutingInfo,
-    SpeakerArr, SpeakerArrangement, SymbolicSampleSizes_,
+    AudioBusBuffers, BusDirection, BusDirections_, BusInfo, BusTypes_, IAttributeListTrait,
+    IAudioProcessor, IAudioProcessorTrait, IComponent, IComponentTrait, IConnectionPoint,
+    IConnectionPointTrait, IEventListTrait, IMessage, IMessageTrait, IParamValueQueueTrait as _,
+    IParameterChangesTrait, IoMode, MediaType, MediaTypes_, ProcessData, ProcessModes,
+    ProcessModes_, ProcessSetup, RestartFlags_, RoutingInfo, SpeakerArr, SpeakerArrangement,
+    SymbolicSampleSizes_,
 };
 use vst3::Steinberg::{
     FUnknown, IBStream, IPluginBase, IPluginBaseTrait, TBool, TUID, kInvalidArgument,
     kNotImplemented, kNotInitialized, kResultFalse, kResultOk, tresult,
 };
 
-use super::util::strcpyw;
+use super::util::{Stream, strcpyw};
 use crate::midi::{Note, NoteEvent};
 use crate::param::{AnyParameterMap, NormalizedValue, ParameterId, ParameterMap, Params};
+use crate::util::{assert_no_alloc, assert_realtime};
+use crate::wrapper::bypass::Bypass;
+use crate::wrapper::custom_state::CustomState;
 use crate::wrapper::vst3::host_application::HostApplication;
 use crate::wrapper::vst3::shared_state::{SHARED_STATE_MSG_ID, SharedState};
 use crate::wrapper::vst3::util::tuid_from_uuid;
-use crate::{AudioBuffer, HostInfo, MidiProcessContext, ProcessContext, ProcessInfo, VST3Plugin};
+use crate::wrapper::{process_deadline, tail_samples};
+use crate::{
+    AudioBuffer, HostInfo, LoopRange, MidiProcessContext, PluginState, ProcessInfo, ProcessMode,
+    TimeSignature, Transport, VST3Plugin,
+};
 
 struct Inner<P> {
     plugin: P,
     host_context: HostApplication,
+    bypass: Bypass<f32>,
+    bypass_f64: Bypass<f64>,
+    sample_rate: f64,
+    process_mode: ProcessMode,
 }
 
 pub struct AudioProcessor<P: VST3Plugin> {
     inner: AtomicRefCell<Option<Inner<P>>>,
     parameters: Rc<ParameterMap<P::Parameters>>,
     shared_state: Arc<SharedState>,
+    /// The latency last reported to the host. Kept outside of `inner`, which is borrowed by
+    /// the audio thread, since the host asks for it on the main thread.
+    latency: AtomicU32,
+    /// The tail in samples, updated in `setupProcessing` and after each block
+    tail: AtomicU32,
+    /// Saves and loads the custom state while `inner` is borrowed by the audio thread
+    custom_state: CustomState,
 }
 
 impl<P: VST3Plugin> vst3::Class for AudioProcessor<P> {
@@ -47,7 +68,10 @@ impl<P: VST3Plugin> AudioProcessor<P> {
         Self {
             inner: AtomicRefCell::new(None),
             parameters,
-            shared_state: Arc::new(SharedState {}),
+            shared_state: Arc::new(SharedState::default()),
+            latency: AtomicU32::new(0),
+            tail: AtomicU32::new(0),
+            custom_state: CustomState::default(),
         }
     }
 }
@@ -105,7 +129,9 @@ impl<P: VST3Plugin> IAudioProcessorTrait for AudioProcessor<P> {
     }
 
     unsafe fn canProcessSampleSize(&self, symbolic_sample_size: i32) -> tresult {
-        if symbolic_sample_size == SymbolicSampleSizes_::kSample32 as _ {
+        if symbolic_sample_size == SymbolicSampleSizes_::kSample32 as _
+            || (P::SUPPORTS_F64 && symbolic_sample_size == SymbolicSampleSizes_::kSample64 as _)
+        {
             kResultOk
         } else {
             kResultFalse
@@ -113,23 +139,47 @@ impl<P: VST3Plugin> IAudioProcessorTrait for AudioProcessor<P> {
     }
 
     unsafe fn getLatencySamples(&self) -> u32 {
-        let inner = self.inner.borrow();
-        if let Some(inner) = inner.as_ref() {
-            inner.plugin.latency_samples() as u32
-        } else {
-            0
-        }
+        self.latency.load(Ordering::Relaxed)
     }
 
     unsafe fn setupProcessing(&self, setup: *mut ProcessSetup) -> tresult {
         let Some(setup) = (unsafe { setup.as_ref() }) else {
             return kInvalidArgument;
         };
+        if unsafe { self.canProcessSampleSize(setup.symbolicSampleSize) } != kResultOk {
+            return kResultFalse;
+        }
         let mut inner = self.inner.borrow_mut();
         if let Some(inner) = inner.as_mut() {
-            inner
-                .plugin
-                .prepare(setup.sampleRate, setup.maxSamplesPerBlock as usize);
+            let process_mode = match setup.processMode as ProcessModes {
+                ProcessModes_::kOffline => ProcessMode::Offline,
+                ProcessModes_::kPrefetch => ProcessMode::Prefetch,
+                _ => ProcessMode::Realtime,
+            };
+            if process_mode != inner.process_mode {
+                inner.process_mode = process_mode;
+                inner.plugin.process_mode_changed(process_mode);
+            }
+
+            let max_samples = setup.maxSamplesPerBlock as usize;
+            inner.plugin.prepare(setup.sampleRate, max_samples);
+            self.custom_state.prepare(setup.sampleRate, max_samples);
+            inner.sample_rate = setup.sampleRate;
+            self.latency
+                .store(inner.plugin.latency_samples() as u32, Ordering::Relaxed);
+            self.tail.store(
+                tail_samples(inner.plugin.tail_time(), setup.sampleRate),
+                Ordering::Relaxed,
+            );
+            if setup.symbolicSampleSize == SymbolicSampleSizes_::kSample64 as _ {
+                inner
+                    .bypass_f64
+                    .prepare_for_plugin(&inner.plugin, setup.sampleRate, max_samples);
+            } else {
+                inner
+                    .bypass
+                    .prepare_for_plugin(&inner.plugin, setup.sampleRate, max_samples);
+            }
             kResultOk
         } else {
             kNotInitialized
@@ -139,15 +189,36 @@ impl<P: VST3Plugin> IAudioProcessorTrait for AudioProcessor<P> {
     // Called with true before processing starts, and false after. Can be called from both UI and
     // realtime thread
     unsafe fn setProcessing(&self, state: TBool) -> tresult {
-        let mut inner = self.inner.borrow_mut();
-        if let Some(inner) = inner.as_mut() {
-            if state == 0 {
-                inner.plugin.reset();
-            }
-            kResultOk
-        } else {
-            kNotInitialized
+        if state == 0 {
+            // The main thread may still be saving or loading the state if starting failed
+            let Ok(mut inner) = self.inner.try_borrow_mut() else {
+                self.custom_state.stop_processing(None::<&mut P>);
+                return kResultFalse;
+            };
+            let Some(inner) = inner.as_mut() else {
+                self.custom_state.stop_processing(None::<&mut P>);
+                return kNotInitialized;
+            };
+            inner.plugin.stop_processing();
+            inner.plugin.reset();
+            self.custom_state.stop_processing(Some(&mut inner.plugin));
+            return kResultOk;
         }
+
+        self.custom_state.start_processing();
+        let result = match self.inner.try_borrow_mut() {
+            Ok(mut inner) => match inner.as_mut() {
+                Some(inner) => {
+                    inner.plugin.start_processing();
+                    return kResultOk;
+                }
+                None => kNotInitialized,
+            },
+            // The main thread is saving or loading the state
+            Err(_) => kResultFalse,
+        };
+        self.custom_state.cancel_start_processing();
+        result
     }
 
     // This method is only called from the audio thread
@@ -194,45 +265,28 @@ impl<P: VST3Plugin> IAudioProcessorTrait for AudioProcessor<P> {
             return kResultOk;
         }
 
-        let input = if data.inputs.is_null() {
-            AudioBuffer::empty()
-        } else {
-            unsafe {
-                AudioBuffer::from_ptr(
-                    (*data.inputs).__field0.channelBuffers32,
-                    (*data.inputs).numChannels as usize,
-                    data.numSamples as usize,
-                )
-            }
-        };
-
-        let mut output = if data.outputs.is_null() {
-            AudioBuffer::empty()
-        } else {
-            unsafe {
-                AudioBuffer::from_ptr(
-                    (*data.outputs).__field0.channelBuffers32,
-                    (*data.outputs).numChannels as usize,
-                    data.numSamples as usize,
-                )
-            }
-        };
-
         let info = ProcessInfo {
             rendering_offline: data.processMode == ProcessModes_::kOffline as _,
             sample_rate: process_context.sampleRate,
+            transport: transport_from_context(process_context),
         };
 
-        let context = ProcessContext {
-            input: &input,
-            output: &mut output,
-            info,
+        let Ok(mut inner) = self.inner.try_borrow_mut() else {
+            // Only happens if the host processes without calling setProcessing first
+            unsafe { clear_outputs(data) };
+            return kResultOk;
         };
-
-        let mut plugin = self.inner.borrow_mut();
-        let Some(plugin) = plugin.as_mut().map(|inner| &mut inner.plugin) else {
+        let Some(Inner {
+            plugin,
+            bypass,
+            bypass_f64,
+            sample_rate,
+            ..
+        }) = inner.as_mut()
+        else {
             return kNotInitialized;
         };
+        self.custom_state.exchange(plugin);
         if P::ACCEPTS_MIDI {
             const NOTE_ON_EVENT: u16 = EventTypes_::kNoteOnEvent as _;
             const NOTE_OFF_EVENT: u16 = EventTypes_::kNoteOffEvent as _;
@@ -255,8 +309,13 @@ impl<P: VST3Plugin> IAudioProcessorTrait for AudioProcessor<P> {
                                 sample_offset: event.sampleOffset,
                                 note: Note::from_midi(note_on_event.pitch as _),
                             };
-
-                            plugin.process_midi(&mut context, self.parameters.parameters_ref(), ev);
+                            assert_no_alloc(|| {
+                                plugin.process_midi(
+                                    &mut context,
+                                    self.parameters.parameters_ref(),
+                                    ev,
+                                )
+                            });
                         }
                         NOTE_OFF_EVENT => {
                             let note_off_event = &unsafe { event.__field0.noteOff };
@@ -265,7 +324,13 @@ impl<P: VST3Plugin> IAudioProcessorTrait for AudioProcessor<P> {
                                 sample_offset: event.sampleOffset,
                                 note: Note::from_midi(note_off_event.pitch as _),
                             };
-                            plugin.process_midi(&mut context, self.parameters.parameters_ref(), ev);
+                            assert_no_alloc(|| {
+                                plugin.process_midi(
+                                    &mut context,
+                                    self.parameters.parameters_ref(),
+                                    ev,
+                                )
+                            });
                         }
                         _ => {}
                     }
@@ -273,7 +338,55 @@ impl<P: VST3Plugin> IAudioProcessorTrait for AudioProcessor<P> {
             }
         }
 
-        plugin.process(context, self.parameters.parameters_ref());
+        let num_samples = data.numSamples as usize;
+        let deadline = process_deadline(num_samples, &info);
+        let parameters = self.parameters.parameters_ref();
+        let bypassed = self
+            .parameters
+            .bypass_parameter()
+            .is_some_and(|bypass| bypass.value());
+        if data.symbolicSampleSize == SymbolicSampleSizes_::kSample64 as _ {
+            if !P::SUPPORTS_F64 {
+                return kInvalidArgument;
+            }
+            let input = unsafe {
+                bus_audio_buffer(data.inputs, num_samples, |bus| {
+                    bus.__field0.channelBuffers64
+                })
+            };
+            let mut output = unsafe {
+                bus_audio_buffer(data.outputs, num_samples, |bus| {
+                    bus.__field0.channelBuffers64
+                })
+            };
+            bypass_f64.process(bypassed, &input, &mut output, info, |context| {
+                assert_realtime(deadline, || plugin.process_f64(context, parameters))
+            });
+        } else {
+            let input = unsafe {
+                bus_audio_buffer(data.inputs, num_samples, |bus| {
+                    bus.__field0.channelBuffers32
+                })
+            };
+            let mut output = unsafe {
+                bus_audio_buffer(data.outputs, num_samples, |bus| {
+                    bus.__field0.channelBuffers32
+                })
+            };
+            bypass.process(bypassed, &input, &mut output, info, |context| {
+                assert_realtime(deadline, || plugin.process(context, parameters))
+            });
+        }
+
+        self.tail.store(
+            tail_samples(plugin.tail_time(), *sample_rate),
+            Ordering::Relaxed,
+        );
+        let latency = plugin.latency_samples() as u32;
+        if self.latency.swap(latency, Ordering::Relaxed) != latency {
+            self.shared_state
+                .request_restart(RestartFlags_::kLatencyChanged);
+        }
 
         /*if let Some(output_param_changes) = data.output_param_changes.upgrade() {
             output_param_changes.add_parameter_data(id, index)
@@ -283,7 +396,71 @@ impl<P: VST3Plugin> IAudioProcessorTrait for AudioProcessor<P> {
     }
 
     unsafe fn getTailSamples(&self) -> u32 {
-        0
+        self.tail.load(Ordering::Relaxed)
+    }
+}
+
+fn transport_from_context(context: &vst3::Steinberg::Vst::ProcessContext) -> Transport {
+    let has_flag = |flag: u32| context.state & flag != 0;
+    Transport {
+        playing: has_flag(StatesAndFlags_::kPlaying),
+        recording: has_flag(StatesAndFlags_::kRecording),
+        tempo: has_flag(StatesAndFlags_::kTempoValid).then_some(context.tempo),
+        time_signature: has_flag(StatesAndFlags_::kTimeSigValid).then_some(TimeSignature {
+            numerator: context.timeSigNumerator as _,
+            denominator: context.timeSigDenominator as _,
+        }),
+        position_samples: Some(context.projectTimeSamples),
+        position_quarters: has_flag(StatesAndFlags_::kProjectTimeMusicValid)
+            .then_some(context.projectTimeMusic),
+        bar_start_quarters: has_flag(StatesAndFlags_::kBarPositionValid)
+            .then_some(context.barPositionMusic),
+        loop_range: (has_flag(StatesAndFlags_::kCycleValid)
+            && has_flag(StatesAndFlags_::kCycleActive))
+        .then_some(LoopRange {
+            start: context.cycleStartMusic,
+            end: context.cycleEndMusic,
+        }),
+    }
+}
+
+/// Creates an audio buffer for the channels of a bus, or an empty buffer if there is no bus.
+/// The input and output share samples when the host processes in place, so they are passed
+/// through [`Bypass::process`], which gives the plugin a copy of the input in that case.
+///
+/// # Safety
+///
+/// `bus` must be null or point to valid bus buffers, and `channel_buffers` must select the
+/// union member that matches the sample size of the processing call.
+unsafe fn bus_audio_buffer<'a, T>(
+    bus: *mut AudioBusBuffers,
+    num_samples: usize,
+    channel_buffers: impl FnOnce(&AudioBusBuffers) -> *mut *mut T,
+) -> AudioBuffer<'a, T> {
+    match unsafe { bus.as_ref() } {
+        Some(bus) => unsafe {
+            AudioBuffer::from_ptr(channel_buffers(bus), bus.numChannels as usize, num_samples)
+        },
+        None => AudioBuffer::empty(),
+    }
+}
+
+/// Silences all output busses.
+///
+/// # Safety
+///
+/// `data` must describe valid output buffers for `numSamples` samples.
+unsafe fn clear_outputs(data: &ProcessData) {
+    let num_samples = data.numSamples as usize;
+    for index in 0..data.numOutputs.max(0) as usize {
+        let bus = unsafe { data.outputs.add(index) };
+        if data.symbolicSampleSize == SymbolicSampleSizes_::kSample64 as _ {
+            unsafe { bus_audio_buffer(bus, num_samples, |bus| bus.__field0.channelBuffers64) }
+                .clear();
+        } else {
+            unsafe { bus_audio_buffer(bus, num_samples, |bus| bus.__field0.channelBuffers32) }
+                .clear();
+        }
     }
 }
 
@@ -305,6 +482,10 @@ impl<P: VST3Plugin> IPluginBaseTrait for AudioProcessor<P> {
             *inner = Some(Inner {
                 plugin: P::new(HostInfo { name }),
                 host_context,
+                bypass: Bypass::new(),
+                bypass_f64: Bypass::new(),
+                sample_rate: 0.0,
+                process_mode: ProcessMode::default(),
             });
             kResultOk
         }
@@ -447,21 +628,44 @@ impl<P: VST3Plugin> IComponentTrait for AudioProcessor<P> {
     }
 
     unsafe fn setState(&self, state: *mut IBStream) -> tresult {
-        let Some(_state) = (unsafe { state.as_mut() }) else {
+        let Some(stream) = (unsafe { ComRef::from_raw(state) }) else {
             return kInvalidArgument;
         };
+        let Ok(state) = PluginState::read(Stream(stream)) else {
+            return kResultFalse;
+        };
 
-        // TODO: Deserialize the state
+        state.apply_parameters(&*self.parameters);
+        self.custom_state.load(state.custom, |custom| {
+            let Ok(mut inner) = self.inner.try_borrow_mut() else {
+                return false;
+            };
+            // Not initialized yet, there is no plugin to load the state into
+            if let Some(inner) = inner.as_mut() {
+                inner.plugin.load_state(custom);
+            }
+            true
+        });
         kResultOk
     }
 
     unsafe fn getState(&self, state: *mut IBStream) -> tresult {
-        let Some(_state) = (unsafe { state.as_mut() }) else {
+        let Some(stream) = (unsafe { ComRef::from_raw(state) }) else {
             return kInvalidArgument;
         };
 
-        // TODO: Serialize the state
-        kResultOk
+        let custom = self.custom_state.save(|| {
+            let inner = self.inner.try_borrow().ok()?;
+            Some(inner.as_ref()?.plugin.save_state())
+        });
+        let Some(custom) = custom else {
+            return kResultFalse;
+        };
+        let state = PluginState::new(&*self.parameters, custom);
+        match state.write(Stream(stream)) {
+            Ok(()) => kResultOk,
+            Err(_) => kResultFalse,
+        }
     }
 }
 
@@ -1,7 +1,8 @@
-use std::cell::{Cell, OnceCell, RefCell};
+use std::cell::{Cell, RefCell};
 use std::ffi::CStr;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 use vst3::Steinberg::Vst::ParameterInfo_::ParameterFlags_;
 use vst3::Steinberg::Vst::{
@@ -22,10 +23,11 @@ use crate::param::{
 use crate::ui::{AppState, HostHandle};
 use crate::wrapper::vst3::host_application::HostApplication;
 use crate::wrapper::vst3::shared_state::{SHARED_STATE_ATTR_ID, SHARED_STATE_MSG_ID, SharedState};
-use crate::{Editor, EditorContext, platform};
+use crate::wrapper::vst3::timer::Timer;
+use crate::{Editor, EditorContext, PluginState, platform};
 
 use super::plugview::PlugView;
-use super::util::strcpyw;
+use super::util::{Stream, strcpyw};
 
 struct VST3HostHandle {
     component_handler: ComPtr<IComponentHandler>,
@@ -58,7 +60,9 @@ pub struct EditController<E: Editor> {
     executor: Rc<platform::Executor>,
     is_editing_parameters_from_gui: Rc<Cell<bool>>,
     parameters: Rc<ParameterMap<E::Parameters>>,
-    shared_state: OnceCell<Arc<SharedState>>,
+    component_handler: Rc<RefCell<Option<ComPtr<IComponentHandler>>>>,
+    /// Polls the restart requests of the audio processor
+    restart_timer: RefCell<Option<Timer>>,
 }
 
 impl<E: Editor> vst3::Class for EditController<E> {
@@ -82,14 +86,65 @@ impl<E: Editor> EditController<E> {
             executor,
             is_editing_parameters_from_gui,
             parameters,
-            shared_state: OnceCell::new(),
+            component_handler: Rc::new(RefCell::new(None)),
+            restart_timer: RefCell::new(None),
         }
     }
+
+    /// Start polling the restart requests of the audio processor on the main thread
+    fn start_restart_timer(&self, shared_state: Arc<SharedState>) {
+        let Some(host_context) = self.host_context.take() else {
+            return;
+        };
+        let component_handler = self.component_handler.clone();
+        let timer = Timer::new(&host_context, RESTART_POLL_INTERVAL, move || {
+            handle_restart_requests(&shared_state, &component_handler)
+        });
+        if timer.is_none() {
+            log::warn!("Could not create a timer, restart requests will be ignored");
+        }
+        self.restart_timer.replace(timer);
+        self.host_context.set(Some(host_context));
+    }
+}
+
+const RESTART_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Restart the component if the audio processor has requested it
+fn handle_restart_requests(
+    shared_state: &SharedState,
+    component_handler: &RefCell<Option<ComPtr<IComponentHandler>>>,
+) {
+    let flags = shared_state.take_restart_flags();
+    if flags == 0 {
+        return;
+    }
+    // The host may call back into the controller while restarting
+    let component_handler = component_handler.borrow().clone();
+    if let Some(component_handler) = component_handler {
+        unsafe { component_handler.restartComponent(flags) };
+    } else {
+        // Try again once the host has provided a component handler
+        shared_state.request_restart(flags);
+    }
 }
 
 #[allow(non_snake_case)]
 impl<E: Editor> IEditControllerTrait for EditController<E> {
-    unsafe fn setComponentState(&self, _state: *mut IBStream) -> tresult {
+    /// Called with the state of the audio processor, so that the controller shows the same
+    /// parameter values
+    unsafe fn setComponentState(&self, state: *mut IBStream) -> tresult {
+        let Some(stream) = (unsafe { ComRef::from_raw(state) }) else {
+            return kInvalidArgument;
+        };
+        let Ok(state) = PluginState::read(Stream(stream)) else {
+            return kResultFalse;
+        };
+
+        let mut app_state = self.app_state.borrow_mut();
+        for &(id, value) in &state.parameters {
+            app_state.set_plain_parameter_value_from_host(id, value);
+        }
         kResultOk
     }
 
@@ -224,7 +279,10 @@ impl<E: Editor> IEditControllerTrait for EditController<E> {
     }
 
     unsafe fn setComponentHandler(&self, handler: *mut IComponentHandler) -> tresult {
-        if let Some(component_handler) = unsafe { ComRef::from_raw(handler) } {
+        let component_handler = unsafe { ComRef::from_raw(handler) };
+        self.component_handler
+            .replace(component_handler.map(|handler| handler.to_com_ptr()));
+        if let Some(component_handler) = component_handler {
             let is_editing_parameters = self.is_editing_parameters_from_gui.clone();
             let handle = Box::new(VST3HostHandle {
                 component_handler: component_handler.to_com_ptr(),
@@ -267,6 +325,7 @@ impl<E: Editor> IPluginBaseTrait for EditController<E> {
     }
 
     unsafe fn terminate(&self) -> tresult {
+        self.restart_timer.replace(None);
         self.host_context.replace(None);
         kResultOk
     }
@@ -303,9 +362,7 @@ impl<E: Editor> IConnectionPointTrait for EditController<E> {
             ))
         };
 
-        self.shared_state
-            .set(shared_state)
-            .expect("The AudioProcessor should only send the shared state once");
+        self.start_restart_timer(shared_state);
         kResultOk
     }
 }
@@ -4,6 +4,7 @@ use vst3::{
     ComPtr, ComRef, Interface,
     Steinberg::{
         FUnknown,
+        Linux::IRunLoop,
         Vst::{IHostApplication, IHostApplicationTrait, IMessage, IMessageTrait},
         kResultOk,
     },
@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use vst3::Steinberg::Linux::{IRunLoop, IRunLoopTrait, ITimerHandler, ITimerHandlerTrait};
+use vst3::Steinberg::kResultOk;
+use vst3::{ComPtr, ComWrapper};
+
+struct TimerHandler {
+    callback: Box<dyn Fn()>,
+}
+
+impl vst3::Class for TimerHandler {
+    type Interfaces = (ITimerHandler,);
+}
+
+impl ITimerHandlerTrait for TimerHandler {
+    unsafe fn onTimer(&self) {
+        (self.callback)()
+    }
+}
+
+/// A timer on the run loop of the host, which is the only way to get called back on the
+/// main thread on Linux. The timer is unregistered when dropped.
+pub struct RunLoopTimer {
+    run_loop: ComPtr<IRunLoop>,
+    handler: ComPtr<ITimerHandler>,
+}
+
+impl RunLoopTimer {
+    pub fn new(
+        run_loop: ComPtr<IRunLoop>,
+        interval: Duration,
+        callback: impl Fn() + 'static,
+    ) -> Option<Self> {
+        let handler = ComWrapper::new(TimerHandler {
+            callback: Box::new(callback),
+        })
+        .to_com_ptr::<ITimerHandler>()?;
+        let result =
+            unsafe { run_loop.registerTimer(handler.as_ptr(), interval.as_millis() as u64) };
+        (result == kResultOk).then_some(Self { run_loop, handler })
+    }
+}
+
+impl Drop for RunLoopTimer {
+    fn drop(&mut self) {
+        unsafe { self.run_loop.unregisterTimer(self.handler.as_ptr()) };
+    }
+}
@@ -9,6 +9,7 @@ mod linux_runloop;
 mod parameters;
 mod plugview;
 mod shared_state;
+mod timer;
 mod util;
 
 pub use audioprocessor::AudioProcessor;
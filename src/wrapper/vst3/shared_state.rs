@@ -1,8 +1,25 @@
 use std::ffi::CStr;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use vst3::Steinberg::Vst::RestartFlags;
 
 pub const SHARED_STATE_MSG_ID: &CStr = c"State";
 pub const SHARED_STATE_ATTR_ID: &CStr = c"State";
 
 /// State shared between the Editor and the AudioProcessor
-#[derive(Debug)]
-pub struct SharedState {}
+#[derive(Debug, Default)]
+pub struct SharedState {
+    restart_flags: AtomicI32,
+}
+
+impl SharedState {
+    /// Ask the edit controller to restart the component with `flags`. Can be called from
+    /// the audio thread, the edit controller polls the requests on a timer on the UI thread.
+    pub fn request_restart(&self, flags: RestartFlags) {
+        self.restart_flags.fetch_or(flags, Ordering::Release);
+    }
+
+    pub fn take_restart_flags(&self) -> RestartFlags {
+        self.restart_flags.swap(0, Ordering::Acquire)
+    }
+}
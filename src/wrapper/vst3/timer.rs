@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use super::host_application::HostApplication;
+#[cfg(target_os = "linux")]
+use super::linux_runloop::RunLoopTimer;
+
+/// Calls a callback periodically on the main thread, for as long as the timer is alive.
+/// Uses the run loop of the host on Linux, and the run loop of the platform elsewhere.
+pub struct Timer {
+    #[cfg(target_os = "linux")]
+    _timer: RunLoopTimer,
+    #[cfg(not(target_os = "linux"))]
+    _timer: crate::platform::Timer,
+}
+
+impl Timer {
+    /// Returns `None` if the timer could not be created, e.g. because the host does not
+    /// provide a run loop
+    pub fn new(
+        host_context: &HostApplication,
+        interval: Duration,
+        callback: impl Fn() + 'static,
+    ) -> Option<Self> {
+        #[cfg(target_os = "linux")]
+        let timer = RunLoopTimer::new(unsafe { host_context.get_runloop() }?, interval, callback);
+        #[cfg(not(target_os = "linux"))]
+        let timer = {
+            let _ = host_context;
+            crate::platform::Timer::new(interval, callback).ok()
+        };
+        timer.map(|timer| Self { _timer: timer })
+    }
+}
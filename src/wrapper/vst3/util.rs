@@ -1,6 +1,8 @@
 use std::ffi::c_char;
+use std::io;
 
-use vst3::Steinberg::{TUID, Vst::TChar};
+use vst3::ComRef;
+use vst3::Steinberg::{IBStream, IBStreamTrait, TUID, Vst::TChar, kResultOk};
 
 pub fn strcpy(src: &str, dst: &mut [c_char]) {
     let src = src.as_bytes();
@@ -40,3 +42,36 @@ pub const fn tuid_from_uuid(uuid: &[u8; 16]) -> TUID {
         uuid[15] as _,
     ]
 }
+
+/// Reads and writes the stream that the host passes when saving and restoring state
+pub struct Stream<'a>(pub ComRef<'a, IBStream>);
+
+impl io::Read for Stream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(i32::MAX as usize) as i32;
+        let mut read = 0;
+        let result = unsafe { self.0.read(buf.as_mut_ptr() as *mut _, len, &mut read) };
+        if result == kResultOk {
+            Ok(read.max(0) as usize)
+        } else {
+            Err(io::Error::other("Failed to read from the host stream"))
+        }
+    }
+}
+
+impl io::Write for Stream<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(i32::MAX as usize) as i32;
+        let mut written = 0;
+        let result = unsafe { self.0.write(buf.as_ptr() as *mut _, len, &mut written) };
+        if result == kResultOk {
+            Ok(written.max(0) as usize)
+        } else {
+            Err(io::Error::other("Failed to write to the host stream"))
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
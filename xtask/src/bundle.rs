@@ -0,0 +1,311 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::moduleinfo::ModuleInfo;
+use crate::osx;
+use crate::package::{cargo, find_package, Package, PluginMetadata};
+use crate::settings::{BundleSettings, BundleType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Os {
+    Linux,
+    MacOS,
+    Windows,
+}
+
+/// The platform that the plugin is built for
+#[derive(Debug)]
+struct Platform {
+    os: Os,
+    arch: String,
+    /// Whether the plugin can be loaded by the xtask
+    is_host: bool,
+}
+
+impl Platform {
+    fn new(target: Option<&str>) -> Result<Self> {
+        let host = (std::env::consts::OS, std::env::consts::ARCH);
+        let (os, arch) = match target {
+            Some(target) => {
+                let arch = target.split('-').next().unwrap_or(target);
+                let os = if target.contains("-linux") {
+                    "linux"
+                } else if target.contains("-apple-darwin") {
+                    "macos"
+                } else if target.contains("-windows") {
+                    "windows"
+                } else {
+                    bail!("Plugins can't be bundled for {target}");
+                };
+                (os, arch)
+            }
+            None => host,
+        };
+        let is_host = (os, arch) == host;
+        let os = match os {
+            "linux" => Os::Linux,
+            "macos" => Os::MacOS,
+            "windows" => Os::Windows,
+            _ => bail!("Plugins can't be bundled on {os}"),
+        };
+        Ok(Self {
+            os,
+            arch: arch.to_string(),
+            is_host,
+        })
+    }
+
+    fn supports(&self, format: BundleType) -> bool {
+        format != BundleType::Auv3 || self.os == Os::MacOS
+    }
+
+    /// The directory of the library in a VST3 bundle on Linux and Windows, named after the
+    /// architecture
+    fn vst3_arch_dir(&self) -> Result<String> {
+        let arch = match (self.os, self.arch.as_str()) {
+            (Os::Linux, "x86_64") => "x86_64-linux",
+            (Os::Linux, "i686" | "i586" | "i386") => "i386-linux",
+            (Os::Linux, "aarch64") => "aarch64-linux",
+            (Os::Linux, "armv7" | "armv7l") => "armv7l-linux",
+            (Os::Windows, "x86_64") => "x86_64-win",
+            (Os::Windows, "i686" | "i586") => "x86-win",
+            (Os::Windows, "aarch64") => "arm64-win",
+            (_, arch) => bail!("VST3 does not support {arch} on {:?}", self.os),
+        };
+        Ok(arch.to_string())
+    }
+}
+
+/// The libraries built from the plugin package
+#[derive(Default)]
+struct Artifacts {
+    dylib: Option<PathBuf>,
+    staticlib: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct BuildMessage {
+    reason: String,
+    #[serde(default)]
+    target: Option<BuildTarget>,
+    #[serde(default)]
+    filenames: Vec<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct BuildTarget {
+    name: String,
+}
+
+/// Build the package, and bundle it in the formats from the settings, or else from the
+/// package metadata. The bundles are written to `target/bundled`.
+pub fn bundle(settings: &BundleSettings) -> Result<()> {
+    let (package, target_dir) = find_package(&settings.package)?;
+    let metadata = package.plugin_metadata()?;
+    let platform = Platform::new(settings.target.as_deref())?;
+
+    let formats = if !settings.formats.is_empty() {
+        settings.formats.clone()
+    } else if let Some(formats) = &metadata.formats {
+        formats
+            .iter()
+            .copied()
+            .filter(|&format| platform.supports(format))
+            .collect()
+    } else {
+        [BundleType::Vst3, BundleType::Clap, BundleType::Auv3]
+            .into_iter()
+            .filter(|&format| platform.supports(format))
+            .collect()
+    };
+    if let Some(format) = formats.iter().find(|&&format| !platform.supports(format)) {
+        bail!("{} plugins can only be bundled for macOS", format.name());
+    }
+    if formats.is_empty() {
+        bail!("There are no formats to bundle {} in", package.name);
+    }
+    // The module info is read from the plugin library, which has to be loaded for that
+    if formats.contains(&BundleType::Vst3) && !platform.is_host {
+        bail!(
+            "VST3 bundles can only be created for the host, since the plugin is loaded to \
+             generate the moduleinfo.json. Build on {}-{:?} or leave out the VST3 format.",
+            platform.arch,
+            platform.os
+        );
+    }
+
+    let artifacts = build(&package, settings)?;
+    let out_dir = target_dir.join("bundled");
+    fs::create_dir_all(&out_dir)?;
+    for format in formats {
+        let bundle = match format {
+            BundleType::Vst3 => {
+                let dylib = require(&artifacts.dylib, &package, "cdylib")?;
+                vst3_bundle(&out_dir, &package, &metadata, &platform, dylib)?
+            }
+            BundleType::Clap => {
+                let dylib = require(&artifacts.dylib, &package, "cdylib")?;
+                clap_bundle(&out_dir, &package, &metadata, &platform, dylib)?
+            }
+            BundleType::Auv3 => {
+                let staticlib = require(&artifacts.staticlib, &package, "staticlib")?;
+                osx::auv3_bundle(&out_dir, &package, &metadata, staticlib)?
+            }
+        };
+        println!("Created {} bundle {}", format.name(), bundle.display());
+    }
+    Ok(())
+}
+
+fn build(package: &Package, settings: &BundleSettings) -> Result<Artifacts> {
+    let lib_name = package.lib_name()?;
+    let mut command = Command::new(cargo());
+    command.args([
+        "build",
+        "--package",
+        &package.name,
+        "--lib",
+        "--message-format=json-render-diagnostics",
+    ]);
+    if settings.release {
+        command.arg("--release");
+    }
+    if let Some(target) = &settings.target {
+        command.args(["--target", target]);
+    }
+    let mut child = command
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Could not run cargo build")?;
+
+    let mut artifacts = Artifacts::default();
+    let stdout = child.stdout.take().expect("stdout is piped");
+    for line in BufReader::new(stdout).lines() {
+        let Ok(message) = serde_json::from_str::<BuildMessage>(&line?) else {
+            continue;
+        };
+        if message.reason != "compiler-artifact"
+            || message.target.is_none_or(|target| target.name != lib_name)
+        {
+            continue;
+        }
+        for file in message.filenames {
+            let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+            if file_name.ends_with(".so")
+                || file_name.ends_with(".dylib")
+                || file_name.ends_with(".dll")
+            {
+                artifacts.dylib = Some(file);
+            } else if file_name.ends_with(".a")
+                || (file_name.ends_with(".lib") && !file_name.ends_with(".dll.lib"))
+            {
+                artifacts.staticlib = Some(file);
+            }
+        }
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("Building {} failed", package.name);
+    }
+    Ok(artifacts)
+}
+
+fn require<'a>(
+    artifact: &'a Option<PathBuf>,
+    package: &Package,
+    crate_type: &str,
+) -> Result<&'a Path> {
+    artifact.as_deref().with_context(|| {
+        format!(
+            "{} must have crate-type {crate_type} in the [lib] section of its Cargo.toml",
+            package.name
+        )
+    })
+}
+
+/// `<name>.vst3/Contents/<arch>/<name>.so` on Linux, `<name>.vst3/Contents/<arch>/<name>.vst3`
+/// on Windows and `<name>.vst3/Contents/MacOS/<name>` on macOS, with the module info in
+/// `Contents/Resources/moduleinfo.json`
+fn vst3_bundle(
+    out_dir: &Path,
+    package: &Package,
+    metadata: &PluginMetadata,
+    platform: &Platform,
+    dylib: &Path,
+) -> Result<PathBuf> {
+    let name = package.plugin_name(metadata);
+    let bundle = out_dir.join(format!("{name}.vst3"));
+    if bundle.exists() {
+        fs::remove_dir_all(&bundle)?;
+    }
+    let contents = bundle.join("Contents");
+    match platform.os {
+        Os::Linux | Os::Windows => {
+            let extension = if platform.os == Os::Linux {
+                "so"
+            } else {
+                "vst3"
+            };
+            let lib_dir = contents.join(platform.vst3_arch_dir()?);
+            fs::create_dir_all(&lib_dir)?;
+            fs::copy(dylib, lib_dir.join(format!("{name}.{extension}")))?;
+        }
+        Os::MacOS => osx::plugin_bundle(
+            &bundle,
+            dylib,
+            &name,
+            &format!("{}.vst3", package.bundle_id(metadata)),
+            &package.version,
+        )?,
+    }
+
+    let module_info = ModuleInfo::read(dylib, &name, &package.version)?;
+    let resources = contents.join("Resources");
+    fs::create_dir_all(&resources)?;
+    fs::write(resources.join("moduleinfo.json"), module_info.to_json())?;
+    Ok(bundle)
+}
+
+/// A `<name>.clap` file on Linux and Windows, and a `<name>.clap` bundle on macOS
+fn clap_bundle(
+    out_dir: &Path,
+    package: &Package,
+    metadata: &PluginMetadata,
+    platform: &Platform,
+    dylib: &Path,
+) -> Result<PathBuf> {
+    if platform.is_host {
+        let library = unsafe { libloading::Library::new(dylib) }
+            .with_context(|| format!("Could not load {}", dylib.display()))?;
+        if unsafe { library.get::<*const ()>(b"clap_entry\0") }.is_err() {
+            bail!(
+                "{} does not export a CLAP plugin, add audioplug_clap_plugin! to it",
+                package.name
+            );
+        }
+    }
+
+    let name = package.plugin_name(metadata);
+    let bundle = out_dir.join(format!("{name}.clap"));
+    if bundle.is_dir() {
+        fs::remove_dir_all(&bundle)?;
+    }
+    match platform.os {
+        Os::Linux | Os::Windows => {
+            fs::copy(dylib, &bundle)?;
+        }
+        Os::MacOS => osx::plugin_bundle(
+            &bundle,
+            dylib,
+            &name,
+            &format!("{}.clap", package.bundle_id(metadata)),
+            &package.version,
+        )?,
+    }
+    Ok(bundle)
+}
@@ -2,13 +2,14 @@ use anyhow::Result;
 use clap::Parser;
 use settings::Settings;
 
+mod bundle;
+mod moduleinfo;
 mod osx;
+mod package;
 mod settings;
 
 fn main() -> Result<()> {
-    println!("Hello, world!");
-
-    let settings = Settings::try_parse()?;
-
-    Ok(())
+    match Settings::parse() {
+        Settings::Bundle(settings) => bundle::bundle(&settings),
+    }
 }
@@ -0,0 +1,260 @@
+//! Generates the `moduleinfo.json` of VST3 bundles, which lets hosts scan plugins without
+//! loading them. Like the `moduleinfotool` of the VST3 SDK, the plugin library is loaded and
+//! its factory describes the classes.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use libloading::{Library, Symbol};
+use serde::Serialize;
+use vst3::ComPtr;
+use vst3::Steinberg::PFactoryInfo_::FactoryFlags_;
+use vst3::Steinberg::{
+    kResultOk, IPluginFactory, IPluginFactory2, IPluginFactory2Trait, IPluginFactoryTrait,
+    PClassInfo2, PFactoryInfo, TUID,
+};
+
+#[derive(Serialize)]
+pub struct ModuleInfo {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Version")]
+    version: String,
+    #[serde(rename = "Factory Info")]
+    factory_info: FactoryInfo,
+    #[serde(rename = "Classes")]
+    classes: Vec<ClassInfo>,
+}
+
+#[derive(Serialize)]
+struct FactoryInfo {
+    #[serde(rename = "Vendor")]
+    vendor: String,
+    #[serde(rename = "URL")]
+    url: String,
+    #[serde(rename = "E-Mail")]
+    email: String,
+    #[serde(rename = "Flags")]
+    flags: FactoryInfoFlags,
+}
+
+#[derive(Serialize)]
+struct FactoryInfoFlags {
+    #[serde(rename = "Unicode")]
+    unicode: bool,
+    #[serde(rename = "Classes Discardable")]
+    classes_discardable: bool,
+    #[serde(rename = "Component Non Discardable")]
+    component_non_discardable: bool,
+}
+
+#[derive(Serialize)]
+struct ClassInfo {
+    #[serde(rename = "CID")]
+    cid: String,
+    #[serde(rename = "Category")]
+    category: String,
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Vendor")]
+    vendor: String,
+    #[serde(rename = "Version")]
+    version: String,
+    #[serde(rename = "SDKVersion")]
+    sdk_version: String,
+    #[serde(rename = "Sub Categories")]
+    sub_categories: Vec<String>,
+    #[serde(rename = "Class Flags")]
+    class_flags: u32,
+    #[serde(rename = "Cardinality")]
+    cardinality: i32,
+    #[serde(rename = "Snapshots")]
+    snapshots: Vec<String>,
+}
+
+#[cfg(not(target_os = "windows"))]
+type EntryFn = unsafe extern "system" fn(*mut c_void) -> bool;
+#[cfg(target_os = "windows")]
+type EntryFn = unsafe extern "system" fn() -> bool;
+type ExitFn = unsafe extern "system" fn() -> bool;
+type GetPluginFactoryFn = unsafe extern "system" fn() -> *mut c_void;
+
+#[cfg(target_os = "linux")]
+const ENTRY_EXIT: (&[u8], &[u8]) = (b"ModuleEntry\0", b"ModuleExit\0");
+#[cfg(target_os = "macos")]
+const ENTRY_EXIT: (&[u8], &[u8]) = (b"bundleEntry\0", b"bundleExit\0");
+#[cfg(target_os = "windows")]
+const ENTRY_EXIT: (&[u8], &[u8]) = (b"InitDll\0", b"ExitDll\0");
+
+impl ModuleInfo {
+    /// Load the plugin library at `path`, which must have been built for the host, and read
+    /// the classes from its factory. `name` and `version` describe the bundle.
+    pub fn read(path: &Path, name: &str, version: &str) -> Result<Self> {
+        let library = unsafe { Library::new(path) }
+            .with_context(|| format!("Could not load {}", path.display()))?;
+        let (entry, exit) = ENTRY_EXIT;
+        if let Ok(entry) = unsafe { library.get::<EntryFn>(entry) } {
+            // The entry functions of audioplug plugins don't use the library handle or bundle
+            #[cfg(not(target_os = "windows"))]
+            let entered = unsafe { entry(std::ptr::null_mut()) };
+            #[cfg(target_os = "windows")]
+            let entered = unsafe { entry() };
+            if !entered {
+                bail!("The module entry of {} failed", path.display());
+            }
+        }
+
+        let info = unsafe { read_factory(&library) };
+        if let Ok(exit) = unsafe { library.get::<ExitFn>(exit) } {
+            unsafe { exit() };
+        }
+        let (factory_info, classes) =
+            info.with_context(|| format!("Could not read the VST3 factory of {}", path.display()))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            factory_info,
+            classes,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("The module info can be serialized")
+    }
+}
+
+unsafe fn read_factory(library: &Library) -> Result<(FactoryInfo, Vec<ClassInfo>)> {
+    let get_factory: Symbol<GetPluginFactoryFn> = unsafe { library.get(b"GetPluginFactory\0") }
+        .context("The library does not export GetPluginFactory")?;
+    let factory = unsafe { ComPtr::from_raw(get_factory() as *mut IPluginFactory) }
+        .context("GetPluginFactory returned null")?;
+
+    let mut info: PFactoryInfo = unsafe { std::mem::zeroed() };
+    if unsafe { factory.getFactoryInfo(&mut info) } != kResultOk {
+        bail!("getFactoryInfo failed");
+    }
+    let flags = info.flags;
+    let factory_info = FactoryInfo {
+        vendor: string(&info.vendor),
+        url: string(&info.url),
+        email: string(&info.email),
+        flags: FactoryInfoFlags {
+            unicode: flags & FactoryFlags_::kUnicode as i32 != 0,
+            classes_discardable: flags & FactoryFlags_::kClassesDiscardable as i32 != 0,
+            component_non_discardable: flags & FactoryFlags_::kComponentNonDiscardable as i32 != 0,
+        },
+    };
+
+    let factory2 = factory
+        .cast::<IPluginFactory2>()
+        .context("The factory does not implement IPluginFactory2")?;
+    let mut classes = Vec::new();
+    for index in 0..unsafe { factory2.countClasses() } {
+        let mut info: PClassInfo2 = unsafe { std::mem::zeroed() };
+        if unsafe { factory2.getClassInfo2(index, &mut info) } != kResultOk {
+            bail!("getClassInfo2 failed for class {index}");
+        }
+        let sub_categories = string(&info.subCategories);
+        classes.push(ClassInfo {
+            cid: cid_string(&info.cid),
+            category: string(&info.category),
+            name: string(&info.name),
+            vendor: string(&info.vendor),
+            version: string(&info.version),
+            sdk_version: string(&info.sdkVersion),
+            sub_categories: sub_categories
+                .split('|')
+                .filter(|category| !category.is_empty())
+                .map(str::to_string)
+                .collect(),
+            class_flags: info.classFlags,
+            cardinality: info.cardinality,
+            snapshots: Vec::new(),
+        });
+    }
+    Ok((factory_info, classes))
+}
+
+fn string(chars: &[c_char]) -> String {
+    let bytes: Vec<u8> = chars.iter().map(|&c| c as u8).collect();
+    CStr::from_bytes_until_nul(&bytes)
+        .map(|string| string.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Formats the class id like `FUID::toString` in the SDK, which reads it as a GUID on
+/// Windows
+fn cid_string(cid: &TUID) -> String {
+    let mut bytes: [u8; 16] = cid.map(|c| c as u8);
+    if cfg!(target_os = "windows") {
+        bytes[0..4].reverse();
+        bytes[4..6].reverse();
+        bytes[6..8].reverse();
+    }
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn module_info_json() {
+        let module_info = ModuleInfo {
+            name: "Gain".to_string(),
+            version: "0.1.0".to_string(),
+            factory_info: FactoryInfo {
+                vendor: "Audioplug".to_string(),
+                url: String::new(),
+                email: String::new(),
+                flags: FactoryInfoFlags {
+                    unicode: true,
+                    classes_discardable: false,
+                    component_non_discardable: false,
+                },
+            },
+            classes: vec![ClassInfo {
+                cid: "000102030405060708090A0B0C0D0E0F".to_string(),
+                category: "Audio Module Class".to_string(),
+                name: "Gain".to_string(),
+                vendor: "Audioplug".to_string(),
+                version: "0.1.0".to_string(),
+                sdk_version: "VST 3.7.12".to_string(),
+                sub_categories: vec!["Fx".to_string()],
+                class_flags: 0,
+                cardinality: 0x7FFF_FFFF,
+                snapshots: Vec::new(),
+            }],
+        };
+        let json: serde_json::Value = serde_json::from_str(&module_info.to_json()).unwrap();
+        assert_eq!(json["Name"], "Gain");
+        assert_eq!(json["Factory Info"]["Vendor"], "Audioplug");
+        assert_eq!(json["Factory Info"]["Flags"]["Unicode"], true);
+        assert_eq!(json["Factory Info"]["Flags"]["Classes Discardable"], false);
+        let class = &json["Classes"][0];
+        assert_eq!(class["CID"], "000102030405060708090A0B0C0D0E0F");
+        assert_eq!(class["SDKVersion"], "VST 3.7.12");
+        assert_eq!(class["Sub Categories"], serde_json::json!(["Fx"]));
+        assert_eq!(class["Cardinality"], 0x7FFF_FFFF);
+        assert_eq!(class["Snapshots"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn factory_strings() {
+        let mut chars = [0 as c_char; 8];
+        for (char, byte) in chars.iter_mut().zip(b"Gain") {
+            *char = *byte as c_char;
+        }
+        assert_eq!(string(&chars), "Gain");
+
+        let cid: TUID = std::array::from_fn(|index| index as c_char);
+        let expected = if cfg!(target_os = "windows") {
+            "030201000504070608090A0B0C0D0E0F"
+        } else {
+            "000102030405060708090A0B0C0D0E0F"
+        };
+        assert_eq!(cid_string(&cid), expected);
+    }
+}
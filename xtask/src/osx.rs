@@ -0,0 +1,299 @@
+//! The bundle layouts of macOS
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::package::{Auv3Metadata, Package, PluginMetadata};
+
+/// Frameworks used by the plugins, which have to be linked explicitly with the static library
+const FRAMEWORKS: &[&str] = &[
+    "Foundation",
+    "AppKit",
+    "AudioToolbox",
+    "AVFAudio",
+    "CoreAudio",
+    "CoreAudioKit",
+    "CoreGraphics",
+    "CoreImage",
+    "CoreText",
+    "Metal",
+    "QuartzCore",
+];
+
+/// The keys of an Info.plist that are common to all bundles
+pub struct BundleInfo<'a> {
+    pub name: &'a str,
+    pub identifier: &'a str,
+    pub version: &'a str,
+    /// `BNDL` for plugins, `APPL` for apps and `XPC!` for app extensions
+    pub package_type: &'a str,
+}
+
+impl BundleInfo<'_> {
+    /// Write the Info.plist and the PkgInfo to `contents`. `extra` is added to the
+    /// top-level dictionary of the Info.plist.
+    pub fn write(&self, contents: &Path, extra: &str) -> Result<()> {
+        fs::create_dir_all(contents)?;
+        let plist = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>CFBundleDevelopmentRegion</key>
+	<string>English</string>
+	<key>CFBundleExecutable</key>
+	<string>{name}</string>
+	<key>CFBundleIdentifier</key>
+	<string>{identifier}</string>
+	<key>CFBundleInfoDictionaryVersion</key>
+	<string>6.0</string>
+	<key>CFBundleName</key>
+	<string>{name}</string>
+	<key>CFBundlePackageType</key>
+	<string>{package_type}</string>
+	<key>CFBundleShortVersionString</key>
+	<string>{version}</string>
+	<key>CFBundleVersion</key>
+	<string>{version}</string>
+	<key>CFBundleSignature</key>
+	<string>????</string>
+{extra}</dict>
+</plist>
+"#,
+            name = escape(self.name),
+            identifier = escape(self.identifier),
+            version = escape(self.version),
+            package_type = self.package_type,
+        );
+        fs::write(contents.join("Info.plist"), plist)?;
+        fs::write(
+            contents.join("PkgInfo"),
+            format!("{}????", self.package_type),
+        )?;
+        Ok(())
+    }
+}
+
+/// A plugin bundle with the library as its executable, used by VST3 and CLAP
+pub fn plugin_bundle(
+    bundle: &Path,
+    library: &Path,
+    name: &str,
+    identifier: &str,
+    version: &str,
+) -> Result<()> {
+    let contents = bundle.join("Contents");
+    BundleInfo {
+        name,
+        identifier,
+        version,
+        package_type: "BNDL",
+    }
+    .write(&contents, "")?;
+    let macos = contents.join("MacOS");
+    fs::create_dir_all(&macos)?;
+    fs::copy(library, macos.join(name))?;
+    Ok(())
+}
+
+/// An AUv3 plugin is an app extension, which is registered when the app that contains it is
+/// opened. Builds `<name>.app` with the extension in `Contents/PlugIns/<name>.appex`, from the
+/// static library of the plugin.
+pub fn auv3_bundle(
+    out_dir: &Path,
+    package: &Package,
+    metadata: &PluginMetadata,
+    staticlib: &Path,
+) -> Result<PathBuf> {
+    let Some(auv3) = &metadata.auv3 else {
+        bail!(
+            "AUv3 plugins need an audio component, add [package.metadata.audioplug.auv3] to the Cargo.toml of {}",
+            package.name
+        );
+    };
+    for (key, code) in [
+        ("type", &auv3.type_),
+        ("subtype", &auv3.subtype),
+        ("manufacturer", &auv3.manufacturer),
+    ] {
+        if code.len() != 4 || !code.is_ascii() {
+            bail!("The AUv3 {key} must be a four character code, not {code:?}");
+        }
+    }
+
+    let name = package.plugin_name(metadata);
+    let vendor = package.vendor(metadata);
+    let identifier = package.bundle_id(metadata);
+    let objc_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../objc");
+    let namespace =
+        std::env::var("AUDIOPLUG_OBJC_NAMESPACE").unwrap_or_else(|_| "AudioPlug".to_string());
+    let view_controller = format!("{namespace}_{}_ViewController", package.lib_name()?);
+
+    let app = out_dir.join(format!("{name}.app"));
+    if app.exists() {
+        fs::remove_dir_all(&app)?;
+    }
+    let app_contents = app.join("Contents");
+    let appex = app_contents.join("PlugIns").join(format!("{name}.appex"));
+    let appex_contents = appex.join("Contents");
+
+    let executable = appex_contents.join("MacOS").join(&name);
+    fs::create_dir_all(executable.parent().unwrap())?;
+    let mut clang = Command::new("clang++");
+    clang
+        .arg("-o")
+        .arg(&executable)
+        .args([
+            "-Wl,-no_adhoc_codesign",
+            "-fobjc-arc",
+            "-fobjc-link-runtime",
+            "-fapplication-extension",
+            "-e",
+            "_NSExtensionMain",
+            "-fmodules",
+        ])
+        .arg(format!(
+            "-DAUDIOPLUG_VIEW_CONTROLLER_NAME={view_controller}"
+        ))
+        .arg(objc_dir.join("view_controller.mm"))
+        .arg(staticlib);
+    for framework in FRAMEWORKS {
+        clang.args(["-framework", framework]);
+    }
+    run(&mut clang)?;
+    BundleInfo {
+        name: &name,
+        identifier: &format!("{identifier}.auv3"),
+        version: &package.version,
+        package_type: "XPC!",
+    }
+    .write(
+        &appex_contents,
+        &extension_plist(auv3, &name, &vendor, &package.version, &view_controller),
+    )?;
+
+    // The app only shows a window, it is needed to register the extension
+    let app_executable = app_contents.join("MacOS").join(&name);
+    fs::create_dir_all(app_executable.parent().unwrap())?;
+    run(Command::new("clang++")
+        .arg("-o")
+        .arg(&app_executable)
+        .args(["-fobjc-link-runtime", "-framework", "Cocoa"])
+        .arg(objc_dir.join("dummy_app.mm")))?;
+    BundleInfo {
+        name: &name,
+        identifier: &identifier,
+        version: &package.version,
+        package_type: "APPL",
+    }
+    .write(&app_contents, "")?;
+
+    // The extension must be signed for the system to load it
+    let mut sign_appex = Command::new("codesign");
+    sign_appex.args([
+        "--force",
+        "--sign",
+        "-",
+        "-o",
+        "runtime",
+        "--timestamp=none",
+    ]);
+    if let Some(entitlements) = &auv3.entitlements {
+        sign_appex
+            .arg("--entitlements")
+            .arg(package.dir().join(entitlements));
+    }
+    run(sign_appex.arg(&appex))?;
+    run(Command::new("codesign")
+        .args(["--force", "--sign", "-", "--timestamp=none"])
+        .arg(&app))?;
+    Ok(app)
+}
+
+fn extension_plist(
+    auv3: &Auv3Metadata,
+    name: &str,
+    vendor: &str,
+    version: &str,
+    view_controller: &str,
+) -> String {
+    let tags: String = auv3
+        .tags
+        .iter()
+        .map(|tag| format!("\t\t\t\t\t\t<string>{}</string>\n", escape(tag)))
+        .collect();
+    format!(
+        r#"	<key>NSExtension</key>
+	<dict>
+		<key>NSExtensionAttributes</key>
+		<dict>
+			<key>AudioComponents</key>
+			<array>
+				<dict>
+					<key>description</key>
+					<string>{description}</string>
+					<key>factoryFunction</key>
+					<string>{view_controller}</string>
+					<key>manufacturer</key>
+					<string>{manufacturer}</string>
+					<key>name</key>
+					<string>{vendor}: {name}</string>
+					<key>sandboxSafe</key>
+					<true/>
+					<key>subtype</key>
+					<string>{subtype}</string>
+					<key>tags</key>
+					<array>
+{tags}					</array>
+					<key>type</key>
+					<string>{type_}</string>
+					<key>version</key>
+					<integer>{version}</integer>
+				</dict>
+			</array>
+		</dict>
+		<key>NSExtensionPointIdentifier</key>
+		<string>com.apple.AudioUnit-UI</string>
+		<key>NSExtensionPrincipalClass</key>
+		<string>{view_controller}</string>
+	</dict>
+"#,
+        description = escape(auv3.description.as_deref().unwrap_or(name)),
+        manufacturer = escape(&auv3.manufacturer),
+        vendor = escape(vendor),
+        name = escape(name),
+        subtype = escape(&auv3.subtype),
+        type_ = escape(&auv3.type_),
+        version = component_version(version),
+    )
+}
+
+/// Audio units have their version as 0xMMMMmmbb, for version MMMM.mm.bb
+fn component_version(version: &str) -> u32 {
+    let mut parts = version
+        .split(['.', '-', '+'])
+        .map(|part| part.parse::<u32>().unwrap_or(0));
+    let major = parts.next().unwrap_or(0);
+    let minor = parts.next().unwrap_or(0);
+    let patch = parts.next().unwrap_or(0);
+    (major.min(0xFFFF) << 16) | (minor.min(0xFF) << 8) | patch.min(0xFF)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let status = command
+        .status()
+        .with_context(|| format!("Could not run {:?}", command.get_program()))?;
+    if !status.success() {
+        bail!("{:?} failed with {status}", command.get_program());
+    }
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::settings::BundleType;
+
+/// A package of the workspace, as described by `cargo metadata`
+#[derive(Debug, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    pub manifest_path: PathBuf,
+    targets: Vec<Target>,
+    #[serde(default)]
+    metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Target {
+    name: String,
+    crate_types: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    target_directory: PathBuf,
+}
+
+/// The `[package.metadata.audioplug]` table of the Cargo.toml of a plugin
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PluginMetadata {
+    /// The name of the bundles. Defaults to the name of the package.
+    pub name: Option<String>,
+    /// Defaults to the name of the first author
+    pub vendor: Option<String>,
+    /// The identifier of the macOS bundles, e.g. `com.example.gain`
+    pub bundle_id: Option<String>,
+    pub formats: Option<Vec<BundleType>>,
+    pub auv3: Option<Auv3Metadata>,
+}
+
+/// The audio component of an AUv3 plugin
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Auv3Metadata {
+    /// Four character code of the audio unit type, e.g. `aufx` for effects and `aumu` for
+    /// instruments
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// Four character code that identifies the plugin among the plugins of the manufacturer
+    pub subtype: String,
+    /// Four character code of the manufacturer
+    pub manufacturer: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Entitlements to sign the app extension with, relative to the Cargo.toml
+    pub entitlements: Option<PathBuf>,
+}
+
+/// Find `name` among the packages of the workspace. Returns the package, and the target
+/// directory of the workspace.
+pub fn find_package(name: &str) -> Result<(Package, PathBuf)> {
+    let output = Command::new(cargo())
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .output()
+        .context("Could not run cargo metadata")?;
+    if !output.status.success() {
+        bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let metadata: Metadata =
+        serde_json::from_slice(&output.stdout).context("Could not parse the cargo metadata")?;
+    let package = metadata
+        .packages
+        .into_iter()
+        .find(|package| package.name == name)
+        .with_context(|| format!("There is no package named {name} in the workspace"))?;
+    Ok((package, metadata.target_directory))
+}
+
+impl Package {
+    pub fn dir(&self) -> &Path {
+        self.manifest_path
+            .parent()
+            .expect("The manifest is in the package directory")
+    }
+
+    /// The name of the library target, which is what the plugin is built from
+    pub fn lib_name(&self) -> Result<&str> {
+        self.targets
+            .iter()
+            .find(|target| {
+                target
+                    .crate_types
+                    .iter()
+                    .any(|crate_type| crate_type == "cdylib" || crate_type == "staticlib")
+            })
+            .map(|target| target.name.as_str())
+            .with_context(|| {
+                format!(
+                    "{} has no library with crate-type cdylib or staticlib",
+                    self.name
+                )
+            })
+    }
+
+    pub fn plugin_metadata(&self) -> Result<PluginMetadata> {
+        let Some(metadata) = self
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get("audioplug"))
+        else {
+            return Ok(PluginMetadata::default());
+        };
+        PluginMetadata::deserialize(metadata)
+            .with_context(|| format!("Invalid [package.metadata.audioplug] in {}", self.name))
+    }
+
+    pub fn plugin_name(&self, metadata: &PluginMetadata) -> String {
+        metadata.name.clone().unwrap_or_else(|| self.name.clone())
+    }
+
+    pub fn vendor(&self, metadata: &PluginMetadata) -> String {
+        metadata.vendor.clone().unwrap_or_else(|| {
+            self.authors
+                .first()
+                // Authors are written as `Name <email>`
+                .map(|author| {
+                    author
+                        .split('<')
+                        .next()
+                        .unwrap_or(author)
+                        .trim()
+                        .to_string()
+                })
+                .filter(|author| !author.is_empty())
+                .unwrap_or_else(|| self.name.clone())
+        })
+    }
+
+    pub fn bundle_id(&self, metadata: &PluginMetadata) -> String {
+        metadata.bundle_id.clone().unwrap_or_else(|| {
+            let vendor: String = self
+                .vendor(metadata)
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect();
+            format!("com.{}.{}", vendor.to_lowercase(), self.name)
+        })
+    }
+}
+
+/// The cargo that runs the xtask, so that the same toolchain builds the plugin
+pub fn cargo() -> String {
+    std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn package(metadata: serde_json::Value) -> Package {
+        serde_json::from_value(serde_json::json!({
+            "name": "gain",
+            "version": "0.1.0",
+            "authors": ["Jane Doe <jane@example.com>"],
+            "manifest_path": "/plugins/gain/Cargo.toml",
+            "targets": [
+                { "name": "build-script-build", "crate_types": ["bin"] },
+                { "name": "gain", "crate_types": ["cdylib", "staticlib"] }
+            ],
+            "metadata": metadata,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn plugin_metadata() {
+        let package = package(serde_json::json!({
+            "audioplug": {
+                "name": "Gain",
+                "formats": ["vst3", "clap"],
+                "auv3": {
+                    "type": "aufx",
+                    "subtype": "gain",
+                    "manufacturer": "Exmp",
+                }
+            }
+        }));
+        let metadata = package.plugin_metadata().unwrap();
+        assert_eq!(package.plugin_name(&metadata), "Gain");
+        assert_eq!(
+            metadata.formats,
+            Some(vec![BundleType::Vst3, BundleType::Clap])
+        );
+        let auv3 = metadata.auv3.as_ref().unwrap();
+        assert_eq!(auv3.type_, "aufx");
+        assert!(auv3.tags.is_empty());
+        assert_eq!(package.lib_name().unwrap(), "gain");
+        assert_eq!(package.dir(), Path::new("/plugins/gain"));
+
+        // The vendor and bundle id default to the first author
+        assert_eq!(package.vendor(&metadata), "Jane Doe");
+        assert_eq!(package.bundle_id(&metadata), "com.janedoe.gain");
+    }
+
+    #[test]
+    fn missing_and_invalid_metadata() {
+        let metadata = package(serde_json::Value::Null).plugin_metadata().unwrap();
+        assert!(metadata.name.is_none());
+        assert!(metadata.formats.is_none());
+
+        let package = package(serde_json::json!({ "audioplug": { "fromats": ["vst3"] } }));
+        assert!(package.plugin_metadata().is_err());
+    }
+}
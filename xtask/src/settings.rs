@@ -1,19 +1,45 @@
-#[derive(Debug, Clone)]
-pub enum BuildSource {
-    Main,
-    Example(String),
-}
+use serde::Deserialize;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum BundleType {
-    WinVST3,
-    MacVST3,
-    AUv3,
-    WinStandalone,
-    MacStandalone,
+    /// A `.vst3` bundle
+    Vst3,
+    /// A `.clap` file on Linux and Windows, and a `.clap` bundle on macOS
+    Clap,
+    /// An app extension inside an app, which registers the audio unit. Only on macOS.
+    Auv3,
+}
+
+impl BundleType {
+    pub fn name(self) -> &'static str {
+        match self {
+            BundleType::Vst3 => "VST3",
+            BundleType::Clap => "CLAP",
+            BundleType::Auv3 => "AUv3",
+        }
+    }
 }
 
 #[derive(clap::Parser)]
-pub struct Settings {
-    wrapper_formats: Option<Vec<BundleType>>,
+#[command(bin_name = "cargo xtask")]
+pub enum Settings {
+    /// Build a plugin package and bundle it in the plugin formats
+    Bundle(BundleSettings),
+}
+
+#[derive(clap::Args)]
+pub struct BundleSettings {
+    /// The package of the plugin
+    pub package: String,
+    /// The formats to bundle. Defaults to the formats in the package metadata, or all formats
+    /// that the platform supports.
+    #[arg(long = "format", value_enum)]
+    pub formats: Vec<BundleType>,
+    /// Build in release mode
+    #[arg(long)]
+    pub release: bool,
+    /// The target triple to build for. Defaults to the host.
+    #[arg(long)]
+    pub target: Option<String>,
 }